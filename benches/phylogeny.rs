@@ -0,0 +1,120 @@
+use color_eyre::eyre::{eyre, Report, Result};
+use petgraph::graph::NodeIndex;
+use petgraph::visit::IntoNodeReferences;
+use rebar::phylogeny::Phylogeny;
+use std::time::{Duration, Instant};
+
+// ----------------------------------------------------------------------------
+// Benchmark
+// ----------------------------------------------------------------------------
+
+/// Benchmark phylogeny searches on a large random phylogeny, against their
+/// previous implementations.
+///
+/// Run with: cargo bench --bench phylogeny
+fn main() -> Result<(), Report> {
+    let (n_tips, n_recombinants, seed) = (2000, 1000, 42);
+    let mut phylogeny = Phylogeny::random(n_tips, n_recombinants, seed)?;
+    phylogeny.config.max_paths = usize::MAX;
+    phylogeny.config.max_visited = usize::MAX;
+    let names = phylogeny.get_names()?;
+    println!(
+        "Random phylogeny: {} nodes, {} recombinants",
+        names.len(),
+        phylogeny.recombinants.len()
+    );
+
+    // ------------------------------------------------------------------------
+    // get_paths: iterative vs. recursive, from every node to the root
+
+    let mut recursive_runtime = Duration::ZERO;
+    let mut iterative_runtime = Duration::ZERO;
+    let mut num_paths = 0;
+    for name in &names {
+        let start = Instant::now();
+        let expected = recursive_paths(&phylogeny, name, "root")?;
+        recursive_runtime += start.elapsed();
+
+        let start = Instant::now();
+        let observed = phylogeny.get_paths(name, "root", petgraph::Incoming)?;
+        iterative_runtime += start.elapsed();
+
+        if observed != expected {
+            return Err(eyre!("Paths from {name} to root are different."));
+        }
+        num_paths += observed.len();
+    }
+
+    println!("Paths: {num_paths}");
+    println!("Recursive: {} ms", recursive_runtime.as_millis());
+    println!("Iterative: {} ms", iterative_runtime.as_millis());
+    println!(
+        "Speedup: {:.1}x",
+        recursive_runtime.as_secs_f64() / iterative_runtime.as_secs_f64()
+    );
+
+    // ------------------------------------------------------------------------
+    // get_node: index vs. linear search, of every node
+
+    let mut linear_runtime = Duration::ZERO;
+    let mut index_runtime = Duration::ZERO;
+    for name in &names {
+        let start = Instant::now();
+        let expected = linear_node(&phylogeny, name)?;
+        linear_runtime += start.elapsed();
+
+        let start = Instant::now();
+        let observed = phylogeny.get_node(name)?;
+        index_runtime += start.elapsed();
+
+        if observed != expected {
+            return Err(eyre!("Node index of {name} is different."));
+        }
+    }
+
+    println!("Linear: {} us", linear_runtime.as_micros());
+    println!("Index: {} us", index_runtime.as_micros());
+    println!(
+        "Speedup: {:.1}x",
+        linear_runtime.as_secs_f64() / index_runtime.as_secs_f64()
+    );
+
+    Ok(())
+}
+
+/// The previous get_node, which searches all nodes of the graph.
+fn linear_node(phylogeny: &Phylogeny, name: &str) -> Result<NodeIndex, Report> {
+    for (idx, n) in phylogeny.graph.node_references() {
+        if n == name {
+            return Ok(idx);
+        }
+    }
+    Err(eyre!("Name {name} is not in the phylogeny."))
+}
+
+/// The previous recursive search of get_paths (towards the root), which looks
+/// up nodes by name and clones paths at every level.
+fn recursive_paths(
+    phylogeny: &Phylogeny,
+    origin: &str,
+    dest: &str,
+) -> Result<Vec<Vec<String>>, Report> {
+    let mut paths: Vec<Vec<String>> = Vec::new();
+    let origin_node = linear_node(phylogeny, origin)?;
+    let _dest_node = linear_node(phylogeny, dest)?;
+
+    if origin == dest {
+        paths.push(vec![origin.to_string()]);
+    } else {
+        let mut neighbors =
+            phylogeny.graph.neighbors_directed(origin_node, petgraph::Incoming).detach();
+        while let Some(parent_node) = neighbors.next_node(&phylogeny.graph) {
+            let parent_name = phylogeny.get_name(&parent_node)?;
+            let mut parent_paths = recursive_paths(phylogeny, &parent_name, dest)?;
+            parent_paths.iter_mut().for_each(|p| p.insert(0, origin.to_string()));
+            paths.extend(parent_paths);
+        }
+    }
+
+    Ok(paths)
+}
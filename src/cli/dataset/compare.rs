@@ -0,0 +1,46 @@
+use clap::Parser;
+use std::path::PathBuf;
+
+// -----------------------------------------------------------------------------
+// Dataset Compare
+
+/// Compare two dataset populations.
+#[derive(Clone, Debug, Parser)]
+#[clap(verbatim_doc_comment)]
+pub struct Args {
+    /// Dataset directory.
+    #[clap(short = 'd', long, required = true)]
+    pub dataset_dir: PathBuf,
+
+    /// First population.
+    #[clap(required = true)]
+    pub a: String,
+
+    /// Second population.
+    #[clap(required = true)]
+    pub b: String,
+
+    /// Output directory.
+    ///
+    /// If provided, the differences will be written to compare_<a>_<b>.tsv.
+    /// If the directory does not exist, it will be created.
+    #[clap(short = 'o', long)]
+    pub output_dir: Option<PathBuf>,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Args {
+    pub fn new() -> Self {
+        Args {
+            dataset_dir: PathBuf::new(),
+            a: String::new(),
+            b: String::new(),
+            output_dir: None,
+        }
+    }
+}
@@ -1,4 +1,6 @@
 use crate::dataset::attributes::{Name, Tag};
+use crate::phylogeny::ConflictPolicy;
+use crate::utils::theme::Theme;
 use clap::Parser;
 use std::path::PathBuf;
 
@@ -25,4 +27,48 @@ pub struct Args {
     /// Download dataset from a summary.json snapshot.
     #[clap(short = 's', long)]
     pub summary: Option<PathBuf>,
+
+    /// NCBI nucleotide accession of the reference (ex. MN908947.3).
+    ///
+    /// The reference sequence and annotations (CDS) are taken from its GenBank
+    /// record, instead of the dataset's defaults.
+    #[clap(short = 'a', long)]
+    pub accession: Option<String>,
+
+    /// Exclude populations with more than this fraction of missing bases (N).
+    #[clap(long)]
+    pub max_missing: Option<f64>,
+
+    /// Exclude populations with more than this many private substitutions.
+    ///
+    /// Private substitutions are those not found in the population's parent(s)
+    /// in the phylogeny.
+    #[clap(long)]
+    pub max_private: Option<usize>,
+
+    /// Additional phylogeny to merge into the dataset phylogeny (phylogeny.json).
+    ///
+    /// For example, curated recombinant parents. Populations with different
+    /// parents in the two phylogenies are resolved with --conflict-policy, and
+    /// reported in phylogeny_conflicts.tsv.
+    #[clap(long)]
+    pub phylogeny: Option<PathBuf>,
+
+    /// How to resolve populations with different parents in a merged --phylogeny.
+    #[clap(long, value_enum, default_value_t = ConflictPolicy::default())]
+    pub conflict_policy: ConflictPolicy,
+
+    /// Color theme of the phylogeny exports (dot, graphml).
+    ///
+    /// A built-in theme ('default', or the colorblind-safe 'colorblind'), or a
+    /// JSON theme file.
+    #[clap(long, default_value = "default")]
+    pub theme: Theme,
+
+    /// Strict mode, for validated (ex. clinical) pipelines.
+    ///
+    /// Refuses http (non-TLS) urls, requires a checksum for every file, and
+    /// fails on unknown fields in the --summary snapshot.
+    #[clap(long)]
+    pub strict: bool,
 }
@@ -0,0 +1,104 @@
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+// -----------------------------------------------------------------------------
+// Dataset Edge Case
+
+/// Add or update dataset edge cases.
+#[derive(Debug, Parser)]
+#[clap(verbatim_doc_comment)]
+pub struct Args {
+    #[clap(subcommand)]
+    pub command: Command,
+}
+
+/// Add or update dataset edge cases.
+#[derive(Debug, Subcommand)]
+#[clap(verbatim_doc_comment)]
+pub enum Command {
+    /// Add an edge case, or update the edge case of a population.
+    Add(AddArgs),
+}
+
+// -----------------------------------------------------------------------------
+// Dataset Edge Case Add
+
+/// Add an edge case, or update the edge case of a population.
+///
+/// Only the parameters provided are changed, the rest are kept from an
+/// existing edge case, or are the run defaults for a new one.
+#[derive(Clone, Debug, Parser)]
+#[clap(verbatim_doc_comment)]
+pub struct AddArgs {
+    /// Dataset directory.
+    #[clap(short = 'd', long, required = true)]
+    pub dataset_dir: PathBuf,
+
+    /// Recombinant population of the edge case.
+    #[clap(required = true)]
+    pub population: String,
+
+    /// Restrict the parent search to these candidate parents.
+    ///
+    /// Comma separated, with wildcards and set expressions (see rebar run).
+    #[clap(long, value_delimiter = ',')]
+    pub parents: Option<Vec<String>>,
+
+    /// Remove these populations from the parent search.
+    ///
+    /// Comma separated, with wildcards and set expressions (see rebar run).
+    #[clap(long, value_delimiter = ',')]
+    pub knockout: Option<Vec<String>>,
+
+    /// Maximum number of search iterations to find each parent.
+    #[clap(long)]
+    pub max_iter: Option<usize>,
+
+    /// Maximum number of parents.
+    #[clap(long)]
+    pub max_parents: Option<usize>,
+
+    /// Minimum number of consecutive bases in a parental region.
+    #[clap(long)]
+    pub min_consecutive: Option<usize>,
+
+    /// Minimum length of a parental region.
+    #[clap(long)]
+    pub min_length: Option<usize>,
+
+    /// Minimum number of substitutions in a parental region.
+    #[clap(long)]
+    pub min_subs: Option<usize>,
+
+    /// Run a naive search, which does not use known recombinant parents.
+    #[clap(long)]
+    pub naive: Option<bool>,
+
+    /// Why the edge case is needed (ex. "no unique substitutions from BA.1").
+    #[clap(long)]
+    pub reason: Option<String>,
+}
+
+impl Default for AddArgs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AddArgs {
+    pub fn new() -> Self {
+        AddArgs {
+            dataset_dir: PathBuf::new(),
+            population: String::new(),
+            parents: None,
+            knockout: None,
+            max_iter: None,
+            max_parents: None,
+            min_consecutive: None,
+            min_length: None,
+            min_subs: None,
+            naive: None,
+            reason: None,
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use clap::Parser;
+use std::path::PathBuf;
+
+// -----------------------------------------------------------------------------
+// Dataset Export Matrix
+
+/// Export a presence/absence matrix of population substitutions.
+#[derive(Clone, Debug, Parser)]
+#[clap(verbatim_doc_comment)]
+pub struct Args {
+    /// Dataset directory.
+    #[clap(short = 'd', long, required = true)]
+    pub dataset_dir: PathBuf,
+
+    /// Populations to include in the matrix.
+    ///
+    /// Supports wildcards and set expressions, comma separated.
+    /// ex. "XBB*" or "XBB.1.5,BA.2.75"
+    #[clap(short = 'p', long, required = true, value_delimiter = ',')]
+    pub populations: Vec<String>,
+
+    /// Output directory.
+    ///
+    /// The matrix will be written to matrix.tsv.
+    /// If the directory does not exist, it will be created.
+    #[clap(short = 'o', long, required = true)]
+    pub output_dir: PathBuf,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Args {
+    pub fn new() -> Self {
+        Args {
+            dataset_dir: PathBuf::new(),
+            populations: Vec::new(),
+            output_dir: PathBuf::new(),
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use clap::Parser;
+use std::path::PathBuf;
+
+// -----------------------------------------------------------------------------
+// Dataset Export Phylogeny
+
+/// Export the dataset phylogeny.
+#[derive(Clone, Debug, Parser)]
+#[clap(verbatim_doc_comment)]
+pub struct Args {
+    /// Dataset directory.
+    #[clap(short = 'd', long, required = true)]
+    pub dataset_dir: PathBuf,
+
+    /// Output path.
+    ///
+    /// The format is chosen by the extension: .json, .dot, .gml, .graphml, .nwk,
+    /// or .mmd (Mermaid)
+    /// If the parent directory does not exist, it will be created.
+    #[clap(short = 'o', long, required = true)]
+    pub output: PathBuf,
+
+    /// Only keep populations with sequence data (the effective search graph).
+    ///
+    /// Populations without a sequence can't be matched or be recombination
+    /// parents. They are removed, and their children are connected to their
+    /// parents instead.
+    #[clap(long)]
+    pub effective: bool,
+
+    /// Include layout hints in .dot and .graphml exports, for large phylogenies.
+    ///
+    /// Nodes have a rank (the longest path from the root) and a cluster (the
+    /// most recent recombinant, or the top-level clade). In .dot exports, nodes
+    /// of the same rank are aligned, and nodes of the same cluster are grouped.
+    #[clap(long)]
+    pub layout_hints: bool,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Args {
+    pub fn new() -> Self {
+        Args {
+            dataset_dir: PathBuf::new(),
+            output: PathBuf::new(),
+            effective: false,
+            layout_hints: false,
+        }
+    }
+}
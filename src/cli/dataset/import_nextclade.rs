@@ -0,0 +1,77 @@
+use crate::utils::theme::Theme;
+use clap::Parser;
+use std::path::PathBuf;
+
+// -----------------------------------------------------------------------------
+// Dataset Import Nextclade
+
+/// Import a Nextclade dataset bundle as a rebar dataset.
+#[derive(Clone, Debug, Parser)]
+#[clap(verbatim_doc_comment)]
+pub struct Args {
+    /// Nextclade dataset directory, or zip bundle.
+    ///
+    /// Must contain a reference (reference.fasta) and tree (tree.json), and
+    /// optionally a genome annotation (genome_annotation.gff3). File names are
+    /// taken from pathogen.json, if present.
+    #[clap(required = true)]
+    pub input: PathBuf,
+
+    /// Node attribute of the tree that defines the populations.
+    #[clap(long, default_value_t = Args::default().clade_attribute)]
+    pub clade_attribute: String,
+
+    /// Map tree node labels to populations, instead of the clade attribute.
+    ///
+    /// A TSV or CSV file with columns 'tip' and 'population'. Many tips can map
+    /// to one population, the first in the tree (preorder) is its representative.
+    #[clap(long)]
+    pub tip_mapping: Option<PathBuf>,
+
+    /// Collapse clades with a single child clade into their child.
+    ///
+    /// Intermediate clades (ex. from deep trees with few tips) are removed from
+    /// the populations and phylogeny, and their branch lengths are added to the
+    /// child's branch.
+    #[clap(long)]
+    pub collapse_unary: bool,
+
+    /// Color theme of the phylogeny exports (dot, graphml).
+    ///
+    /// A built-in theme ('default', or the colorblind-safe 'colorblind'), or a
+    /// JSON theme file.
+    #[clap(long, default_value = "default")]
+    pub theme: Theme,
+
+    /// Output directory.
+    ///
+    /// If the directory does not exist, it will be created.
+    #[clap(short = 'o', long, required = true)]
+    pub output_dir: PathBuf,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Args {
+            input: PathBuf::new(),
+            clade_attribute: "clade_membership".to_string(),
+            tip_mapping: None,
+            collapse_unary: false,
+            theme: Theme::new(),
+            output_dir: PathBuf::new(),
+        }
+    }
+}
+
+impl Args {
+    pub fn new() -> Self {
+        Args {
+            input: PathBuf::new(),
+            clade_attribute: String::new(),
+            tip_mapping: None,
+            collapse_unary: false,
+            theme: Theme::new(),
+            output_dir: PathBuf::new(),
+        }
+    }
+}
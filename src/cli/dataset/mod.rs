@@ -1,9 +1,17 @@
+pub mod compare;
 pub mod download;
+pub mod edge_case;
+pub mod export_matrix;
+pub mod export_phylogeny;
+pub mod import_nextclade;
 pub mod list;
+pub mod query;
+pub mod self_test;
+pub mod stats;
 
 use clap::{Parser, Subcommand};
 
-/// List or download datasets.
+/// List, download, import, summarize, compare, query, export, edit, or test datasets.
 #[derive(Parser, Debug)]
 #[clap(verbatim_doc_comment)]
 pub struct Args {
@@ -11,7 +19,7 @@ pub struct Args {
     pub command: Command,
 }
 
-/// List or download datasets.
+/// List, download, import, summarize, compare, query, export, edit, or test datasets.
 #[derive(Subcommand, Debug)]
 #[clap(verbatim_doc_comment)]
 pub enum Command {
@@ -20,4 +28,28 @@ pub enum Command {
 
     /// Download dataset.
     Download(download::Args),
+
+    /// Import a Nextclade dataset.
+    ImportNextclade(import_nextclade::Args),
+
+    /// Report dataset statistics.
+    Stats(stats::Args),
+
+    /// Compare two dataset populations.
+    Compare(compare::Args),
+
+    /// Query populations by mutation.
+    Query(query::Args),
+
+    /// Export a presence/absence matrix of population substitutions.
+    ExportMatrix(export_matrix::Args),
+
+    /// Export the dataset phylogeny.
+    ExportPhylogeny(export_phylogeny::Args),
+
+    /// Add or update dataset edge cases.
+    EdgeCase(edge_case::Args),
+
+    /// Run dataset populations as queries, and report accuracy against the phylogeny.
+    SelfTest(self_test::Args),
 }
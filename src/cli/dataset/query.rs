@@ -0,0 +1,52 @@
+use crate::dataset::signature::Signature;
+use crate::sequence::Substitution;
+use clap::Parser;
+use std::path::PathBuf;
+
+// -----------------------------------------------------------------------------
+// Dataset Query
+
+/// Query dataset populations by mutation, or mutation signature.
+#[derive(Clone, Debug, Parser)]
+#[clap(verbatim_doc_comment)]
+pub struct Args {
+    /// Dataset directory.
+    #[clap(short = 'd', long, required = true)]
+    pub dataset_dir: PathBuf,
+
+    /// Substitution to search for (ex. C22995A).
+    #[clap(short = 'm', long, required_unless_present = "signature")]
+    pub mutation: Option<Substitution>,
+
+    /// Mutation signature to search for.
+    ///
+    /// Combines has(<substitution>) with 'and', 'or', 'not' and parentheses.
+    /// ex. "has(C22995A) and not has(T22917G)"
+    #[clap(short = 's', long, conflicts_with = "mutation")]
+    pub signature: Option<Signature>,
+
+    /// Output directory.
+    ///
+    /// If provided, the populations will be written to query_<mutation>.tsv,
+    /// or query.tsv for a signature.
+    /// If the directory does not exist, it will be created.
+    #[clap(short = 'o', long)]
+    pub output_dir: Option<PathBuf>,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Args {
+    pub fn new() -> Self {
+        Args {
+            dataset_dir: PathBuf::new(),
+            mutation: None,
+            signature: None,
+            output_dir: None,
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use crate::cli::run;
+use clap::Parser;
+use std::path::PathBuf;
+
+// -----------------------------------------------------------------------------
+// Dataset Self Test
+
+/// Run dataset populations as queries, and report accuracy against the phylogeny.
+#[derive(Clone, Debug, Parser)]
+#[clap(verbatim_doc_comment)]
+pub struct Args {
+    /// Dataset directory.
+    #[clap(short = 'd', long, required = true)]
+    pub dataset_dir: PathBuf,
+
+    /// Populations to test.
+    ///
+    /// Comma separated, with wildcards and set expressions (see rebar run).
+    /// Defaults to all populations.
+    #[clap(long, value_delimiter = ',', default_values_t = Args::default().populations)]
+    pub populations: Vec<String>,
+
+    /// Number of bases to mask at the 5' and 3' ends.
+    ///
+    /// Comma separated. Use --mask 0,0 to disable masking.
+    #[clap(short = 'm', long, value_delimiter = ',', default_values_t = run::Args::default().mask)]
+    pub mask: Vec<usize>,
+
+    /// Minimum length of a parental region.
+    #[clap(short = 'l', long, default_value_t = run::Args::default().min_length)]
+    pub min_length: usize,
+
+    /// Number of CPU threads to use.
+    ///
+    /// Use 0 for all available threads.
+    #[clap(short = 't', long, default_value_t = run::Args::default().threads)]
+    pub threads: usize,
+
+    /// Output directory.
+    ///
+    /// The run is written to run/, and the accuracy to self_test.tsv and
+    /// accuracy.json. If the directory does not exist, it will be created.
+    #[clap(short = 'o', long, required = true)]
+    pub output_dir: PathBuf,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        let run = run::Args::default();
+        Args {
+            dataset_dir: PathBuf::new(),
+            populations: vec!["*".to_string()],
+            mask: run.mask,
+            min_length: run.min_length,
+            threads: run.threads,
+            output_dir: PathBuf::new(),
+        }
+    }
+}
+
+impl Args {
+    pub fn new() -> Self {
+        Args {
+            dataset_dir: PathBuf::new(),
+            populations: Vec::new(),
+            mask: Vec::new(),
+            min_length: 0,
+            threads: 0,
+            output_dir: PathBuf::new(),
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use clap::Parser;
+use std::path::PathBuf;
+
+// -----------------------------------------------------------------------------
+// Dataset Stats
+
+/// Report dataset statistics.
+#[derive(Clone, Debug, Parser)]
+#[clap(verbatim_doc_comment)]
+pub struct Args {
+    /// Dataset directory.
+    #[clap(short = 'd', long, required = true)]
+    pub dataset_dir: PathBuf,
+
+    /// Number of most homoplasic substitutions to report.
+    #[clap(long, default_value_t = 10)]
+    pub top: usize,
+
+    /// Output directory.
+    ///
+    /// If provided, statistics will be written to stats.json and
+    /// homoplasies.tsv. If the directory does not exist, it will be created.
+    #[clap(short = 'o', long)]
+    pub output_dir: Option<PathBuf>,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Args {
+            dataset_dir: PathBuf::new(),
+            top: 10,
+            output_dir: None,
+        }
+    }
+}
+
+impl Args {
+    pub fn new() -> Self {
+        Args {
+            dataset_dir: PathBuf::new(),
+            top: 0,
+            output_dir: None,
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use clap::Parser;
+use std::path::PathBuf;
+
+/// Compare the results of two output directories from 'run'.
+#[derive(Clone, Debug, Parser)]
+#[clap(verbatim_doc_comment)]
+pub struct Args {
+    /// Output directory of the old run (ex. previous version or dataset).
+    #[clap(required = true)]
+    pub old: PathBuf,
+
+    /// Output directory of the new run.
+    #[clap(required = true)]
+    pub new: PathBuf,
+
+    /// Maximum distance (bp) a breakpoint can shift, before it is reported.
+    #[clap(short = 't', long, default_value_t = Args::default().tolerance)]
+    pub tolerance: usize,
+
+    /// Output directory.
+    ///
+    /// If provided, the changes will be written to diff_runs.tsv.
+    /// If the directory does not exist, it will be created.
+    #[clap(short = 'o', long)]
+    pub output_dir: Option<PathBuf>,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Args {
+    pub fn new() -> Self {
+        Args {
+            old: PathBuf::new(),
+            new: PathBuf::new(),
+            tolerance: 0,
+            output_dir: None,
+        }
+    }
+}
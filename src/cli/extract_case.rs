@@ -0,0 +1,51 @@
+use clap::Parser;
+use std::path::PathBuf;
+
+/// Bundle the inputs to reproduce the result of one sequence from 'run'.
+#[derive(Clone, Debug, Parser)]
+#[clap(verbatim_doc_comment)]
+pub struct Args {
+    /// Output directory from rebar run.
+    #[clap(short = 'r', long, required = true)]
+    pub run_dir: PathBuf,
+
+    /// Sequence to extract, by its strain in the linelist.
+    #[clap(short = 's', long, required = true)]
+    pub sequence_id: String,
+
+    /// Output tar archive (ex. case.tar).
+    ///
+    /// If the parent directory does not exist, it will be created.
+    #[clap(short = 'o', long, required = true)]
+    pub output: PathBuf,
+
+    /// Dataset directory.
+    ///
+    /// Defaults to the dataset directory recorded in run_args.json.
+    #[clap(short = 'd', long)]
+    pub dataset_dir: Option<PathBuf>,
+
+    /// Input fasta alignment, that the sequence was read from.
+    ///
+    /// Defaults to the alignment recorded in run_args.json.
+    #[clap(short = 'a', long)]
+    pub alignment: Option<PathBuf>,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Args {
+    pub fn new() -> Self {
+        Args {
+            run_dir: PathBuf::new(),
+            sequence_id: String::new(),
+            output: PathBuf::new(),
+            dataset_dir: None,
+            alignment: None,
+        }
+    }
+}
@@ -0,0 +1,25 @@
+use clap::Parser;
+use std::path::PathBuf;
+
+/// Inspect and validate an output directory from 'run'.
+#[derive(Clone, Debug, Parser)]
+#[clap(verbatim_doc_comment)]
+pub struct Args {
+    /// Output directory from rebar run.
+    #[clap(required = true)]
+    pub output_dir: PathBuf,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Args {
+    pub fn new() -> Self {
+        Args {
+            output_dir: PathBuf::new(),
+        }
+    }
+}
@@ -1,7 +1,11 @@
 pub mod dataset;
+pub mod diff_runs;
+pub mod extract_case;
+pub mod inspect;
 pub mod plot;
 pub mod run;
 pub mod simulate;
+pub mod validate_output;
 
 use clap::{Parser, Subcommand, ValueEnum};
 use serde::Serialize;
@@ -29,9 +33,19 @@ pub struct Cli {
     #[clap(hide_possible_values = false)]
     #[clap(global = true)]
     pub verbosity: Verbosity,
+
+    /// Silence progress bars and logs, except for errors.
+    #[clap(short = 'q', long)]
+    #[clap(global = true)]
+    pub quiet: bool,
+
+    /// Write logs to stderr as JSON lines, and hide progress bars.
+    #[clap(long)]
+    #[clap(global = true)]
+    pub json_logs: bool,
 }
 
-/// Rebar CLI commands (dataset, run, plot).
+/// Rebar CLI commands (dataset, run, plot, simulate, inspect, validate-output, diff-runs, extract-case).
 #[derive(Subcommand, Debug)]
 #[clap(verbatim_doc_comment)]
 pub enum Command {
@@ -39,6 +53,10 @@ pub enum Command {
     Run(Box<run::Args>),
     Plot(Box<plot::Args>),
     Simulate(Box<simulate::Args>),
+    Inspect(Box<inspect::Args>),
+    ValidateOutput(Box<validate_output::Args>),
+    DiffRuns(Box<diff_runs::Args>),
+    ExtractCase(Box<extract_case::Args>),
 }
 
 // -----------------------------------------------------------------------------
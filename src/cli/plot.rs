@@ -1,3 +1,5 @@
+use crate::plot::canvas::ImageFormat;
+use crate::utils::theme::Theme;
 use clap::Parser;
 use std::path::PathBuf;
 
@@ -33,6 +35,28 @@ pub struct Args {
     /// the genome size and number of samples, this may cause a crash.
     #[clap(short = 'p', long)]
     pub all_coords: bool,
+
+    /// Stack samples with the same parents in one figure.
+    ///
+    /// Barcodes of recombinants with the same parents (ex. different breakpoints)
+    /// are combined, so that their samples share coordinates and annotations.
+    /// Plots are named by parents (ex. BA.1_BA.2.png).
+    #[clap(long)]
+    pub by_parents: bool,
+
+    /// Color theme.
+    ///
+    /// A built-in theme ('default', or the colorblind-safe 'colorblind'), or a
+    /// JSON theme file.
+    #[clap(long, default_value = "default")]
+    pub theme: Theme,
+
+    /// Image format of the plots.
+    ///
+    /// Either a raster image ('png'), or a vector image ('svg') that can be
+    /// edited (ex. in Inkscape) for publication.
+    #[clap(long, default_value_t = ImageFormat::default())]
+    pub format: ImageFormat,
 }
 
 impl Default for Args {
@@ -49,6 +73,9 @@ impl Args {
             barcodes_file: None,
             output_dir: None,
             all_coords: false,
+            by_parents: false,
+            theme: Theme::new(),
+            format: ImageFormat::default(),
         }
     }
 }
@@ -1,9 +1,11 @@
+use crate::dataset::attributes::NameMatching;
+use crate::export::sink::OutputFormat;
+use crate::utils;
+use chrono::NaiveDate;
 use clap::{Args as ClapArgs, Parser};
 use color_eyre::eyre::{Report, Result, WrapErr};
 use either::*;
 use serde::{Deserialize, Serialize};
-use std::fs::File;
-use std::io::Write;
 use std::path::{Path, PathBuf};
 
 /// Detect recombination in a dataset population and/or input alignment.
@@ -11,12 +13,43 @@ use std::path::{Path, PathBuf};
 #[clap(verbatim_doc_comment)]
 pub struct Args {
     /// Dataset directory.
-    #[clap(short = 'd', long, required = true)]
+    ///
+    /// Not required when using --self-dataset or --dataset-url.
+    #[clap(
+        short = 'd',
+        long,
+        required_unless_present_any = ["self_dataset", "dataset_url"]
+    )]
     #[serde(
         skip_serializing_if = "Args::is_default_dataset_dir",
         skip_deserializing
     )]
-    pub dataset_dir: PathBuf,
+    pub dataset_dir: Option<PathBuf>,
+
+    /// Remote dataset url, to run without downloading the dataset first.
+    ///
+    /// The url of a dataset summary (ex. https://.../summary.json). Only the
+    /// files needed for the run are fetched from the same location, into the
+    /// --dataset-cache.
+    #[clap(long, conflicts_with_all = ["dataset_dir", "self_dataset"])]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dataset_url: Option<String>,
+
+    /// Cache directory for remote datasets (--dataset-url).
+    ///
+    /// Defaults to a 'rebar' directory in the system temporary directory.
+    #[clap(long, requires = "dataset_url")]
+    #[serde(skip)]
+    pub dataset_cache: Option<PathBuf>,
+
+    /// Strict mode, for validated (ex. clinical) pipelines.
+    ///
+    /// The dataset summary (summary.json) must have no unknown fields, every
+    /// dataset file must have an https url and a checksum that matches its
+    /// contents, and the --dataset-url must be https.
+    #[clap(long, conflicts_with = "self_dataset")]
+    #[serde(default)]
+    pub strict: bool,
 
     #[command(flatten)]
     #[serde(skip_serializing_if = "Args::is_default_input", skip_deserializing)]
@@ -26,14 +59,67 @@ pub struct Args {
     #[arg(hide = true)]
     pub population: Option<String>,
 
+    // Hidden attribute, why an edge case is needed.
+    #[arg(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+
+    /// Use the input alignment as the dataset.
+    ///
+    /// Detect recombination among the sequences in the --alignment, instead of
+    /// against a dataset. The first sequence is used as the reference.
+    #[arg(long, requires = "alignment", default_value_t = Args::default().self_dataset)]
+    #[serde(default)]
+    pub self_dataset: bool,
+
+    /// How strictly population names are matched to the dataset.
+    ///
+    /// Applies to --populations, --parents, and --knockout. By default,
+    /// whitespace and case are ignored (ex. 'xbb.1.5 ' is 'XBB.1.5'), and the
+    /// canonical name is reported.
+    #[arg(long, default_value_t = Args::default().name_matching)]
+    #[serde(default)]
+    pub name_matching: NameMatching,
+
     /// Restrict parent search to just these candidate parents.
+    ///
+    /// Populations can be combined with set operations, ex. "XBB* & !XBB.1.16*".
     #[arg(long, value_delimiter = ',')]
     pub parents: Option<Vec<String>>,
 
+    /// Restrict parent search to populations dated on or before this date.
+    ///
+    /// Format: YYYY-MM-DD. Requires population dates in the dataset.
+    /// Populations without a date are still searched.
+    #[arg(long)]
+    #[serde(default)]
+    pub max_date: Option<NaiveDate>,
+
+    /// Restrict the dataset to populations known as of this date.
+    ///
+    /// Format: YYYY-MM-DD. Requires population dates in the dataset.
+    /// Populations dated after this date are removed from the dataset, to
+    /// retrospectively evaluate what would have been detected at the time.
+    /// Populations without a date are retained.
+    #[arg(long)]
+    #[serde(default)]
+    pub as_of: Option<NaiveDate>,
+
+    /// Metadata of the sequences, with their collection dates (tsv or csv).
+    ///
+    /// The metadata has a 'strain' column that matches the sequence ids, and a
+    /// 'date' column (YYYY-MM-DD). Recombinants whose parents were first seen
+    /// (see dataset dates) after the collection date are flagged in the
+    /// linelist temporal_flag column, as these are usually artifacts.
+    #[arg(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<PathBuf>,
+
     /// Remove these populations from the dataset.
     ///
     /// Regardless of whether you use '*' or not, all descendants of the
-    /// specified populations will be removed.
+    /// specified populations will be removed, from the sequences, mutations,
+    /// and phylogeny.
     #[arg(short = 'k', long)]
     pub knockout: Option<Vec<String>>,
 
@@ -68,13 +154,178 @@ pub struct Args {
     #[arg(short = 's', long, default_value_t = Args::default().min_subs)]
     pub min_subs: usize,
 
+    /// Maximum number of reversions tolerated in a parental region.
+    ///
+    /// A reversion is a site where the sequence has the reference base, but
+    /// the parent of the region has a substitution. By default, reversions
+    /// break up a parental region.
+    #[arg(long, default_value_t = Args::default().max_reversions)]
+    #[serde(default)]
+    pub max_reversions: usize,
+
+    /// Maximum fraction of missing data in a parental region.
+    ///
+    /// Regions with more missing data in the sequence are split at the missing
+    /// stretches, and pieces too short to be a region are removed. By default,
+    /// regions are not split.
+    #[arg(long)]
+    #[serde(default)]
+    pub max_missing_in_region: Option<f64>,
+
+    /// Primer scheme of the sequences, as a BED file of primers or amplicons.
+    ///
+    /// Amplicons that dropped out of a sequence (ie. entirely missing) would
+    /// otherwise truncate the parental regions around them. Within the
+    /// neighboring amplicons, the missing bases count towards --min-length,
+    /// and --min-consecutive is scaled down by the fraction that is missing.
+    /// Regions that are only kept by this adjustment are reported as warnings,
+    /// as are breakpoints that fall on the boundary between two amplicons.
+    #[arg(long, visible_alias = "primers")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub primer_bed: Option<PathBuf>,
+
+    /// Mask the primer binding sites of the sequences (--primer-bed).
+    ///
+    /// Primers can introduce the base of the primer rather than the genome,
+    /// so substitutions at primer sites are treated as missing data.
+    #[arg(long, default_value_t = Args::default().mask_primers)]
+    #[serde(default)]
+    pub mask_primers: bool,
+
     /// Run a naive search, which does not use information about edge cases or known recombinant parents.
     #[arg(short = 'u', long, default_value_t = Args::default().naive)]
     pub naive: bool,
 
+    /// Allow an unknown secondary parent.
+    ///
+    /// If only one parent can be found in the dataset, report the second parent
+    /// as 'unknown' with the region of substitutions it does not explain.
+    #[arg(long, default_value_t = Args::default().allow_unknown_parent)]
+    #[serde(default)]
+    pub allow_unknown_parent: bool,
+
+    /// Precompute parent pair profiles of frequently observed populations.
+    ///
+    /// Profiles of the informative sites between a consensus population and
+    /// all candidate parents are computed at once, when the population is
+    /// observed in a second sequence, rather than on demand for each sequence.
+    /// Useful for large batch runs.
+    #[arg(long, default_value_t = Args::default().precompute_pairs)]
+    #[serde(default)]
+    pub precompute_pairs: bool,
+
+    /// Collapse populations with identical barcodes for the search.
+    ///
+    /// Populations with the same substitutions are searched once, by a
+    /// representative of their class, and then expanded back to all members.
+    /// The results are the same, but the search is faster for datasets with
+    /// many identical barcodes (see rebar dataset stats).
+    #[arg(long, default_value_t = Args::default().collapse_barcodes)]
+    #[serde(default)]
+    pub collapse_barcodes: bool,
+
+    /// Approximate the best match search, for very large batches.
+    ///
+    /// Populations are compared to each sequence by MinHash signatures of their
+    /// substitutions, and only the K nearest are scored exactly. Smaller values
+    /// are faster, but more likely to miss the true best match. The
+    /// recombination parent search is not affected.
+    #[arg(long, value_name = "K")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub approximate: Option<usize>,
+
+    /// Maximum number of top populations to report for each sequence.
+    ///
+    /// Sequences that match many populations equally well (ex. hundreds of
+    /// sibling lineages) are truncated to this many top populations, with the
+    /// consensus population first, then the rest in natural order. The total
+    /// number is always reported in top_populations_total. By default, all top
+    /// populations are reported.
+    #[arg(long, value_name = "N")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_top_populations: Option<usize>,
+
+    /// Select and order the columns of the linelist.
+    ///
+    /// Comma separated. By default, all columns are included.
+    #[arg(long, value_delimiter = ',')]
+    #[serde(default)]
+    pub linelist_columns: Option<Vec<String>>,
+
+    /// Record the runtime and complexity of each sequence's search.
+    ///
+    /// Adds the linelist columns runtime_ms (wall time), candidate_parents
+    /// (number of candidate parents checked for recombination), and
+    /// search_iterations (number of parent search iterations). Useful to find
+    /// inputs that dominate the runtime of batch runs.
+    #[arg(long, default_value_t = Args::default().profile)]
+    #[serde(default)]
+    pub profile: bool,
+
+    /// Quote linelist values that spreadsheets would convert.
+    ///
+    /// Breakpoints (ex. 12-12), dates, decimals, and lists of numbers are
+    /// enclosed in double quotes, so that they are read as text when opened in
+    /// Excel, regardless of locale.
+    #[arg(long, default_value_t = Args::default().locale_safe)]
+    #[serde(default)]
+    pub locale_safe: bool,
+
+    /// Additional formats to write the linelist in, comma separated.
+    ///
+    /// The linelist is always written as TSV (linelist.tsv), and additionally
+    /// to linelist.<format> in the same directory, with the same columns.
+    #[arg(long, value_delimiter = ',')]
+    #[serde(default)]
+    pub output_format: Vec<OutputFormat>,
+
+    /// Explain why sequences were not called recombinant.
+    ///
+    /// Reports the best secondary parent candidate of each non-recombinant
+    /// sequence, and the threshold that rejected it, to qc/negatives.tsv.
+    #[arg(long, default_value_t = Args::default().explain_negatives)]
+    #[serde(default)]
+    pub explain_negatives: bool,
+
+    /// Summarize how designated recombinants were detected, in self-test runs
+    /// (see rebar dataset self-test).
+    ///
+    /// For each recombinant population in --populations, compares the parent
+    /// search with a naive search (--naive), to report whether it needs its
+    /// designated parents or edge case to be detected, to qc/edge_cases.tsv.
+    #[arg(long, default_value_t = Args::default().edge_case_stats)]
+    #[serde(default)]
+    pub edge_case_stats: bool,
+
+    /// Write the full result of each sequence to samples/<strain>.json.
+    ///
+    /// Includes the best match, parents, regions, breakpoints, and the
+    /// supporting and conflicting substitutions of each population.
+    #[arg(long, default_value_t = Args::default().sample_outputs)]
+    #[serde(default)]
+    pub sample_outputs: bool,
+
+    /// Summary of a previous run, to report changes in recombinants.
+    ///
+    /// Either the summary.json or the output directory of the previous run (ex.
+    /// output/latest). Recombinants that are new, or whose proportion of
+    /// sequences changed by at least --novelty-fold-change, are reported in
+    /// qc/novelty.tsv.
+    #[clap(long)]
+    #[serde(skip)]
+    pub previous_summary: Option<PathBuf>,
+
+    /// Minimum fold change in the proportion of a recombinant, from the
+    /// previous run, to report it as increased or decreased.
+    #[clap(long, default_value_t = Args::default().novelty_fold_change)]
+    #[serde(skip)]
+    pub novelty_fold_change: f64,
+
     /// Output directory.
     ///
-    /// If the directory does not exist, it will be created.
+    /// If the directory does not exist, it will be created. Use auto (ex.
+    /// output/auto) for a new date-stamped directory for each run,
+    /// rebar_<dataset>_<tag>_<timestamp>, with a link to the latest run.
     #[clap(short = 'o', long, required = true)]
     #[serde(
         skip_serializing_if = "Args::is_default_output_dir",
@@ -83,17 +334,63 @@ pub struct Args {
     pub output_dir: PathBuf,
 
     /// Number of CPU threads to use.
+    ///
+    /// Sequences are searched in parallel, and the outputs are written in
+    /// input order (see --unordered). Use 0 for all available threads.
     #[clap(short = 't', long, default_value_t = Args::default().threads)]
     #[serde(skip)]
     pub threads: usize,
+
+    /// Write results in the order sequences finish, rather than input order.
+    ///
+    /// Maximizes throughput with many threads, but the output order is not
+    /// reproducible between runs.
+    #[clap(long)]
+    #[serde(skip)]
+    pub unordered: bool,
+
+    /// Maximum number of results waiting to be written.
+    ///
+    /// When full, workers wait for the writer (ex. a slow disk), rather than
+    /// reading and searching more sequences. Defaults to 64 per thread.
+    #[clap(long)]
+    #[serde(skip)]
+    pub queue_size: Option<usize>,
+
+    /// Stop the run at the first sequence that fails.
+    ///
+    /// By default, sequences that fail (ex. a record that is not aligned to
+    /// the reference) are skipped and reported in qc/failures.tsv, and the run
+    /// exits with an error after all other sequences are written.
+    #[clap(long)]
+    #[serde(skip)]
+    pub fail_fast: bool,
+
+    /// Maximum memory (RSS) of the run, in megabytes (MB).
+    ///
+    /// Near the limit, the run switches to lower-memory strategies (disables
+    /// the parent pair cache, shrinks the queue, and discards results after
+    /// they are written). If the limit is still exceeded, the run stops with
+    /// an error rather than being killed by the operating system.
+    #[clap(long)]
+    #[serde(skip)]
+    pub max_memory: Option<u64>,
 }
 
 impl Default for Args {
     fn default() -> Self {
         Args {
-            dataset_dir: PathBuf::new(),
+            dataset_dir: None,
+            dataset_url: None,
+            dataset_cache: None,
+            strict: false,
             input: Input::default(),
+            self_dataset: false,
+            name_matching: NameMatching::default(),
             knockout: None,
+            max_date: None,
+            as_of: None,
+            metadata: None,
             mask: vec![100, 200],
             max_iter: 3,
             min_parents: 2,
@@ -101,11 +398,34 @@ impl Default for Args {
             min_consecutive: 3,
             min_length: 500,
             min_subs: 1,
+            max_reversions: 0,
+            max_missing_in_region: None,
+            primer_bed: None,
+            mask_primers: false,
             naive: false,
+            allow_unknown_parent: false,
+            precompute_pairs: false,
+            collapse_barcodes: false,
+            approximate: None,
+            max_top_populations: None,
+            linelist_columns: None,
+            profile: false,
+            locale_safe: false,
+            output_format: Vec::new(),
+            explain_negatives: false,
+            edge_case_stats: false,
+            sample_outputs: false,
+            previous_summary: None,
+            novelty_fold_change: 2.0,
             output_dir: PathBuf::new(),
             parents: None,
             population: None,
+            reason: None,
             threads: 1,
+            unordered: false,
+            queue_size: None,
+            fail_fast: false,
+            max_memory: None,
         }
     }
 }
@@ -113,9 +433,17 @@ impl Default for Args {
 impl Args {
     pub fn new() -> Self {
         Args {
-            dataset_dir: PathBuf::new(),
+            dataset_dir: None,
+            dataset_url: None,
+            dataset_cache: None,
+            strict: false,
             input: Input::default(),
+            self_dataset: false,
+            name_matching: NameMatching::default(),
             knockout: None,
+            max_date: None,
+            as_of: None,
+            metadata: None,
             mask: vec![0, 0],
             max_iter: 0,
             min_parents: 0,
@@ -123,17 +451,40 @@ impl Args {
             min_consecutive: 0,
             min_length: 0,
             min_subs: 0,
+            max_reversions: 0,
+            max_missing_in_region: None,
+            primer_bed: None,
+            mask_primers: false,
+            linelist_columns: None,
+            profile: false,
+            locale_safe: false,
+            output_format: Vec::new(),
+            explain_negatives: false,
+            edge_case_stats: false,
+            sample_outputs: false,
+            previous_summary: None,
+            novelty_fold_change: 0.0,
             output_dir: PathBuf::new(),
             parents: None,
             population: None,
+            reason: None,
             threads: 0,
+            unordered: false,
+            queue_size: None,
+            fail_fast: false,
+            max_memory: None,
             naive: false,
+            allow_unknown_parent: false,
+            precompute_pairs: false,
+            collapse_barcodes: false,
+            approximate: None,
+            max_top_populations: None,
         }
     }
 
     /// Check if input is default.
-    pub fn is_default_dataset_dir(path: &Path) -> bool {
-        path == Args::default().dataset_dir
+    pub fn is_default_dataset_dir(path: &Option<PathBuf>) -> bool {
+        path == &Args::default().dataset_dir
     }
 
     /// Check if input is default.
@@ -166,8 +517,7 @@ impl Args {
     ///     Left: Args
     ///     Right: Vec<args>
     pub fn read(path: &Path, multiple: bool) -> Result<Either<Args, Vec<Args>>, Report> {
-        let input = std::fs::read_to_string(path)
-            .wrap_err_with(|| format!("Failed to read file: {path:?}."))?;
+        let input = utils::read_to_string(path)?;
         let output: Vec<Args> = serde_json::from_str(&input)
             .wrap_err_with(|| format!("Failed to parse file: {path:?}"))?;
 
@@ -180,17 +530,12 @@ impl Args {
 
     /// Write args to file.
     pub fn write(args: &[Args], path: &Path) -> Result<(), Report> {
-        // create file
-        let mut file = File::create(path)
-            .wrap_err_with(|| format!("Failed to create file: {path:?}"))?;
-
         // parse to string
         let args = serde_json::to_string_pretty(args)
             .wrap_err_with(|| format!("Failed to parse: {args:?}"))?;
 
         // write to file
-        file.write_all(format!("{}\n", args).as_bytes())
-            .wrap_err_with(|| format!("Failed to write file: {path:?}"))?;
+        utils::write_atomic(path, format!("{}\n", args))?;
 
         Ok(())
     }
@@ -206,6 +551,14 @@ pub struct Input {
     /// Input dataset population.
     #[arg(long)]
     pub alignment: Option<PathBuf>,
+
+    /// Input unaligned long reads (fasta or fastq), one file per sample.
+    ///
+    /// Reads are mapped to the reference and classified independently.
+    /// Comma separated.
+    #[arg(long, value_delimiter = ',')]
+    #[serde(default)]
+    pub reads: Option<Vec<PathBuf>>,
 }
 
 impl Default for Input {
@@ -219,6 +572,7 @@ impl Input {
         Input {
             populations: None,
             alignment: None,
+            reads: None,
         }
     }
 }
@@ -18,10 +18,21 @@ pub struct Args {
 
     /// Specify the breakpoints.
     ///
-    /// If not provided, will be random.
+    /// The last coordinate of each parental region, except the last, comma
+    /// separated. If not provided, will be random.
     #[arg(long, value_delimiter = ',')]
     pub breakpoints: Option<Vec<usize>>,
 
+    /// Number of recombinants to simulate, with random breakpoints.
+    #[arg(short = 'n', long, default_value_t = 1)]
+    pub num_sequences: usize,
+
+    /// Random seed, to reproduce the random breakpoints.
+    ///
+    /// If not provided, a seed is chosen and reported in the logs.
+    #[arg(long)]
+    pub seed: Option<u64>,
+
     /// Output directory.
     ///
     /// If the directory does not exist, it will be created.
@@ -39,6 +50,8 @@ impl Args {
     pub fn new() -> Self {
         Args {
             breakpoints: None,
+            num_sequences: 1,
+            seed: None,
             dataset_dir: PathBuf::new(),
             output_dir: PathBuf::new(),
             parents: Vec::new(),
@@ -0,0 +1,32 @@
+use clap::Parser;
+use std::path::PathBuf;
+
+/// Validate a previous output directory from 'run' against the current schema.
+#[derive(Clone, Debug, Parser)]
+#[clap(verbatim_doc_comment)]
+pub struct Args {
+    /// Output directory from rebar run.
+    #[clap(required = true)]
+    pub output_dir: PathBuf,
+
+    /// Dataset directory, to check that populations and parents exist.
+    ///
+    /// Defaults to the dataset directory recorded in run_args.json.
+    #[clap(short = 'd', long)]
+    pub dataset_dir: Option<PathBuf>,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Args {
+    pub fn new() -> Self {
+        Args {
+            output_dir: PathBuf::new(),
+            dataset_dir: None,
+        }
+    }
+}
@@ -1,5 +1,9 @@
-use crate::utils::remote_file::RemoteFile;
+use crate::phylogeny::BranchLength;
+use crate::utils;
+use crate::utils::table::Table;
+use crate::utils::versioned_file::{self, VersionedFile};
 use chrono::prelude::*;
+use clap::ValueEnum;
 use color_eyre::eyre::{eyre, Report, Result, WrapErr};
 use color_eyre::Help;
 use indoc::formatdoc;
@@ -8,8 +12,6 @@ use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::default::Default;
 use std::fmt;
-use std::fs::File;
-use std::io::Write;
 use std::path::Path;
 use std::str::FromStr;
 use strum::{EnumIter, EnumProperty};
@@ -22,11 +24,14 @@ use strum::{EnumIter, EnumProperty};
 )]
 pub enum Name {
     #[serde(rename = "sars-cov-2")]
-    #[strum(props(implemented = "true"))]
+    #[strum(props(implemented = "true", dates = "false"))]
     SarsCov2,
     #[serde(rename = "toy1")]
-    #[strum(props(implemented = "true"))]
+    #[strum(props(implemented = "true", dates = "true"))]
     Toy1,
+    #[serde(rename = "toy-mlst")]
+    #[strum(props(implemented = "true", dates = "false"))]
+    ToyMlst,
     #[serde(rename = "rsv-a")]
     #[strum(props(implemented = "false"))]
     RsvA,
@@ -48,7 +53,9 @@ impl Name {
                 compatibility.dataset.min_date =
                     Some(NaiveDate::parse_from_str("2023-02-09", "%Y-%m-%d")?);
             }
-            Name::Toy1 => compatibility.cli.version = Some(">=0.2.0".to_string()),
+            Name::Toy1 | Name::ToyMlst => {
+                compatibility.cli.version = Some(">=0.2.0".to_string())
+            }
             _ => compatibility.cli.version = Some(">=1.0.0".to_string()),
         }
         Ok(compatibility)
@@ -60,6 +67,7 @@ impl fmt::Display for Name {
         let name = match self {
             Name::SarsCov2 => String::from("sars-cov-2"),
             Name::Toy1 => String::from("toy1"),
+            Name::ToyMlst => String::from("toy-mlst"),
             Name::RsvA => String::from("rsv-a"),
             Name::RsvB => String::from("rsv-b"),
             Name::Custom => String::from("custom"),
@@ -76,6 +84,7 @@ impl FromStr for Name {
         let name = match name {
             "sars-cov-2" => Name::SarsCov2,
             "toy1" => Name::Toy1,
+            "toy-mlst" => Name::ToyMlst,
             "rsv-a" => Name::RsvA,
             "rsv-b" => Name::RsvB,
             "custom" => Name::Custom,
@@ -134,6 +143,33 @@ impl FromStr for Tag {
     }
 }
 
+// ----------------------------------------------------------------------------
+// Population Name Matching
+
+/// How strictly population names (ex. --populations) are matched to the dataset.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum NameMatching {
+    /// Names must match exactly.
+    Exact,
+    /// Leading and trailing whitespace is ignored.
+    Trim,
+    /// Whitespace and case are ignored, unless multiple names would match.
+    #[default]
+    IgnoreCase,
+}
+
+impl fmt::Display for NameMatching {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let matching = match self {
+            NameMatching::Exact => "exact",
+            NameMatching::Trim => "trim",
+            NameMatching::IgnoreCase => "ignore-case",
+        };
+        write!(f, "{matching}")
+    }
+}
+
 // ----------------------------------------------------------------------------
 // Dataset Compatibility
 
@@ -254,9 +290,15 @@ pub struct Summary {
     pub version: String,
     pub tag: Tag,
     pub name: Name,
-    pub reference: RemoteFile,
-    pub populations: RemoteFile,
-    pub misc: BTreeMap<String, RemoteFile>,
+    pub reference: VersionedFile,
+    pub populations: VersionedFile,
+    pub misc: BTreeMap<String, VersionedFile>,
+    #[serde(default)]
+    pub branch_length: BranchLength,
+    /// NCBI nucleotide accession of the reference, to fetch its sequence and
+    /// annotations from GenBank.
+    #[serde(default)]
+    pub accession: Option<String>,
 }
 
 impl Default for Summary {
@@ -270,15 +312,16 @@ impl Summary {
             version: format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
             tag: Tag::Custom,
             name: Name::Custom,
-            reference: RemoteFile::new(),
-            populations: RemoteFile::new(),
+            reference: VersionedFile::new(),
+            populations: VersionedFile::new(),
             misc: BTreeMap::new(),
+            branch_length: BranchLength::None,
+            accession: None,
         }
     }
     /// Read summary from file.
     pub fn read(path: &Path) -> Result<Summary, Report> {
-        let summary = std::fs::read_to_string(path)
-            .wrap_err_with(|| format!("Failed to read file: {path:?}."))?;
+        let summary = utils::read_to_string(path)?;
         let summary = serde_json::from_str(&summary)
             .wrap_err_with(|| format!("Failed to parse file: {path:?}"))?;
 
@@ -287,20 +330,259 @@ impl Summary {
 
     /// Write summary to file.
     pub fn write(&self, path: &Path) -> Result<(), Report> {
-        // create output file
-        let mut file = File::create(path)
-            .wrap_err_with(|| format!("Failed to create file: {path:?}"))?;
-
         // parse to string
         let output = serde_json::to_string_pretty(self)
             .wrap_err_with(|| format!("Failed to parse: {self:?}"))?;
 
         // write to file
-        file.write_all(format!("{}\n", output).as_bytes())
-            .wrap_err_with(|| format!("Failed to write file: {path:?}"))?;
+        utils::write_atomic(path, format!("{}\n", output))?;
 
         Ok(())
     }
+
+    /// Get all files of the dataset, by name (ex. reference, misc.alias_key).
+    pub fn files(&self) -> Vec<(String, &VersionedFile)> {
+        let mut files = vec![
+            ("reference".to_string(), &self.reference),
+            ("populations".to_string(), &self.populations),
+        ];
+        files.extend(self.misc.iter().map(|(name, file)| (format!("misc.{name}"), file)));
+        files
+    }
+
+    /// Add checksums to files that weren't fetched (ex. created by rebar).
+    pub fn update_checksums(&mut self) -> Result<(), Report> {
+        let files = [&mut self.reference, &mut self.populations]
+            .into_iter()
+            .chain(self.misc.values_mut());
+        for file in files {
+            if file.checksum.is_none() && file.local_path.exists() {
+                file.checksum = Some(versioned_file::checksum(&file.local_path)?);
+            }
+        }
+        Ok(())
+    }
+
+    /// Read summary from file, with strict validation (see validate_strict).
+    ///
+    /// Fields that are not part of the summary are an error, rather than
+    /// ignored.
+    pub fn read_strict(path: &Path) -> Result<Summary, Report> {
+        let summary = utils::read_to_string(path)?;
+        let raw: serde_json::Value = serde_json::from_str(&summary)
+            .wrap_err_with(|| format!("Failed to parse file: {path:?}"))?;
+        let summary: Summary = serde_json::from_value(raw.clone())
+            .wrap_err_with(|| format!("Failed to parse file: {path:?}"))?;
+
+        let known = serde_json::to_value(&summary)?;
+        let unknown = unknown_fields(&raw, &known, "");
+        if !unknown.is_empty() {
+            return Err(eyre!(
+                "Dataset summary has unknown fields: {}",
+                unknown.join(", ")
+            ))
+            .wrap_err_with(|| format!("Failed strict validation: {path:?}"))
+            .suggestion("Was the summary created by a different version of rebar?");
+        }
+        summary
+            .validate_strict()
+            .wrap_err_with(|| format!("Failed strict validation: {path:?}"))?;
+
+        Ok(summary)
+    }
+
+    /// Validate the summary for strict mode (ex. validated clinical pipelines).
+    ///
+    /// Every file must have a checksum, and remote files must be downloaded
+    /// over https, rather than another scheme (ex. http, ftp).
+    pub fn validate_strict(&self) -> Result<(), Report> {
+        let mut problems = Vec::new();
+        for (name, file) in self.files() {
+            let url = file.url.to_lowercase();
+            let local = !url.contains("://") || url.starts_with("file://");
+            if !url.starts_with("https://") && !local {
+                problems.push(format!(
+                    "{name} has an insecure url (not https): {}",
+                    file.url
+                ));
+            }
+            if file.checksum.is_none() {
+                problems.push(format!("{name} has no checksum"));
+            }
+        }
+        if !problems.is_empty() {
+            return Err(eyre!("Dataset summary is not strict: {}", problems.join("; ")))
+                .suggestion("Please re-download the dataset with https urls, which records checksums.");
+        }
+        Ok(())
+    }
+
+    /// Verify the checksums of the files in a dataset directory.
+    ///
+    /// Files are found in the dataset directory by the name of their local
+    /// path, so that datasets can be moved after they were downloaded. Files
+    /// that are missing, or were modified after download, are an error.
+    pub fn verify_checksums(&self, dataset_dir: &Path) -> Result<(), Report> {
+        let mut problems = Vec::new();
+        for (name, file) in self.files() {
+            let (Some(expected), Some(file_name)) =
+                (&file.checksum, file.local_path.file_name())
+            else {
+                continue;
+            };
+            let path = dataset_dir.join(file_name);
+            if !path.exists() {
+                problems.push(format!("{name} is missing: {path:?}"));
+                continue;
+            }
+            let checksum = versioned_file::checksum(&path)?;
+            if &checksum != expected {
+                problems.push(format!(
+                    "{name} checksum does not match: expected {expected}, found {checksum}"
+                ));
+            }
+        }
+        if !problems.is_empty() {
+            return Err(eyre!("Dataset files have changed: {}", problems.join("; ")))
+                .suggestion("Please re-download the dataset, the files may have been modified after download.");
+        }
+        Ok(())
+    }
+}
+
+/// Get the fields of a JSON value that are not in a known value, as dotted
+/// paths (ex. misc.tree.extra).
+fn unknown_fields(
+    value: &serde_json::Value,
+    known: &serde_json::Value,
+    prefix: &str,
+) -> Vec<String> {
+    let (Some(value), Some(known)) = (value.as_object(), known.as_object()) else {
+        return Vec::new();
+    };
+    value
+        .iter()
+        .flat_map(|(key, value)| {
+            let path = format!("{prefix}{key}");
+            match known.get(key) {
+                Some(known) => unknown_fields(value, known, &format!("{path}.")),
+                None => vec![path],
+            }
+        })
+        .collect()
+}
+
+// ----------------------------------------------------------------------------
+// Tip Mapping
+
+/// Mapping of phylogeny tip labels to population names.
+///
+/// Used when importing external trees, whose labels are not population names
+/// (ex. sequence accessions). Many tips can map to one population, but a tip
+/// can only map to one population.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct TipMapping {
+    pub tips: BTreeMap<String, String>,
+}
+
+impl TipMapping {
+    pub fn new() -> Self {
+        TipMapping {
+            tips: BTreeMap::new(),
+        }
+    }
+
+    /// Read mapping from a TSV or CSV file, with columns 'tip' and 'population'.
+    pub fn read(path: &Path) -> Result<TipMapping, Report> {
+        let table = Table::read(path)?;
+        let tip_i = table.header_position("tip")?;
+        let population_i = table.header_position("population")?;
+
+        let mut mapping = TipMapping::new();
+        for row in &table.rows {
+            let (Some(tip), Some(population)) = (row.get(tip_i), row.get(population_i))
+            else {
+                continue;
+            };
+            if tip.is_empty() || population.is_empty() {
+                continue;
+            }
+            match mapping.tips.get(tip) {
+                Some(existing) if existing != population => {
+                    return Err(eyre!(
+                        "Tip {tip} is mapped to multiple populations: {existing}, {population}"
+                    ))
+                    .suggestion("A tip can only map to one population.")
+                    .wrap_err_with(|| format!("Failed to parse file: {path:?}"));
+                }
+                _ => {
+                    mapping.tips.insert(tip.clone(), population.clone());
+                }
+            }
+        }
+
+        if mapping.tips.is_empty() {
+            return Err(eyre!("Tip mapping is empty: {path:?}"))
+                .suggestion("Columns 'tip' and 'population' must have values.");
+        }
+
+        Ok(mapping)
+    }
+
+    /// Get the population of a tip.
+    pub fn population(&self, tip: &str) -> Option<&String> {
+        self.tips.get(tip)
+    }
+
+    /// Get the tips of a population.
+    pub fn population_tips(&self, population: &str) -> Vec<&String> {
+        self.tips.iter().filter(|(_, p)| *p == population).map(|(t, _)| t).collect()
+    }
+
+    /// Report how the node labels of a tree were mapped.
+    ///
+    /// Labels should be in preorder, the first label of a population is its
+    /// representative, and other labels are merged into it. Tips without a
+    /// mapping are unmapped, and mapped tips that are not in the tree are
+    /// not found.
+    pub fn report(&self, labels: &[String], tips: &[String]) -> Table {
+        let mut table = Table::new();
+        table.headers =
+            vec!["tip", "population", "status"].into_iter().map(String::from).collect();
+
+        let mut representatives = Vec::new();
+        for label in labels {
+            let Some(population) = self.population(label) else {
+                if tips.contains(label) {
+                    table.rows.push(vec![
+                        label.clone(),
+                        String::new(),
+                        "unmapped".into(),
+                    ]);
+                }
+                continue;
+            };
+            let status = if representatives.contains(&population) {
+                "merged"
+            } else {
+                representatives.push(population);
+                "representative"
+            };
+            table.rows.push(vec![label.clone(), population.clone(), status.into()]);
+        }
+
+        for (tip, population) in &self.tips {
+            if !labels.contains(tip) {
+                table.rows.push(vec![
+                    tip.clone(),
+                    population.clone(),
+                    "not_found".into(),
+                ]);
+            }
+        }
+
+        table
+    }
 }
 
 // ----------------------------------------------------------------------------
@@ -0,0 +1,57 @@
+use crate::sequence::{Sequence, Substitution};
+use itertools::Itertools;
+use std::collections::BTreeMap;
+
+// ----------------------------------------------------------------------------
+// Barcode Classes
+
+/// Populations with identical barcodes (substitutions), collapsed into
+/// equivalence classes.
+///
+/// Populations in a class have the same parsimony with every sequence, so
+/// only the representative of each class (the first member by name) needs to
+/// be searched. Matches are expanded back to all members.
+#[derive(Clone, Debug, Default)]
+pub struct BarcodeClasses {
+    /// Members of each class (including the representative), by representative.
+    pub classes: BTreeMap<String, Vec<String>>,
+}
+
+impl BarcodeClasses {
+    pub fn new() -> Self {
+        BarcodeClasses::default()
+    }
+
+    /// Collapse dataset populations with identical barcodes.
+    pub fn from_populations(populations: &BTreeMap<String, Sequence>) -> Self {
+        let mut barcodes: BTreeMap<Vec<Substitution>, Vec<String>> = BTreeMap::new();
+        for (name, sequence) in populations {
+            let barcode = sequence.substitutions.iter().copied().sorted().collect_vec();
+            barcodes.entry(barcode).or_default().push(name.clone());
+        }
+        let classes =
+            barcodes.into_values().map(|members| (members[0].clone(), members)).collect();
+        BarcodeClasses { classes }
+    }
+
+    /// Check if a population is the representative of its class.
+    pub fn is_representative(&self, population: &str) -> bool {
+        self.classes.contains_key(population)
+    }
+
+    /// Get the members of a class, by its representative.
+    pub fn members(&self, representative: &str) -> &[String] {
+        self.classes.get(representative).map(|m| m.as_slice()).unwrap_or_default()
+    }
+
+    /// Classes with more than one member, which are collapsed.
+    pub fn collapsed(&self) -> impl Iterator<Item = (&String, &Vec<String>)> {
+        self.classes.iter().filter(|(_rep, members)| members.len() > 1)
+    }
+
+    /// Number of populations that are not searched, because they are collapsed
+    /// into a representative.
+    pub fn num_collapsed(&self) -> usize {
+        self.collapsed().map(|(_rep, members)| members.len() - 1).sum()
+    }
+}
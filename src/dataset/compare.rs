@@ -0,0 +1,56 @@
+use crate::cli;
+use crate::dataset::load;
+use crate::utils::table::Table;
+use color_eyre::eyre::{Report, Result};
+use itertools::Itertools;
+use log::info;
+
+/// Compare two dataset populations, reporting blocks of differences.
+pub fn compare(args: &cli::dataset::compare::Args) -> Result<(), Report> {
+    // differences are reported without masking the genome ends
+    let mask = vec![0, 0];
+    let dataset = load::dataset(&args.dataset_dir, &mask)?;
+
+    let regions = dataset.compare_populations(&args.a, &args.b)?;
+
+    let mut table = Table::new();
+    table.headers = vec![
+        "start",
+        "end",
+        "population",
+        "num_substitutions",
+        "substitutions",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect_vec();
+    table.rows = regions
+        .iter()
+        .map(|region| {
+            vec![
+                region.start.to_string(),
+                region.end.to_string(),
+                region.origin.clone(),
+                region.substitutions.len().to_string(),
+                region.substitutions.iter().join(","),
+            ]
+        })
+        .collect_vec();
+
+    info!(
+        "Found {} block(s) of differences between {} and {}.",
+        regions.len(),
+        args.a,
+        args.b
+    );
+
+    println!("\n{}", table.to_markdown()?);
+
+    if let Some(output_dir) = &args.output_dir {
+        let output_path = output_dir.join(format!("compare_{}_{}.tsv", args.a, args.b));
+        info!("Exporting comparison: {output_path:?}");
+        table.write(&output_path)?;
+    }
+
+    Ok(())
+}
@@ -1,13 +1,18 @@
 use crate::cli;
 use crate::dataset;
 use crate::dataset::attributes::{check_compatibility, Name, Summary};
-use crate::dataset::{sarscov2, toy1};
-use crate::{utils, utils::remote_file::RemoteFile};
-use color_eyre::eyre::{Report, Result};
+use crate::dataset::{ncbi, sarscov2, toy1, toy_mlst, Dataset};
+use crate::phylogeny::Phylogeny;
+use crate::utils;
+use crate::utils::table::Table;
+use crate::utils::versioned_file::VersionedFile;
+use color_eyre::eyre::{eyre, Report, Result, WrapErr};
+use color_eyre::Help;
 use itertools::Itertools;
 use log::{info, warn};
 use std::fs::create_dir_all;
 use std::path::Path;
+use strum::EnumProperty;
 
 /// Download dataset
 pub async fn dataset(args: &mut cli::dataset::download::Args) -> Result<(), Report> {
@@ -18,7 +23,11 @@ pub async fn dataset(args: &mut cli::dataset::download::Args) -> Result<(), Repo
 
     let mut summary: Summary = if let Some(summary_path) = &args.summary {
         info!("Importing summary: {summary_path:?}");
-        let summary = Summary::read(summary_path)?;
+        let summary = if args.strict {
+            Summary::read_strict(summary_path)?
+        } else {
+            Summary::read(summary_path)?
+        };
 
         // Warn if summary conflicts with any CLI args
         if summary.name != args.name || summary.tag != args.tag {
@@ -35,9 +44,18 @@ pub async fn dataset(args: &mut cli::dataset::download::Args) -> Result<(), Repo
         summary
     };
 
+    // An accession replaces the reference of the summary, and its GenBank record
+    if args.accession.is_some() && args.accession != summary.accession {
+        summary.accession = args.accession.clone();
+        summary.misc.remove("genbank");
+    }
+
     // --------------------------------------------------------------------
     // Compatibility Check
 
+    if args.name.get_str("implemented").unwrap_or("false") != "true" {
+        return Err(not_implemented(&args.name));
+    }
     check_compatibility(&args.name, &args.tag)?;
 
     // Warn if the directory already exists
@@ -57,7 +75,9 @@ pub async fn dataset(args: &mut cli::dataset::download::Args) -> Result<(), Repo
     let output_path = args.output_dir.join("reference.fasta");
     info!("Downloading reference: {output_path:?}");
 
-    summary.reference = if args.summary.is_some() {
+    summary.reference = if summary.accession.is_some() {
+        ncbi::reference(&mut summary, &args.output_dir).await?
+    } else if args.summary.is_some() {
         snapshot(&summary.reference, &output_path).await?
     } else {
         match args.name {
@@ -65,7 +85,11 @@ pub async fn dataset(args: &mut cli::dataset::download::Args) -> Result<(), Repo
                 sarscov2::download::reference(&args.tag, &output_path).await?
             }
             Name::Toy1 => toy1::download::reference(&args.tag, &output_path)?,
-            _ => todo!(),
+            Name::ToyMlst => toy_mlst::download::reference(&args.tag, &output_path)?,
+            _ => {
+                return Err(eyre!("Dataset {} has no default reference.", args.name))
+                    .suggestion("Please specify the reference with --accession.")
+            }
         }
     };
 
@@ -83,7 +107,8 @@ pub async fn dataset(args: &mut cli::dataset::download::Args) -> Result<(), Repo
                 sarscov2::download::populations(&args.tag, &output_path).await?
             }
             Name::Toy1 => toy1::download::populations(&args.tag, &output_path)?,
-            _ => todo!(),
+            Name::ToyMlst => toy_mlst::download::populations(&args.tag, &output_path)?,
+            _ => return Err(not_implemented(&args.name)),
         }
     };
 
@@ -94,37 +119,134 @@ pub async fn dataset(args: &mut cli::dataset::download::Args) -> Result<(), Repo
     info!("Creating annotations: {output_path:?}");
 
     let annotations = match args.name {
+        _ if summary.accession.is_some() => ncbi::annotations(&summary)?,
         Name::SarsCov2 => sarscov2::annotations::build()?,
         Name::Toy1 => toy1::annotations::build()?,
-        _ => todo!(),
+        Name::ToyMlst => toy_mlst::annotations::build()?,
+        _ => return Err(not_implemented(&args.name)),
     };
     annotations.write(&output_path)?;
 
+    // --------------------------------------------------------------------
+    // Dates (Optional)
+
+    let output_path = args.output_dir.join("dates.tsv");
+    let dates = match args.name {
+        Name::Toy1 => Some(toy1::dates::build()?),
+        _ => None,
+    };
+    if let Some(dates) = dates {
+        info!("Creating dates: {output_path:?}");
+        dates.write(&output_path)?;
+    }
+
     // --------------------------------------------------------------------
     // Graph (Phylogeny)
 
     let output_path = args.output_dir.join("phylogeny.json");
     info!("Building phylogeny: {output_path:?}");
 
-    let phylogeny = match args.name {
+    let mut phylogeny = match args.name {
         Name::SarsCov2 => {
             sarscov2::phylogeny::build(&mut summary, &args.output_dir).await?
         }
         Name::Toy1 => toy1::phylogeny::build()?,
-        _ => todo!(),
+        Name::ToyMlst => toy_mlst::phylogeny::build()?,
+        _ => return Err(not_implemented(&args.name)),
     };
+    let dates_path = args.output_dir.join("dates.tsv");
+    if dates_path.exists() {
+        phylogeny.dates = dataset::load::parse_dates(&dates_path)?;
+    }
+    if let Some(other_path) = &args.phylogeny {
+        info!("Merging phylogeny: {other_path:?}");
+        let other = Phylogeny::read(other_path)?;
+        let conflicts = phylogeny
+            .merge(&other, args.conflict_policy)
+            .wrap_err_with(|| format!("Failed to merge phylogeny: {other_path:?}"))?;
+
+        let output_path = args.output_dir.join("phylogeny_conflicts.tsv");
+        info!("Exporting phylogeny conflicts: {output_path:?}");
+        let mut table = Table::new();
+        table.headers = vec!["population", "parents", "other_parents"]
+            .into_iter()
+            .map(String::from)
+            .collect_vec();
+        table.rows = conflicts
+            .iter()
+            .map(|c| {
+                vec![
+                    c.name.clone(),
+                    c.parents.join(","),
+                    c.other_parents.join(","),
+                ]
+            })
+            .collect_vec();
+        table.write(&output_path)?;
+        if !conflicts.is_empty() {
+            warn!(
+                "{} population(s) have conflicting parents in {other_path:?}, see: {output_path:?}",
+                conflicts.len()
+            );
+        }
+    }
+    phylogeny.theme = args.theme.clone();
+    phylogeny.validate_branch_lengths()?;
+    summary.branch_length = phylogeny.branch_length;
     phylogeny.write(&output_path)?;
     // Also write as .dot file for graphviz visualization.
     let output_path = args.output_dir.join("phylogeny.dot");
     info!("Exporting graphviz phylogeny: {output_path:?}");
     phylogeny.write(&output_path)?;
+    // Also write as .graphml file for cytoscape and gephi.
+    let output_path = args.output_dir.join("phylogeny.graphml");
+    info!("Exporting GraphML phylogeny: {output_path:?}");
+    phylogeny.write(&output_path)?;
+
+    // --------------------------------------------------------------------
+    // Population Quality (Optional)
+
+    let mask = vec![0, 0];
+    if args.max_missing.is_some() || args.max_private.is_some() {
+        let output_path = args.output_dir.join("populations_excluded.tsv");
+        info!("Checking population quality: {output_path:?}");
+        let populations_path = &summary.populations.local_path;
+
+        let mut dataset = Dataset::new();
+        (dataset.populations, dataset.mutations) = dataset::load::parse_populations(
+            populations_path,
+            &summary.reference.local_path,
+            &mask,
+        )?;
+        dataset.phylogeny = phylogeny.clone();
+        let excluded =
+            dataset.check_population_quality(args.max_missing, args.max_private)?;
+        excluded.write(&output_path)?;
+
+        if !excluded.rows.is_empty() {
+            warn!(
+                "{} population(s) were excluded for low quality, see: {output_path:?}",
+                excluded.rows.len()
+            );
+            let excluded = excluded.rows.iter().map(|row| &row[0]).collect_vec();
+            let mut sequences = String::new();
+            for record in utils::read_fasta(populations_path)?.records() {
+                let record = record?;
+                if excluded.contains(&&record.id().to_string()) {
+                    continue;
+                }
+                let seq = String::from_utf8_lossy(record.seq());
+                sequences.push_str(&format!(">{}\n{seq}\n", record.id()));
+            }
+            utils::write_atomic(populations_path, sequences)?;
+        }
+    }
 
     // --------------------------------------------------------------------
     // Export Mutations
 
     let output_path = args.output_dir.join("mutations.json");
     info!("Mapping mutations to populations: {output_path:?}");
-    let mask = vec![0, 0];
     let (_populations, mutations) = dataset::load::parse_populations(
         &summary.populations.local_path,
         &summary.reference.local_path,
@@ -144,7 +266,8 @@ pub async fn dataset(args: &mut cli::dataset::download::Args) -> Result<(), Repo
     let mut edge_cases = match args.name {
         Name::SarsCov2 => dataset::sarscov2::edge_cases::default()?,
         Name::Toy1 => dataset::toy1::edge_cases::default()?,
-        _ => todo!(),
+        Name::ToyMlst => dataset::toy_mlst::edge_cases::default()?,
+        _ => return Err(not_implemented(&args.name)),
     };
     let manual_populations =
         edge_cases.iter().filter_map(|e| e.population.clone()).collect_vec();
@@ -173,10 +296,29 @@ pub async fn dataset(args: &mut cli::dataset::download::Args) -> Result<(), Repo
     // --------------------------------------------------------------------
     // Export Summary
 
+    summary.update_checksums()?;
+    if args.strict {
+        summary.validate_strict()?;
+    }
     let output_path = args.output_dir.join("summary.json");
     info!("Exporting summary: {output_path:?}");
     summary.write(&output_path)?;
 
+    // --------------------------------------------------------------------
+    // Phylogeny Consistency
+
+    let output_path = args.output_dir.join("phylogeny_consistency.tsv");
+    info!("Checking phylogeny consistency: {output_path:?}");
+    let dataset = dataset::load::dataset(&args.output_dir, &mask)?;
+    let consistency = dataset.check_phylogeny_consistency()?;
+    if !consistency.rows.is_empty() {
+        warn!(
+            "{} population(s) have substitutions that conflict with the phylogeny.",
+            consistency.rows.len()
+        );
+    }
+    consistency.write(&output_path)?;
+
     // --------------------------------------------------------------------
     // Finish
 
@@ -186,19 +328,19 @@ pub async fn dataset(args: &mut cli::dataset::download::Args) -> Result<(), Repo
 
 /// Download remote file from a summary snapshot.
 pub async fn snapshot(
-    snapshot: &RemoteFile,
+    snapshot: &VersionedFile,
     output_path: &Path,
-) -> Result<RemoteFile, Report> {
-    // Check extension for decompression
-    let ext = utils::path_to_ext(Path::new(&snapshot.url))?;
-    let decompress = ext == "zst";
-
-    // Update the local path to the desired output
+) -> Result<VersionedFile, Report> {
+    // Update the local path to the desired output, and fetch from the source
     let mut remote_file = snapshot.clone();
     remote_file.local_path = output_path.to_path_buf();
-
-    // Download the file
-    utils::download_file(&snapshot.url, output_path, decompress).await?;
+    remote_file.fetch().await?;
 
     Ok(remote_file)
 }
+
+/// The error for a dataset that can't be downloaded yet.
+fn not_implemented(name: &Name) -> Report {
+    eyre!("Dataset {name} is not implemented yet.")
+        .suggestion("Please see the available datasets with: rebar dataset list")
+}
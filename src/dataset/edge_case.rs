@@ -0,0 +1,113 @@
+use crate::cli;
+use crate::dataset::{load, not_found};
+use color_eyre::eyre::{eyre, Report, Result, WrapErr};
+use color_eyre::Help;
+use log::info;
+
+/// Add an edge case to a dataset, or update the edge case of a population.
+///
+/// The population, parents, and knockout are validated against the dataset,
+/// and edge_cases.json is checked that it can be read back before it is
+/// replaced.
+pub fn add(args: &cli::dataset::edge_case::AddArgs) -> Result<(), Report> {
+    // loading the dataset also checks that the current edge cases can be read
+    let mask = vec![0, 0];
+    let dataset = load::dataset(&args.dataset_dir, &mask)?;
+    let population = &dataset.canonical_name(&args.population)?;
+
+    // ------------------------------------------------------------------------
+    // Validate
+
+    // edge cases are applied to the recombinant ancestor of the best match
+    if dataset.phylogeny.is_empty() {
+        return Err(eyre!("Dataset has no phylogeny: {:?}", args.dataset_dir))
+            .suggestion("Edge cases are only applied to recombinants in the phylogeny.");
+    }
+    if dataset.phylogeny.get_node(population).is_err() {
+        let names = dataset.phylogeny.get_names()?;
+        return Err(not_found(population, "dataset phylogeny", &names));
+    }
+    if !dataset.phylogeny.is_recombinant(population)? {
+        return Err(eyre!(
+            "{population} is not a recombinant in the dataset phylogeny."
+        ))
+        .suggestion("Edge cases are only applied to recombinants.");
+    }
+    for populations in [&args.parents, &args.knockout].into_iter().flatten() {
+        let expanded = dataset.expand_populations(populations)?;
+        if expanded.is_empty() {
+            return Err(eyre!("No populations match: {}", populations.join(",")));
+        }
+    }
+    for (name, value) in [
+        ("--max-parents", args.max_parents),
+        ("--max-iter", args.max_iter),
+    ] {
+        if value == Some(0) {
+            return Err(eyre!("{name} must be greater than 0."));
+        }
+    }
+
+    // ------------------------------------------------------------------------
+    // Add or Update
+
+    let mut edge_cases = dataset.edge_cases;
+    let position =
+        edge_cases.iter().position(|e| e.population.as_ref() == Some(population));
+    let edge_case = match position {
+        Some(i) => {
+            info!("Updating edge case: {population}");
+            &mut edge_cases[i]
+        }
+        None => {
+            info!("Adding edge case: {population}");
+            edge_cases.push(cli::run::Args {
+                population: Some(population.clone()),
+                ..Default::default()
+            });
+            edge_cases.last_mut().unwrap()
+        }
+    };
+
+    if let Some(parents) = &args.parents {
+        edge_case.parents = Some(parents.clone());
+    }
+    if let Some(knockout) = &args.knockout {
+        edge_case.knockout = Some(knockout.clone());
+    }
+    if let Some(max_iter) = args.max_iter {
+        edge_case.max_iter = max_iter;
+    }
+    if let Some(max_parents) = args.max_parents {
+        edge_case.max_parents = max_parents;
+    }
+    if let Some(min_consecutive) = args.min_consecutive {
+        edge_case.min_consecutive = min_consecutive;
+    }
+    if let Some(min_length) = args.min_length {
+        edge_case.min_length = min_length;
+    }
+    if let Some(min_subs) = args.min_subs {
+        edge_case.min_subs = min_subs;
+    }
+    if let Some(naive) = args.naive {
+        edge_case.naive = naive;
+    }
+    if let Some(reason) = &args.reason {
+        edge_case.reason = Some(reason.clone());
+    }
+
+    // ------------------------------------------------------------------------
+    // Export
+
+    // check that the edge cases can be read back, before replacing the file
+    let output_path = args.dataset_dir.join("edge_cases.json");
+    let json = serde_json::to_string_pretty(&edge_cases)?;
+    serde_json::from_str::<Vec<cli::run::Args>>(&json)
+        .wrap_err_with(|| format!("Failed to validate edge cases: {output_path:?}"))?;
+
+    info!("Exporting edge cases: {output_path:?}");
+    cli::run::Args::write(&edge_cases, &output_path)?;
+
+    Ok(())
+}
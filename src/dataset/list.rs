@@ -14,6 +14,7 @@ pub fn datasets(args: &cli::dataset::list::Args) -> Result<(), Report> {
         "CLI Version",
         "Minimum Tag Date",
         "Maximum Tag Date",
+        "Population Dates",
     ]
     .into_iter()
     .map(String::from)
@@ -53,6 +54,7 @@ pub fn datasets(args: &cli::dataset::list::Args) -> Result<(), Report> {
             cli_version.to_string(),
             min_date.to_string(),
             max_date.to_string(),
+            name.get_str("dates").unwrap_or("false").to_string(),
         ];
         table.rows.push(row);
     }
@@ -3,8 +3,11 @@ use crate::dataset::attributes::{Name, Summary, Tag};
 use crate::dataset::Dataset;
 use crate::phylogeny::Phylogeny;
 use crate::sequence::{read_reference, Sequence, Substitution};
-use bio::io::fasta;
+use crate::utils;
+use crate::utils::table::Table;
+use chrono::NaiveDate;
 use color_eyre::eyre::{eyre, Report, Result, WrapErr};
+use color_eyre::Help;
 use log::{info, warn};
 use std::collections::BTreeMap;
 use std::path::Path;
@@ -36,15 +39,17 @@ pub fn dataset(dataset_dir: &Path, mask: &Vec<usize>) -> Result<Dataset, Report>
 
     // Summary
     let summary_path = dataset_dir.join("summary.json");
-    if summary_path.exists() {
+    let summary = if summary_path.exists() {
         let summary = Summary::read(&summary_path)?;
         dataset.name = summary.name;
-        dataset.tag = summary.tag;
+        dataset.tag = summary.tag.clone();
+        Some(summary)
     } else {
         warn!("No summary was found: {summary_path:?}");
         dataset.name = Name::Custom;
         dataset.tag = Tag::Custom;
-    }
+        None
+    };
 
     // Edge Cases
     let edge_cases_path = dataset_dir.join("edge_cases.json");
@@ -56,6 +61,15 @@ pub fn dataset(dataset_dir: &Path, mask: &Vec<usize>) -> Result<Dataset, Report>
         Vec::new()
     };
 
+    // Annotations
+    let annotations_path = dataset_dir.join("annotations.tsv");
+    dataset.annotations = if annotations_path.exists() {
+        Table::read(&annotations_path)?
+    } else {
+        warn!("No annotations were found: {annotations_path:?}");
+        Table::new()
+    };
+
     // Phylogeny
     let phylogeny_path = dataset_dir.join("phylogeny.json");
     dataset.phylogeny = if phylogeny_path.exists() {
@@ -64,6 +78,25 @@ pub fn dataset(dataset_dir: &Path, mask: &Vec<usize>) -> Result<Dataset, Report>
         warn!("No phylogeny was found: {phylogeny_path:?}");
         Phylogeny::new()
     };
+    dataset.phylogeny.validate_branch_lengths()?;
+    if let Some(summary) = &summary {
+        if phylogeny_path.exists()
+            && summary.branch_length != dataset.phylogeny.branch_length
+        {
+            return Err(eyre!(
+                "Phylogeny branch lengths ({}) do not match the dataset summary ({}).",
+                dataset.phylogeny.branch_length,
+                summary.branch_length
+            ))
+            .suggestion("Please convert the phylogeny, or update summary.json.");
+        }
+    }
+
+    // Dates, these take precedence over dates in the phylogeny
+    let dates_path = dataset_dir.join("dates.tsv");
+    if dates_path.exists() {
+        dataset.phylogeny.dates.extend(parse_dates(&dates_path)?);
+    }
 
     // --------------------------------------------------------------------
     // Done
@@ -71,6 +104,31 @@ pub fn dataset(dataset_dir: &Path, mask: &Vec<usize>) -> Result<Dataset, Report>
     Ok(dataset)
 }
 
+// ----------------------------------------------------------------------------
+// Alignment
+// ----------------------------------------------------------------------------
+
+/// Load dataset from the sequences of an alignment.
+///
+/// The first sequence is the reference, and every sequence (including the
+/// first) is a population. There is no phylogeny or edge cases.
+pub fn alignment(alignment_path: &Path, mask: &Vec<usize>) -> Result<Dataset, Report> {
+    info!("Loading dataset from alignment: {:?}", alignment_path);
+
+    let mut dataset = Dataset::new();
+    dataset.reference = read_reference(alignment_path, mask)?;
+    (dataset.populations, dataset.mutations) =
+        parse_populations(alignment_path, alignment_path, mask)?;
+
+    if dataset.populations.len() < 2 {
+        return Err(eyre!(
+            "Alignment {alignment_path:?} must contain at least 2 sequences."
+        ));
+    }
+
+    Ok(dataset)
+}
+
 // ----------------------------------------------------------------------------
 // Parse Populations
 // ----------------------------------------------------------------------------
@@ -88,9 +146,7 @@ pub fn parse_populations(
     Report,
 > {
     // read in populations from fasta
-    let populations_reader = fasta::Reader::from_file(populations_path)
-        .map_err(|e| eyre!(e))
-        .wrap_err(format!("Failed to read file: {populations_path:?}"))?;
+    let populations_reader = utils::read_fasta(populations_path)?;
 
     // read in reference from fasta
     let reference = read_reference(reference_path, mask)?;
@@ -100,13 +156,41 @@ pub fn parse_populations(
 
     for result in populations_reader.records() {
         let record = result?;
-        let sequence = Sequence::from_record(record, Some(&reference), mask)?;
-        populations.insert(sequence.id.clone(), sequence.clone());
+        let mut sequence = Sequence::from_record(record, Some(&reference), mask)?;
+        // populations are stored sparsely, bases are looked up by Sequence::base
+        sequence.compact();
 
-        for sub in sequence.substitutions {
-            mutations.entry(sub).or_insert(Vec::new()).push(sequence.id.clone());
+        for sub in &sequence.substitutions {
+            mutations.entry(*sub).or_insert(Vec::new()).push(sequence.id.clone());
         }
+        populations.insert(sequence.id.clone(), sequence);
     }
 
     Ok((populations, mutations))
 }
+
+// ----------------------------------------------------------------------------
+// Parse Dates
+// ----------------------------------------------------------------------------
+
+/// Parse population dates (ex. designation, first-seen) from a table.
+///
+/// The table must have the columns 'population' and 'date' (YYYY-MM-DD).
+pub fn parse_dates(path: &Path) -> Result<BTreeMap<String, NaiveDate>, Report> {
+    let table = Table::read(path)?;
+    let population_i = table.header_position("population")?;
+    let date_i = table.header_position("date")?;
+
+    let dates = table
+        .rows
+        .iter()
+        .map(|row| {
+            let date = NaiveDate::parse_from_str(&row[date_i], "%Y-%m-%d")
+                .wrap_err_with(|| format!("Failed to parse date: {}", row[date_i]))?;
+            Ok((row[population_i].clone(), date))
+        })
+        .collect::<Result<BTreeMap<_, _>, Report>>()
+        .wrap_err_with(|| format!("Failed to parse dates: {path:?}"))?;
+
+    Ok(dates)
+}
@@ -0,0 +1,86 @@
+use crate::cli;
+use crate::dataset::{load, Dataset};
+use crate::utils::table::Table;
+use color_eyre::eyre::{eyre, Report, Result};
+use itertools::Itertools;
+use log::info;
+use std::collections::BTreeSet;
+
+/// Export a presence/absence matrix of the substitutions of dataset populations.
+pub fn export_matrix(args: &cli::dataset::export_matrix::Args) -> Result<(), Report> {
+    // substitutions are reported without masking the genome ends
+    let mask = vec![0, 0];
+    let dataset = load::dataset(&args.dataset_dir, &mask)?;
+
+    let populations = dataset.expand_populations(&args.populations)?;
+    let table = matrix(&dataset, &populations)?;
+    info!(
+        "Found {} informative site(s) in {} population(s).",
+        table.rows.len(),
+        populations.len()
+    );
+
+    let output_path = args.output_dir.join("matrix.tsv");
+    info!("Exporting matrix: {output_path:?}");
+    table.write(&output_path)?;
+
+    Ok(())
+}
+
+/// Create a presence/absence matrix of population substitutions.
+///
+/// Rows are substitutions (coord, reference, alt) in coordinate order, and
+/// columns are populations, with 1 if present, 0 if absent, and NA if the
+/// population is missing data at the coordinate. Only informative sites are
+/// kept, where the substitution is present in some but not all populations
+/// with data.
+pub fn matrix(dataset: &Dataset, populations: &[String]) -> Result<Table, Report> {
+    let sequences = populations
+        .iter()
+        .map(|p| {
+            dataset
+                .populations
+                .get(p)
+                .ok_or_else(|| eyre!("Population {p} has no sequence in the dataset."))
+        })
+        .collect::<Result<Vec<_>, Report>>()?;
+
+    let mut table = Table::new();
+    table.headers = ["coord", "reference", "alt"]
+        .into_iter()
+        .map(String::from)
+        .chain(populations.iter().cloned())
+        .collect_vec();
+
+    let substitutions: BTreeSet<(usize, char, char)> = sequences
+        .iter()
+        .flat_map(|s| s.substitutions.iter().map(|s| (s.coord, s.reference, s.alt)))
+        .collect();
+
+    for (coord, reference, alt) in substitutions {
+        let values = sequences
+            .iter()
+            .map(|s| {
+                if s.missing.contains(&coord) {
+                    "NA"
+                } else if s.substitutions.iter().any(|s| s.coord == coord && s.alt == alt)
+                {
+                    "1"
+                } else {
+                    "0"
+                }
+            })
+            .collect_vec();
+        // skip substitutions shared by all populations with data
+        if !values.contains(&"0") {
+            continue;
+        }
+        let row = [coord.to_string(), reference.to_string(), alt.to_string()]
+            .into_iter()
+            .chain(values.into_iter().map(String::from))
+            .collect_vec();
+        table.rows.push(row);
+    }
+
+    Ok(table)
+}
@@ -0,0 +1,89 @@
+use crate::sequence::{Sequence, Substitution};
+use itertools::Itertools;
+use std::collections::BTreeMap;
+
+/// Number of hash functions in a MinHash signature.
+pub const NUM_HASHES: usize = 128;
+
+// ----------------------------------------------------------------------------
+// MinHash Index
+
+/// MinHash signatures of population substitutions, for approximate search.
+///
+/// The fraction of signature positions that two populations share estimates the
+/// Jaccard similarity of their substitutions, in constant time regardless of
+/// how many substitutions they have.
+#[derive(Clone, Debug, Default)]
+pub struct MinHashIndex {
+    pub signatures: BTreeMap<String, Vec<u64>>,
+}
+
+impl MinHashIndex {
+    pub fn new() -> Self {
+        MinHashIndex::default()
+    }
+
+    /// Create an index of the signatures of dataset populations.
+    pub fn from_populations(populations: &BTreeMap<String, Sequence>) -> Self {
+        let signatures = populations
+            .iter()
+            .map(|(name, sequence)| (name.clone(), signature(&sequence.substitutions)))
+            .collect();
+        MinHashIndex { signatures }
+    }
+
+    /// Get the k nearest populations to a sequence, by estimated similarity.
+    ///
+    /// Optionally restricted to a list of populations. Populations with the same
+    /// similarity are ordered by name.
+    pub fn nearest<'p>(
+        &'p self,
+        sequence: &Sequence,
+        populations: Option<&Vec<&String>>,
+        k: usize,
+    ) -> Vec<&'p String> {
+        let query = signature(&sequence.substitutions);
+        self.signatures
+            .iter()
+            .filter(|(name, _)| populations.map(|p| p.contains(name)).unwrap_or(true))
+            .map(|(name, signature)| (name, similarity(&query, signature)))
+            .sorted_by(|a, b| b.1.cmp(&a.1))
+            .take(k)
+            .map(|(name, _)| name)
+            .collect_vec()
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Functions
+// ----------------------------------------------------------------------------
+
+/// Hash a 64-bit key (splitmix64 finalizer), so signatures are reproducible.
+fn mix(key: u64) -> u64 {
+    let mut z = key.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Create the MinHash signature of substitutions.
+///
+/// Each position is the minimum of a different hash function over the
+/// substitutions. Without substitutions, all positions are the maximum.
+pub fn signature(substitutions: &[Substitution]) -> Vec<u64> {
+    let keys = substitutions
+        .iter()
+        .map(|sub| mix(((sub.coord as u64) << 8) | sub.alt as u64))
+        .collect_vec();
+    (0..NUM_HASHES as u64)
+        .map(|i| {
+            let seed = mix(i);
+            keys.iter().map(|key| mix(key ^ seed)).min().unwrap_or(u64::MAX)
+        })
+        .collect_vec()
+}
+
+/// Number of positions two signatures share, out of NUM_HASHES.
+pub fn similarity(a: &[u64], b: &[u64]) -> usize {
+    a.iter().zip(b).filter(|(a, b)| a == b).count()
+}
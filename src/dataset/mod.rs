@@ -1,24 +1,50 @@
 pub mod attributes;
+pub mod barcodes;
+pub mod compare;
 pub mod download;
+pub mod edge_case;
 pub mod list;
 pub mod load;
+pub mod matrix;
+pub mod minhash;
+pub mod ncbi;
+pub mod nextclade;
+pub mod phylogeny;
+pub mod query;
+pub mod remote;
 pub mod sarscov2;
+pub mod selection;
+pub mod self_test;
+pub mod signature;
+pub mod stats;
 pub mod toy1;
+pub mod toy_mlst;
 
 use crate::cli::run;
+use crate::dataset::attributes::NameMatching;
+use crate::dataset::barcodes::BarcodeClasses;
+use crate::dataset::minhash::MinHashIndex;
 use crate::phylogeny::Phylogeny;
+use crate::recombination::amplicons::PrimerScheme;
+use crate::recombination::pairs::{self, PairCache, PairProfile};
+use crate::recombination::Region;
+use crate::sequence::scoring::{self, ScoringModel};
 use crate::sequence::{parsimony, Sequence, Substitution};
+use crate::utils;
+use crate::utils::table::Table;
+use chrono::NaiveDate;
 use color_eyre::eyre::{eyre, Report, Result, WrapErr};
+use color_eyre::Help;
 use indoc::formatdoc;
 use itertools::Itertools;
-use log::debug;
+use log::{debug, info};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::default::Default;
 use std::fmt;
-use std::fs::File;
-use std::io::Write;
 use std::path::Path;
+use std::sync::Arc;
 
 // ----------------------------------------------------------------------------
 // Dataset
@@ -32,6 +58,24 @@ pub struct Dataset {
     pub mutations: BTreeMap<Substitution, Vec<String>>,
     pub phylogeny: Phylogeny,
     pub edge_cases: Vec<run::Args>,
+    #[serde(skip)]
+    pub annotations: Table,
+    #[serde(skip)]
+    pub pair_cache: PairCache,
+    #[serde(skip)]
+    pub minhash: Option<MinHashIndex>,
+    /// Populations with identical barcodes, collapsed for search.
+    #[serde(skip)]
+    pub barcode_classes: Option<BarcodeClasses>,
+    /// Amplicons of the primer scheme of the sequences, to detect dropouts.
+    #[serde(skip)]
+    pub primer_scheme: Option<PrimerScheme>,
+    /// Model to rank candidate populations in searches, parsimony by default.
+    #[serde(skip, default = "scoring::default_model")]
+    pub scoring: Arc<dyn ScoringModel>,
+    /// How strictly population names are matched to the dataset.
+    #[serde(skip)]
+    pub name_matching: attributes::NameMatching,
 }
 
 impl fmt::Display for Dataset {
@@ -56,6 +100,13 @@ impl Dataset {
             mutations: BTreeMap::new(),
             phylogeny: Phylogeny::new(),
             edge_cases: Vec::new(),
+            annotations: Table::new(),
+            pair_cache: PairCache::new(),
+            minhash: None,
+            barcode_classes: None,
+            primer_scheme: None,
+            scoring: scoring::default_model(),
+            name_matching: attributes::NameMatching::default(),
         }
     }
 
@@ -73,9 +124,13 @@ impl Dataset {
             .collect_vec();
 
         // construct consensus
-        let consensus = (0..self.reference.genome_length)
+        let consensus = (1..=self.reference.genome_length)
             .map(|coord| {
-                let bases = sequences.iter().map(|s| s.seq[coord]).unique().collect_vec();
+                let bases = sequences
+                    .iter()
+                    .map(|s| s.base(coord, &self.reference))
+                    .unique()
+                    .collect_vec();
                 if bases.len() == 1 {
                     bases[0]
                 } else {
@@ -96,7 +151,96 @@ impl Dataset {
         Ok(sequence)
     }
 
-    /// Expand list of populations with wildcarding.
+    /// Expand a population name with wildcarding.
+    /// Get the canonical form of a population name, as it is in the dataset.
+    ///
+    /// Depending on the name matching, whitespace and case are ignored (ex.
+    /// 'xbb.1.5 ' is 'XBB.1.5'). Wildcards are matched by the name before the
+    /// '*'. Names that don't match are returned as is (trimmed), so that they
+    /// can be reported as not found with suggestions.
+    pub fn canonical_name(&self, name: &str) -> Result<String, Report> {
+        let trimmed = match self.name_matching {
+            NameMatching::Exact => name,
+            NameMatching::Trim | NameMatching::IgnoreCase => name.trim(),
+        };
+        let ignore_case = self.name_matching == NameMatching::IgnoreCase;
+
+        // wildcards, match the name before the '*'
+        if trimmed == "*" || trimmed == "X*" || (ignore_case && trimmed == "x*") {
+            return Ok(trimmed.to_uppercase());
+        }
+        if let Some(stem) = trimmed.strip_suffix('*') {
+            return Ok(format!("{}*", self.canonical_name(stem)?));
+        }
+
+        let exists = |p: &str| {
+            self.populations.contains_key(p) || self.phylogeny.get_node(p).is_ok()
+        };
+        let canonical = if exists(trimmed) || !ignore_case {
+            trimmed.to_string()
+        } else {
+            let names = self.phylogeny.get_names()?;
+            let matches = self
+                .populations
+                .keys()
+                .chain(names.iter())
+                .filter(|p| p.eq_ignore_ascii_case(trimmed))
+                .unique()
+                .collect_vec();
+            match matches.len() {
+                0 => trimmed.to_string(),
+                1 => matches[0].to_string(),
+                _ => {
+                    return Err(eyre!(
+                        "Population {trimmed} matches multiple populations, ignoring case: {}",
+                        matches.iter().join(", ")
+                    ))
+                    .suggestion("Please use the exact case of the population name.")
+                }
+            }
+        };
+
+        if canonical != name && exists(&canonical) {
+            info!("Population {name:?} was matched to {canonical:?}.");
+        }
+        Ok(canonical)
+    }
+
+    pub fn expand_population(&self, p: &str) -> Result<Vec<String>, Report> {
+        let p = &self.canonical_name(p)?;
+        // if population is '*', use all populations in dataset
+        if p == "*" {
+            Ok(self.populations.keys().cloned().collect_vec())
+        }
+        // if population is 'X*', use all recombinants in dataset
+        else if p == "X*" {
+            Ok(self.phylogeny.get_recombinants_all()?)
+        }
+        // if population ends with '*' expand descendants
+        else if p.ends_with('*') {
+            let p = p.replace('*', "");
+            if self.phylogeny.get_node(&p).is_err() {
+                let names = self.phylogeny.get_names()?;
+                return Err(not_found(&p, "dataset phylogeny", &names));
+            }
+            self.phylogeny.get_descendants(&p)
+        }
+        // simple population name, that is in the dataset
+        else if self.populations.contains_key(p) {
+            Ok(vec![p.to_string()])
+        } else {
+            Err(not_found(p, "dataset", self.populations.keys()))
+        }
+    }
+
+    /// Select populations with a set expression (ex. "XBB* & !XBB.1.16*").
+    ///
+    /// See [`selection::Selection`] for the syntax.
+    pub fn select(&self, expression: &str) -> Result<Vec<String>, Report> {
+        expression.parse::<selection::Selection>()?.evaluate(self)
+    }
+
+    /// Expand list of populations with wildcarding and set expressions.
     pub fn expand_populations(
         &self,
         populations: &[String],
@@ -104,27 +248,7 @@ impl Dataset {
         // expand '*' to get descendants
         let expanded = populations
             .iter()
-            .map(|p| {
-                // if population is '*', use all populations in dataset
-                if p == "*" {
-                    Ok(self.populations.keys().cloned().collect_vec())
-                }
-                // if population is 'X*', use all recombinants in dataset
-                else if p == "X*" {
-                    Ok(self.phylogeny.get_recombinants_all()?)
-                }
-                // if population ends with '*' expand descendants
-                else if p.ends_with('*') {
-                    let p = p.replace('*', "");
-                    self.phylogeny.get_descendants(&p)
-                }
-                // simple population name, that is in the dataset
-                else if self.populations.contains_key(p) {
-                    Ok(vec![p.to_string()])
-                } else {
-                    Err(eyre!("{p} is not present in the dataset."))
-                }
-            })
+            .map(|p| self.select(p))
             // flatten and handle the `Result` layer
             .collect::<Result<Vec<_>, Report>>()?
             .into_iter()
@@ -136,6 +260,58 @@ impl Dataset {
         Ok(expanded)
     }
 
+    /// Remove populations, and all of their descendants, from the dataset.
+    ///
+    /// Populations can use wildcards and set expressions (see select), and are
+    /// removed from the sequences, mutations, and phylogeny before a search, so
+    /// that sequences are classified as if the populations were never
+    /// designated. Returns the names of the removed populations.
+    pub fn knockout(&mut self, populations: &[&str]) -> Result<Vec<String>, Report> {
+        let mut knockout = BTreeSet::new();
+        for population in populations {
+            for population in self.select(population)? {
+                if self.phylogeny.get_node(&population).is_ok() {
+                    knockout.extend(self.phylogeny.get_descendants(&population)?);
+                }
+                knockout.insert(population);
+            }
+        }
+
+        debug!("Removing knockout populations from the fasta.");
+        self.populations.retain(|id, _| !knockout.contains(id));
+
+        debug!("Removing knockout populations from the mutations.");
+        self.mutations.retain(|_sub, populations| {
+            populations.retain(|p| !knockout.contains(p));
+            !populations.is_empty()
+        });
+
+        if !self.phylogeny.is_empty() {
+            debug!("Removing knockout populations from the phylogeny.");
+            self.phylogeny.retain(|name| !knockout.contains(name))?;
+        }
+
+        Ok(knockout.into_iter().collect())
+    }
+
+    /// Search for the best match approximately, with the MinHash index.
+    ///
+    /// Only the k nearest populations by estimated similarity are scored
+    /// exactly, so the true best match may be missed if k is too small.
+    /// Without an index, this is the same as an exact search.
+    pub fn search_approximate(
+        &self,
+        sequence: &Sequence,
+        populations: Option<&Vec<&String>>,
+        k: usize,
+    ) -> Result<SearchResult, Report> {
+        let Some(minhash) = &self.minhash else {
+            return self.search(sequence, populations, None);
+        };
+        let nearest = minhash.nearest(sequence, populations, k);
+        self.search(sequence, Some(&nearest), None)
+    }
+
     /// Search dataset for a population parsimony match to the sequence.
     pub fn search(
         &self,
@@ -165,17 +341,28 @@ impl Dataset {
             sequence.substitutions.iter().collect()
         };
 
+        let is_searched = |pop: &String| {
+            if let Some(populations) = populations {
+                populations.contains(&pop)
+            } else {
+                true
+            }
+        };
+        // populations with identical barcodes have the same support, so with
+        // collapsed barcodes only the representative of each class is counted
+        let classes = self.barcode_classes.as_ref();
+
         // Count up all matching population subs ("support")
         let mut max_support = 0;
         let population_support_counts: BTreeMap<&String, usize> = self
             .populations
             .iter()
-            .filter(|(pop, _seq)| {
-                if let Some(populations) = populations {
-                    populations.contains(pop)
-                } else {
-                    true
+            .filter(|(pop, _seq)| match classes {
+                Some(classes) => {
+                    classes.is_representative(pop)
+                        && classes.members(pop).iter().any(is_searched)
                 }
+                None => is_searched(pop),
             })
             .filter_map(|(pop, seq)| {
                 let count = seq
@@ -192,15 +379,23 @@ impl Dataset {
             .collect();
 
         // todo!() decide how much wiggle room we want to give in max support
-        // if we want to do max_support - 10, we might need to alter pretty_print
-        // so that it only displays the first N candidates (ex. 5,10)
-        // this will also cause slow downs
+        // if we want to do max_support - 10, this will cause slow downs
         let population_matches = population_support_counts
             .into_iter()
             .filter_map(|(pop, count)| (count == max_support).then_some(pop))
             //.filter(|(_pop, count)| *count >= (max_support - 10))
             .collect_vec();
 
+        // expand the matching classes back to their searched members
+        let population_matches = match classes {
+            Some(classes) => population_matches
+                .into_iter()
+                .flat_map(|pop| classes.members(pop))
+                .filter(|pop| is_searched(pop))
+                .collect_vec(),
+            None => population_matches,
+        };
+
         if population_matches.is_empty() {
             return Err(eyre!("No mutations matched a population in the dataset."));
         }
@@ -209,6 +404,7 @@ impl Dataset {
         // Conflict
 
         // check which populations have extra subs/lacking subs
+        let mut model_scores = BTreeMap::new();
         population_matches.into_iter().for_each(|pop| {
             // calculate the parsimony score, and store results in map by population
             let pop_seq = &self.populations[pop];
@@ -217,6 +413,8 @@ impl Dataset {
                     .unwrap_or_else(|_| {
                         panic!("Failed to create summary from sequence {}", &sequence.id)
                     });
+            // rank by the scoring model, but report the parsimony score
+            model_scores.insert(pop.to_owned(), self.scoring.score(&summary));
             result.support.insert(pop.to_owned(), summary.support);
             result.conflict_ref.insert(pop.to_owned(), summary.conflict_ref);
             result.conflict_alt.insert(pop.to_owned(), summary.conflict_alt);
@@ -229,15 +427,13 @@ impl Dataset {
         // beyond that, prefer matches with highest support or lowest conflict?
         // Ex. XCU parent #1 could be FL.23 (highest support) or XBC.1 (lowest conflict)
 
-        // which population(s) has the highest score?
+        // which population(s) has the highest score, by the scoring model?
         // reminder: it can be negative when extreme recombinant genomic size
-        let max_score =
-            result.score.values().max().expect("Failed to get max score of result.");
+        let max_score = model_scores.values().copied().fold(f64::NEG_INFINITY, f64::max);
 
-        let max_score_populations = result
-            .score
+        let max_score_populations = model_scores
             .iter()
-            .filter_map(|(pop, count)| (count == max_score).then_some(pop))
+            .filter_map(|(pop, score)| (*score == max_score).then_some(pop))
             .collect_vec();
 
         // break additional ties by max support
@@ -250,15 +446,15 @@ impl Dataset {
             .max()
             .unwrap_or(0);
 
-        result.top_populations = result
-            .score
+        result.top_populations = model_scores
             .iter()
             .zip(result.support.iter())
             .filter_map(|((pop, score), (_, subs))| {
-                (score == max_score && subs.len() == max_support).then_some(pop)
+                (*score == max_score && subs.len() == max_support).then_some(pop)
             })
             .cloned()
             .collect_vec();
+        result.top_populations_total = result.top_populations.len();
 
         // --------------------------------------------------------------------
         // Consensus Population
@@ -266,12 +462,26 @@ impl Dataset {
         let consensus_population = if self.phylogeny.is_empty() {
             //result.top_populations.iter().join("|")
             // just take first?
+            if result.top_populations.len() > 1 {
+                result.warnings.push(format!(
+                    "tie broken arbitrarily: {} chosen from {}",
+                    result.top_populations[0],
+                    result.top_populations.iter().join(",")
+                ));
+            }
             result.top_populations[0].clone()
         } else {
-            self.phylogeny.get_common_ancestor(&result.top_populations)?
+            self.phylogeny.get_mrca(&result.top_populations, false)?
         };
         result.consensus_population = consensus_population.clone();
 
+        // Top populations that tie, and are resolved to their common ancestor
+        // (ex. tips of a polytomy), are equally likely. The ancestor is the
+        // consensus, and the tied populations are the candidates.
+        if !result.top_populations.contains(&consensus_population) {
+            result.mrca_candidates = result.top_populations.clone();
+        }
+
         // if the common_ancestor was not in the populations list, add it
         let consensus_sequence = if !result
             .top_populations
@@ -359,7 +569,10 @@ impl Dataset {
             });
         result.private.sort();
 
-        debug!("Search Result:\n{}", result.pretty_print());
+        debug!(
+            "Search Result:\n{}",
+            result.pretty_print(parsimony::MAX_DISPLAY_ITEMS)
+        );
         Ok(result)
     }
 
@@ -393,6 +606,292 @@ impl Dataset {
             Err(eyre!("No ancestor of {population} has sequence data."))
         }
     }
+
+    /// Get the populations with a substitution, in dataset order.
+    ///
+    /// The mutations index is keyed by coordinate, so the populations at a
+    /// coordinate are checked for the exact substitution (ex. C22995A vs C22995T).
+    pub fn populations_with(&self, substitution: &Substitution) -> Vec<&String> {
+        self.mutations
+            .get(substitution)
+            .map(|populations| {
+                populations
+                    .iter()
+                    .filter(|pop| {
+                        self.populations
+                            .get(*pop)
+                            .map(|seq| seq.substitutions.contains(substitution))
+                            .unwrap_or_default()
+                    })
+                    .collect_vec()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Compare two populations, returning contiguous blocks of differences.
+    ///
+    /// Each block (region) is a run of consecutive sites where the same
+    /// population (origin) has a substitution that the other does not. If both
+    /// populations have a different substitution at a site, the origin is both
+    /// populations (ex. 'A,B'). Only informative sites are compared (see
+    /// pairs::informative_sites), so sites that are missing or deleted in either
+    /// population are excluded. Multiple alternating blocks indicate the
+    /// populations are plausible parents of a recombinant, since each could
+    /// contribute a distinct part of the genome.
+    pub fn compare_populations(&self, a: &str, b: &str) -> Result<Vec<Region>, Report> {
+        let (a, b) = (&self.canonical_name(a)?, &self.canonical_name(b)?);
+        let get = |p: &str| {
+            self.populations
+                .get(p)
+                .ok_or_else(|| not_found(p, "dataset", self.populations.keys()))
+        };
+        let (seq_a, seq_b) = (get(a)?, get(b)?);
+
+        // the same informative sites as the parent pair profiles
+        let sites = pairs::informative_sites(seq_a, seq_b);
+
+        // substitutions in one population but not the other, by coordinate
+        let mut diffs: BTreeMap<usize, Vec<(Substitution, &str)>> = BTreeMap::new();
+        for (name, seq, other) in [(a, seq_a, seq_b), (b, seq_b, seq_a)] {
+            seq.substitutions
+                .iter()
+                .filter(|sub| !other.substitutions.contains(sub))
+                .filter(|sub| sites.binary_search(&sub.coord).is_ok())
+                .for_each(|sub| diffs.entry(sub.coord).or_default().push((*sub, name)));
+        }
+
+        // group consecutive sites with the same origin, which is the population
+        // with the substitution, or both populations if they have different alts
+        let mut regions: Vec<Region> = Vec::new();
+        for (coord, subs) in diffs {
+            let origin = subs.iter().map(|(_sub, name)| *name).join(",");
+            let subs = subs.into_iter().map(|(sub, _name)| sub);
+            match regions.last_mut() {
+                Some(region) if region.origin == origin => {
+                    region.end = coord;
+                    region.substitutions.extend(subs);
+                }
+                _ => regions.push(Region {
+                    start: coord,
+                    end: coord,
+                    origin,
+                    substitutions: subs.collect(),
+                    reversions: Vec::new(),
+                    missing: 0.0,
+                }),
+            }
+        }
+
+        Ok(regions)
+    }
+
+    /// Get the informative sites between two populations, using the pair cache.
+    pub fn pair_profile(&self, a: &str, b: &str) -> Result<PairProfile, Report> {
+        if let Some(profile) = self.pair_cache.get(a, b)? {
+            return Ok(profile);
+        }
+        let get = |p: &str| {
+            self.populations
+                .get(p)
+                .ok_or_else(|| not_found(p, "dataset", self.populations.keys()))
+        };
+        let profile = pairs::informative_sites(get(a)?, get(b)?);
+        self.pair_cache.insert(a, b, profile, false)
+    }
+
+    /// Precompute the pair profiles of populations against candidate parents.
+    ///
+    /// Returns the number of new pairs added to the cache.
+    pub fn precompute_pairs(
+        &self,
+        populations: &[&String],
+        candidates: &[&String],
+    ) -> Result<usize, Report> {
+        let before = self.pair_cache.stats()?.pairs;
+        let population_pairs = populations
+            .iter()
+            .cartesian_product(candidates.iter())
+            .filter(|(a, b)| a != b)
+            .filter(|(a, b)| {
+                self.populations.contains_key(**a) && self.populations.contains_key(**b)
+            })
+            .collect_vec();
+        population_pairs.par_iter().try_for_each(|(a, b)| {
+            let profile =
+                pairs::informative_sites(&self.populations[**a], &self.populations[**b]);
+            self.pair_cache.insert(a, b, profile, true).map(|_| ())
+        })?;
+        Ok(self.pair_cache.stats()?.pairs - before)
+    }
+
+    /// Restrict the dataset to populations known as of a date.
+    ///
+    /// Removes populations dated after the date, and their descendants, from
+    /// the sequences, mutations, and phylogeny. Populations without a date are
+    /// otherwise retained. Returns the names of the removed populations.
+    pub fn as_of(&mut self, date: &NaiveDate) -> Result<Vec<String>, Report> {
+        let mut remove = BTreeSet::new();
+        for (population, population_date) in &self.phylogeny.dates {
+            if population_date <= date {
+                continue;
+            }
+            if self.phylogeny.get_node(population).is_ok() {
+                remove.extend(self.phylogeny.get_descendants(population)?);
+            }
+            remove.insert(population.clone());
+        }
+
+        self.populations.retain(|pop, _| !remove.contains(pop));
+        self.mutations.retain(|_sub, populations| {
+            populations.retain(|p| !remove.contains(p));
+            !populations.is_empty()
+        });
+
+        if !self.phylogeny.is_empty() {
+            self.phylogeny.retain(|name| !remove.contains(name))?;
+        }
+        self.phylogeny.dates.retain(|pop, _| !remove.contains(pop));
+
+        Ok(remove.into_iter().collect())
+    }
+
+    /// Check that population substitutions are consistent with the phylogeny.
+    ///
+    /// Each population is expected to have all the substitutions of its parent.
+    /// Returns a table of populations that are missing parental substitutions,
+    /// either because of reversions (back to the reference base) or conflicts
+    /// (a different base), which might indicate a mis-designation. Recombinants
+    /// are skipped, as they are not expected to have all substitutions of a parent.
+    pub fn check_phylogeny_consistency(&self) -> Result<Table, Report> {
+        let mut table = Table::new();
+        table.headers = vec![
+            "population",
+            "parent",
+            "parent_substitutions",
+            "reversions",
+            "conflicts",
+            "substitutions",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect_vec();
+
+        if self.phylogeny.is_empty() {
+            return Ok(table);
+        }
+
+        for (population, sequence) in &self.populations {
+            // skip populations not in the phylogeny, and recombinants
+            if !self.phylogeny.is_recombinant(population).is_ok_and(|r| !r) {
+                continue;
+            }
+
+            for parent in self.phylogeny.get_parents(population)? {
+                let Ok(parent) = self.get_ancestor_with_sequence(&parent) else {
+                    continue;
+                };
+                let parent_subs = &self.populations[&parent].substitutions;
+
+                // parent substitutions not found in the population
+                let missing = parent_subs
+                    .iter()
+                    .filter(|sub| {
+                        !sequence.substitutions.contains(sub)
+                            && sequence.missing.binary_search(&sub.coord).is_err()
+                            && !sequence.deletions.contains(&sub.to_deletion())
+                    })
+                    .collect_vec();
+
+                if missing.is_empty() {
+                    continue;
+                }
+                let reversions = missing
+                    .iter()
+                    .filter(|sub| {
+                        sequence.base(sub.coord, &self.reference) == sub.reference
+                    })
+                    .count();
+                let conflicts = missing.len() - reversions;
+                debug!("Population {population} is missing substitutions of parent {parent}: {}", missing.iter().join(","));
+
+                table.rows.push(vec![
+                    population.to_string(),
+                    parent.to_string(),
+                    parent_subs.len().to_string(),
+                    reversions.to_string(),
+                    conflicts.to_string(),
+                    missing.iter().join(","),
+                ]);
+            }
+        }
+
+        Ok(table)
+    }
+
+    /// Check the quality of population sequences, for exclusion from the dataset.
+    ///
+    /// Returns a table of populations that have more than `max_missing` fraction
+    /// of missing bases, or more than `max_private` private substitutions. Private
+    /// substitutions are those not found in any parent, or the closest ancestor
+    /// with a sequence, in the phylogeny. An implausible number of private
+    /// substitutions suggests a poor consensus sequence, or a mis-designation.
+    pub fn check_population_quality(
+        &self,
+        max_missing: Option<f64>,
+        max_private: Option<usize>,
+    ) -> Result<Table, Report> {
+        let mut table = Table::new();
+        table.headers = vec!["population", "reason", "value", "threshold"]
+            .into_iter()
+            .map(String::from)
+            .collect_vec();
+
+        for (population, sequence) in &self.populations {
+            if let Some(max_missing) = max_missing {
+                let missing =
+                    sequence.missing.len() as f64 / sequence.genome_length as f64;
+                if missing > max_missing {
+                    table.rows.push(vec![
+                        population.to_string(),
+                        "missing".to_string(),
+                        utils::fmt::float(missing),
+                        max_missing.to_string(),
+                    ]);
+                    continue;
+                }
+            }
+
+            let Some(max_private) = max_private else {
+                continue;
+            };
+            // populations not in the phylogeny can't be compared to a parent
+            if self.phylogeny.get_node(population).is_err() {
+                continue;
+            }
+            // not a set, substitutions are ordered by coordinate only
+            let mut parent_subs: Vec<&Substitution> = Vec::new();
+            for parent in self.phylogeny.get_parents(population)? {
+                if let Ok(parent) = self.get_ancestor_with_sequence(&parent) {
+                    parent_subs.extend(&self.populations[&parent].substitutions);
+                }
+            }
+            let private = sequence
+                .substitutions
+                .iter()
+                .filter(|sub| !parent_subs.contains(sub))
+                .count();
+            if private > max_private {
+                table.rows.push(vec![
+                    population.to_string(),
+                    "private_substitutions".to_string(),
+                    private.to_string(),
+                    max_private.to_string(),
+                ]);
+            }
+        }
+
+        Ok(table)
+    }
 }
 
 // ----------------------------------------------------------------------------
@@ -403,6 +902,13 @@ pub struct SearchResult {
     pub sequence_id: String,
     pub consensus_population: String,
     pub top_populations: Vec<String>,
+    /// Number of top populations, before they were truncated.
+    #[serde(default)]
+    pub top_populations_total: usize,
+    /// Top populations that are equally close to the sequence, when they are
+    /// resolved to their common ancestor (ex. tips of a polytomy).
+    #[serde(default)]
+    pub mrca_candidates: Vec<String>,
     pub substitutions: Vec<Substitution>,
     pub support: BTreeMap<String, Vec<Substitution>>,
     pub private: Vec<Substitution>,
@@ -410,6 +916,9 @@ pub struct SearchResult {
     pub conflict_alt: BTreeMap<String, Vec<Substitution>>,
     pub score: BTreeMap<String, isize>,
     pub recombinant: Option<String>,
+    /// Warnings about the search of this sequence, for the outputs.
+    #[serde(default)]
+    pub warnings: Vec<String>,
 }
 
 impl SearchResult {
@@ -418,6 +927,8 @@ impl SearchResult {
             sequence_id: sequence.id.clone(),
             consensus_population: String::new(),
             top_populations: Vec::new(),
+            top_populations_total: 0,
+            mrca_candidates: Vec::new(),
             support: BTreeMap::new(),
             private: Vec::new(),
             conflict_ref: BTreeMap::new(),
@@ -425,90 +936,128 @@ impl SearchResult {
             substitutions: Vec::new(),
             score: BTreeMap::new(),
             recombinant: None,
+            warnings: Vec::new(),
         }
     }
 
-    pub fn pretty_print(&self) -> String {
-        // Order the population lists from 'best' to 'worst'
-
-        let max_display_items = 10;
-
-        // score
-        let mut score_order: Vec<(String, isize)> =
-            self.score.clone().into_iter().collect();
-        score_order.sort_by(|a, b| b.1.cmp(&a.1));
-
-        // put consensus population first, regardless of score
-        let consensus_score: (String, isize) = score_order
+    /// Populations ordered from 'best' to 'worst' score, with the consensus first.
+    ///
+    /// Populations with the same score are ordered by name.
+    pub fn population_order(&self) -> Vec<&String> {
+        let mut populations = self
+            .score
             .iter()
-            .find(|(pop, _score)| *pop == self.consensus_population)
-            .cloned()
-            .expect("Failed to order consensus populations by score.");
-
-        score_order.retain(|(pop, _score)| *pop != self.consensus_population);
-        score_order.insert(0, consensus_score);
+            .sorted_by(|a, b| b.1.cmp(a.1))
+            .map(|(pop, _score)| pop)
+            .collect_vec();
+        // put consensus population first, regardless of score
+        if let Some(i) = populations.iter().position(|p| **p == self.consensus_population)
+        {
+            let consensus = populations.remove(i);
+            populations.insert(0, consensus);
+        }
+        populations
+    }
 
-        // restrict display items for brevity
-        let display_suffix = if score_order.len() > max_display_items {
-            score_order = score_order[0..max_display_items].to_vec();
-            "\n  ..."
-        } else {
-            ""
+    /// Truncate the top populations to at most `max` populations.
+    ///
+    /// The consensus population is kept first (if it's a top population),
+    /// followed by the rest in natural order, so that truncation is
+    /// deterministic. The MRCA candidates are truncated the same way, and the
+    /// scores and substitutions of dropped populations are removed.
+    pub fn truncate_top_populations(&mut self, max: usize) {
+        let consensus = self.consensus_population.clone();
+        let truncate = |populations: &[String]| {
+            populations
+                .iter()
+                .sorted_by(|a, b| {
+                    (**a != consensus)
+                        .cmp(&(**b != consensus))
+                        .then_with(|| utils::table::natural_cmp(a, b))
+                })
+                .take(max)
+                .cloned()
+                .collect_vec()
         };
+        self.top_populations = truncate(&self.top_populations);
+        self.mrca_candidates = truncate(&self.mrca_candidates);
 
-        let mut support_order: Vec<String> = Vec::new();
-        let mut conflict_ref_order: Vec<String> = Vec::new();
-        let mut conflict_alt_order: Vec<String> = Vec::new();
-
-        score_order.iter().for_each(|(pop, _count)| {
-            let subs = &self.support[pop];
-            let count = subs.len();
-            support_order.push(format!("- {pop} ({count}): {}", subs.iter().join(", ")));
-
-            let subs = &self.conflict_ref[pop];
-            let count = subs.len();
-            conflict_ref_order
-                .push(format!("- {pop} ({count}): {}", subs.iter().join(", ")));
-
-            let subs = &self.conflict_alt[pop];
-            let count = subs.len();
-            conflict_alt_order
-                .push(format!("- {pop} ({count}): {}", subs.iter().join(", ")));
-        });
+        let top_populations = &self.top_populations;
+        let keep = |p: &String| *p == consensus || top_populations.contains(p);
+        self.score.retain(|p, _| keep(p));
+        self.support.retain(|p, _| keep(p));
+        self.conflict_ref.retain(|p, _| keep(p));
+        self.conflict_alt.retain(|p, _| keep(p));
+    }
 
-        // Pretty string formatting for yaml
-        let score_order = score_order
-            .iter()
-            .map(|(pop, count)| format!("- {}: {}", &pop, &count))
-            .collect::<Vec<_>>();
+    /// Pretty-print the search result (yaml-like), with at most `max_items`
+    /// populations and substitutions per section.
+    pub fn pretty_print(&self, max_items: usize) -> String {
+        let populations = self.population_order();
 
         formatdoc!(
             "sequence_id: {}
             consensus_population: {}
             top_populations: {}
+            top_populations_total: {}
+            mrca_candidates: {}
             recombinant: {}
             substitutions: {}
-            score:\n  {}{display_suffix}
-            support:\n  {}{display_suffix}
-            conflict_ref:\n  {}{display_suffix}
-            conflict_alt:\n  {}{display_suffix}
+            score:\n  {}
+            support:\n  {}
+            conflict_ref:\n  {}
+            conflict_alt:\n  {}
             private: {}",
             self.sequence_id,
             self.consensus_population,
-            self.top_populations.join(", "),
-            self.recombinant.clone().unwrap_or("None".to_string()),
-            self.substitutions.iter().join(", "),
-            score_order.join("\n  "),
-            support_order.join("\n  "),
-            conflict_ref_order.join("\n  "),
-            conflict_alt_order.join("\n  "),
-            self.private.iter().join(", ")
+            parsimony::join_capped(&self.top_populations, max_items),
+            self.top_populations_total,
+            parsimony::join_capped(&self.mrca_candidates, max_items),
+            self.recombinant.as_deref().unwrap_or("None"),
+            parsimony::join_capped(&self.substitutions, max_items),
+            parsimony::pretty_print_scores(&populations, &self.score, max_items),
+            parsimony::pretty_print_section(&populations, &self.support, max_items),
+            parsimony::pretty_print_section(&populations, &self.conflict_ref, max_items),
+            parsimony::pretty_print_section(&populations, &self.conflict_alt, max_items),
+            parsimony::join_capped(&self.private, max_items),
+        )
+    }
+
+    /// Summarize the search result on one line, for logs.
+    pub fn one_line(&self) -> String {
+        let population = &self.consensus_population;
+        let count = |section: &BTreeMap<String, Vec<Substitution>>| {
+            section.get(population).map(Vec::len).unwrap_or_default()
+        };
+        format!(
+            "{}: {population} (score: {}, support: {}, conflict_ref: {}, conflict_alt: {}, private: {}, top_populations: {})",
+            self.sequence_id,
+            self.score.get(population).copied().unwrap_or_default(),
+            count(&self.support),
+            count(&self.conflict_ref),
+            count(&self.conflict_alt),
+            self.private.len(),
+            self.top_populations_total,
         )
     }
 }
 
 // ----------------------------------------------------------------------------
 // Functions
+
+/// Error for a population name that can't be found, with suggestions.
+pub fn not_found<'a, I>(population: &str, source: &str, names: I) -> Report
+where
+    I: IntoIterator<Item = &'a String>,
+{
+    let report = eyre!("{population} is not present in the {source}.");
+    let suggestions = utils::suggest(population, names);
+    if suggestions.is_empty() {
+        report
+    } else {
+        report.suggestion(format!("Did you mean: {}?", suggestions.join(", ")))
+    }
+}
 // ----------------------------------------------------------------------------
 
 /// Write mapping of mutations to populations, coordinate sorted.
@@ -523,17 +1072,13 @@ pub fn write_mutations(
     // convert substitution to string for serde pretty
     let mutations =
         mutations.iter().map(|(sub, pops)| (sub.to_string(), pops)).collect_vec();
-    // create output file
-    let mut file = File::create(path)
-        .wrap_err_with(|| format!("Failed to create file: {path:?}"))?;
 
     // parse to string
     let output = serde_json::to_string_pretty(&mutations)
         .wrap_err_with(|| "Failed to parse mutations.".to_string())?;
 
     // write to file
-    file.write_all(format!("{}\n", output).as_bytes())
-        .wrap_err_with(|| format!("Failed to write file: {path:?}"))?;
+    utils::write_atomic(path, format!("{}\n", output))?;
 
     Ok(())
 }
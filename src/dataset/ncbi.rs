@@ -0,0 +1,225 @@
+use crate::dataset::attributes::Summary;
+use crate::dataset::download::snapshot;
+use crate::utils;
+use crate::utils::table::Table;
+use crate::utils::versioned_file::{Format, VersionedFile};
+use color_eyre::eyre::{eyre, Report, Result, WrapErr};
+use color_eyre::Help;
+use itertools::Itertools;
+use log::info;
+use std::path::Path;
+
+/// NCBI E-utilities endpoint for fetching records.
+pub const EFETCH_URL: &str = "https://eutils.ncbi.nlm.nih.gov/entrez/eutils/efetch.fcgi";
+
+// ----------------------------------------------------------------------------
+// GenBank
+
+/// A GenBank record, reduced to what a dataset needs.
+#[derive(Clone, Debug, Default)]
+pub struct GenBank {
+    /// Versioned accession (ex. MN908947.3).
+    pub id: String,
+    pub sequence: String,
+    /// CDS features as rebar annotations (gene, abbreviation, start, end).
+    pub annotations: Table,
+}
+
+/// A GenBank feature, with its location and qualifiers (ex. gene="S").
+#[derive(Clone, Debug, Default)]
+struct Feature {
+    key: String,
+    location: String,
+    qualifiers: Vec<(String, String)>,
+}
+
+impl GenBank {
+    pub fn new() -> Self {
+        GenBank::default()
+    }
+
+    /// Parse a GenBank flat file.
+    ///
+    /// CDS features are named by their gene, product, or locus tag. Features
+    /// split across multiple ranges (ex. join, complement) span from the first
+    /// to the last coordinate, and only the first feature of a gene is kept.
+    pub fn read(path: &Path) -> Result<Self, Report> {
+        let contents = utils::read_to_string(path)?;
+        let mut genbank = GenBank::new();
+        genbank.annotations.headers = vec!["gene", "abbreviation", "start", "end"]
+            .into_iter()
+            .map(String::from)
+            .collect_vec();
+
+        let mut features: Vec<Feature> = Vec::new();
+        let mut section = "";
+
+        for line in contents.lines() {
+            if line.starts_with("//") {
+                break;
+            }
+            // section headers are unindented (ex. LOCUS, FEATURES, ORIGIN)
+            if !line.starts_with(' ') && !line.is_empty() {
+                section = line.split_whitespace().next().unwrap_or_default();
+                let value = line.split_whitespace().nth(1).unwrap_or_default();
+                match section {
+                    "VERSION" => genbank.id = value.to_string(),
+                    "ACCESSION" | "LOCUS" if genbank.id.is_empty() => {
+                        genbank.id = value.to_string()
+                    }
+                    _ => (),
+                }
+                continue;
+            }
+
+            match section {
+                "FEATURES" => {
+                    let content = line.trim();
+                    // feature keys are indented 5 spaces, qualifiers 21
+                    let indent = line.len() - line.trim_start().len();
+                    if indent < 21 {
+                        let (key, location) = content
+                            .split_once(char::is_whitespace)
+                            .unwrap_or((content, ""));
+                        features.push(Feature {
+                            key: key.to_string(),
+                            location: location.trim().to_string(),
+                            qualifiers: Vec::new(),
+                        });
+                    } else if let Some(Feature {
+                        location,
+                        qualifiers,
+                        ..
+                    }) = features.last_mut()
+                    {
+                        if let Some(qualifier) = content.strip_prefix('/') {
+                            let (key, value) =
+                                qualifier.split_once('=').unwrap_or((qualifier, ""));
+                            qualifiers.push((key.to_string(), value.to_string()));
+                        } else if let Some((_, value)) = qualifiers.last_mut() {
+                            value.push(' ');
+                            value.push_str(content);
+                        } else {
+                            location.push_str(content);
+                        }
+                    }
+                }
+                "ORIGIN" => genbank.sequence.extend(
+                    line.chars()
+                        .filter(|c| c.is_ascii_alphabetic())
+                        .map(|c| c.to_ascii_uppercase()),
+                ),
+                _ => (),
+            }
+        }
+
+        if genbank.sequence.is_empty() {
+            return Err(eyre!("GenBank record has no sequence: {path:?}"))
+                .suggestion("Was the record downloaded with its sequence (ORIGIN)?");
+        }
+
+        for feature in features.iter().filter(|f| f.key == "CDS") {
+            let Some(name) = ["gene", "product", "locus_tag"].iter().find_map(|key| {
+                let qualifier = feature.qualifiers.iter().find(|q| q.0 == *key);
+                qualifier.map(|q| q.1.trim_matches('"'))
+            }) else {
+                continue;
+            };
+            if genbank.annotations.rows.iter().any(|row| row[0] == name) {
+                continue;
+            }
+            // ranges on other records (ex. AB000001.1:1..10) are ignored
+            let coords = feature
+                .location
+                .split(',')
+                .filter(|range| !range.contains(':'))
+                .flat_map(|range| range.split(|c: char| !c.is_ascii_digit()))
+                .filter_map(|coord| coord.parse::<usize>().ok())
+                .collect_vec();
+            let (Some(start), Some(end)) = (coords.iter().min(), coords.iter().max())
+            else {
+                continue;
+            };
+            genbank.annotations.rows.push(vec![
+                name.to_string(),
+                name.to_string(),
+                start.to_string(),
+                end.to_string(),
+            ]);
+        }
+
+        Ok(genbank)
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Functions
+// ----------------------------------------------------------------------------
+
+/// Get the url of the GenBank record of an NCBI nucleotide accession.
+pub fn url(accession: &str) -> String {
+    format!("{EFETCH_URL}?db=nuccore&id={accession}&rettype=gbwithparts&retmode=text")
+}
+
+/// Download the GenBank record of an NCBI nucleotide accession.
+pub async fn genbank(
+    accession: &str,
+    output_path: &Path,
+) -> Result<VersionedFile, Report> {
+    let mut remote_file = VersionedFile::from_url(&url(accession), output_path);
+    remote_file
+        .fetch()
+        .await
+        .wrap_err_with(|| format!("Failed to fetch NCBI accession: {accession}"))
+        .suggestion(
+            "Is the accession a valid NCBI nucleotide accession (ex. MN908947.3)?",
+        )?;
+    Ok(remote_file)
+}
+
+/// Create the dataset reference from the summary's NCBI accession.
+///
+/// The GenBank record is recorded in the summary as 'genbank', and re-used from
+/// a summary snapshot if present. Returns the reference extracted from it.
+pub async fn reference(
+    summary: &mut Summary,
+    output_dir: &Path,
+) -> Result<VersionedFile, Report> {
+    let accession = summary
+        .accession
+        .clone()
+        .ok_or_else(|| eyre!("Dataset summary has no NCBI accession."))?;
+    let output_path = output_dir.join("reference.gb");
+    info!("Downloading GenBank record of {accession}: {output_path:?}");
+
+    let remote_file = if summary.misc.contains_key("genbank") {
+        snapshot(&summary.misc["genbank"], &output_path).await?
+    } else {
+        genbank(&accession, &output_path).await?
+    };
+    let record = GenBank::read(&remote_file.local_path)?;
+
+    let output_path = output_dir.join("reference.fasta");
+    utils::write_atomic(
+        &output_path,
+        format!(">{}\n{}\n", record.id, record.sequence),
+    )?;
+    let reference = VersionedFile {
+        local_path: output_path,
+        format: Format::Fasta,
+        ..remote_file.clone()
+    };
+    summary.misc.insert("genbank".to_string(), remote_file);
+
+    Ok(reference)
+}
+
+/// Create dataset annotations from the summary's GenBank record.
+pub fn annotations(summary: &Summary) -> Result<Table, Report> {
+    let genbank = summary
+        .misc
+        .get("genbank")
+        .ok_or_else(|| eyre!("Dataset summary has no GenBank record."))?;
+    let annotations = GenBank::read(&genbank.local_path)?.annotations;
+    Ok(annotations)
+}
@@ -0,0 +1,444 @@
+use crate::cli;
+use crate::dataset;
+use crate::dataset::attributes::{Summary, TipMapping};
+use crate::phylogeny::{BranchLength, Phylogeny, ROOT};
+use crate::sequence::read_reference;
+use crate::utils;
+use crate::utils::table::Table;
+use crate::utils::versioned_file::{Format, VersionedFile};
+use chrono::Local;
+use color_eyre::eyre::{eyre, Report, Result, WrapErr};
+use color_eyre::Help;
+use itertools::Itertools;
+use log::{debug, info, warn};
+use petgraph::graph::NodeIndex;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs::create_dir_all;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+// ----------------------------------------------------------------------------
+// Nextclade Dataset Files
+
+/// Paths to the files of a Nextclade dataset bundle.
+#[derive(Clone, Debug)]
+pub struct Files {
+    pub reference: PathBuf,
+    pub tree: PathBuf,
+    pub annotation: Option<PathBuf>,
+}
+
+impl Files {
+    /// Locate the files of a Nextclade dataset directory.
+    ///
+    /// File names are taken from pathogen.json (Nextclade v3), falling back to
+    /// the default names of v3 and v2 datasets.
+    pub fn locate(dataset_dir: &Path) -> Result<Self, Report> {
+        let pathogen_path = dataset_dir.join("pathogen.json");
+        let pathogen: Value = if pathogen_path.exists() {
+            let pathogen = utils::read_to_string(&pathogen_path)?;
+            serde_json::from_str(&pathogen)
+                .wrap_err_with(|| format!("Failed to parse file: {pathogen_path:?}"))?
+        } else {
+            Value::Null
+        };
+        let file_name = |key: &str| pathogen["files"][key].as_str().map(PathBuf::from);
+
+        let reference = dataset_dir.join(
+            file_name("reference").unwrap_or_else(|| PathBuf::from("reference.fasta")),
+        );
+        let tree =
+            dataset_dir.join(file_name("treeJson").unwrap_or_else(|| "tree.json".into()));
+        let annotation = match file_name("genomeAnnotation") {
+            Some(file_name) => Some(dataset_dir.join(file_name)),
+            None => ["genome_annotation.gff3", "genemap.gff"]
+                .into_iter()
+                .map(|file_name| dataset_dir.join(file_name))
+                .find(|path| path.exists()),
+        };
+
+        for path in [&reference, &tree] {
+            if !path.exists() {
+                return Err(eyre!("Nextclade dataset file does not exist: {path:?}"))
+                    .suggestion("A Nextclade dataset must have a reference and tree.");
+            }
+        }
+
+        Ok(Files {
+            reference,
+            tree,
+            annotation,
+        })
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Functions
+// ----------------------------------------------------------------------------
+
+/// Convert a Nextclade genome annotation (GFF3) into rebar annotations.
+///
+/// Genes are taken from 'gene' features, or 'CDS' features if there are no genes.
+pub fn parse_annotation(path: &Path) -> Result<Table, Report> {
+    let gff = utils::read_to_string(path)?;
+    let features = gff
+        .lines()
+        .filter(|line| !line.starts_with('#') && !line.trim().is_empty())
+        .map(|line| line.split('\t').collect_vec())
+        .filter(|columns| columns.len() >= 9)
+        .collect_vec();
+
+    let feature_type = if features.iter().any(|columns| columns[2] == "gene") {
+        "gene"
+    } else {
+        "CDS"
+    };
+
+    let mut table = Table::new();
+    table.headers = vec!["gene", "abbreviation", "start", "end"]
+        .into_iter()
+        .map(String::from)
+        .collect_vec();
+
+    for columns in features.iter().filter(|columns| columns[2] == feature_type) {
+        let attributes: BTreeMap<&str, &str> = columns[8]
+            .split(';')
+            .filter_map(|attribute| attribute.trim().split_once('='))
+            .collect();
+        let Some(name) = ["gene_name", "Name", "gene", "ID"]
+            .iter()
+            .find_map(|key| attributes.get(key))
+        else {
+            continue;
+        };
+        let name = name.to_string();
+        // a gene can be split across multiple features (ex. ORF1ab)
+        if table.rows.iter().any(|row| row[0] == name) {
+            continue;
+        }
+        table.rows.push(vec![
+            name.clone(),
+            name,
+            columns[3].to_string(),
+            columns[4].to_string(),
+        ]);
+    }
+
+    Ok(table)
+}
+
+/// Apply nucleotide mutations of an Auspice tree branch (ex. C241T) to a sequence.
+fn apply_mutations(sequence: &mut [char], mutations: &[Value]) -> Result<(), Report> {
+    for mutation in mutations {
+        let mutation = mutation
+            .as_str()
+            .ok_or_else(|| eyre!("Failed to parse tree mutation: {mutation}"))?;
+        let alt = mutation.chars().last().unwrap_or_default();
+        let coord = mutation
+            .get(1..mutation.len().saturating_sub(1))
+            .and_then(|coord| coord.parse::<usize>().ok())
+            .filter(|coord| *coord >= 1 && *coord <= sequence.len())
+            .ok_or_else(|| eyre!("Failed to parse tree mutation: {mutation}"))?;
+        sequence[coord - 1] = alt;
+    }
+    Ok(())
+}
+
+/// Clade representative sequences and the clade phylogeny of a Nextclade tree.
+#[derive(Debug, Default)]
+struct Clades {
+    sequences: BTreeMap<String, Vec<char>>,
+    phylogeny: Phylogeny,
+    indices: BTreeMap<String, NodeIndex>,
+    /// Node attribute that defines the clades.
+    clade_attribute: String,
+    /// Node labels that define the clades, instead of the clade attribute.
+    tip_mapping: Option<TipMapping>,
+    /// Node labels (preorder), and which are tips.
+    labels: Vec<String>,
+    tips: Vec<String>,
+}
+
+impl Clades {
+    /// Walk the tree (preorder), accumulating mutations from the reference.
+    ///
+    /// The representative of a clade is the first node whose clade differs from
+    /// its parent, and the clade is a child of its parent's clade. The branch
+    /// length is the number of mutations between the two representatives.
+    fn walk(
+        &mut self,
+        node: &Value,
+        sequence: &[char],
+        parent_clade: &str,
+        mutations: usize,
+    ) -> Result<(), Report> {
+        let mut sequence = sequence.to_vec();
+        let nuc = node["branch_attrs"]["mutations"]["nuc"].as_array();
+        let nuc = nuc.map(Vec::as_slice).unwrap_or_default();
+        apply_mutations(&mut sequence, nuc)?;
+        let mut mutations = mutations + nuc.len();
+
+        let children = node["children"].as_array();
+        let label = node["name"].as_str().unwrap_or_default().to_string();
+        if !label.is_empty() {
+            self.labels.push(label.clone());
+            if children.map(Vec::len).unwrap_or_default() == 0 {
+                self.tips.push(label.clone());
+            }
+        }
+
+        let clade = match &self.tip_mapping {
+            Some(tip_mapping) => tip_mapping.population(&label).map(String::as_str),
+            None => node["node_attrs"][&self.clade_attribute]["value"].as_str(),
+        };
+        let clade = clade.unwrap_or(parent_clade).to_string();
+
+        if clade != parent_clade && !self.sequences.contains_key(&clade) {
+            if clade == ROOT {
+                return Err(eyre!("Nextclade clade is named '{ROOT}'."))
+                    .suggestion(format!("'{ROOT}' is reserved for the phylogeny root."));
+            }
+            let clade_index = self.phylogeny.add_node(clade.clone());
+            let parent_index = self.indices[parent_clade];
+            self.phylogeny.graph.add_edge(parent_index, clade_index, mutations as f64);
+            self.indices.insert(clade.clone(), clade_index);
+            self.sequences.insert(clade.clone(), sequence.clone());
+            mutations = 0;
+        }
+
+        for child in children.into_iter().flatten() {
+            self.walk(child, &sequence, &clade, mutations)?;
+        }
+        Ok(())
+    }
+}
+
+/// Get the dataset directory of an extracted bundle.
+///
+/// Bundles are usually flat, but some are zipped from their parent directory,
+/// so a single directory in the bundle is the dataset.
+fn bundle_dir(extracted: &Path) -> Result<PathBuf, Report> {
+    let entries = std::fs::read_dir(extracted)?.collect::<Result<Vec<_>, _>>()?;
+    match entries.as_slice() {
+        [entry] if entry.path().is_dir() => Ok(entry.path()),
+        _ => Ok(extracted.to_path_buf()),
+    }
+}
+
+/// Import a Nextclade dataset bundle as a rebar dataset.
+pub fn import(args: &cli::dataset::import_nextclade::Args) -> Result<(), Report> {
+    info!("Importing Nextclade dataset: {:?}", &args.input);
+
+    // a zip bundle (ex. nextclade dataset get --output-zip) is extracted to a
+    // temporary directory, which is removed once the import is done
+    let ext = utils::path_to_ext(&args.input).unwrap_or_default();
+    let bundle = if args.input.is_file() && ext == "zip" {
+        let temp_dir = TempDir::new()?;
+        info!("Extracting dataset bundle: {:?}", &args.input);
+        utils::zip::extract(&args.input, temp_dir.path())?;
+        Some(temp_dir)
+    } else {
+        None
+    };
+    let input_dir = match &bundle {
+        Some(temp_dir) => bundle_dir(temp_dir.path())?,
+        None if args.input.is_dir() => args.input.clone(),
+        None => {
+            return Err(eyre!(
+                "Nextclade dataset is not a directory or zip bundle: {:?}",
+                &args.input
+            ))
+        }
+    };
+    let files = Files::locate(&input_dir)?;
+
+    // Warn if the directory already exists
+    if !args.output_dir.exists() {
+        info!("Creating output directory: {:?}", &args.output_dir);
+        create_dir_all(&args.output_dir)?;
+    } else {
+        warn!(
+            "Proceed with caution! --output-dir {:?} already exists.",
+            args.output_dir
+        );
+    }
+
+    let mut summary = Summary::new();
+
+    // --------------------------------------------------------------------
+    // Reference
+
+    let output_path = args.output_dir.join("reference.fasta");
+    info!("Copying reference: {output_path:?}");
+    let mask = vec![0, 0];
+    let reference = read_reference(&files.reference, &mask)?;
+    let sequence = reference.seq.iter().collect::<String>();
+    utils::write_atomic(&output_path, format!(">{}\n{sequence}\n", reference.id))?;
+    summary.reference =
+        VersionedFile::from_url(&files.reference.to_string_lossy(), &output_path);
+    summary.reference.date_created = Local::now().into();
+
+    // --------------------------------------------------------------------
+    // Annotations
+
+    if let Some(annotation_path) = &files.annotation {
+        let output_path = args.output_dir.join("annotations.tsv");
+        info!("Converting annotations: {output_path:?}");
+        let annotations = parse_annotation(annotation_path)?;
+        annotations.write(&output_path)?;
+    }
+
+    // --------------------------------------------------------------------
+    // Clades
+
+    info!("Parsing clades from tree: {:?}", &files.tree);
+    let tree = utils::read_to_string(&files.tree)?;
+    let tree: Value = serde_json::from_str(&tree)
+        .wrap_err_with(|| format!("Failed to parse file: {:?}", &files.tree))?;
+    let tree = tree.get("tree").unwrap_or(&tree);
+
+    let mut clades = Clades {
+        clade_attribute: args.clade_attribute.clone(),
+        ..Default::default()
+    };
+    if let Some(path) = &args.tip_mapping {
+        info!("Reading tip mapping: {path:?}");
+        clades.tip_mapping = Some(TipMapping::read(path)?);
+    }
+    clades.phylogeny.branch_length = BranchLength::Mutations;
+    let root_index = clades.phylogeny.add_node(ROOT.to_string());
+    clades.indices.insert(ROOT.to_string(), root_index);
+    clades
+        .walk(tree, &reference.seq, ROOT, 0)
+        .wrap_err_with(|| format!("Failed to parse tree: {:?}", &files.tree))?;
+
+    if clades.sequences.is_empty() {
+        let suggestion = match &args.tip_mapping {
+            Some(path) => format!("Do the tips in {path:?} match the tree node names?"),
+            None => format!(
+                "Is '{}' the clade attribute of the tree nodes? See --clade-attribute.",
+                args.clade_attribute
+            ),
+        };
+        return Err(eyre!("No clades found in tree: {:?}", &files.tree))
+            .suggestion(suggestion);
+    }
+    info!("Found {} clades.", clades.sequences.len());
+
+    // --------------------------------------------------------------------
+    // Tip Mapping (Optional)
+
+    if let Some(tip_mapping) = &clades.tip_mapping {
+        let output_path = args.output_dir.join("tip_mapping.tsv");
+        info!("Reporting tip mapping: {output_path:?}");
+        let report = tip_mapping.report(&clades.labels, &clades.tips);
+        for status in ["unmapped", "not_found"] {
+            let num_tips = report.filter("status", status)?.rows.len();
+            if num_tips > 0 {
+                warn!("{num_tips} tip(s) are {status}, see: {output_path:?}");
+            }
+        }
+        report.write(&output_path)?;
+    }
+
+    // --------------------------------------------------------------------
+    // Collapse Unary Clades (Optional)
+
+    if args.collapse_unary {
+        let collapsed = clades.phylogeny.collapse_unary_nodes()?;
+        collapsed.iter().for_each(|clade| {
+            clades.sequences.remove(clade);
+        });
+        info!("Collapsed {} unary clade(s).", collapsed.len());
+        debug!("Collapsed clades: {}", collapsed.iter().join(", "));
+    }
+
+    // --------------------------------------------------------------------
+    // Populations
+
+    let output_path = args.output_dir.join("populations.fasta");
+    info!("Exporting clade sequences: {output_path:?}");
+    let sequences = clades
+        .sequences
+        .iter()
+        .map(|(clade, sequence)| format!(">{clade}\n{}\n", sequence.iter().join("")))
+        .join("");
+    utils::write_atomic(&output_path, sequences)?;
+    // populations are derived from the tree, so they have no source to fetch
+    summary.populations = VersionedFile {
+        local_path: output_path,
+        format: Format::Fasta,
+        ..Default::default()
+    };
+    let tree_url = files.tree.to_string_lossy();
+    summary.misc.insert(
+        "tree".to_string(),
+        VersionedFile::from_url(&tree_url, &files.tree),
+    );
+    summary.populations.date_created = Local::now().into();
+    if let Some(path) = &args.tip_mapping {
+        let url = path.to_string_lossy();
+        summary.misc.insert(
+            "tip_mapping".to_string(),
+            VersionedFile::from_url(&url, path),
+        );
+    }
+
+    // --------------------------------------------------------------------
+    // Graph (Phylogeny)
+
+    let mut phylogeny = clades.phylogeny;
+    phylogeny.theme = args.theme.clone();
+    phylogeny.validate_branch_lengths()?;
+    phylogeny.recombinants = phylogeny.get_recombinants()?;
+    phylogeny.recombinants_all = phylogeny.get_recombinants_all()?;
+    summary.branch_length = phylogeny.branch_length;
+
+    let output_path = args.output_dir.join("phylogeny.json");
+    info!("Building phylogeny: {output_path:?}");
+    phylogeny.write(&output_path)?;
+    let output_path = args.output_dir.join("phylogeny.dot");
+    info!("Exporting graphviz phylogeny: {output_path:?}");
+    phylogeny.write(&output_path)?;
+    let output_path = args.output_dir.join("phylogeny.graphml");
+    info!("Exporting GraphML phylogeny: {output_path:?}");
+    phylogeny.write(&output_path)?;
+
+    // --------------------------------------------------------------------
+    // Export Mutations
+
+    let output_path = args.output_dir.join("mutations.json");
+    info!("Mapping mutations to populations: {output_path:?}");
+    let (_populations, mutations) = dataset::load::parse_populations(
+        &summary.populations.local_path,
+        &summary.reference.local_path,
+        &mask,
+    )?;
+    dataset::write_mutations(&mutations, &output_path)?;
+
+    // --------------------------------------------------------------------
+    // Edge Cases
+
+    // Nextclade clades are not recombinant, so there are no edge cases.
+    let output_path = args.output_dir.join("edge_cases.json");
+    info!("Creating edge cases: {output_path:?}");
+    cli::run::Args::write(&[], &output_path)?;
+
+    // --------------------------------------------------------------------
+    // Export Summary
+
+    let output_path = args.output_dir.join("summary.json");
+    info!("Exporting summary: {output_path:?}");
+    summary.update_checksums()?;
+    summary.write(&output_path)?;
+
+    // --------------------------------------------------------------------
+    // Validate
+
+    info!("Validating dataset: {:?}", &args.output_dir);
+    dataset::load::dataset(&args.output_dir, &mask)?;
+
+    info!("Done.");
+    Ok(())
+}
@@ -0,0 +1,51 @@
+use crate::cli;
+use crate::dataset::{load, Dataset};
+use crate::phylogeny::Phylogeny;
+use color_eyre::eyre::{eyre, Report, Result};
+use color_eyre::Help;
+use log::{debug, info};
+
+/// Export the dataset phylogeny, optionally as the effective search graph.
+pub fn export_phylogeny(
+    args: &cli::dataset::export_phylogeny::Args,
+) -> Result<(), Report> {
+    let mask = vec![0, 0];
+    let dataset = load::dataset(&args.dataset_dir, &mask)?;
+    if dataset.phylogeny.is_empty() {
+        return Err(eyre!("Dataset has no phylogeny: {:?}", args.dataset_dir))
+            .suggestion("Please check that the dataset includes phylogeny.json");
+    }
+
+    let mut phylogeny = if args.effective {
+        let (phylogeny, removed) = effective_phylogeny(&dataset)?;
+        info!(
+            "Removed {} population(s) without sequence data.",
+            removed.len()
+        );
+        debug!("Populations without sequence data: {}", removed.join(", "));
+        phylogeny
+    } else {
+        dataset.phylogeny
+    };
+
+    phylogeny.layout_hints = args.layout_hints;
+
+    info!("Exporting phylogeny: {:?}", args.output);
+    phylogeny.write(&args.output)?;
+
+    Ok(())
+}
+
+/// Get the effective search graph of a dataset, the phylogeny of populations
+/// with sequence data.
+///
+/// Populations without a sequence can't be a best match or a recombination
+/// parent, so they are removed, and their children are connected to their
+/// parents. Returns the phylogeny and the names of the removed populations.
+pub fn effective_phylogeny(
+    dataset: &Dataset,
+) -> Result<(Phylogeny, Vec<String>), Report> {
+    let mut phylogeny = dataset.phylogeny.clone();
+    let removed = phylogeny.retain(|name| dataset.populations.contains_key(name))?;
+    Ok((phylogeny, removed))
+}
@@ -0,0 +1,82 @@
+use crate::cli;
+use crate::dataset::load;
+use crate::dataset::signature::Signature;
+use crate::sequence::translate;
+use crate::utils::table::Table;
+use color_eyre::eyre::{eyre, Report, Result};
+use itertools::Itertools;
+use log::info;
+
+/// Query dataset populations with a mutation or signature, and their descendants.
+pub fn query(args: &cli::dataset::query::Args) -> Result<(), Report> {
+    // mutations are reported without masking the genome ends
+    let mask = vec![0, 0];
+    let dataset = load::dataset(&args.dataset_dir, &mask)?;
+
+    // a single mutation is the simplest signature
+    let (signature, file_name) = match (&args.mutation, &args.signature) {
+        (_, Some(signature)) => (signature.clone(), "query.tsv".to_string()),
+        (Some(mutation), None) => {
+            (Signature::Has(*mutation), format!("query_{mutation}.tsv"))
+        }
+        (None, None) => {
+            return Err(eyre!("Either --mutation or --signature is required."))
+        }
+    };
+
+    let populations = signature.evaluate(&dataset);
+
+    let mut table = Table::new();
+    table.headers = vec![
+        "population",
+        "descendants",
+        "descendants_matching",
+        "aa_substitutions",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect_vec();
+
+    for population in &populations {
+        let mut row = vec![population.to_string(), String::new(), String::new()];
+        // protein-level substitutions, if the dataset has annotations
+        let aa_substitutions = match dataset.populations.get(population) {
+            Some(sequence) => translate::substitutions(
+                sequence,
+                &dataset.reference,
+                &dataset.annotations,
+            )?,
+            None => Vec::new(),
+        };
+        // descendants can only be counted for populations in the phylogeny
+        if dataset.phylogeny.get_node(population).is_ok() {
+            let descendants = dataset
+                .phylogeny
+                .get_descendants(population)?
+                .into_iter()
+                .filter(|d| d != population)
+                .collect_vec();
+            let num_matching =
+                descendants.iter().filter(|d| populations.contains(d)).count();
+            row[1] = descendants.len().to_string();
+            row[2] = num_matching.to_string();
+        }
+        row.push(aa_substitutions.iter().join(","));
+        table.rows.push(row);
+    }
+
+    info!(
+        "Found {} population(s) matching {signature}.",
+        populations.len()
+    );
+
+    println!("\n{}", table.to_markdown()?);
+
+    if let Some(output_dir) = &args.output_dir {
+        let output_path = output_dir.join(file_name);
+        info!("Exporting query: {output_path:?}");
+        table.write(&output_path)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,100 @@
+use crate::utils;
+use crate::utils::versioned_file::VersionedFile;
+use color_eyre::eyre::{Report, Result, WrapErr};
+use color_eyre::Help;
+use log::{debug, info, warn};
+use std::path::{Path, PathBuf};
+
+/// Dataset files that are required for a run.
+pub const REQUIRED_FILES: &[&str] = &["reference.fasta", "populations.fasta"];
+/// Dataset files that are used by a run if present.
+pub const OPTIONAL_FILES: &[&str] = &[
+    "phylogeny.json",
+    "edge_cases.json",
+    "annotations.tsv",
+    "dates.tsv",
+];
+
+// ----------------------------------------------------------------------------
+// Functions
+// ----------------------------------------------------------------------------
+
+/// Default cache directory for remote datasets.
+pub fn default_cache_dir() -> PathBuf {
+    std::env::temp_dir().join("rebar").join("datasets")
+}
+
+/// Get the base url of a dataset, from the url of its summary or directory.
+pub fn base_url(url: &str) -> String {
+    let url = url.trim_end_matches('/');
+    match url.rsplit_once('/') {
+        Some((base, file_name)) if file_name.ends_with(".json") => base.to_string(),
+        _ => url.to_string(),
+    }
+}
+
+/// Fetch the files of a remote dataset needed for a run, into a cache directory.
+///
+/// The summary (summary.json) is always fetched, other files are only fetched
+/// if they are not cached, or if the summary has changed since they were.
+/// Returns the dataset directory in the cache, which can be loaded as usual.
+pub async fn fetch(url: &str, cache_dir: &Path) -> Result<PathBuf, Report> {
+    let base_url = base_url(url);
+    // one cache directory per dataset url
+    let key = base_url
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    let dataset_dir = cache_dir.join(key);
+    info!("Fetching remote dataset: {base_url} to {dataset_dir:?}");
+
+    // the summary identifies the version of the dataset, it replaces the
+    // cached summary last, so an interrupted fetch is retried next time
+    let summary_path = dataset_dir.join("summary.json");
+    let fetched_path = dataset_dir.join("summary.json.fetched");
+    let cached_summary = utils::read_to_string(&summary_path).ok();
+    VersionedFile::from_url(&format!("{base_url}/summary.json"), &fetched_path)
+        .fetch()
+        .await
+        .wrap_err_with(|| format!("Failed to fetch remote dataset: {url}"))
+        .suggestion("Is --dataset-url the url of a dataset summary.json?")?;
+    let is_stale = cached_summary != Some(utils::read_to_string(&fetched_path)?);
+    if is_stale {
+        debug!("Remote dataset has changed, cached files will be replaced.");
+    }
+
+    for file_name in REQUIRED_FILES.iter().chain(OPTIONAL_FILES) {
+        let local_path = dataset_dir.join(file_name);
+        if local_path.exists() && !is_stale {
+            debug!("Using cached file: {local_path:?}");
+            continue;
+        }
+        let mut file =
+            VersionedFile::from_url(&format!("{base_url}/{file_name}"), &local_path);
+        match file.fetch().await {
+            Ok(()) => (),
+            Err(e) if REQUIRED_FILES.contains(file_name) => {
+                return Err(e)
+                    .wrap_err_with(|| format!("Failed to fetch remote dataset: {url}"))
+            }
+            Err(e) => {
+                warn!("Optional dataset file could not be fetched: {file_name}");
+                debug!("{e}");
+                // don't use a stale optional file from a previous version
+                if local_path.exists() {
+                    std::fs::remove_file(&local_path)?;
+                }
+            }
+        }
+    }
+    std::fs::rename(&fetched_path, &summary_path)
+        .wrap_err_with(|| format!("Failed to write file: {summary_path:?}"))?;
+
+    Ok(dataset_dir)
+}
@@ -1,3 +1,4 @@
+use crate::utils;
 use crate::{dataset, phylogeny::Phylogeny, utils::table::Table};
 use bio::io::fasta;
 use color_eyre::eyre::{eyre, Report, Result, WrapErr};
@@ -92,9 +93,7 @@ pub async fn build(
     // read populations fasta, to check if any lineages are missing in notes
     let populations_path = &summary.populations.local_path;
     let populations_file_name = populations_path.file_name().unwrap().to_str().unwrap();
-    let alignment_reader = fasta::Reader::from_file(populations_path)
-        .map_err(|e| eyre!(e))
-        .wrap_err("Failed to read file: {populations_path:?}")?;
+    let alignment_reader = fasta::Reader::from_bufread(summary.populations.open()?);
 
     // keep track of population names in alignment, cross-reference against
     // lineage notes + alias_key later
@@ -134,13 +133,13 @@ pub async fn build(
 
     // Add root node
     let name = "root".to_string();
-    phylogeny.graph.add_node(name);
+    phylogeny.add_node(name);
 
     // todo!() Do this twice? in case lineages are accidentally out of order?
 
     // Add descendants
     for name in graph_order {
-        let id = phylogeny.graph.add_node(name.clone());
+        let id = phylogeny.add_node(name.clone());
         if !graph_data.contains_key(&name) {
             return Err(
                 eyre!("Parents of {name} are unknown in the phylogeny graph.")
@@ -167,7 +166,7 @@ pub async fn build(
                     .suggestion("Please check if {parent} is in the alias key."));
             }
             let parent_id = phylogeny.get_node(parent)?;
-            phylogeny.graph.add_edge(parent_id, id, 1);
+            phylogeny.graph.add_edge(parent_id, id, 1.0);
         }
     }
 
@@ -377,8 +376,7 @@ pub fn decompress_lineage(
 /// values are a vector of parents.
 pub fn read_alias_key(path: &Path) -> Result<BTreeMap<String, Vec<String>>, Report> {
     // read to json
-    let alias_key_str =
-        std::fs::read_to_string(path).expect("Couldn't read alias_key file.");
+    let alias_key_str = utils::read_to_string(path)?;
     let alias_key_val: serde_json::Value =
         serde_json::from_str(&alias_key_str).expect("Couldn't convert alias_key to json");
     // deserialize to object (raw, mixed types)
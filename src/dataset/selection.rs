@@ -0,0 +1,212 @@
+use crate::dataset::Dataset;
+use color_eyre::eyre::{eyre, Report, Result};
+use color_eyre::Help;
+use itertools::Itertools;
+use std::fmt;
+use std::str::FromStr;
+
+/// Suggestion for population selection syntax errors.
+const SYNTAX: &str = "Selections combine populations with '&' (and), '|' (or), ' - ' (minus), '!' (not) and parentheses, ex. \"XBB* & !XBB.1.16*\"";
+
+// ----------------------------------------------------------------------------
+// Selection
+
+/// An expression tree of set operations on populations.
+///
+/// Populations are names or wildcards (ex. 'XBB*' for XBB and its descendants).
+/// Operators from lowest to highest precedence are: union ('|') and difference
+/// (' - ', with spaces), intersection ('&'), and complement ('!').
+#[derive(Clone, Debug, PartialEq)]
+pub enum Selection {
+    Population(String),
+    Not(Box<Selection>),
+    And(Box<Selection>, Box<Selection>),
+    Or(Box<Selection>, Box<Selection>),
+    Minus(Box<Selection>, Box<Selection>),
+}
+
+impl fmt::Display for Selection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Selection::Population(name) => write!(f, "{name}"),
+            Selection::Not(a) => write!(f, "!{a}"),
+            Selection::And(a, b) => write!(f, "({a} & {b})"),
+            Selection::Or(a, b) => write!(f, "({a} | {b})"),
+            Selection::Minus(a, b) => write!(f, "({a} - {b})"),
+        }
+    }
+}
+
+impl FromStr for Selection {
+    type Err = Report;
+
+    fn from_str(text: &str) -> Result<Self, Report> {
+        let mut parser = Parser {
+            tokens: tokenize(text),
+            pos: 0,
+        };
+        let selection =
+            parser.union().and_then(|selection| match parser.tokens.get(parser.pos) {
+                Some(token) => Err(eyre!("Unexpected '{token}'")),
+                None => Ok(selection),
+            });
+        selection
+            .map_err(|e| eyre!("Invalid population selection: {text}: {e}"))
+            .suggestion(SYNTAX)
+    }
+}
+
+impl Selection {
+    /// Evaluate the selection against a dataset, into population names.
+    ///
+    /// Populations are ordered by first appearance, the complement is in
+    /// dataset order.
+    pub fn evaluate(&self, dataset: &Dataset) -> Result<Vec<String>, Report> {
+        let populations = match self {
+            Selection::Population(pattern) => dataset.expand_population(pattern)?,
+            Selection::Not(a) => {
+                let a = a.evaluate(dataset)?;
+                dataset.populations.keys().filter(|p| !a.contains(p)).cloned().collect()
+            }
+            Selection::And(a, b) => {
+                let b = b.evaluate(dataset)?;
+                a.evaluate(dataset)?.into_iter().filter(|p| b.contains(p)).collect()
+            }
+            Selection::Or(a, b) => {
+                let mut a = a.evaluate(dataset)?;
+                a.extend(b.evaluate(dataset)?);
+                a
+            }
+            Selection::Minus(a, b) => {
+                let b = b.evaluate(dataset)?;
+                a.evaluate(dataset)?.into_iter().filter(|p| !b.contains(p)).collect()
+            }
+        };
+        Ok(populations.into_iter().unique().collect_vec())
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Parser
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    And,
+    Or,
+    Minus,
+    Not,
+    Open,
+    Close,
+    Name(String),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::And => write!(f, "&"),
+            Token::Or => write!(f, "|"),
+            Token::Minus => write!(f, "-"),
+            Token::Not => write!(f, "!"),
+            Token::Open => write!(f, "("),
+            Token::Close => write!(f, ")"),
+            Token::Name(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+/// Split a selection into tokens.
+///
+/// A '-' is only an operator when separated by spaces, as it can be part of a
+/// population name.
+fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut name = String::new();
+    let flush = |name: &mut String, tokens: &mut Vec<Token>| {
+        match name.as_str() {
+            "" => (),
+            "-" => tokens.push(Token::Minus),
+            _ => tokens.push(Token::Name(name.clone())),
+        }
+        name.clear();
+    };
+
+    for c in text.chars() {
+        let token = match c {
+            '&' => Some(Token::And),
+            '|' => Some(Token::Or),
+            '!' => Some(Token::Not),
+            '(' => Some(Token::Open),
+            ')' => Some(Token::Close),
+            _ if c.is_whitespace() => None,
+            _ => {
+                name.push(c);
+                continue;
+            }
+        };
+        flush(&mut name, &mut tokens);
+        tokens.extend(token);
+    }
+    flush(&mut name, &mut tokens);
+
+    tokens
+}
+
+/// Recursive descent parser of population selections.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    /// union := intersection (('|' | '-') intersection)*
+    fn union(&mut self) -> Result<Selection, Report> {
+        let mut selection = self.intersection()?;
+        while let Some(token @ (Token::Or | Token::Minus)) = self.peek().cloned() {
+            self.pos += 1;
+            let other = Box::new(self.intersection()?);
+            selection = match token {
+                Token::Or => Selection::Or(Box::new(selection), other),
+                _ => Selection::Minus(Box::new(selection), other),
+            };
+        }
+        Ok(selection)
+    }
+
+    /// intersection := complement ('&' complement)*
+    fn intersection(&mut self) -> Result<Selection, Report> {
+        let mut selection = self.complement()?;
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            let other = self.complement()?;
+            selection = Selection::And(Box::new(selection), Box::new(other));
+        }
+        Ok(selection)
+    }
+
+    /// complement := '!' complement | '(' union ')' | population
+    fn complement(&mut self) -> Result<Selection, Report> {
+        match self.next() {
+            Some(Token::Not) => Ok(Selection::Not(Box::new(self.complement()?))),
+            Some(Token::Open) => {
+                let selection = self.union()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(selection),
+                    _ => Err(eyre!("Missing ')'")),
+                }
+            }
+            Some(Token::Name(name)) => Ok(Selection::Population(name)),
+            Some(token) => Err(eyre!("Unexpected '{token}'")),
+            None => Err(eyre!("Unexpected end")),
+        }
+    }
+}
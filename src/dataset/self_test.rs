@@ -0,0 +1,317 @@
+use crate::cli;
+use crate::dataset::{Dataset, SearchResult};
+use crate::export::sink::OutputSink;
+use crate::recombination::validate::{self, Status, Validate};
+use crate::recombination::Recombination;
+use crate::run;
+use crate::run::layout::Layout;
+use crate::utils;
+use crate::utils::fmt;
+use crate::utils::table::Table;
+use color_eyre::eyre::{eyre, Report, Result};
+use color_eyre::Help;
+use itertools::Itertools;
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+
+// ----------------------------------------------------------------------------
+// Outcome
+// ----------------------------------------------------------------------------
+
+/// The detection of a dataset population, compared with the phylogeny.
+#[derive(Clone, Debug)]
+pub struct Outcome {
+    pub population: String,
+    /// Recombinant ancestor of the population in the phylogeny, if any.
+    pub expected_recombinant: Option<String>,
+    /// Parents of the recombinant ancestor in the phylogeny.
+    pub expected_parents: Vec<String>,
+    pub observed_population: String,
+    pub observed_recombinant: Option<String>,
+    pub observed_parents: Vec<String>,
+    /// How the population was detected, if it is a designated recombinant
+    /// (see rebar run --edge-case-stats).
+    pub detection: Option<String>,
+    pub validate: Validate,
+}
+
+/// Collect the outcome of each population as the run writes its results.
+struct OutcomeSink {
+    outcomes: Rc<RefCell<Vec<Outcome>>>,
+}
+
+impl OutputSink for OutcomeSink {
+    fn on_result(
+        &mut self,
+        best_match: &SearchResult,
+        recombination: &Recombination,
+        dataset: &Dataset,
+    ) -> Result<(), Report> {
+        // sequences that are not dataset populations have no expected values
+        let Some(validate) = validate::compare(dataset, best_match, recombination)?
+        else {
+            return Ok(());
+        };
+        let population = best_match.sequence_id.replace("population_", "");
+        let expected_recombinant =
+            dataset.phylogeny.get_recombinant_ancestor(&population)?;
+        let expected_parents = match &expected_recombinant {
+            Some(recombinant) => dataset.phylogeny.get_parents(recombinant)?,
+            None => Vec::new(),
+        };
+        self.outcomes.borrow_mut().push(Outcome {
+            population,
+            expected_recombinant,
+            expected_parents,
+            observed_population: best_match.consensus_population.clone(),
+            observed_recombinant: recombination.recombinant.clone(),
+            observed_parents: recombination.parents.clone(),
+            detection: None,
+            validate,
+        });
+        Ok(())
+    }
+
+    fn on_finish(self: Box<Self>) -> Result<(), Report> {
+        Ok(())
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Accuracy
+// ----------------------------------------------------------------------------
+
+/// Accuracy of the detection of dataset populations, against the phylogeny.
+///
+/// Fractions are 0 if there are no populations to compute them from.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Accuracy {
+    pub populations: usize,
+    /// Number of populations that passed validation.
+    pub pass: usize,
+    /// Fraction of populations that passed validation.
+    #[serde(serialize_with = "fmt::serialize_float")]
+    pub accuracy: f64,
+    /// Number of populations with a recombinant ancestor (or that are one).
+    pub recombinants: usize,
+    /// Number of recombinant populations that were detected as recombinant.
+    pub recombinants_detected: usize,
+    /// Fraction of recombinant populations that were detected as recombinant.
+    #[serde(serialize_with = "fmt::serialize_float")]
+    pub sensitivity: f64,
+    /// Number of non-recombinant populations that were detected as recombinant.
+    pub false_positives: usize,
+    /// Fraction of non-recombinant populations that were not detected as
+    /// recombinant.
+    #[serde(serialize_with = "fmt::serialize_float")]
+    pub specificity: f64,
+    /// Number of populations that failed validation, by reason.
+    pub failures: BTreeMap<String, usize>,
+    /// Number of designated recombinants, by how they were detected.
+    pub detection: BTreeMap<String, usize>,
+}
+
+impl Default for Accuracy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Accuracy {
+    pub fn new() -> Self {
+        Accuracy {
+            populations: 0,
+            pass: 0,
+            accuracy: 0.0,
+            recombinants: 0,
+            recombinants_detected: 0,
+            sensitivity: 0.0,
+            false_positives: 0,
+            specificity: 0.0,
+            failures: BTreeMap::new(),
+            detection: BTreeMap::new(),
+        }
+    }
+
+    /// Summarize the accuracy of population outcomes.
+    pub fn from_outcomes(outcomes: &[Outcome]) -> Self {
+        let fraction = |n: usize, total: usize| match total {
+            0 => 0.0,
+            total => n as f64 / total as f64,
+        };
+        let mut accuracy = Accuracy::new();
+
+        accuracy.populations = outcomes.len();
+        accuracy.pass = outcomes
+            .iter()
+            .filter(|outcome| matches!(outcome.validate.status, Status::Pass))
+            .count();
+        accuracy.accuracy = fraction(accuracy.pass, accuracy.populations);
+
+        let (recombinants, non_recombinants): (Vec<_>, Vec<_>) =
+            outcomes.iter().partition(|outcome| outcome.expected_recombinant.is_some());
+        let detected = |outcome: &Outcome| outcome.observed_recombinant.is_some();
+        accuracy.recombinants = recombinants.len();
+        accuracy.recombinants_detected =
+            recombinants.iter().filter(|outcome| detected(outcome)).count();
+        accuracy.sensitivity =
+            fraction(accuracy.recombinants_detected, accuracy.recombinants);
+        accuracy.false_positives =
+            non_recombinants.iter().filter(|outcome| detected(outcome)).count();
+        accuracy.specificity = fraction(
+            non_recombinants.len() - accuracy.false_positives,
+            non_recombinants.len(),
+        );
+
+        accuracy.failures = outcomes
+            .iter()
+            .flat_map(|outcome| &outcome.validate.details)
+            .map(|details| details.to_string())
+            .counts()
+            .into_iter()
+            .collect();
+        accuracy.detection = outcomes
+            .iter()
+            .filter_map(|outcome| outcome.detection.clone())
+            .counts()
+            .into_iter()
+            .collect();
+
+        accuracy
+    }
+
+    /// Convert the accuracy to a table, with failures and detection by key.
+    pub fn to_table(&self) -> Table {
+        let mut table = Table::new();
+        table.headers = vec!["statistic".to_string(), "value".to_string()];
+        table.rows = [
+            ("populations", self.populations.to_string()),
+            ("pass", self.pass.to_string()),
+            ("accuracy", fmt::float(self.accuracy)),
+            ("recombinants", self.recombinants.to_string()),
+            (
+                "recombinants_detected",
+                self.recombinants_detected.to_string(),
+            ),
+            ("sensitivity", fmt::float(self.sensitivity)),
+            ("false_positives", self.false_positives.to_string()),
+            ("specificity", fmt::float(self.specificity)),
+        ]
+        .into_iter()
+        .map(|(statistic, value)| (statistic.to_string(), value))
+        .chain(
+            self.failures.iter().map(|(d, n)| (format!("failures_{d}"), n.to_string())),
+        )
+        .chain(
+            self.detection.iter().map(|(d, n)| (format!("detection_{d}"), n.to_string())),
+        )
+        .map(|(statistic, value)| vec![statistic, value])
+        .collect_vec();
+
+        table
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Functions
+// ----------------------------------------------------------------------------
+
+/// Create a table of population outcomes (self_test.tsv), sorted by population.
+pub fn table(outcomes: &[Outcome]) -> Table {
+    let mut table = Table::new();
+    table.headers = vec![
+        "population",
+        "expected_recombinant",
+        "expected_parents",
+        "observed_population",
+        "observed_recombinant",
+        "observed_parents",
+        "detection",
+        "validate",
+        "validate_details",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect_vec();
+
+    for outcome in outcomes.iter().sorted_by(|a, b| a.population.cmp(&b.population)) {
+        table.rows.push(vec![
+            outcome.population.clone(),
+            outcome.expected_recombinant.clone().unwrap_or_default(),
+            outcome.expected_parents.join(","),
+            outcome.observed_population.clone(),
+            outcome.observed_recombinant.clone().unwrap_or_default(),
+            outcome.observed_parents.join(","),
+            outcome.detection.clone().unwrap_or_default(),
+            outcome.validate.status.to_string(),
+            outcome.validate.details.iter().join(","),
+        ]);
+    }
+    table
+}
+
+/// Run dataset populations as queries, and report accuracy against the phylogeny.
+///
+/// Each population is searched like a query sequence, and its consensus
+/// population, recombinant ancestor, and parents are validated against the
+/// phylogeny (see the validate column of rebar run). Designated recombinants
+/// are also compared with a naive search (see rebar run --edge-case-stats).
+pub fn self_test(args: &cli::dataset::self_test::Args) -> Result<(), Report> {
+    let phylogeny_path = args.dataset_dir.join("phylogeny.json");
+    if !phylogeny_path.exists() {
+        return Err(eyre!("No phylogeny was found: {phylogeny_path:?}"))
+            .suggestion("A self-test requires a phylogeny, as the expected results.");
+    }
+
+    // the run outputs are kept for inspection, next to the accuracy
+    let run_dir = args.output_dir.join("run");
+    let mut run_args = cli::run::Args {
+        input: cli::run::Input {
+            populations: Some(args.populations.clone()),
+            ..Default::default()
+        },
+        dataset_dir: Some(args.dataset_dir.clone()),
+        output_dir: run_dir.clone(),
+        mask: args.mask.clone(),
+        min_length: args.min_length,
+        threads: args.threads,
+        edge_case_stats: true,
+        ..Default::default()
+    };
+    let outcomes = Rc::new(RefCell::new(Vec::new()));
+    let sink = OutcomeSink {
+        outcomes: Rc::clone(&outcomes),
+    };
+    run::run_with_sinks(&mut run_args, vec![Box::new(sink)])?;
+    let mut outcomes = outcomes.take();
+
+    // how designated recombinants were detected, from the edge case statistics
+    let edge_cases = Table::read(&Layout::new(&run_dir).edge_cases())?;
+    let population_i = edge_cases.header_position("population")?;
+    let detection_i = edge_cases.header_position("detection")?;
+    let detections: HashMap<&String, &String> = edge_cases
+        .rows
+        .iter()
+        .map(|row| (&row[population_i], &row[detection_i]))
+        .collect();
+    for outcome in outcomes.iter_mut() {
+        outcome.detection = detections.get(&outcome.population).map(|d| d.to_string());
+    }
+
+    let accuracy = Accuracy::from_outcomes(&outcomes);
+    println!("\n{}", accuracy.to_table().to_markdown()?);
+
+    let output_path = args.output_dir.join("self_test.tsv");
+    info!("Exporting self-test results: {output_path:?}");
+    table(&outcomes).write(&output_path)?;
+
+    let output_path = args.output_dir.join("accuracy.json");
+    info!("Exporting self-test accuracy: {output_path:?}");
+    let output = serde_json::to_string_pretty(&accuracy)?;
+    utils::write_atomic(&output_path, format!("{output}\n"))?;
+
+    Ok(())
+}
@@ -0,0 +1,179 @@
+use crate::dataset::Dataset;
+use crate::sequence::Substitution;
+use color_eyre::eyre::{eyre, Report, Result};
+use color_eyre::Help;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+
+/// Suggestion for mutation signature syntax errors.
+const SYNTAX: &str = "Signatures combine has(<substitution>) with 'and', 'or', 'not' and parentheses, ex. \"has(C22995A) and not has(T22917G)\"";
+
+// ----------------------------------------------------------------------------
+// Signature
+
+/// A boolean expression of the substitutions a population has.
+///
+/// Operators from lowest to highest precedence are: 'or', 'and', and 'not'.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Signature {
+    Has(Substitution),
+    Not(Box<Signature>),
+    And(Box<Signature>, Box<Signature>),
+    Or(Box<Signature>, Box<Signature>),
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Signature::Has(substitution) => write!(f, "has({substitution})"),
+            Signature::Not(a) => write!(f, "not {a}"),
+            Signature::And(a, b) => write!(f, "({a} and {b})"),
+            Signature::Or(a, b) => write!(f, "({a} or {b})"),
+        }
+    }
+}
+
+impl FromStr for Signature {
+    type Err = Report;
+
+    fn from_str(text: &str) -> Result<Self, Report> {
+        let mut parser = Parser {
+            tokens: tokenize(text),
+            pos: 0,
+        };
+        let signature =
+            parser.or().and_then(|signature| match parser.tokens.get(parser.pos) {
+                Some(token) => Err(eyre!("Unexpected '{token}'")),
+                None => Ok(signature),
+            });
+        signature
+            .map_err(|e| eyre!("Invalid mutation signature: {text}: {e}"))
+            .suggestion(SYNTAX)
+    }
+}
+
+impl Signature {
+    /// Evaluate the signature against the dataset mutations, into population names.
+    ///
+    /// Populations are in dataset order.
+    pub fn evaluate(&self, dataset: &Dataset) -> Vec<String> {
+        self.members(dataset).into_iter().cloned().collect()
+    }
+
+    fn members<'d>(&self, dataset: &'d Dataset) -> BTreeSet<&'d String> {
+        match self {
+            Signature::Has(substitution) => {
+                dataset.populations_with(substitution).into_iter().collect()
+            }
+            Signature::Not(a) => {
+                let a = a.members(dataset);
+                dataset.populations.keys().filter(|p| !a.contains(p)).collect()
+            }
+            Signature::And(a, b) => {
+                a.members(dataset).intersection(&b.members(dataset)).copied().collect()
+            }
+            Signature::Or(a, b) => {
+                a.members(dataset).union(&b.members(dataset)).copied().collect()
+            }
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Parser
+
+/// Split a signature into tokens, words and parentheses.
+fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    for c in text.chars() {
+        if c.is_whitespace() || c == '(' || c == ')' {
+            if !word.is_empty() {
+                tokens.push(std::mem::take(&mut word));
+            }
+            if !c.is_whitespace() {
+                tokens.push(c.to_string());
+            }
+        } else {
+            word.push(c);
+        }
+    }
+    if !word.is_empty() {
+        tokens.push(word);
+    }
+    tokens
+}
+
+/// Recursive descent parser of mutation signatures.
+struct Parser {
+    tokens: Vec<String>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<String> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn peek_is(&self, keyword: &str) -> bool {
+        self.tokens.get(self.pos).is_some_and(|t| t.eq_ignore_ascii_case(keyword))
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), Report> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => Err(eyre!("Expected '{expected}', found '{token}'")),
+            None => Err(eyre!("Missing '{expected}'")),
+        }
+    }
+
+    /// or := and ('or' and)*
+    fn or(&mut self) -> Result<Signature, Report> {
+        let mut signature = self.and()?;
+        while self.peek_is("or") {
+            self.pos += 1;
+            let other = self.and()?;
+            signature = Signature::Or(Box::new(signature), Box::new(other));
+        }
+        Ok(signature)
+    }
+
+    /// and := not ('and' not)*
+    fn and(&mut self) -> Result<Signature, Report> {
+        let mut signature = self.not()?;
+        while self.peek_is("and") {
+            self.pos += 1;
+            let other = self.not()?;
+            signature = Signature::And(Box::new(signature), Box::new(other));
+        }
+        Ok(signature)
+    }
+
+    /// not := 'not' not | '(' or ')' | 'has' '(' substitution ')'
+    fn not(&mut self) -> Result<Signature, Report> {
+        match self.next() {
+            Some(token) if token.eq_ignore_ascii_case("not") => {
+                Ok(Signature::Not(Box::new(self.not()?)))
+            }
+            Some(token) if token == "(" => {
+                let signature = self.or()?;
+                self.expect(")")?;
+                Ok(signature)
+            }
+            Some(token) if token.eq_ignore_ascii_case("has") => {
+                self.expect("(")?;
+                let substitution = match self.next() {
+                    Some(token) if token != ")" => Substitution::from_str(&token)?,
+                    _ => return Err(eyre!("Missing substitution in has()")),
+                };
+                self.expect(")")?;
+                Ok(Signature::Has(substitution))
+            }
+            Some(token) => Err(eyre!("Unexpected '{token}'")),
+            None => Err(eyre!("Unexpected end")),
+        }
+    }
+}
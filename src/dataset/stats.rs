@@ -0,0 +1,328 @@
+use crate::cli;
+use crate::dataset::attributes::{Name, Tag};
+use crate::dataset::barcodes::BarcodeClasses;
+use crate::dataset::{load, Dataset};
+use crate::sequence::Substitution;
+use crate::utils;
+use crate::utils::fmt;
+use crate::utils::table::Table;
+use color_eyre::eyre::{Report, Result};
+use itertools::Itertools;
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::default::Default;
+
+// ----------------------------------------------------------------------------
+// Stats
+// ----------------------------------------------------------------------------
+
+/// Dataset-wide statistics, to sanity-check (custom) datasets.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Stats {
+    pub name: Name,
+    pub tag: Tag,
+    pub genome_length: usize,
+    pub populations: usize,
+    pub recombinants: usize,
+    /// Number of unique substitutions across all populations.
+    pub substitutions: usize,
+    /// Distribution of the number of substitutions per population.
+    pub substitutions_per_population: Distribution,
+    /// Longest path (edges) from the root of the phylogeny.
+    pub phylogeny_depth: usize,
+    /// Substitutions that arose most often independently in the phylogeny.
+    pub homoplasies: Vec<Homoplasy>,
+    /// Populations with identical barcodes (see rebar run --collapse-barcodes).
+    #[serde(default)]
+    pub barcode_classes: Vec<BarcodeClass>,
+}
+
+/// Summary of a distribution of counts.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Distribution {
+    pub min: usize,
+    pub max: usize,
+    #[serde(serialize_with = "fmt::serialize_float")]
+    pub mean: f64,
+    #[serde(serialize_with = "fmt::serialize_float")]
+    pub median: f64,
+}
+
+/// A substitution found in populations that don't share it with a parent.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Homoplasy {
+    pub substitution: String,
+    /// Number of independent origins in the phylogeny.
+    pub origins: usize,
+    /// Number of populations with this substitution.
+    pub populations: usize,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Stats {
+            name: Name::Custom,
+            tag: Tag::Custom,
+            genome_length: 0,
+            populations: 0,
+            recombinants: 0,
+            substitutions: 0,
+            substitutions_per_population: Distribution::default(),
+            phylogeny_depth: 0,
+            homoplasies: Vec::new(),
+            barcode_classes: Vec::new(),
+        }
+    }
+
+    /// Compute statistics of a dataset, reporting the top N homoplasies.
+    pub fn from_dataset(dataset: &Dataset, top: usize) -> Result<Self, Report> {
+        let mut stats = Stats::new();
+
+        stats.name = dataset.name;
+        stats.tag = dataset.tag.clone();
+        stats.genome_length = dataset.reference.genome_length;
+        stats.populations = dataset.populations.len();
+        stats.recombinants = dataset
+            .phylogeny
+            .recombinants_all
+            .iter()
+            .filter(|pop| dataset.populations.contains_key(*pop))
+            .count();
+
+        // index substitutions by coordinate and alt, since the dataset mutations
+        // group all substitutions at the same coordinate together
+        let mut substitutions: BTreeMap<
+            (usize, char),
+            (Substitution, BTreeSet<&String>),
+        > = BTreeMap::new();
+        for (pop, sequence) in &dataset.populations {
+            for sub in &sequence.substitutions {
+                substitutions
+                    .entry((sub.coord, sub.alt))
+                    .or_insert_with(|| (*sub, BTreeSet::new()))
+                    .1
+                    .insert(pop);
+            }
+        }
+        stats.substitutions = substitutions.len();
+
+        // --------------------------------------------------------------------
+        // Substitutions Per Population
+
+        let counts = dataset
+            .populations
+            .values()
+            .map(|sequence| sequence.substitutions.len())
+            .sorted()
+            .collect_vec();
+        stats.substitutions_per_population = Distribution::from_counts(&counts);
+
+        // --------------------------------------------------------------------
+        // Phylogeny
+
+        if !dataset.phylogeny.is_empty() {
+            stats.phylogeny_depth = dataset.phylogeny.get_depth()?;
+        }
+
+        // --------------------------------------------------------------------
+        // Homoplasies
+
+        // lookup parents once, populations without a phylogeny have no parents
+        let parents = dataset
+            .populations
+            .keys()
+            .map(|pop| {
+                let parents = if dataset.phylogeny.get_node(pop).is_ok() {
+                    dataset.phylogeny.get_parents(pop)?
+                } else {
+                    Vec::new()
+                };
+                Ok((pop, parents))
+            })
+            .collect::<Result<BTreeMap<_, _>, Report>>()?;
+
+        // a substitution arises independently in a population, if none of
+        // that population's parents also have the substitution
+        stats.homoplasies = substitutions
+            .values()
+            .map(|(sub, populations)| {
+                let origins = populations
+                    .iter()
+                    .filter(|pop| {
+                        !parents[*pop].iter().any(|parent| populations.contains(parent))
+                    })
+                    .count();
+                Homoplasy {
+                    substitution: sub.to_string(),
+                    origins,
+                    populations: populations.len(),
+                }
+            })
+            .filter(|homoplasy| homoplasy.origins > 1)
+            // most origins first, then by coordinate order
+            .enumerate()
+            .sorted_by(|(i1, h1), (i2, h2)| h2.origins.cmp(&h1.origins).then(i1.cmp(i2)))
+            .map(|(_i, homoplasy)| homoplasy)
+            .take(top)
+            .collect_vec();
+
+        // --------------------------------------------------------------------
+        // Barcode Classes
+
+        stats.barcode_classes = BarcodeClasses::from_populations(&dataset.populations)
+            .collapsed()
+            .map(|(representative, members)| BarcodeClass {
+                representative: representative.clone(),
+                members: members.clone(),
+            })
+            .collect_vec();
+
+        Ok(stats)
+    }
+
+    /// Convert the summary statistics (excluding homoplasies) to a table.
+    pub fn to_table(&self) -> Table {
+        let dist = &self.substitutions_per_population;
+        let mut table = Table::new();
+        table.headers = vec!["statistic".to_string(), "value".to_string()];
+        table.rows = vec![
+            ("name", self.name.to_string()),
+            ("tag", self.tag.to_string()),
+            ("genome_length", self.genome_length.to_string()),
+            ("populations", self.populations.to_string()),
+            ("recombinants", self.recombinants.to_string()),
+            ("substitutions", self.substitutions.to_string()),
+            ("substitutions_per_population_min", dist.min.to_string()),
+            ("substitutions_per_population_max", dist.max.to_string()),
+            ("substitutions_per_population_mean", fmt::float(dist.mean)),
+            (
+                "substitutions_per_population_median",
+                fmt::float(dist.median),
+            ),
+            ("phylogeny_depth", self.phylogeny_depth.to_string()),
+            ("barcode_classes", self.barcode_classes.len().to_string()),
+            (
+                "barcode_class_members",
+                self.barcode_classes
+                    .iter()
+                    .map(|c| c.members.len())
+                    .sum::<usize>()
+                    .to_string(),
+            ),
+        ]
+        .into_iter()
+        .map(|(statistic, value)| vec![statistic.to_string(), value])
+        .collect_vec();
+
+        table
+    }
+
+    /// Convert the barcode classes to a table, with comma separated members.
+    pub fn barcode_classes_table(&self) -> Table {
+        let mut table = Table::new();
+        table.headers =
+            vec!["representative", "members"].into_iter().map(String::from).collect_vec();
+        table.rows = self
+            .barcode_classes
+            .iter()
+            .map(|c| vec![c.representative.clone(), c.members.join(",")])
+            .collect_vec();
+
+        table
+    }
+
+    /// Convert the homoplasies to a table.
+    pub fn homoplasies_table(&self) -> Table {
+        let mut table = Table::new();
+        table.headers = vec!["substitution", "origins", "populations"]
+            .into_iter()
+            .map(String::from)
+            .collect_vec();
+        table.rows = self
+            .homoplasies
+            .iter()
+            .map(|h| {
+                vec![
+                    h.substitution.clone(),
+                    h.origins.to_string(),
+                    h.populations.to_string(),
+                ]
+            })
+            .collect_vec();
+
+        table
+    }
+}
+
+impl Distribution {
+    /// Summarize counts, which must already be sorted.
+    pub fn from_counts(counts: &[usize]) -> Self {
+        if counts.is_empty() {
+            return Distribution::default();
+        }
+
+        let n = counts.len();
+        let mean = counts.iter().sum::<usize>() as f64 / n as f64;
+        // middle value if odd, average of the middle two values if even
+        let median = (counts[(n - 1) / 2] + counts[n / 2]) as f64 / 2.0;
+
+        Distribution {
+            min: counts[0],
+            max: counts[n - 1],
+            mean,
+            median,
+        }
+    }
+}
+
+/// Populations with identical barcodes, which are searched once by the
+/// representative with --collapse-barcodes.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BarcodeClass {
+    pub representative: String,
+    pub members: Vec<String>,
+}
+
+// ----------------------------------------------------------------------------
+// Functions
+// ----------------------------------------------------------------------------
+
+/// Report dataset statistics.
+pub fn stats(args: &cli::dataset::stats::Args) -> Result<(), Report> {
+    // statistics are reported without masking the genome ends
+    let mask = vec![0, 0];
+    let dataset = load::dataset(&args.dataset_dir, &mask)?;
+
+    let stats = Stats::from_dataset(&dataset, args.top)?;
+    let table = stats.to_table();
+    let homoplasies = stats.homoplasies_table();
+
+    println!("\n{}", table.to_markdown()?);
+    if !homoplasies.rows.is_empty() {
+        println!("{}", homoplasies.to_markdown()?);
+    }
+
+    if let Some(output_dir) = &args.output_dir {
+        let output_path = output_dir.join("stats.json");
+        info!("Exporting dataset stats: {output_path:?}");
+        let output = serde_json::to_string_pretty(&stats)?;
+        utils::write_atomic(&output_path, format!("{output}\n"))?;
+
+        let output_path = output_dir.join("homoplasies.tsv");
+        info!("Exporting homoplasies: {output_path:?}");
+        homoplasies.write(&output_path)?;
+
+        let output_path = output_dir.join("barcode_classes.tsv");
+        info!("Exporting barcode classes: {output_path:?}");
+        stats.barcode_classes_table().write(&output_path)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,26 @@
+use crate::utils::table::Table;
+use color_eyre::eyre::{Report, Result};
+use itertools::Itertools;
+
+/// Create Toy1 population dates.
+pub fn build() -> Result<Table, Report> {
+    let mut table = Table::new();
+
+    let headers = vec!["population", "date"];
+    let rows = vec![
+        vec!["A", "2020-01-01"],
+        vec!["B", "2020-02-01"],
+        vec!["C", "2020-03-01"],
+        vec!["D", "2020-04-01"],
+        vec!["E", "2020-05-01"],
+    ];
+
+    // Convert values to String
+    table.headers = headers.into_iter().map(String::from).collect_vec();
+    table.rows = rows
+        .into_iter()
+        .map(|row| row.into_iter().map(String::from).collect_vec())
+        .collect_vec();
+
+    Ok(table)
+}
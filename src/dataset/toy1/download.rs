@@ -1,14 +1,13 @@
 use crate::dataset::attributes::Tag;
-use crate::utils::remote_file::RemoteFile;
+use crate::utils;
+use crate::utils::versioned_file::{Format, VersionedFile};
 use chrono::Local;
-use color_eyre::eyre::{Report, Result, WrapErr};
+use color_eyre::eyre::{Report, Result};
 use indoc::formatdoc;
-use std::fs::File;
-use std::io::Write;
 use std::path::Path;
 
 /// Create and write Toy1 reference sequence.
-pub fn reference(_tag: &Tag, path: &Path) -> Result<RemoteFile, Report> {
+pub fn reference(_tag: &Tag, path: &Path) -> Result<VersionedFile, Report> {
     let sequences = formatdoc!(
         "
         >Reference
@@ -16,13 +15,11 @@ pub fn reference(_tag: &Tag, path: &Path) -> Result<RemoteFile, Report> {
         "
     );
 
-    let mut file = File::create(path)
-        .wrap_err_with(|| format!("Unable to create file: {path:?}"))?;
-    file.write_all(sequences.as_bytes())
-        .wrap_err_with(|| format!("Unable to write file: {path:?}"))?;
+    utils::write_atomic(path, sequences)?;
 
-    let remote_file = RemoteFile {
+    let remote_file = VersionedFile {
         local_path: path.to_owned(),
+        format: Format::Fasta,
         date_created: Local::now().into(),
         ..Default::default()
     };
@@ -31,7 +28,7 @@ pub fn reference(_tag: &Tag, path: &Path) -> Result<RemoteFile, Report> {
 }
 
 /// Create and write Toy1 populations sequence.
-pub fn populations(_tag: &Tag, path: &Path) -> Result<RemoteFile, Report> {
+pub fn populations(_tag: &Tag, path: &Path) -> Result<VersionedFile, Report> {
     let sequences = formatdoc!(
         "
         >A
@@ -47,13 +44,11 @@ pub fn populations(_tag: &Tag, path: &Path) -> Result<RemoteFile, Report> {
         "
     );
 
-    let mut file = File::create(path)
-        .wrap_err_with(|| format!("Unable to create file: {path:?}"))?;
-    file.write_all(sequences.as_bytes())
-        .wrap_err_with(|| format!("Unable to write file: {path:?}"))?;
+    utils::write_atomic(path, sequences)?;
 
-    let remote_file = RemoteFile {
+    let remote_file = VersionedFile {
         local_path: path.to_owned(),
+        format: Format::Fasta,
         date_created: Local::now().into(),
         ..Default::default()
     };
@@ -1,4 +1,5 @@
 pub mod annotations;
+pub mod dates;
 pub mod download;
 pub mod edge_cases;
 pub mod phylogeny;
@@ -6,34 +6,34 @@ pub fn build() -> Result<Phylogeny, Report> {
 
     // Add root node
     let name = "root".to_string();
-    let root_id = phylogeny.graph.add_node(name.clone());
+    let root_id = phylogeny.add_node(name.clone());
 
     // Add A node
     let name = "A".to_string();
-    let a_id = phylogeny.graph.add_node(name.clone());
-    phylogeny.graph.add_edge(root_id, a_id, 1);
+    let a_id = phylogeny.add_node(name.clone());
+    phylogeny.graph.add_edge(root_id, a_id, 1.0);
 
     // Add B node
     let name = "B".to_string();
-    let b_id = phylogeny.graph.add_node(name.clone());
-    phylogeny.graph.add_edge(root_id, b_id, 1);
+    let b_id = phylogeny.add_node(name.clone());
+    phylogeny.graph.add_edge(root_id, b_id, 1.0);
 
     // Add C node
     let name = "C".to_string();
-    let c_id = phylogeny.graph.add_node(name.clone());
-    phylogeny.graph.add_edge(root_id, c_id, 1);
+    let c_id = phylogeny.add_node(name.clone());
+    phylogeny.graph.add_edge(root_id, c_id, 1.0);
 
     // Add recombinant D node
     let name = "D".to_string();
-    let d_id = phylogeny.graph.add_node(name.clone());
-    phylogeny.graph.add_edge(a_id, d_id, 1);
-    phylogeny.graph.add_edge(b_id, d_id, 1);
+    let d_id = phylogeny.add_node(name.clone());
+    phylogeny.graph.add_edge(a_id, d_id, 1.0);
+    phylogeny.graph.add_edge(b_id, d_id, 1.0);
 
     // Add recursive recombinant E node
     let name = "E".to_string();
-    let e_id = phylogeny.graph.add_node(name.clone());
-    phylogeny.graph.add_edge(d_id, e_id, 1);
-    phylogeny.graph.add_edge(c_id, e_id, 1);
+    let e_id = phylogeny.add_node(name.clone());
+    phylogeny.graph.add_edge(d_id, e_id, 1.0);
+    phylogeny.graph.add_edge(c_id, e_id, 1.0);
 
     Ok(phylogeny)
 }
@@ -0,0 +1,25 @@
+use crate::dataset::toy_mlst::scheme::Scheme;
+use crate::utils::table::Table;
+use color_eyre::eyre::{Report, Result};
+use itertools::Itertools;
+
+/// Create ToyMlst genome annotations, one per locus.
+pub fn build() -> Result<Table, Report> {
+    let mut table = Table::new();
+
+    table.headers = vec!["gene", "abbreviation", "start", "end"]
+        .into_iter()
+        .map(String::from)
+        .collect_vec();
+    table.rows = Scheme::default()
+        .loci
+        .into_iter()
+        .map(|locus| {
+            let start = locus.start.to_string();
+            let end = locus.end.to_string();
+            vec![locus.name.clone(), locus.name, start, end]
+        })
+        .collect_vec();
+
+    Ok(table)
+}
@@ -0,0 +1,39 @@
+use crate::dataset::attributes::Tag;
+use crate::dataset::toy_mlst::scheme::Scheme;
+use crate::utils;
+use crate::utils::versioned_file::{Format, VersionedFile};
+use chrono::Local;
+use color_eyre::eyre::{Report, Result};
+use std::path::Path;
+
+/// Create and write ToyMlst reference sequence (core-genome alignment).
+pub fn reference(_tag: &Tag, path: &Path) -> Result<VersionedFile, Report> {
+    let sequences = Scheme::default().reference_fasta();
+
+    utils::write_atomic(path, sequences)?;
+
+    let remote_file = VersionedFile {
+        local_path: path.to_owned(),
+        format: Format::Fasta,
+        date_created: Local::now().into(),
+        ..Default::default()
+    };
+
+    Ok(remote_file)
+}
+
+/// Create and write ToyMlst populations (sequence types) sequence.
+pub fn populations(_tag: &Tag, path: &Path) -> Result<VersionedFile, Report> {
+    let sequences = Scheme::default().populations_fasta();
+
+    utils::write_atomic(path, sequences)?;
+
+    let remote_file = VersionedFile {
+        local_path: path.to_owned(),
+        format: Format::Fasta,
+        date_created: Local::now().into(),
+        ..Default::default()
+    };
+
+    Ok(remote_file)
+}
@@ -0,0 +1,8 @@
+use crate::cli::run;
+use color_eyre::eyre::{Report, Result};
+
+/// Create default ToyMlst recombinant edge cases.
+pub fn default() -> Result<Vec<run::Args>, Report> {
+    let edge_cases: Vec<run::Args> = Vec::new();
+    Ok(edge_cases)
+}
@@ -0,0 +1,5 @@
+pub mod annotations;
+pub mod download;
+pub mod edge_cases;
+pub mod phylogeny;
+pub mod scheme;
@@ -0,0 +1,24 @@
+use crate::phylogeny::Phylogeny;
+use color_eyre::eyre::{Report, Result};
+
+pub fn build() -> Result<Phylogeny, Report> {
+    let mut phylogeny = Phylogeny::new();
+
+    // Add root node
+    let root_id = phylogeny.add_node("root".to_string());
+
+    // Add the unrelated sequence types
+    let st1_id = phylogeny.add_node("ST1".to_string());
+    phylogeny.graph.add_edge(root_id, st1_id, 1.0);
+    let st2_id = phylogeny.add_node("ST2".to_string());
+    phylogeny.graph.add_edge(root_id, st2_id, 1.0);
+    let st3_id = phylogeny.add_node("ST3".to_string());
+    phylogeny.graph.add_edge(root_id, st3_id, 1.0);
+
+    // Add recombinant ST4 node, ST1 with a fragment imported from ST2
+    let st4_id = phylogeny.add_node("ST4".to_string());
+    phylogeny.graph.add_edge(st1_id, st4_id, 1.0);
+    phylogeny.graph.add_edge(st2_id, st4_id, 1.0);
+
+    Ok(phylogeny)
+}
@@ -0,0 +1,120 @@
+use itertools::Itertools;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::collections::BTreeMap;
+
+/// Housekeeping loci of the default scheme, modelled on a 7-locus MLST scheme.
+pub const LOCI: [&str; 7] = ["aroE", "ddl", "gdh", "gki", "recP", "spi", "xpt"];
+
+const BASES: [char; 4] = ['A', 'C', 'G', 'T'];
+
+// ----------------------------------------------------------------------------
+// Locus
+// ----------------------------------------------------------------------------
+
+/// A locus of the core-genome alignment, by coordinates (1-based, inclusive).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Locus {
+    pub name: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+// ----------------------------------------------------------------------------
+// Scheme
+// ----------------------------------------------------------------------------
+
+/// A synthetic MLST-like scheme of a bacterial species.
+///
+/// The loci are concatenated into a core-genome alignment, and each sequence
+/// type (ST) is a population with its own allele at every locus. ST1, ST2, and
+/// ST3 are unrelated, and ST4 is the ST1 background with a fragment (the
+/// middle third of the loci) imported from ST2 by homologous recombination.
+#[derive(Clone, Debug)]
+pub struct Scheme {
+    pub loci: Vec<Locus>,
+    pub reference: String,
+    pub populations: BTreeMap<String, String>,
+}
+
+impl Default for Scheme {
+    fn default() -> Self {
+        Self::new(LOCI.len(), 450, 0)
+    }
+}
+
+impl Scheme {
+    /// Build a scheme of num_loci loci, each locus_length bases long.
+    ///
+    /// Loci past the default housekeeping loci are numbered (ex. locus0008),
+    /// which scales the scheme up to a core-genome MLST (cgMLST) of thousands
+    /// of loci. The same seed always builds the same scheme.
+    pub fn new(num_loci: usize, locus_length: usize, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let sequence_types = ["ST1", "ST2", "ST3"];
+        let sites_per_allele = (locus_length / 50).clamp(1, 5);
+
+        let loci = (0..num_loci)
+            .map(|i| Locus {
+                name: LOCI
+                    .get(i)
+                    .map(|name| name.to_string())
+                    .unwrap_or_else(|| format!("locus{:04}", i + 1)),
+                start: i * locus_length + 1,
+                end: (i + 1) * locus_length,
+            })
+            .collect_vec();
+
+        let reference = (0..num_loci * locus_length)
+            .map(|_| BASES[rng.gen_range(0..BASES.len())])
+            .collect::<String>();
+
+        // every sequence type gets its own sites within each locus, so that
+        // the alleles of different sequence types never share a mutation
+        let mut sequences = vec![reference.chars().collect_vec(); sequence_types.len()];
+        let mut offsets = (0..locus_length).collect_vec();
+        for locus in &loci {
+            offsets.shuffle(&mut rng);
+            let mut offsets = offsets.iter();
+            for sequence in sequences.iter_mut() {
+                for offset in offsets.by_ref().take(sites_per_allele) {
+                    let i = locus.start - 1 + offset;
+                    let alt = BASES.iter().filter(|b| **b != sequence[i]).collect_vec();
+                    sequence[i] = *alt[rng.gen_range(0..alt.len())];
+                }
+            }
+        }
+
+        // the recombinant imports the middle third of the loci from ST2
+        let import = &loci[num_loci / 3..(2 * num_loci).div_ceil(3)];
+        let mut recombinant = sequences[0].clone();
+        if let (Some(first), Some(last)) = (import.first(), import.last()) {
+            let range = first.start - 1..last.end;
+            recombinant[range.clone()].copy_from_slice(&sequences[1][range]);
+        }
+
+        let populations = sequence_types
+            .iter()
+            .zip(sequences)
+            .chain([(&"ST4", recombinant)])
+            .map(|(name, sequence)| (name.to_string(), sequence.into_iter().collect()))
+            .collect();
+
+        Scheme {
+            loci,
+            reference,
+            populations,
+        }
+    }
+
+    /// Format the reference as a fasta.
+    pub fn reference_fasta(&self) -> String {
+        format!(">Reference\n{}\n", self.reference)
+    }
+
+    /// Format the populations as a fasta.
+    pub fn populations_fasta(&self) -> String {
+        self.populations.iter().map(|(name, seq)| format!(">{name}\n{seq}\n")).join("")
+    }
+}
@@ -0,0 +1,152 @@
+use crate::cli;
+use crate::recombination::Breakpoint;
+use crate::run::layout::Layout;
+use crate::utils::table::Table;
+use color_eyre::eyre::{eyre, Report, Result};
+use itertools::Itertools;
+use log::{info, warn};
+use std::collections::BTreeMap;
+use std::fs::create_dir_all;
+
+/// Linelist columns that are compared between runs.
+pub const DIFF_COLUMNS: &[&str] =
+    &["population", "recombinant", "parents", "breakpoints"];
+
+/// Compare the results of two run output directories, reporting changed samples.
+///
+/// Samples are matched by strain, and reported if their population,
+/// recombinant, or parents changed, or if a breakpoint shifted by more than the
+/// tolerance. This is useful to validate an upgrade of rebar, or the dataset.
+pub fn diff_runs(args: &cli::diff_runs::Args) -> Result<(), Report> {
+    let mut linelists = Vec::new();
+    for output_dir in [&args.old, &args.new] {
+        if !output_dir.exists() {
+            return Err(eyre!("Output directory {output_dir:?} does not exist."));
+        }
+        let linelist_path = Layout::new(output_dir).find_linelist()?;
+        info!("Reading linelist: {linelist_path:?}");
+        linelists.push(Table::read(&linelist_path)?);
+    }
+
+    let table = diff(&linelists[0], &linelists[1], args.tolerance)?;
+    let num_changed = table.rows.iter().map(|row| &row[0]).unique().count();
+    let mut strains = Vec::new();
+    for linelist in &linelists {
+        let strain_i = linelist.header_position("strain")?;
+        strains.extend(linelist.rows.iter().filter_map(|row| row.get(strain_i)));
+    }
+    let num_samples = strains.into_iter().unique().count();
+    info!("Found {num_changed} of {num_samples} sample(s) with changes.");
+
+    println!("\n{}", table.to_markdown()?);
+
+    if let Some(output_dir) = &args.output_dir {
+        if !output_dir.exists() {
+            create_dir_all(output_dir)?;
+        }
+        let output_path = output_dir.join("diff_runs.tsv");
+        info!("Exporting changes: {output_path:?}");
+        table.write(&output_path)?;
+    }
+
+    Ok(())
+}
+
+/// Compare two linelists by strain, returning a table of changes.
+///
+/// Each change is a row of: strain, change, old value, new value. Strains
+/// are reported in the order of the old linelist, followed by strains only in
+/// the new linelist.
+pub fn diff(old: &Table, new: &Table, tolerance: usize) -> Result<Table, Report> {
+    let mut table = Table::new();
+    table.headers = vec!["strain", "change", "old", "new"]
+        .into_iter()
+        .map(String::from)
+        .collect_vec();
+
+    // columns that can be compared, which might have been excluded from
+    // either linelist by --linelist-columns
+    let columns = DIFF_COLUMNS
+        .iter()
+        .filter(|column| {
+            let compared = old.headers.iter().any(|h| h == *column)
+                && new.headers.iter().any(|h| h == *column);
+            if !compared {
+                warn!(
+                    "Column {column} is not in both linelists, it will not be compared."
+                );
+            }
+            compared
+        })
+        .collect_vec();
+
+    let strain_i = (
+        old.header_position("strain")?,
+        new.header_position("strain")?,
+    );
+    let new_rows: BTreeMap<&String, &Vec<String>> =
+        new.rows.iter().map(|row| (&row[strain_i.1], row)).collect();
+    let old_rows: BTreeMap<&String, &Vec<String>> =
+        old.rows.iter().map(|row| (&row[strain_i.0], row)).collect();
+
+    // the value of a column in a row, empty if missing
+    let value = |table: &Table, row: &Vec<String>, column: &str| -> String {
+        let i = table.header_position(column).ok();
+        i.and_then(|i| row.get(i)).cloned().unwrap_or_default()
+    };
+
+    for old_row in &old.rows {
+        let strain = &old_row[strain_i.0];
+        let Some(new_row) = new_rows.get(strain) else {
+            let population = value(old, old_row, "population");
+            table.rows.push(vec![
+                strain.clone(),
+                "removed".into(),
+                population,
+                "".into(),
+            ]);
+            continue;
+        };
+        for column in &columns {
+            let old_value = value(old, old_row, column);
+            let new_value = value(new, new_row, column);
+            let changed = if **column == "breakpoints" {
+                breakpoints_shifted(&old_value, &new_value, tolerance)?
+            } else {
+                old_value != new_value
+            };
+            if changed {
+                table.rows.push(vec![
+                    strain.clone(),
+                    column.to_string(),
+                    old_value,
+                    new_value,
+                ]);
+            }
+        }
+    }
+
+    for new_row in &new.rows {
+        let strain = &new_row[strain_i.1];
+        if !old_rows.contains_key(strain) {
+            let population = value(new, new_row, "population");
+            table.rows.push(vec![strain.clone(), "added".into(), "".into(), population]);
+        }
+    }
+
+    Ok(table)
+}
+
+/// Check if breakpoints changed in number, or shifted by more than the tolerance.
+pub fn breakpoints_shifted(
+    old: &str,
+    new: &str,
+    tolerance: usize,
+) -> Result<bool, Report> {
+    let (old, new) = (Breakpoint::parse_list(old)?, Breakpoint::parse_list(new)?);
+    if old.len() != new.len() {
+        return Ok(true);
+    }
+    let shifted = old.iter().zip(new.iter()).any(|(old, new)| old.shift(new) > tolerance);
+    Ok(shifted)
+}
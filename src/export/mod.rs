@@ -1,127 +1,589 @@
+pub mod sink;
+
 use crate::dataset::{Dataset, SearchResult};
-use crate::recombination::{validate, Recombination};
+use crate::phylogeny::{BranchLength, ROOT};
+use crate::recombination::{temporal, validate, Recombination};
+use crate::sequence::translate;
 use crate::utils;
-use color_eyre::eyre::{Report, Result};
+use crate::utils::fmt;
+use chrono::prelude::*;
+use color_eyre::eyre::{eyre, Report, Result, WrapErr};
+use color_eyre::Help;
 use itertools::Itertools;
+use serde_json::json;
+use std::collections::{BTreeMap, HashSet};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
 
 // ----------------------------------------------------------------------------
 // LineList
 
+/// All columns of the linelist, in default order.
+pub const LINELIST_COLUMNS: &[&str] = &[
+    "strain",
+    "source",
+    "validate",
+    "validate_details",
+    "population",
+    "top_populations",
+    "top_populations_total",
+    "recombinant",
+    "parents",
+    "breakpoints",
+    "edge_case",
+    "mixture",
+    "warnings",
+    "temporal_flag",
+    "unique_key",
+    "regions",
+    "regions_missing",
+    "substitutions",
+    "reversions",
+    "aa_substitutions",
+    "genome_length",
+    "sequence_hash",
+    "dataset_name",
+    "dataset_tag",
+    "cli_version",
+];
+
+/// Runtime and complexity columns of the linelist, only included with --profile.
+pub const PROFILE_COLUMNS: &[&str] =
+    &["runtime_ms", "candidate_parents", "search_iterations"];
+
+/// All known columns of the linelist, including the profile columns.
+pub fn all_linelist_columns() -> Vec<&'static str> {
+    LINELIST_COLUMNS.iter().chain(PROFILE_COLUMNS).copied().collect_vec()
+}
+
+/// Default columns of the linelist, with the profile columns if requested.
+pub fn default_linelist_columns(profile: bool) -> Vec<String> {
+    let columns = if profile {
+        all_linelist_columns()
+    } else {
+        LINELIST_COLUMNS.to_vec()
+    };
+    columns.into_iter().map(String::from).collect_vec()
+}
+
+/// Get the linelist headers, and their positions in a linelist row.
+///
+/// By default, the profile columns are only included if `profile`.
+pub fn linelist_positions(
+    columns: Option<&[String]>,
+    profile: bool,
+) -> Result<(Vec<String>, Vec<usize>), Report> {
+    let headers = match columns {
+        Some(columns) => columns.to_vec(),
+        None => default_linelist_columns(profile),
+    };
+    let all_columns = all_linelist_columns();
+    let positions = headers
+        .iter()
+        .map(|column| {
+            all_columns
+                .iter()
+                .position(|c| c == column)
+                .ok_or_else(|| eyre!("Unknown linelist column: {column}"))
+        })
+        .collect::<Result<Vec<_>, Report>>()?;
+    Ok((headers, positions))
+}
+
+/// Check that requested linelist columns are known.
+///
+/// Profile columns are only known if the run is profiled.
+pub fn check_linelist_columns(columns: &[String], profile: bool) -> Result<(), Report> {
+    let all_columns = all_linelist_columns();
+    for column in columns {
+        if !all_columns.contains(&column.as_str()) {
+            return Err(eyre!("Unknown linelist column: {column}")).suggestion(format!(
+                "Columns must be one of: {}",
+                all_columns.join(",")
+            ));
+        }
+        if !profile && PROFILE_COLUMNS.contains(&column.as_str()) {
+            return Err(eyre!("Linelist column requires --profile: {column}"))
+                .suggestion("Please add --profile to the run.");
+        }
+    }
+    Ok(())
+}
+
 pub fn linelist(
     results: &Vec<(SearchResult, Recombination)>,
     dataset: &Dataset,
 ) -> Result<utils::table::Table, Report> {
     let mut table = utils::table::Table::new();
-
-    table.headers = vec![
-        "strain",
-        "validate",
-        "validate_details",
-        "population",
-        "recombinant",
-        "parents",
-        "breakpoints",
-        "edge_case",
-        "unique_key",
-        "regions",
-        "substitutions",
-        "genome_length",
-        "dataset_name",
-        "dataset_tag",
-        "cli_version",
-    ]
-    .into_iter()
-    .map(|s| s.to_string())
-    .collect_vec();
-
-    // iterate in parallel, checking for same sequence id
+    table.headers = default_linelist_columns(true);
     for (best_match, recombination) in results {
-        // initialize the table row
-        let mut row = vec![String::new(); table.headers.len()];
+        table.rows.push(linelist_row(best_match, recombination, dataset)?);
+    }
+    Ok(table)
+}
 
-        // strain
-        let strain = recombination.sequence.id.to_string();
-        row[table.header_position("strain")?] = strain.clone();
+/// Create a linelist row for a single sequence, with all columns in default order.
+///
+/// The profile columns are last.
+pub fn linelist_row(
+    best_match: &SearchResult,
+    recombination: &Recombination,
+    dataset: &Dataset,
+) -> Result<Vec<String>, Report> {
+    // initialize the table row
+    let columns = all_linelist_columns();
+    let mut row = vec![String::new(); columns.len()];
+    let position = |column: &str| {
+        columns
+            .iter()
+            .position(|c| *c == column)
+            .ok_or_else(|| eyre!("Unknown linelist column: {column}"))
+    };
 
-        // population
-        let population = best_match.consensus_population.to_string();
-        row[table.header_position("population")?] = population.clone();
+    // strain
+    let strain = recombination.sequence.id.to_string();
+    row[position("strain")?] = strain.clone();
 
-        // recombinant
-        if let Some(recombinant) = &recombination.recombinant {
-            row[table.header_position("recombinant")?] = recombinant.clone();
-        }
+    // source, the input the sequence was read from
+    if let Some(source) = &recombination.sequence.source {
+        row[position("source")?] = source.to_string();
+    }
 
-        // parents
-        let parents = recombination.parents.join(",").to_string();
-        row[table.header_position("parents")?] = parents;
-
-        // breakpoints
-        let breakpoints = recombination.breakpoints.iter().join(",").to_string();
-        row[table.header_position("breakpoints")?] = breakpoints;
-
-        // edge_case
-        let edge_case = recombination.edge_case.to_string();
-        row[table.header_position("edge_case")?] = edge_case;
-
-        // validate, currently requires phylogeny
-        if !dataset.phylogeny.is_empty() {
-            let validate = validate::validate(dataset, best_match, recombination)?;
-            if let Some(validate) = validate {
-                row[table.header_position("validate")?] = validate.status.to_string();
-                row[table.header_position("validate_details")?] =
-                    validate.details.iter().join(";");
-            }
+    // population
+    let population = best_match.consensus_population.to_string();
+    row[position("population")?] = population.clone();
+
+    // top_populations, truncated with --max-top-populations
+    row[position("top_populations")?] = best_match.top_populations.join(",");
+    row[position("top_populations_total")?] =
+        best_match.top_populations_total.to_string();
+
+    // recombinant
+    if let Some(recombinant) = &recombination.recombinant {
+        row[position("recombinant")?] = recombinant.clone();
+    }
+
+    // parents
+    let parents = recombination.parents.join(",").to_string();
+    row[position("parents")?] = parents;
+
+    // breakpoints
+    let breakpoints = recombination.breakpoints.iter().join(",").to_string();
+    row[position("breakpoints")?] = breakpoints;
+
+    // edge_case
+    let edge_case = recombination.edge_case.to_string();
+    row[position("edge_case")?] = edge_case;
+
+    // mixture, co-infection vs recombination
+    if let Some(mixture) = &recombination.mixture {
+        row[position("mixture")?] = mixture.to_string();
+    }
+
+    // validate, currently requires phylogeny
+    if !dataset.phylogeny.is_empty() {
+        let validate = validate::validate(dataset, best_match, recombination)?;
+        if let Some(validate) = validate {
+            row[position("validate")?] = validate.status.to_string();
+            row[position("validate_details")?] = validate.details.iter().join(";");
         }
+    }
 
-        // unique_key
-        let unique_key = recombination.unique_key.to_string();
-        row[table.header_position("unique_key")?] = unique_key;
+    // warnings, from the consensus and parent searches
+    let warnings =
+        best_match.warnings.iter().chain(&recombination.warnings).unique().join(";");
+    row[position("warnings")?] = warnings;
 
-        // regions
-        let regions = recombination.regions.values().join(",").to_string();
-        row[table.header_position("regions")?] = regions;
+    // temporal_flag, parents first seen after the sequence was collected
+    let temporal_flag = temporal::check(dataset, recombination).iter().join(";");
+    row[position("temporal_flag")?] = temporal_flag;
 
-        // genome_length
-        let genome_length = recombination.genome_length.to_string();
-        row[table.header_position("genome_length")?] = genome_length;
+    // unique_key
+    let unique_key = recombination.unique_key.to_string();
+    row[position("unique_key")?] = unique_key;
 
-        // dataset name
-        row[table.header_position("dataset_name")?] = dataset.name.to_string();
+    // regions
+    let regions = recombination.regions.values().join(",").to_string();
+    row[position("regions")?] = regions;
 
-        // dataset tag
-        row[table.header_position("dataset_tag")?] = dataset.tag.to_string();
+    // regions_missing, fraction of missing data in each region
+    let regions_missing =
+        recombination.regions.values().map(|region| fmt::float(region.missing)).join(",");
+    row[position("regions_missing")?] = regions_missing;
 
-        // cli version
-        row[table.header_position("cli_version")?] =
-            env!("CARGO_PKG_VERSION").to_string();
+    // genome_length
+    let genome_length = recombination.genome_length.to_string();
+    row[position("genome_length")?] = genome_length;
 
-        // --------------------------------------------------------------------
-        // Substitutions, annotated by parental origin or private
+    // sequence_hash, the same for sequences with the same content
+    row[position("sequence_hash")?] = recombination.sequence.content_hash();
 
-        let subs_by_origin = recombination.get_substitution_origins(best_match)?;
-        let mut origins = Vec::new();
-        // origin order: primary parent, secondary parent, recombinant, private
-        if recombination.recombinant.is_some() {
-            origins.extend(recombination.parents.clone());
-        } else {
-            origins.push(best_match.consensus_population.clone());
-        }
-        origins.push("private".to_string());
+    // dataset name
+    row[position("dataset_name")?] = dataset.name.to_string();
+
+    // dataset tag
+    row[position("dataset_tag")?] = dataset.tag.to_string();
+
+    // cli version
+    row[position("cli_version")?] = env!("CARGO_PKG_VERSION").to_string();
+
+    // --------------------------------------------------------------------
+    // Substitutions, annotated by parental origin or private
+
+    let subs_by_origin = recombination.get_substitution_origins(best_match)?;
+    let mut origins = Vec::new();
+    // origin order: primary parent, secondary parent, recombinant, private
+    if recombination.recombinant.is_some() {
+        origins.extend(recombination.parents.clone());
+    } else {
+        origins.push(best_match.consensus_population.clone());
+    }
+    origins.push("private".to_string());
+
+    // string format
+    let substitutions = origins
+        .iter()
+        .filter_map(|o| {
+            let subs = subs_by_origin.get(o).cloned().unwrap_or_default();
+            let subs_format = format!("{}|{o}", subs.iter().join(","));
+            (!subs.is_empty()).then_some(subs_format)
+        })
+        .join(";");
+    row[position("substitutions")?] = substitutions;
+
+    // --------------------------------------------------------------------
+    // Reversions, annotated by parental origin
+
+    let reversions = if recombination.recombinant.is_some() {
+        recombination.reversions.clone()
+    } else {
+        let population = &best_match.consensus_population;
+        let conflict_ref =
+            best_match.conflict_ref.get(population).cloned().unwrap_or_default();
+        let reversions = recombination.sequence.reversions(&conflict_ref);
+        BTreeMap::from([(population.clone(), reversions)])
+    };
+    let reversions = origins
+        .iter()
+        .filter_map(|o| {
+            let subs = reversions.get(o).cloned().unwrap_or_default();
+            (!subs.is_empty()).then_some(format!("{}|{o}", subs.iter().join(",")))
+        })
+        .join(";");
+    row[position("reversions")?] = reversions;
+
+    // --------------------------------------------------------------------
+    // Amino acid substitutions, in the dataset annotations
+
+    let aa_substitutions = translate::substitutions(
+        recombination.sequence,
+        &dataset.reference,
+        &dataset.annotations,
+    )?;
+    row[position("aa_substitutions")?] = aa_substitutions.iter().join(",");
+
+    // --------------------------------------------------------------------
+    // Profile, runtime and complexity of the search
+
+    let profile = &recombination.profile;
+    row[position("runtime_ms")?] = fmt::float(profile.runtime.as_secs_f64() * 1000.0);
+    row[position("candidate_parents")?] = profile.candidate_parents.to_string();
+    row[position("search_iterations")?] = profile.search_iterations.to_string();
+
+    Ok(row)
+}
+
+/// Write linelist rows to file as sequences are processed, rather than all at once.
+///
+/// Rows are written to a temporary file, which is moved to the output path when
+/// finished, so an interrupted run never leaves a partial linelist.
+pub struct LinelistWriter {
+    path: PathBuf,
+    delim: String,
+    positions: Vec<usize>,
+    locale_safe: bool,
+    writer: BufWriter<NamedTempFile>,
+}
+
+impl LinelistWriter {
+    /// Create a linelist writer, with optional selected columns.
+    ///
+    /// By default, the profile columns are only written if `profile`. With
+    /// `locale_safe`, values that spreadsheets would convert are quoted.
+    pub fn new(
+        path: &Path,
+        columns: Option<&[String]>,
+        profile: bool,
+        locale_safe: bool,
+    ) -> Result<Self, Report> {
+        let delim = utils::path_to_delim(path)?.to_string();
+        let (headers, positions) = linelist_positions(columns, profile)?;
 
-        // string format
-        let substitutions = origins
+        let mut writer = BufWriter::new(utils::create_temp_file(path)?);
+        writeln!(writer, "{}", headers.iter().join(&delim))
+            .wrap_err_with(|| format!("Failed to write file: {path:?}"))?;
+
+        Ok(LinelistWriter {
+            path: path.to_path_buf(),
+            delim,
+            positions,
+            locale_safe,
+            writer,
+        })
+    }
+
+    /// Write the linelist row of a single sequence.
+    pub fn write(
+        &mut self,
+        best_match: &SearchResult,
+        recombination: &Recombination,
+        dataset: &Dataset,
+    ) -> Result<(), Report> {
+        let row = linelist_row(best_match, recombination, dataset)?;
+        let row = self
+            .positions
             .iter()
-            .filter_map(|o| {
-                let subs = subs_by_origin.get(o).cloned().unwrap_or_default();
-                let subs_format = format!("{}|{o}", subs.iter().join(","));
-                (!subs.is_empty()).then_some(subs_format)
+            .map(|i| match self.locale_safe {
+                true => utils::table::locale_safe(&row[*i]),
+                false => row[*i].clone(),
             })
-            .join(";");
-        row[table.header_position("substitutions")?] = substitutions;
+            .join(&self.delim);
+        writeln!(self.writer, "{row}")
+            .wrap_err_with(|| format!("Failed to write file: {:?}", self.path))
+    }
+
+    /// Flush all rows, and move the linelist to the output path.
+    pub fn finish(self) -> Result<(), Report> {
+        let file = self
+            .writer
+            .into_inner()
+            .map_err(|e| eyre!("Failed to write file: {:?}. {e}", self.path))?;
+        file.as_file()
+            .sync_all()
+            .wrap_err_with(|| format!("Failed to write file: {:?}", self.path))?;
+        utils::persist_atomic(file, &self.path)
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Genes
+
+/// Summarize which recombination parent contributes each gene.
+///
+/// Genes that overlap a breakpoint interval are 'mixed'.
+pub fn genes(
+    results: &Vec<(SearchResult, Recombination)>,
+    dataset: &Dataset,
+) -> Result<utils::table::Table, Report> {
+    let mut table = utils::table::Table::new();
+    table.headers = vec!["strain", "gene", "abbreviation", "start", "end", "parent"]
+        .into_iter()
+        .map(String::from)
+        .collect_vec();
+
+    let annotations = &dataset.annotations;
+    let gene_i = annotations.header_position("gene")?;
+    let abbrev_i = annotations.header_position("abbreviation")?;
+    let starts = annotations.get_column_as::<usize>("start")?;
+    let ends = annotations.get_column_as::<usize>("end")?;
+
+    for (_best_match, recombination) in results {
+        if recombination.regions.is_empty() {
+            continue;
+        }
+        let regions = recombination.regions.values().collect_vec();
+
+        for (gene, (start, end)) in annotations.rows.iter().zip(starts.iter().zip(&ends))
+        {
+            let (start, end) = (*start, *end);
+
+            // a breakpoint inside the gene means it is mixed
+            let is_mixed = recombination
+                .breakpoints
+                .iter()
+                .any(|b| b.start <= end && b.end >= start);
+
+            let parent = if is_mixed {
+                "mixed".to_string()
+            } else {
+                // extend the first and last region to the genome ends
+                regions
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, region)| {
+                        let region_start = if *i == 0 { 1 } else { region.start };
+                        let region_end = if *i == regions.len() - 1 {
+                            recombination.genome_length
+                        } else {
+                            region.end
+                        };
+                        region_start <= end && region_end >= start
+                    })
+                    .map(|(_i, region)| region.origin.clone())
+                    .unique()
+                    .join(",")
+            };
 
-        table.rows.push(row);
+            table.rows.push(vec![
+                recombination.sequence.id.clone(),
+                gene[gene_i].clone(),
+                gene[abbrev_i].clone(),
+                start.to_string(),
+                end.to_string(),
+                parent,
+            ]);
+        }
     }
 
     Ok(table)
 }
+
+// ----------------------------------------------------------------------------
+// Auspice
+
+/// Export the dataset phylogeny and sample placements as Auspice (v2) JSON.
+///
+/// Recombinant populations have multiple parents, but Auspice requires a
+/// tree, so they are placed under the first parent visited. Samples are placed as
+/// tips under their best matching (consensus) population. The file can be
+/// dropped into auspice.us for interactive exploration.
+pub fn auspice(
+    results: &Vec<(SearchResult, Recombination)>,
+    dataset: &Dataset,
+) -> Result<serde_json::Value, Report> {
+    // samples grouped by their consensus population
+    let mut samples: BTreeMap<&String, Vec<&(SearchResult, Recombination)>> =
+        BTreeMap::new();
+    for result in results {
+        samples.entry(&result.0.consensus_population).or_default().push(result);
+    }
+
+    let mut placed = HashSet::new();
+    let tree = auspice_node(dataset, ROOT, 0.0, &samples, &mut placed)?;
+
+    let coloring = |key: &str, title: &str| json!({"key": key, "title": title, "type": "categorical"});
+    let auspice = json!({
+        "version": "v2",
+        "meta": {
+            "title": format!("rebar: {} {}", dataset.name, dataset.tag),
+            "updated": Utc::now().format("%Y-%m-%d").to_string(),
+            "panels": ["tree"],
+            "colorings": [
+                coloring("recombinant", "Recombinant"),
+                coloring("parents", "Parents"),
+                coloring("population", "Population"),
+                coloring("node_type", "Node Type"),
+            ],
+            "display_defaults": {"color_by": "recombinant"},
+        },
+        "tree": tree,
+    });
+
+    Ok(auspice)
+}
+
+/// Convert a phylogeny node, its descendants, and samples to Auspice nodes.
+fn auspice_node(
+    dataset: &Dataset,
+    name: &str,
+    div: f64,
+    samples: &BTreeMap<&String, Vec<&(SearchResult, Recombination)>>,
+    placed: &mut HashSet<String>,
+) -> Result<serde_json::Value, Report> {
+    let phylogeny = &dataset.phylogeny;
+    placed.insert(name.to_string());
+    let mut node_attrs = json!({
+        "div": div,
+        "node_type": {"value": "population"},
+        "population": {"value": name},
+    });
+
+    // recombinant status
+    let is_recombinant = phylogeny.recombinants_all.iter().any(|r| r == name);
+    let recombinant = if is_recombinant { "Yes" } else { "No" };
+    node_attrs["recombinant"] = json!({ "value": recombinant });
+    if phylogeny.is_recombinant(name).unwrap_or(false) {
+        // parents are returned last added first, reverse this
+        let parents = phylogeny.get_parents(name)?.into_iter().rev().join(",");
+        node_attrs["parents"] = json!({ "value": parents });
+    }
+    if let Some(date) = phylogeny.dates.get(name) {
+        node_attrs["num_date"] = json!({ "value": decimal_year(date) });
+    }
+
+    // children are populations in the phylogeny, or in the dataset if there is
+    // no phylogeny (ex. --self-dataset)
+    let mut children = Vec::new();
+    if phylogeny.is_empty() {
+        if name == ROOT {
+            for population in dataset.populations.keys() {
+                let child_div = div + 1.0;
+                children.push(auspice_node(
+                    dataset, population, child_div, samples, placed,
+                )?);
+            }
+        }
+    } else if phylogeny.get_node(name).is_ok() {
+        let node = phylogeny.get_node(name)?;
+        for child in phylogeny.get_children(name)? {
+            // recombinants are placed under the first parent visited
+            if placed.contains(&child) {
+                continue;
+            }
+            let child_node = phylogeny.get_node(&child)?;
+            let edge = phylogeny
+                .graph
+                .find_edge(node, child_node)
+                .ok_or_else(|| eyre!("Failed to find edge from {name} to {child}"))?;
+            let child_div = div + phylogeny.graph[edge];
+            children.push(auspice_node(dataset, &child, child_div, samples, placed)?);
+        }
+    }
+
+    // sample placements
+    for (best_match, recombination) in
+        samples.get(&name.to_string()).into_iter().flatten()
+    {
+        let strain = &recombination.sequence.id;
+        let sample_div = match phylogeny.branch_length {
+            BranchLength::Mutations => div + best_match.private.len() as f64,
+            BranchLength::None => div + 1.0,
+            _ => div,
+        };
+        let recombinant = recombination.recombinant.clone().unwrap_or("No".to_string());
+        let mut sample_attrs = json!({
+            "div": sample_div,
+            "node_type": {"value": "sample"},
+            "population": {"value": name},
+            "recombinant": {"value": recombinant},
+        });
+        if !recombination.parents.is_empty() {
+            sample_attrs["parents"] = json!({"value": recombination.parents.join(",")});
+            sample_attrs["breakpoints"] =
+                json!({"value": recombination.breakpoints.iter().join(",")});
+            sample_attrs["regions"] =
+                json!({"value": recombination.regions.values().join(",")});
+        }
+        // names must be unique in auspice (ex. --self-dataset)
+        let sample_name = if strain == name || dataset.populations.contains_key(strain) {
+            format!("{strain} (sample)")
+        } else {
+            strain.to_string()
+        };
+        children.push(json!({"name": sample_name, "node_attrs": sample_attrs}));
+    }
+
+    let mut auspice_node = json!({"name": name, "node_attrs": node_attrs});
+    if !children.is_empty() {
+        auspice_node["children"] = json!(children);
+    }
+
+    Ok(auspice_node)
+}
+
+/// Convert a date to a decimal year (ex. 2020-07-02 is 2020.5).
+fn decimal_year(date: &NaiveDate) -> f64 {
+    let days_in_year = if date.leap_year() { 366.0 } else { 365.0 };
+    date.year() as f64 + date.ordinal0() as f64 / days_in_year
+}
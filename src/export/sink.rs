@@ -0,0 +1,418 @@
+use crate::dataset::{Dataset, SearchResult};
+use crate::export::{linelist_positions, linelist_row, LinelistWriter};
+use crate::recombination::Recombination;
+use crate::utils;
+use clap::ValueEnum;
+use color_eyre::eyre::{eyre, Report, Result, WrapErr};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::create_dir_all;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+// ----------------------------------------------------------------------------
+// Output Sink
+
+/// A destination for the results of a run, such as a file or database.
+///
+/// Results are received one at a time as they finish, in output order (see
+/// --unordered), so that they can be streamed without holding the whole run.
+pub trait OutputSink {
+    /// Receive the result of a single sequence.
+    fn on_result(
+        &mut self,
+        best_match: &SearchResult,
+        recombination: &Recombination,
+        dataset: &Dataset,
+    ) -> Result<(), Report>;
+
+    /// Flush and close the output, after all results were received.
+    fn on_finish(self: Box<Self>) -> Result<(), Report>;
+}
+
+impl OutputSink for LinelistWriter {
+    fn on_result(
+        &mut self,
+        best_match: &SearchResult,
+        recombination: &Recombination,
+        dataset: &Dataset,
+    ) -> Result<(), Report> {
+        self.write(best_match, recombination, dataset)
+    }
+
+    fn on_finish(self: Box<Self>) -> Result<(), Report> {
+        self.finish()
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Output Format
+
+/// Additional formats of the linelist, besides TSV.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    /// JSON array of records, keyed by column (linelist.json).
+    Json,
+    /// Apache Parquet, requires the 'parquet' feature (linelist.parquet).
+    Parquet,
+}
+
+impl std::fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let format = match self {
+            OutputFormat::Json => "json",
+            OutputFormat::Parquet => "parquet",
+        };
+        write!(f, "{format}")
+    }
+}
+
+impl OutputFormat {
+    /// Create the sink of the format, with optional selected columns.
+    pub fn sink(
+        &self,
+        path: &Path,
+        columns: Option<&[String]>,
+        profile: bool,
+    ) -> Result<Box<dyn OutputSink>, Report> {
+        match self {
+            OutputFormat::Json => Ok(Box::new(JsonSink::new(path, columns, profile)?)),
+            #[cfg(feature = "parquet")]
+            OutputFormat::Parquet => {
+                Ok(Box::new(ParquetSink::new(path, columns, profile)?))
+            }
+            #[cfg(not(feature = "parquet"))]
+            OutputFormat::Parquet => {
+                use color_eyre::Help;
+                Err(eyre!(
+                    "Output format parquet is not supported by this build of rebar."
+                ))
+                .suggestion("Please build rebar with: cargo build --features parquet")
+            }
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+// JSON Sink
+
+/// Write linelist rows as a JSON array of records, keyed by column.
+///
+/// All values are strings, as in the TSV linelist.
+pub struct JsonSink {
+    path: PathBuf,
+    headers: Vec<String>,
+    positions: Vec<usize>,
+    num_records: usize,
+    writer: BufWriter<NamedTempFile>,
+}
+
+impl JsonSink {
+    pub fn new(
+        path: &Path,
+        columns: Option<&[String]>,
+        profile: bool,
+    ) -> Result<Self, Report> {
+        let (headers, positions) = linelist_positions(columns, profile)?;
+        let mut writer = BufWriter::new(utils::create_temp_file(path)?);
+        write!(writer, "[")
+            .wrap_err_with(|| format!("Failed to write file: {path:?}"))?;
+        Ok(JsonSink {
+            path: path.to_path_buf(),
+            headers,
+            positions,
+            num_records: 0,
+            writer,
+        })
+    }
+}
+
+impl OutputSink for JsonSink {
+    fn on_result(
+        &mut self,
+        best_match: &SearchResult,
+        recombination: &Recombination,
+        dataset: &Dataset,
+    ) -> Result<(), Report> {
+        let row = linelist_row(best_match, recombination, dataset)?;
+        // keys are written in column order, one record per line
+        let record = self
+            .headers
+            .iter()
+            .zip(&self.positions)
+            .map(|(header, i)| {
+                let (key, value) = (json_string(header)?, json_string(&row[*i])?);
+                Ok(format!("{key}: {value}"))
+            })
+            .collect::<Result<Vec<_>, Report>>()?
+            .join(", ");
+        let separator = if self.num_records == 0 { "" } else { "," };
+        write!(self.writer, "{separator}\n{{{record}}}")
+            .wrap_err_with(|| format!("Failed to write file: {:?}", self.path))?;
+        self.num_records += 1;
+        Ok(())
+    }
+
+    fn on_finish(mut self: Box<Self>) -> Result<(), Report> {
+        writeln!(self.writer, "\n]")
+            .wrap_err_with(|| format!("Failed to write file: {:?}", self.path))?;
+        let file = self
+            .writer
+            .into_inner()
+            .map_err(|e| eyre!("Failed to write file: {:?}. {e}", self.path))?;
+        file.as_file()
+            .sync_all()
+            .wrap_err_with(|| format!("Failed to write file: {:?}", self.path))?;
+        utils::persist_atomic(file, &self.path)
+    }
+}
+
+/// Escape a value as a JSON string.
+fn json_string(value: &str) -> Result<String, Report> {
+    serde_json::to_string(value)
+        .wrap_err_with(|| format!("Failed to serialize value: {value}"))
+}
+
+// ----------------------------------------------------------------------------
+// Sample Sink
+
+/// The full result of a single sequence, as written by [SampleSink].
+#[derive(Serialize)]
+struct Sample<'a> {
+    strain: &'a str,
+    source: Option<String>,
+    best_match: &'a SearchResult,
+    recombination: &'a Recombination<'a>,
+}
+
+/// Write the full result of each sequence to its own JSON file
+/// (<strain>.json), including the parsimony of the best match and parents.
+///
+/// Characters of the strain that are not safe in file names are replaced
+/// with underscores, and a suffix is added if two strains collide.
+pub struct SampleSink {
+    dir: PathBuf,
+    names_seen: BTreeMap<String, usize>,
+}
+
+impl SampleSink {
+    pub fn new(dir: &Path) -> Result<Self, Report> {
+        create_dir_all(dir)
+            .wrap_err_with(|| format!("Failed to create directory: {dir:?}"))?;
+        Ok(SampleSink {
+            dir: dir.to_path_buf(),
+            names_seen: BTreeMap::new(),
+        })
+    }
+}
+
+impl OutputSink for SampleSink {
+    fn on_result(
+        &mut self,
+        best_match: &SearchResult,
+        recombination: &Recombination,
+        _dataset: &Dataset,
+    ) -> Result<(), Report> {
+        let sequence = recombination.sequence;
+        let sample = Sample {
+            strain: &sequence.id,
+            source: sequence.source.map(|source| source.to_string()),
+            best_match,
+            recombination,
+        };
+
+        let mut file_name = utils::sanitize_file_name(&sequence.id);
+        let num_duplicates = self.names_seen.entry(file_name.clone()).or_insert(0);
+        if *num_duplicates > 0 {
+            file_name = format!("{file_name}_{num_duplicates}");
+        }
+        *num_duplicates += 1;
+
+        let path = self.dir.join(format!("{file_name}.json"));
+        let output = serde_json::to_string_pretty(&sample)
+            .wrap_err_with(|| format!("Failed to serialize sample: {}", sequence.id))?;
+        utils::write_atomic(&path, format!("{output}\n"))
+    }
+
+    fn on_finish(self: Box<Self>) -> Result<(), Report> {
+        Ok(())
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Negatives Writer
+
+/// Write why each non-recombinant sequence was not called recombinant, with
+/// --explain-negatives (negatives.tsv).
+pub struct NegativesWriter {
+    path: PathBuf,
+    writer: BufWriter<NamedTempFile>,
+}
+
+impl NegativesWriter {
+    pub fn new(path: &Path) -> Result<Self, Report> {
+        let mut writer = BufWriter::new(utils::create_temp_file(path)?);
+        writeln!(writer, "strain\tpopulation\tcandidate\tthreshold\tdetail")
+            .wrap_err_with(|| format!("Failed to write file: {path:?}"))?;
+        Ok(NegativesWriter {
+            path: path.to_path_buf(),
+            writer,
+        })
+    }
+}
+
+impl OutputSink for NegativesWriter {
+    fn on_result(
+        &mut self,
+        best_match: &SearchResult,
+        recombination: &Recombination,
+        _dataset: &Dataset,
+    ) -> Result<(), Report> {
+        let Some(explanation) = &recombination.explanation else {
+            return Ok(());
+        };
+        let row = [
+            recombination.sequence.id.as_str(),
+            best_match.consensus_population.as_str(),
+            explanation.candidate.as_deref().unwrap_or_default(),
+            explanation.threshold.as_deref().unwrap_or_default(),
+            explanation.detail.as_str(),
+        ];
+        writeln!(self.writer, "{}", row.join("\t"))
+            .wrap_err_with(|| format!("Failed to write file: {:?}", self.path))
+    }
+
+    fn on_finish(self: Box<Self>) -> Result<(), Report> {
+        let file = self
+            .writer
+            .into_inner()
+            .map_err(|e| eyre!("Failed to write file: {:?}. {e}", self.path))?;
+        file.as_file()
+            .sync_all()
+            .wrap_err_with(|| format!("Failed to write file: {:?}", self.path))?;
+        utils::persist_atomic(file, &self.path)
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Parquet Sink
+
+/// Number of rows per Parquet row group.
+#[cfg(feature = "parquet")]
+pub const ROW_GROUP_SIZE: usize = 10000;
+
+/// Write linelist rows to an Apache Parquet file, with a string column per
+/// linelist column.
+///
+/// Rows are buffered, and written in row groups of ROW_GROUP_SIZE.
+#[cfg(feature = "parquet")]
+pub struct ParquetSink {
+    path: PathBuf,
+    positions: Vec<usize>,
+    rows: Vec<Vec<String>>,
+    writer: parquet::file::writer::SerializedFileWriter<NamedTempFile>,
+}
+
+#[cfg(feature = "parquet")]
+impl ParquetSink {
+    pub fn new(
+        path: &Path,
+        columns: Option<&[String]>,
+        profile: bool,
+    ) -> Result<Self, Report> {
+        use parquet::basic::{LogicalType, Repetition, Type as PhysicalType};
+        use parquet::file::properties::WriterProperties;
+        use parquet::schema::types::Type;
+        use std::sync::Arc;
+
+        let (headers, positions) = linelist_positions(columns, profile)?;
+        let fields = headers
+            .iter()
+            .map(|header| {
+                let field =
+                    Type::primitive_type_builder(header, PhysicalType::BYTE_ARRAY)
+                        .with_repetition(Repetition::REQUIRED)
+                        .with_logical_type(Some(LogicalType::String))
+                        .build()?;
+                Ok(Arc::new(field))
+            })
+            .collect::<Result<Vec<_>, parquet::errors::ParquetError>>()
+            .and_then(|fields| {
+                Type::group_type_builder("linelist").with_fields(fields).build()
+            })
+            .wrap_err_with(|| format!("Failed to create parquet schema: {path:?}"))?;
+
+        let properties = Arc::new(WriterProperties::builder().build());
+        let writer = parquet::file::writer::SerializedFileWriter::new(
+            utils::create_temp_file(path)?,
+            Arc::new(fields),
+            properties,
+        )
+        .wrap_err_with(|| format!("Failed to write file: {path:?}"))?;
+
+        Ok(ParquetSink {
+            path: path.to_path_buf(),
+            positions,
+            rows: Vec::new(),
+            writer,
+        })
+    }
+
+    /// Write the buffered rows as a row group.
+    fn write_row_group(&mut self) -> Result<(), Report> {
+        use parquet::data_type::{ByteArray, ByteArrayType};
+
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+        let mut row_group = self.writer.next_row_group()?;
+        let mut column_i = 0;
+        while let Some(mut column) = row_group.next_column()? {
+            let values = self
+                .rows
+                .iter()
+                .map(|row| ByteArray::from(row[column_i].as_str()))
+                .collect::<Vec<_>>();
+            column.typed::<ByteArrayType>().write_batch(&values, None, None)?;
+            column.close()?;
+            column_i += 1;
+        }
+        row_group.close()?;
+        self.rows.clear();
+        Ok(())
+    }
+}
+
+#[cfg(feature = "parquet")]
+impl OutputSink for ParquetSink {
+    fn on_result(
+        &mut self,
+        best_match: &SearchResult,
+        recombination: &Recombination,
+        dataset: &Dataset,
+    ) -> Result<(), Report> {
+        let row = linelist_row(best_match, recombination, dataset)?;
+        let row = self.positions.iter().map(|i| row[*i].clone()).collect::<Vec<_>>();
+        self.rows.push(row);
+        if self.rows.len() >= ROW_GROUP_SIZE {
+            self.write_row_group()
+                .wrap_err_with(|| format!("Failed to write file: {:?}", self.path))?;
+        }
+        Ok(())
+    }
+
+    fn on_finish(mut self: Box<Self>) -> Result<(), Report> {
+        self.write_row_group()
+            .wrap_err_with(|| format!("Failed to write file: {:?}", self.path))?;
+        let file = self
+            .writer
+            .into_inner()
+            .wrap_err_with(|| format!("Failed to write file: {:?}", self.path))?;
+        file.as_file()
+            .sync_all()
+            .wrap_err_with(|| format!("Failed to write file: {:?}", self.path))?;
+        utils::persist_atomic(file, &self.path)
+    }
+}
@@ -0,0 +1,220 @@
+use crate::cli;
+use crate::dataset::{load, not_found};
+use crate::recombination::UNKNOWN_PARENT;
+use crate::run::layout::Layout;
+use crate::utils;
+use crate::utils::table::Table;
+use color_eyre::eyre::{eyre, Report, Result, WrapErr};
+use color_eyre::Help;
+use itertools::Itertools;
+use log::info;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+/// Bundle the inputs to reproduce the result of one sequence from a run.
+///
+/// The case is a tar archive with the sequence (sequence.fasta), the run args
+/// (run_args.json), the linelist row and sample output of the original result,
+/// the primer scheme (primers.bed) and metadata row (metadata.tsv) if they were
+/// used, and a slice of the dataset. The dataset slice has the best match,
+/// parents, and their ancestors in the phylogeny, along with their edge cases.
+/// Paths in the run args are relative to the case directory.
+pub fn extract_case(args: &cli::extract_case::Args) -> Result<(), Report> {
+    let run_dir = &args.run_dir;
+    if !run_dir.exists() {
+        return Err(eyre!("Output directory {run_dir:?} does not exist."));
+    }
+    let layout = Layout::new(run_dir);
+    let case_name = args
+        .output
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .ok_or_else(|| eyre!("Invalid --output: {:?}", args.output))?;
+    let mut files: Vec<(String, Vec<u8>)> = Vec::new();
+    let mut add_file = |path: &str, contents: Vec<u8>| {
+        files.push((format!("{case_name}/{path}"), contents));
+    };
+
+    // ------------------------------------------------------------------------
+    // Run Args
+
+    let run_args_path = layout.run_args();
+    info!("Reading run args: {run_args_path:?}");
+    let run_args = utils::read_to_string(&run_args_path)?;
+    let mut run_args: serde_json::Value = serde_json::from_str(&run_args)
+        .wrap_err_with(|| format!("Failed to parse file: {run_args_path:?}"))?;
+    // paths are not deserialized into the run args (ex. for edge cases)
+    let path_arg = |value: &serde_json::Value| value.as_str().map(PathBuf::from);
+    let dataset_dir = args
+        .dataset_dir
+        .clone()
+        .or_else(|| path_arg(&run_args["dataset_dir"]))
+        .ok_or_else(|| eyre!("No dataset directory was found in: {run_args_path:?}"))
+        .suggestion("Please provide the dataset with --dataset-dir.")?;
+    let alignment =
+        args.alignment.clone().or_else(|| path_arg(&run_args["input"]["alignment"]));
+
+    // ------------------------------------------------------------------------
+    // Linelist Row
+
+    let linelist_path = layout.find_linelist()?;
+    info!("Reading linelist: {linelist_path:?}");
+    let mut linelist = Table::read(&linelist_path)?;
+    let strain_i = linelist.header_position("strain")?;
+    let strains = linelist.rows.iter().map(|row| row[strain_i].clone()).collect_vec();
+    let strain = &args.sequence_id;
+    let Some(row) = linelist.rows.iter().find(|row| &row[strain_i] == strain).cloned()
+    else {
+        return Err(not_found(strain, "linelist", &strains));
+    };
+    linelist.rows = vec![row.clone()];
+    add_file("linelist.tsv", linelist.to_delimited('\t').into_bytes());
+
+    // the full result, with --sample-outputs
+    let sample_path =
+        layout.samples_dir().join(format!("{}.json", utils::sanitize_file_name(strain)));
+    if sample_path.exists() {
+        add_file(
+            "sample.json",
+            utils::read_to_string(&sample_path)?.into_bytes(),
+        );
+    }
+
+    // ------------------------------------------------------------------------
+    // Sequence
+
+    let mask = vec![0, 0];
+    let dataset = load::dataset(&dataset_dir, &mask)?;
+
+    // sequences from --populations are named population_<name>
+    let population = strain
+        .strip_prefix("population_")
+        .filter(|population| dataset.populations.contains_key(*population));
+    let (fasta_path, record_id) = match (population, &alignment) {
+        (Some(population), _) => (dataset_dir.join("populations.fasta"), population),
+        (None, Some(alignment)) => (alignment.clone(), strain.as_str()),
+        (None, None) => {
+            return Err(eyre!("No alignment was found in: {run_args_path:?}"))
+                .suggestion("Please provide the alignment with --alignment.")
+        }
+    };
+    let sequence = read_records(&fasta_path, &BTreeSet::from([record_id.to_string()]))?;
+    if sequence.is_empty() {
+        return Err(eyre!(
+            "Sequence {record_id} was not found in: {fasta_path:?}"
+        ))
+        .suggestion("Sequences from --reads can't be extracted.");
+    }
+    let sequence =
+        sequence.replacen(&format!(">{record_id}\n"), &format!(">{strain}\n"), 1);
+    add_file("sequence.fasta", sequence.into_bytes());
+
+    // ------------------------------------------------------------------------
+    // Dataset Slice
+
+    let value = |column: &str| linelist.header_position(column).map(|i| row[i].clone());
+    let mut populations: BTreeSet<String> = BTreeSet::new();
+    populations.insert(value("population")?);
+    populations.extend(value("parents")?.split(',').map(String::from));
+    populations.retain(|p| !p.is_empty() && p != UNKNOWN_PARENT);
+
+    let mut phylogeny = dataset.phylogeny.clone();
+    if !phylogeny.is_empty() {
+        // the designated parents of a recombinant, and ancestors of all
+        for population in populations.clone() {
+            if phylogeny.get_node(&population).is_err() {
+                continue;
+            }
+            if let Some(recombinant) = phylogeny.get_recombinant_ancestor(&population)? {
+                populations.extend(phylogeny.get_parents(&recombinant)?);
+            }
+        }
+        for population in populations.clone() {
+            if phylogeny.get_node(&population).is_ok() {
+                populations
+                    .extend(phylogeny.get_ancestors(&population)?.into_iter().flatten());
+            }
+        }
+        phylogeny.retain(|name| populations.contains(name))?;
+        phylogeny.dates.retain(|name, _date| populations.contains(name));
+        add_file("dataset/phylogeny.json", phylogeny.to_json()?.into_bytes());
+    }
+    info!("Extracting {} dataset population(s).", populations.len());
+
+    let populations_path = dataset_dir.join("populations.fasta");
+    add_file(
+        "dataset/populations.fasta",
+        read_records(&populations_path, &populations)?.into_bytes(),
+    );
+    for file in ["reference.fasta", "annotations.tsv", "summary.json"] {
+        let path = dataset_dir.join(file);
+        if path.exists() {
+            add_file(
+                &format!("dataset/{file}"),
+                utils::read_to_string(&path)?.into_bytes(),
+            );
+        }
+    }
+    let edge_cases = dataset
+        .edge_cases
+        .iter()
+        .filter(|e| e.population.as_ref().is_some_and(|p| populations.contains(p)))
+        .collect_vec();
+    if !edge_cases.is_empty() {
+        let edge_cases = serde_json::to_string_pretty(&edge_cases)?;
+        add_file(
+            "dataset/edge_cases.json",
+            format!("{edge_cases}\n").into_bytes(),
+        );
+    }
+
+    // ------------------------------------------------------------------------
+    // Case Run Args
+
+    run_args["dataset_dir"] = "dataset".into();
+    run_args["input"] = serde_json::json!({"alignment": "sequence.fasta"});
+    if let Some(primer_bed) = path_arg(&run_args["primer_bed"]) {
+        add_file(
+            "primers.bed",
+            utils::read_to_string(&primer_bed)?.into_bytes(),
+        );
+        run_args["primer_bed"] = "primers.bed".into();
+    }
+    // the metadata of just this sequence
+    if let Some(metadata_path) = path_arg(&run_args["metadata"]) {
+        let metadata = Table::read(&metadata_path)?.filter("strain", strain)?;
+        add_file("metadata.tsv", metadata.to_delimited('\t').into_bytes());
+        run_args["metadata"] = "metadata.tsv".into();
+    }
+    if let Some(run_args) = run_args.as_object_mut() {
+        run_args.remove("output_dir");
+    }
+    // check that the run args can still be read
+    serde_json::from_value::<cli::run::Args>(run_args.clone())
+        .wrap_err_with(|| format!("Failed to parse file: {run_args_path:?}"))
+        .suggestion("The output might be from an incompatible version of rebar.")?;
+    let run_args = serde_json::to_string_pretty(&run_args)?;
+    add_file("run_args.json", format!("{run_args}\n").into_bytes());
+
+    // ------------------------------------------------------------------------
+    // Export
+
+    info!("Exporting case: {:?}", args.output);
+    utils::tar::write(&args.output, &files)?;
+
+    Ok(())
+}
+
+/// Read the fasta records with matching ids, as fasta text.
+fn read_records(path: &Path, ids: &BTreeSet<String>) -> Result<String, Report> {
+    let mut output = String::new();
+    for record in utils::read_fasta(path)?.records() {
+        let record =
+            record.wrap_err_with(|| format!("Unable to parse fasta: {path:?}"))?;
+        if ids.contains(record.id()) {
+            let seq = String::from_utf8_lossy(record.seq());
+            output.push_str(&format!(">{}\n{seq}\n", record.id()));
+        }
+    }
+    Ok(output)
+}
@@ -0,0 +1,118 @@
+use crate::cli;
+use crate::run::layout::{Layout, Manifest, LAYOUT_VERSION};
+use crate::utils::table::Table;
+use color_eyre::eyre::{eyre, Report, Result};
+use color_eyre::Help;
+use itertools::Itertools;
+use log::{info, warn};
+
+/// Inspect a run output directory, validate its layout and summarize results.
+pub fn inspect(args: &cli::inspect::Args) -> Result<(), Report> {
+    let output_dir = &args.output_dir;
+    if !output_dir.exists() {
+        return Err(eyre!("Output directory {output_dir:?} does not exist."));
+    }
+    let layout = Layout::new(output_dir);
+
+    // ------------------------------------------------------------------------
+    // Validate Layout
+
+    let manifest_path = layout.manifest();
+    if !manifest_path.exists() {
+        return Err(eyre!("Manifest {manifest_path:?} does not exist."))
+            .suggestion("Output directories from older versions of rebar have no manifest, please re-run.");
+    }
+    info!("Reading manifest: {manifest_path:?}");
+    let manifest = Manifest::read(&manifest_path)?;
+    if manifest.layout_version < LAYOUT_VERSION {
+        warn!(
+            "Output layout version {} is older than the current version {LAYOUT_VERSION}.",
+            manifest.layout_version
+        );
+    }
+
+    let mut problems = Vec::new();
+
+    for dir in layout.dirs() {
+        if !dir.is_dir() {
+            problems.push(format!("Missing directory: {dir:?}"));
+        }
+    }
+    for file in &manifest.files {
+        let path = layout.root.join(&file.path);
+        if !path.exists() {
+            problems.push(format!("Missing file: {path:?}"));
+        } else if path.metadata()?.len() != file.bytes {
+            problems.push(format!("File size differs from manifest: {path:?}"));
+        }
+    }
+    // files that are present but not recorded in the manifest
+    let current = Manifest::from_layout(&layout)?;
+    for file in current.files {
+        if !manifest.files.iter().any(|f| f.path == file.path) {
+            problems.push(format!("File is not in manifest: {:?}", file.path));
+        }
+    }
+
+    if !problems.is_empty() {
+        problems.iter().for_each(|p| warn!("{p}"));
+        return Err(eyre!(
+            "Output directory {output_dir:?} failed validation with {} problem(s).",
+            problems.len()
+        ));
+    }
+
+    // ------------------------------------------------------------------------
+    // Summarize Results
+
+    let linelist = Table::read(&layout.linelist())?;
+
+    // non-empty values of a column, which might have been excluded from
+    // the linelist by --linelist-columns
+    let values = |header: &str| {
+        linelist.header_position(header).ok().map(|i| {
+            linelist
+                .rows
+                .iter()
+                .filter_map(|row| (!row[i].is_empty()).then_some(&row[i]))
+                .collect_vec()
+        })
+    };
+    let num_recombinants =
+        values("recombinant").map(|v| v.len().to_string()).unwrap_or("NA".to_string());
+    let num_unique_keys = values("unique_key")
+        .map(|v| v.into_iter().unique().count().to_string())
+        .unwrap_or("NA".to_string());
+    let count_files = |dir: std::path::PathBuf| {
+        manifest
+            .files
+            .iter()
+            .filter(|f| layout.root.join(&f.path).starts_with(&dir))
+            .count()
+    };
+
+    let mut table = Table::new();
+    table.headers =
+        vec!["Attribute", "Value"].into_iter().map(String::from).collect_vec();
+    table.rows = vec![
+        ("Layout Version", manifest.layout_version.to_string()),
+        ("CLI Version", manifest.cli_version.clone()),
+        (
+            "Date Created",
+            manifest.date_created.format("%Y-%m-%d %H:%M:%S").to_string(),
+        ),
+        ("Files", manifest.files.len().to_string()),
+        ("Sequences", linelist.rows.len().to_string()),
+        ("Recombinants", num_recombinants),
+        ("Unique Recombinants", num_unique_keys),
+        ("Barcodes", count_files(layout.barcodes_dir()).to_string()),
+        ("Plots", count_files(layout.plots_dir()).to_string()),
+    ]
+    .into_iter()
+    .map(|(k, v)| vec![k.to_string(), v])
+    .collect_vec();
+
+    println!("\n{}", table.to_markdown()?);
+
+    Ok(())
+}
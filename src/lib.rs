@@ -1,10 +1,16 @@
 pub mod cli;
 pub mod dataset;
+pub mod diff_runs;
 pub mod export;
+pub mod extract_case;
+pub mod inspect;
 pub mod phylogeny;
 pub mod plot;
+pub mod prelude;
+pub mod reads;
 pub mod recombination;
 pub mod run;
 pub mod sequence;
 pub mod simulate;
 pub mod utils;
+pub mod validate_output;
@@ -1,5 +1,6 @@
 use clap::Parser;
-use color_eyre::eyre::{Report, Result};
+use color_eyre::eyre::{eyre, Report, Result};
+use color_eyre::Help;
 use rebar::cli::{dataset, Cli, Command};
 
 #[tokio::main]
@@ -13,12 +14,8 @@ async fn main() -> Result<(), Report> {
     // initialize color_eyre crate for colorized logs
     color_eyre::install()?;
 
-    // Set logging/verbosity level via RUST_LOG
-    std::env::set_var("RUST_LOG", args.verbosity.to_string());
-    //std::env::set_var("RUST_LOG_COLOR", "auto".to_string());
-
-    // initialize env_logger crate for logging/verbosity level
-    env_logger::init();
+    // initialize env_logger crate for logging/verbosity level, and progress bars
+    rebar::utils::logging::init(&args.verbosity, args.quiet, args.json_logs);
 
     // check which CLI command we're running (dataset, run, plot)
     match args.command {
@@ -28,13 +25,56 @@ async fn main() -> Result<(), Report> {
             dataset::Command::Download(mut args) => {
                 rebar::dataset::download::dataset(&mut args).await?
             }
+            dataset::Command::ImportNextclade(args) => {
+                rebar::dataset::nextclade::import(&args)?
+            }
+            dataset::Command::Stats(args) => rebar::dataset::stats::stats(&args)?,
+            dataset::Command::Compare(args) => rebar::dataset::compare::compare(&args)?,
+            dataset::Command::Query(args) => rebar::dataset::query::query(&args)?,
+            dataset::Command::ExportMatrix(args) => {
+                rebar::dataset::matrix::export_matrix(&args)?
+            }
+            dataset::Command::ExportPhylogeny(args) => {
+                rebar::dataset::phylogeny::export_phylogeny(&args)?
+            }
+            dataset::Command::EdgeCase(args) => match args.command {
+                dataset::edge_case::Command::Add(args) => {
+                    rebar::dataset::edge_case::add(&args)?
+                }
+            },
+            dataset::Command::SelfTest(args) => {
+                rebar::dataset::self_test::self_test(&args)?
+            }
         },
         // Run
-        Command::Run(mut args) => rebar::run::run(&mut args)?,
+        Command::Run(mut args) => {
+            // fetch a remote dataset into the cache, and run it like a local one
+            if let Some(url) = &args.dataset_url {
+                if args.strict && !url.starts_with("https://") {
+                    return Err(eyre!("Invalid --dataset-url with --strict: {url}"))
+                        .suggestion("Please provide an https url.");
+                }
+                let cache_dir = args
+                    .dataset_cache
+                    .clone()
+                    .unwrap_or_else(rebar::dataset::remote::default_cache_dir);
+                let dataset_dir = rebar::dataset::remote::fetch(url, &cache_dir).await?;
+                args.dataset_dir = Some(dataset_dir);
+            }
+            rebar::run::run(&mut args)?
+        }
         // Plot
         Command::Plot(args) => rebar::plot::plot(&args)?,
         // Simulate
         Command::Simulate(args) => rebar::simulate::simulate(&args)?,
+        // Inspect
+        Command::Inspect(args) => rebar::inspect::inspect(&args)?,
+        // Validate Output
+        Command::ValidateOutput(args) => rebar::validate_output::validate_output(&args)?,
+        // Diff Runs
+        Command::DiffRuns(args) => rebar::diff_runs::diff_runs(&args)?,
+        // Extract Case
+        Command::ExtractCase(args) => rebar::extract_case::extract_case(&args)?,
     }
 
     Ok(())
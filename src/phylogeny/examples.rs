@@ -0,0 +1,74 @@
+use crate::phylogeny::{Phylogeny, ROOT};
+use color_eyre::eyre::{Report, Result};
+
+// ----------------------------------------------------------------------------
+// Examples
+//
+// Simplified phylogenies (ancestral recombination graphs) of published
+// SARS-CoV-2 recombinants, built without network access, for tests and docs.
+// Intermediate Pango lineages that don't affect the recombination are omitted,
+// and branch lengths are none (1).
+// ----------------------------------------------------------------------------
+
+/// Build a phylogeny from (parent, child) edges.
+///
+/// Nodes are added in order of first appearance, the first parent should be
+/// the root.
+pub fn from_edges(edges: &[(&str, &str)]) -> Result<Phylogeny, Report> {
+    let mut phylogeny = Phylogeny::new();
+    for (parent, child) in edges {
+        let [parent, child] = [parent, child].map(|name| {
+            phylogeny
+                .get_node(name)
+                .unwrap_or_else(|_| phylogeny.add_node(name.to_string()))
+        });
+        phylogeny.graph.add_edge(parent, child, 1.0);
+    }
+    phylogeny.recombinants = phylogeny.get_recombinants()?;
+    phylogeny.recombinants_all = phylogeny.get_recombinants_all()?;
+    Ok(phylogeny)
+}
+
+/// Edges from the root to BA.1 and BA.2.
+const OMICRON: &[(&str, &str)] = &[
+    (ROOT, "B.1.1.529"),
+    ("B.1.1.529", "BA.1"),
+    ("B.1.1.529", "BA.2"),
+];
+
+/// Edges of XBB, a recombinant of BJ.1 and BM.1.1.1 (both BA.2 descendants).
+const XBB: &[(&str, &str)] = &[
+    ("BA.2", "BA.2.10"),
+    ("BA.2.10", "BA.2.10.1"),
+    ("BA.2.10.1", "BJ.1"),
+    ("BA.2", "BA.2.75"),
+    ("BA.2.75", "BA.2.75.3"),
+    ("BA.2.75.3", "BM.1"),
+    ("BM.1", "BM.1.1"),
+    ("BM.1.1", "BM.1.1.1"),
+    ("BJ.1", "XBB"),
+    ("BM.1.1.1", "XBB"),
+    ("XBB", "XBB.1"),
+    ("XBB.1", "XBB.1.5"),
+];
+
+/// XE, a recombinant of BA.1 and BA.2.
+pub fn xe() -> Result<Phylogeny, Report> {
+    let edges = [OMICRON, &[("BA.1", "XE"), ("BA.2", "XE")]].concat();
+    from_edges(&edges)
+}
+
+/// XBB, a recombinant of BJ.1 and BM.1.1.1, with descendants XBB.1 and XBB.1.5.
+pub fn xbb() -> Result<Phylogeny, Report> {
+    let edges = [OMICRON, XBB].concat();
+    from_edges(&edges)
+}
+
+/// XBL, a recursive recombinant of XBB.1.5 and BA.2.75.
+///
+/// Since XBB is itself a recombinant with a BA.2.75 parent, XBL has multiple
+/// paths to BA.2.75.
+pub fn xbl() -> Result<Phylogeny, Report> {
+    let edges = [OMICRON, XBB, &[("XBB.1.5", "XBL"), ("BA.2.75", "XBL")]].concat();
+    from_edges(&edges)
+}
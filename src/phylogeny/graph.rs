@@ -0,0 +1,385 @@
+use crate::phylogeny::{BranchLength, Phylogeny, ROOT};
+use crate::utils;
+use crate::utils::theme::Color;
+use clap::ValueEnum;
+use color_eyre::eyre::{eyre, Report, Result, WrapErr};
+use color_eyre::Help;
+use itertools::Itertools;
+use log::warn;
+use petgraph::algo::toposort;
+use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+use petgraph::Direction;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+// ----------------------------------------------------------------------------
+// Functions
+// ----------------------------------------------------------------------------
+
+/// Escape reserved characters of XML (and GML) strings.
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Unescape reserved characters of XML strings.
+pub fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Parse the attributes of an XML tag (ex. `node id="n0"`).
+fn parse_attributes(tag: &str) -> BTreeMap<String, String> {
+    let mut attributes = BTreeMap::new();
+    let mut rest = tag;
+    while let Some(i) = rest.find('=') {
+        let key = rest[..i].split_whitespace().last().unwrap_or_default().to_string();
+        let value = rest[i + 1..].trim_start();
+        let Some(quote) = value.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+            break;
+        };
+        let value = &value[1..];
+        let Some(end) = value.find(quote) else {
+            break;
+        };
+        attributes.insert(key, unescape(&value[..end]));
+        rest = &value[end + 1..];
+    }
+    attributes
+}
+
+// ----------------------------------------------------------------------------
+// Layout Hint
+
+/// Layout hints of a node in graph exports, for renderers of large phylogenies.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LayoutHint {
+    /// Longest path (edges) from the root, nodes of the same rank are aligned.
+    pub rank: usize,
+    /// Lineage of the node, the most recent recombinant ancestor (or itself),
+    /// otherwise the top-level clade (a child of the root).
+    pub cluster: String,
+}
+
+// ----------------------------------------------------------------------------
+// Phylogeny
+
+impl Phylogeny {
+    /// Classify a node as 'root', 'recombinant', or 'non_recombinant'.
+    pub fn get_node_type(&self, name: &str) -> Result<&'static str, Report> {
+        let node_type = if name == ROOT {
+            "root"
+        } else if self.is_recombinant(name)? {
+            "recombinant"
+        } else {
+            "non_recombinant"
+        };
+        Ok(node_type)
+    }
+
+    /// Get the display color of a node, by its type and the theme.
+    pub fn get_node_color(&self, name: &str) -> Result<Color, Report> {
+        let color = match self.get_node_type(name)? {
+            "root" => self.theme.root,
+            "recombinant" => self.theme.recombinant,
+            _ => self.theme.non_recombinant,
+        };
+        Ok(color)
+    }
+
+    /// Get the layout hints of all nodes (rank and cluster), see LayoutHint.
+    pub fn get_layout_hints(&self) -> Result<BTreeMap<NodeIndex, LayoutHint>, Report> {
+        // visit nodes in topological order, so parents are visited before children
+        let nodes = toposort(&self.graph, None)
+            .map_err(|cycle| eyre!("Phylogeny contains a cycle at node: {cycle:?}"))?;
+
+        let mut hints: BTreeMap<NodeIndex, LayoutHint> = BTreeMap::new();
+        for node in nodes {
+            let parents =
+                self.graph.neighbors_directed(node, Direction::Incoming).collect_vec();
+            let rank = parents.iter().map(|p| hints[p].rank + 1).max().unwrap_or(0);
+            let cluster = match parents.as_slice() {
+                // a non-recombinant below the top-level clade is in its parent's cluster
+                [parent] if hints[parent].rank > 0 => hints[parent].cluster.clone(),
+                _ => self.get_name(&node)?,
+            };
+            hints.insert(node, LayoutHint { rank, cluster });
+        }
+
+        Ok(hints)
+    }
+
+    /// Convert phylogeny to GML (Graph Modelling Language), for Cytoscape and Gephi.
+    ///
+    /// Nodes have a label, type, color, and date (if available) and edges have
+    /// a length.
+    pub fn to_gml(&self) -> Result<String, Report> {
+        let mut lines = vec![
+            "graph [".to_string(),
+            "  directed 1".to_string(),
+            format!("  branch_length \"{}\"", self.branch_length),
+        ];
+        for (i, name) in self.graph.node_weights().enumerate() {
+            lines.push("  node [".to_string());
+            lines.push(format!("    id {i}"));
+            lines.push(format!("    label \"{}\"", escape(name)));
+            lines.push(format!("    type \"{}\"", self.get_node_type(name)?));
+            lines.push(format!("    color \"{}\"", self.get_node_color(name)?));
+            if let Some(date) = self.dates.get(name) {
+                lines.push(format!("    date \"{date}\""));
+            }
+            lines.push("  ]".to_string());
+        }
+        for edge in self.graph.edge_references() {
+            lines.push("  edge [".to_string());
+            lines.push(format!("    source {}", edge.source().index()));
+            lines.push(format!("    target {}", edge.target().index()));
+            lines.push(format!("    length {}", edge.weight()));
+            lines.push("  ]".to_string());
+        }
+        lines.push("]".to_string());
+
+        Ok(lines.join("\n") + "\n")
+    }
+
+    /// Convert phylogeny to GraphML, for Cytoscape and Gephi.
+    ///
+    /// Nodes have a label, type, color, and date (if available) and edges have
+    /// a length. The branch length units are a graph attribute. With layout
+    /// hints, nodes also have a rank and cluster.
+    pub fn to_graphml(&self) -> Result<String, Report> {
+        let mut lines = vec![
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#.to_string(),
+            r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#.to_string(),
+        ];
+        let mut keys = vec![
+            ("graph", "branch_length", "string"),
+            ("node", "label", "string"),
+            ("node", "type", "string"),
+            ("node", "color", "string"),
+            ("node", "date", "string"),
+            ("edge", "length", "double"),
+        ];
+        let hints = match self.layout_hints {
+            true => {
+                keys.extend([("node", "rank", "int"), ("node", "cluster", "string")]);
+                self.get_layout_hints()?
+            }
+            false => BTreeMap::new(),
+        };
+        for (target, name, attr_type) in keys {
+            lines.push(format!(
+                r#"  <key id="{name}" for="{target}" attr.name="{name}" attr.type="{attr_type}"/>"#
+            ));
+        }
+        lines.push(r#"  <graph id="G" edgedefault="directed">"#.to_string());
+        lines.push(format!(
+            r#"    <data key="branch_length">{}</data>"#,
+            self.branch_length
+        ));
+
+        for (i, name) in self.graph.node_weights().enumerate() {
+            lines.push(format!(r#"    <node id="n{i}">"#));
+            lines.push(format!(
+                r#"      <data key="label">{}</data>"#,
+                escape(name)
+            ));
+            lines.push(format!(
+                r#"      <data key="type">{}</data>"#,
+                self.get_node_type(name)?
+            ));
+            lines.push(format!(
+                r#"      <data key="color">{}</data>"#,
+                self.get_node_color(name)?
+            ));
+            if let Some(date) = self.dates.get(name) {
+                lines.push(format!(r#"      <data key="date">{date}</data>"#));
+            }
+            if let Some(hint) = hints.get(&NodeIndex::new(i)) {
+                lines.push(format!(r#"      <data key="rank">{}</data>"#, hint.rank));
+                lines.push(format!(
+                    r#"      <data key="cluster">{}</data>"#,
+                    escape(&hint.cluster)
+                ));
+            }
+            lines.push("    </node>".to_string());
+        }
+        for (i, edge) in self.graph.edge_references().enumerate() {
+            lines.push(format!(
+                r#"    <edge id="e{i}" source="n{}" target="n{}">"#,
+                edge.source().index(),
+                edge.target().index()
+            ));
+            lines.push(format!(
+                r#"      <data key="length">{}</data>"#,
+                edge.weight()
+            ));
+            lines.push("    </edge>".to_string());
+        }
+        lines.push("  </graph>".to_string());
+        lines.push("</graphml>".to_string());
+
+        Ok(lines.join("\n") + "\n")
+    }
+
+    /// Create a phylogeny from GraphML.
+    ///
+    /// Node names are taken from the 'label' (or 'name') attribute, falling back
+    /// to the node id. Edge lengths are taken from the 'length' (or 'weight')
+    /// attribute, and their units from the 'branch_length' graph attribute.
+    /// Without units, every branch has a length of 1.
+    pub fn from_graphml(graphml: &str) -> Result<Self, Report> {
+        // attribute names of the data keys (ex. d0 => label)
+        let mut key_names: BTreeMap<String, String> = BTreeMap::new();
+
+        let mut graph_data: BTreeMap<String, String> = BTreeMap::new();
+        let mut nodes: Vec<(String, BTreeMap<String, String>)> = Vec::new();
+        let mut edges: Vec<(String, String, BTreeMap<String, String>)> = Vec::new();
+
+        // the element that data currently belongs to: graph, node, or edge
+        let mut element = "graph";
+        let mut rest = graphml;
+
+        while let Some(start) = rest.find('<') {
+            let Some(end) = rest[start..].find('>').map(|end| start + end) else {
+                return Err(eyre!("Unexpected end of GraphML, missing '>'."));
+            };
+            let tag = &rest[start + 1..end];
+            rest = &rest[end + 1..];
+
+            let self_closing = tag.ends_with('/');
+            let tag = tag.trim_end_matches('/');
+            let tag_name = tag.split_whitespace().next().unwrap_or_default();
+            let attributes = parse_attributes(tag);
+
+            match tag_name {
+                "key" => {
+                    if let Some(id) = attributes.get("id") {
+                        let name = attributes.get("attr.name").unwrap_or(id);
+                        key_names.insert(id.clone(), name.clone());
+                    }
+                }
+                "node" => {
+                    let id = attributes
+                        .get("id")
+                        .ok_or_else(|| eyre!("GraphML node is missing an id."))?;
+                    nodes.push((id.clone(), BTreeMap::new()));
+                    element = if self_closing { "graph" } else { "node" };
+                }
+                "edge" => {
+                    let (Some(source), Some(target)) =
+                        (attributes.get("source"), attributes.get("target"))
+                    else {
+                        return Err(eyre!("GraphML edge is missing a source or target."));
+                    };
+                    edges.push((source.clone(), target.clone(), BTreeMap::new()));
+                    element = if self_closing { "graph" } else { "edge" };
+                }
+                "/node" | "/edge" => element = "graph",
+                "data" if !self_closing => {
+                    let key = attributes.get("key").cloned().unwrap_or_default();
+                    let key = key_names.get(&key).cloned().unwrap_or(key);
+                    let value_end = rest
+                        .find("</data>")
+                        .ok_or_else(|| eyre!("GraphML data is missing '</data>'."))?;
+                    let value = unescape(rest[..value_end].trim());
+                    rest = &rest[value_end + "</data>".len()..];
+                    let data = match element {
+                        "node" => nodes.last_mut().map(|(_, data)| data),
+                        "edge" => edges.last_mut().map(|(_, _, data)| data),
+                        _ => Some(&mut graph_data),
+                    };
+                    if let Some(data) = data {
+                        data.insert(key, value);
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        // branch length units
+        let branch_length = match graph_data.get("branch_length") {
+            Some(unit) => BranchLength::from_str(&unit.replace('_', "-"), true)
+                .map_err(|e| eyre!("Unknown branch length units: {unit}. {e}"))?,
+            None => BranchLength::None,
+        };
+        let has_lengths = edges.iter().any(|(_, _, data)| {
+            data.contains_key("length") || data.contains_key("weight")
+        });
+        if has_lengths && branch_length == BranchLength::None {
+            warn!("GraphML edges have lengths, but no units, lengths will be ignored.");
+        }
+
+        let mut phylogeny = Phylogeny::new();
+        phylogeny.branch_length = branch_length;
+
+        // nodes
+        let mut indices = BTreeMap::new();
+        for (id, data) in &nodes {
+            let name = data.get("label").or(data.get("name")).unwrap_or(id);
+            if phylogeny.get_node(name).is_ok() {
+                return Err(eyre!("Duplicate node name in GraphML: {name}"))
+                    .suggestion("Node names must be unique.");
+            }
+            let index = phylogeny.add_node(name.clone());
+            indices.insert(id.clone(), index);
+            if let Some(date) = data.get("date") {
+                let date = date.parse().wrap_err_with(|| {
+                    format!("Failed to parse date of {name}: {date}")
+                })?;
+                phylogeny.dates.insert(name.clone(), date);
+            }
+        }
+
+        // edges
+        for (source, target, data) in &edges {
+            let get_index = |id: &String| {
+                indices
+                    .get(id)
+                    .ok_or_else(|| eyre!("GraphML edge refers to an unknown node: {id}"))
+            };
+            let length = match branch_length {
+                BranchLength::None => phylogeny.default_branch_length(),
+                _ => {
+                    let length =
+                        data.get("length").or(data.get("weight")).ok_or_else(|| {
+                            eyre!("GraphML edge {source} -> {target} has no length.")
+                        })?;
+                    length.parse::<f64>().wrap_err_with(|| {
+                        format!("Failed to parse length of edge {source} -> {target}")
+                    })?
+                }
+            };
+            phylogeny.graph.add_edge(*get_index(source)?, *get_index(target)?, length);
+        }
+
+        if phylogeny.get_node(ROOT).is_err() {
+            return Err(eyre!("GraphML phylogeny has no '{ROOT}' node.")).suggestion(
+                format!("Please name the top node of the phylogeny '{ROOT}'."),
+            );
+        }
+        if toposort(&phylogeny.graph, None).is_err() {
+            return Err(eyre!("GraphML phylogeny contains a cycle."));
+        }
+
+        phylogeny.validate_branch_lengths()?;
+        phylogeny.recombinants = phylogeny.get_recombinants()?;
+        phylogeny.recombinants_all = phylogeny.get_recombinants_all()?;
+
+        Ok(phylogeny)
+    }
+
+    /// Read phylogeny from a GraphML file.
+    pub fn read_graphml(path: &Path) -> Result<Self, Report> {
+        let graphml = utils::read_to_string(path)?;
+        Phylogeny::from_graphml(&graphml)
+            .wrap_err_with(|| format!("Failed to parse file: {path:?}"))
+    }
+}
@@ -0,0 +1,66 @@
+use crate::phylogeny::Phylogeny;
+use color_eyre::eyre::{eyre, Report, Result, WrapErr};
+use color_eyre::Help;
+use serde_json::Value;
+
+/// Version of the phylogeny JSON schema.
+///
+/// Increment this whenever fields are renamed, moved, or removed, so that
+/// phylogenies written by newer versions of rebar are not misread. Phylogenies
+/// without a version were written before the schema was versioned (1).
+pub const SCHEMA_VERSION: u64 = 1;
+
+/// Field of the schema version in phylogeny JSON.
+pub const SCHEMA_VERSION_FIELD: &str = "schema_version";
+
+// ----------------------------------------------------------------------------
+// Phylogeny
+
+impl Phylogeny {
+    /// Convert phylogeny to JSON, for rebar.
+    ///
+    /// The graph has a list of node names (`nodes`), and branches (`edges`)
+    /// from a parent index to a child index, with a length. The branch length
+    /// units, dates, and branch support are included, along with the schema
+    /// version.
+    pub fn to_json(&self) -> Result<String, Report> {
+        let mut json = serde_json::to_value(self)
+            .wrap_err_with(|| "Failed to serialize phylogeny.".to_string())?;
+        if let Value::Object(fields) = &mut json {
+            fields.insert(SCHEMA_VERSION_FIELD.to_string(), SCHEMA_VERSION.into());
+        }
+        let json = serde_json::to_string_pretty(&json)
+            .wrap_err_with(|| "Failed to serialize phylogeny.".to_string())?;
+        Ok(json)
+    }
+
+    /// Create a phylogeny from JSON.
+    ///
+    /// Phylogenies with a newer schema version than this version of rebar
+    /// supports are an error.
+    pub fn from_json(json: &str) -> Result<Self, Report> {
+        let json: Value = serde_json::from_str(json)
+            .wrap_err_with(|| "Failed to parse phylogeny JSON.".to_string())?;
+
+        let version = match json.get(SCHEMA_VERSION_FIELD) {
+            Some(version) => version.as_u64().ok_or_else(|| {
+                eyre!("Invalid phylogeny {SCHEMA_VERSION_FIELD}: {version}")
+            })?,
+            None => 1,
+        };
+        if version > SCHEMA_VERSION {
+            return Err(eyre!(
+                "Phylogeny {SCHEMA_VERSION_FIELD} {version} is not supported (maximum: {SCHEMA_VERSION})."
+            ))
+            .suggestion("Please upgrade rebar, or re-create the phylogeny with this version.");
+        }
+
+        let mut phylogeny: Phylogeny = serde_json::from_value(json)
+            .wrap_err_with(|| "Failed to parse phylogeny JSON.".to_string())?;
+        phylogeny.index_nodes();
+        phylogeny.recombinants = phylogeny.get_recombinants()?;
+        phylogeny.recombinants_all = phylogeny.get_recombinants_all()?;
+
+        Ok(phylogeny)
+    }
+}
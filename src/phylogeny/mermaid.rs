@@ -0,0 +1,322 @@
+use crate::phylogeny::{BranchLength, Phylogeny, ROOT};
+use crate::utils;
+use clap::ValueEnum;
+use color_eyre::eyre::{eyre, Report, Result, WrapErr};
+use color_eyre::Help;
+use log::warn;
+use petgraph::algo::toposort;
+use petgraph::visit::EdgeRef;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+/// Class of recombinant nodes in Mermaid diagrams.
+pub const RECOMBINANT_CLASS: &str = "recombinant";
+
+// ----------------------------------------------------------------------------
+// Mermaid Node
+
+/// A node reference in a Mermaid statement (ex. `n1["BA.1"]:::recombinant`).
+#[derive(Clone, Debug, Default, PartialEq)]
+struct MermaidNode {
+    id: String,
+    label: Option<String>,
+    class: Option<String>,
+}
+
+// ----------------------------------------------------------------------------
+// Functions
+// ----------------------------------------------------------------------------
+
+/// Escape quotes in a Mermaid label, as an entity code.
+fn escape(label: &str) -> String {
+    label.replace('"', "#quot;")
+}
+
+/// Unescape quotes in a Mermaid label.
+fn unescape(label: &str) -> String {
+    label.replace("#quot;", "\"")
+}
+
+/// Parse a node reference from the start of a statement, returning the node
+/// and the rest of the statement.
+fn parse_node(statement: &str) -> Result<(MermaidNode, &str), Report> {
+    let statement = statement.trim_start();
+    let end = statement
+        .find(|c: char| "[({:|".contains(c) || c.is_whitespace() || c == '-')
+        .unwrap_or(statement.len());
+    let id = statement[..end].to_string();
+    if id.is_empty() {
+        return Err(eyre!("Expected a Mermaid node: {statement}"));
+    }
+    let mut node = MermaidNode {
+        id,
+        ..Default::default()
+    };
+    let mut rest = &statement[end..];
+
+    // node shapes, ex. n1["label"], n1(label), n1{label}
+    if let Some(open) = rest.chars().next().filter(|c| "[({".contains(*c)) {
+        let close = match open {
+            '[' => ']',
+            '(' => ')',
+            _ => '}',
+        };
+        let inner = rest.trim_start_matches(open);
+        let (label, after) = match inner.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted.find('"').ok_or_else(|| {
+                    eyre!("Mermaid label of {} is missing a quote.", node.id)
+                })?;
+                (&quoted[..end], quoted[end + 1..].trim_start_matches(close))
+            }
+            None => {
+                let end = inner.find(close).ok_or_else(|| {
+                    eyre!("Mermaid label of {} is missing a '{close}'.", node.id)
+                })?;
+                (&inner[..end], inner[end..].trim_start_matches(close))
+            }
+        };
+        node.label = Some(unescape(label.trim()));
+        rest = after;
+    }
+
+    // node classes, ex. n1:::recombinant
+    if let Some(after) = rest.strip_prefix(":::") {
+        let end =
+            after.find(|c: char| c.is_whitespace() || c == '-').unwrap_or(after.len());
+        node.class = Some(after[..end].to_string());
+        rest = &after[end..];
+    }
+
+    Ok((node, rest))
+}
+
+/// Parse an edge from the start of a statement, returning the edge text (ex.
+/// a weight) and the rest of the statement, or None if there is no edge.
+fn parse_edge(statement: &str) -> Result<Option<(Option<String>, &str)>, Report> {
+    let statement = statement.trim_start();
+    // ex. A -->|1| B
+    if let Some(rest) = statement.strip_prefix("-->") {
+        let rest = rest.trim_start();
+        let Some(text) = rest.strip_prefix('|') else {
+            return Ok(Some((None, rest)));
+        };
+        let end = text
+            .find('|')
+            .ok_or_else(|| eyre!("Mermaid edge text is missing a '|': {statement}"))?;
+        return Ok(Some((
+            Some(text[..end].trim().to_string()),
+            &text[end + 1..],
+        )));
+    }
+    // ex. A -- 1 --> B
+    if let Some(rest) = statement.strip_prefix("--") {
+        let end = rest
+            .find("-->")
+            .ok_or_else(|| eyre!("Mermaid edge is missing an arrow: {statement}"))?;
+        return Ok(Some((
+            Some(rest[..end].trim().to_string()),
+            &rest[end + 3..],
+        )));
+    }
+    if statement.is_empty() {
+        return Ok(None);
+    }
+    Err(eyre!("Unsupported Mermaid statement: {statement}"))
+        .suggestion("Edges must be directed arrows (ex. A --> B).")
+}
+
+// ----------------------------------------------------------------------------
+// Phylogeny
+
+impl Phylogeny {
+    /// Convert phylogeny to a Mermaid flowchart, for markdown docs.
+    ///
+    /// Nodes have an id (ex. n1) and are labelled by name, and recombinants
+    /// have the recombinant class. Edges are labelled by their length, unless
+    /// the units are none. The units are written as a comment.
+    pub fn to_mermaid(&self) -> Result<String, Report> {
+        let mut lines = vec![
+            "graph LR".to_string(),
+            format!("    %% branch_length: {}", self.branch_length),
+        ];
+        for (i, name) in self.graph.node_weights().enumerate() {
+            let class = match self.is_recombinant(name)? {
+                true => format!(":::{RECOMBINANT_CLASS}"),
+                false => String::new(),
+            };
+            lines.push(format!("    n{i}[\"{}\"]{class}", escape(name)));
+        }
+        for edge in self.graph.edge_references() {
+            let (source, target) = (edge.source().index(), edge.target().index());
+            let line = match self.branch_length {
+                BranchLength::None => format!("    n{source} --> n{target}"),
+                _ => format!("    n{source} -->|{}| n{target}", edge.weight()),
+            };
+            lines.push(line);
+        }
+        lines.push(format!(
+            "    classDef {RECOMBINANT_CLASS} fill:{}",
+            self.theme.recombinant
+        ));
+
+        Ok(lines.join("\n") + "\n")
+    }
+
+    /// Create a phylogeny from a Mermaid flowchart (graph or flowchart).
+    ///
+    /// Nodes are named by their label, falling back to their id. Edges must
+    /// be directed, and their text is the branch length. The units are read
+    /// from a `%% branch_length: <units>` comment. Without units, every branch
+    /// has a length of 1. Nodes with the recombinant class must have multiple
+    /// parents. Styles and class definitions are ignored.
+    pub fn from_mermaid(mermaid: &str) -> Result<Self, Report> {
+        let mut branch_length = BranchLength::None;
+        let mut has_header = false;
+
+        // node names and classes by id, in order of appearance
+        let mut ids: Vec<String> = Vec::new();
+        let mut names: BTreeMap<String, String> = BTreeMap::new();
+        let mut recombinants: BTreeSet<String> = BTreeSet::new();
+        let mut edges: Vec<(String, String, Option<String>)> = Vec::new();
+
+        let statements = mermaid.lines().flat_map(|line| line.split(';'));
+        for statement in statements.map(str::trim).filter(|s| !s.is_empty()) {
+            // comments, including the branch length units
+            if let Some(comment) = statement.strip_prefix("%%") {
+                if let Some(unit) = comment.trim().strip_prefix("branch_length:") {
+                    let unit = unit.trim();
+                    branch_length = BranchLength::from_str(&unit.replace('_', "-"), true)
+                        .map_err(|e| eyre!("Unknown branch length units: {unit}. {e}"))?;
+                }
+                continue;
+            }
+            let keyword = statement.split_whitespace().next().unwrap_or_default();
+            match keyword {
+                "graph" | "flowchart" if !has_header => {
+                    has_header = true;
+                    continue;
+                }
+                "classDef" | "class" | "style" | "linkStyle" | "click" | "direction" => {
+                    continue
+                }
+                "subgraph" | "end" => {
+                    return Err(eyre!("Mermaid subgraphs are not supported."))
+                }
+                _ if !has_header => {
+                    return Err(eyre!(
+                        "Mermaid diagram must start with graph or flowchart."
+                    ))
+                    .suggestion("Please start the diagram with: graph LR")
+                }
+                _ => (),
+            }
+
+            // a chain of nodes and edges, ex. A --> B -->|2| C
+            let (mut source, mut rest) = parse_node(statement)?;
+            let mut chain = vec![source.clone()];
+            while let Some((text, after)) = parse_edge(rest)? {
+                let (target, after) = parse_node(after)?;
+                edges.push((source.id.clone(), target.id.clone(), text));
+                chain.push(target.clone());
+                source = target;
+                rest = after;
+            }
+
+            for node in chain {
+                if !ids.contains(&node.id) {
+                    ids.push(node.id.clone());
+                }
+                if let Some(label) = node.label {
+                    match names.get(&node.id) {
+                        Some(name) if *name != label => {
+                            return Err(eyre!(
+                                "Mermaid node {} has multiple labels: {name}, {label}",
+                                node.id
+                            ))
+                        }
+                        _ => names.insert(node.id.clone(), label),
+                    };
+                }
+                if node.class.as_deref() == Some(RECOMBINANT_CLASS) {
+                    recombinants.insert(node.id);
+                }
+            }
+        }
+        if !has_header {
+            return Err(eyre!("Mermaid diagram must start with graph or flowchart."));
+        }
+
+        let has_lengths = edges.iter().any(|(_, _, text)| text.is_some());
+        if has_lengths && branch_length == BranchLength::None {
+            warn!("Mermaid edges have lengths, but no units, lengths will be ignored.");
+        }
+
+        let mut phylogeny = Phylogeny::new();
+        phylogeny.branch_length = branch_length;
+
+        // nodes
+        let mut indices = BTreeMap::new();
+        for id in &ids {
+            let name = names.get(id).unwrap_or(id);
+            if phylogeny.get_node(name).is_ok() {
+                return Err(eyre!("Duplicate node name in Mermaid diagram: {name}"));
+            }
+            indices.insert(id, phylogeny.add_node(name.clone()));
+        }
+
+        // edges
+        for (source, target, text) in &edges {
+            let (source_index, target_index) = (indices[source], indices[target]);
+            if phylogeny.graph.find_edge(source_index, target_index).is_some() {
+                return Err(eyre!("Duplicate Mermaid edge: {source} --> {target}"));
+            }
+            let length = match branch_length {
+                BranchLength::None => phylogeny.default_branch_length(),
+                _ => {
+                    let text = text.as_ref().ok_or_else(|| {
+                        eyre!("Mermaid edge {source} --> {target} has no length.")
+                    })?;
+                    text.parse::<f64>().wrap_err_with(|| {
+                        format!("Failed to parse length of edge {source} --> {target}")
+                    })?
+                }
+            };
+            phylogeny.graph.add_edge(source_index, target_index, length);
+        }
+
+        if phylogeny.get_node(ROOT).is_err() {
+            return Err(eyre!("Mermaid phylogeny has no '{ROOT}' node.")).suggestion(
+                format!("Please name the top node of the phylogeny '{ROOT}'."),
+            );
+        }
+        if toposort(&phylogeny.graph, None).is_err() {
+            return Err(eyre!("Mermaid phylogeny contains a cycle."));
+        }
+
+        // the recombinant class must agree with the graph
+        for id in &recombinants {
+            let name = names.get(id).unwrap_or(id);
+            let num_parents = phylogeny.get_parents(name)?.len();
+            if num_parents < 2 {
+                return Err(eyre!(
+                    "Mermaid node {name} is a {RECOMBINANT_CLASS}, but has {num_parents} parent(s)."
+                ))
+                .suggestion("Recombinants must have an edge from each parent.");
+            }
+        }
+
+        phylogeny.validate_branch_lengths()?;
+        phylogeny.recombinants = phylogeny.get_recombinants()?;
+        phylogeny.recombinants_all = phylogeny.get_recombinants_all()?;
+
+        Ok(phylogeny)
+    }
+
+    /// Read phylogeny from a Mermaid file.
+    pub fn read_mermaid(path: &Path) -> Result<Self, Report> {
+        let mermaid = utils::read_to_string(path)?;
+        Phylogeny::from_mermaid(&mermaid)
+            .wrap_err_with(|| format!("Failed to parse file: {path:?}"))
+    }
+}
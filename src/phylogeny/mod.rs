@@ -1,30 +1,203 @@
+pub mod examples;
+pub mod graph;
+pub mod json;
+pub mod mermaid;
+pub mod newick;
+pub mod random;
+
 use crate::utils;
+use crate::utils::theme::Theme;
+use chrono::NaiveDate;
+use clap::ValueEnum;
 use color_eyre::eyre::{eyre, ContextCompat, Report, Result, WrapErr};
 use color_eyre::Help;
 use itertools::Itertools;
 use log::debug;
+use petgraph::algo::toposort;
 use petgraph::dot::{Config, Dot};
 use petgraph::graph::{Graph, NodeIndex};
-use petgraph::visit::{Dfs, IntoNodeReferences};
+use petgraph::visit::{Dfs, EdgeRef, IntoNodeReferences};
 use petgraph::Direction;
 use serde::{Deserialize, Serialize};
-use serde_json;
-use std::fs::File;
-use std::io::Write;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::path::Path;
 use std::string::ToString;
 
+/// Name of the root node of every phylogeny.
+pub const ROOT: &str = "root";
+
+// ----------------------------------------------------------------------------
+// Branch Length
+
+/// Units of the branch lengths (edge weights) of a phylogeny.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum BranchLength {
+    /// No branch lengths, every branch has a length of 1.
+    #[default]
+    None,
+    /// Number of mutations.
+    Mutations,
+    /// Substitutions per site.
+    SubsPerSite,
+    /// Time in years.
+    Years,
+}
+
+impl std::fmt::Display for BranchLength {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let unit = match self {
+            BranchLength::None => "none",
+            BranchLength::Mutations => "mutations",
+            BranchLength::SubsPerSite => "subs-per-site",
+            BranchLength::Years => "years",
+        };
+        write!(f, "{unit}")
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Conflict Policy
+
+/// How to resolve a node that has different parents in two merged phylogenies.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, ValueEnum)]
+pub enum ConflictPolicy {
+    /// Return an error describing all conflicts.
+    #[default]
+    Error,
+    /// Keep the parents from the original phylogeny.
+    Keep,
+    /// Replace with the parents from the other phylogeny.
+    Replace,
+    /// Keep parents from both phylogenies, the node becomes a recombinant.
+    Union,
+}
+
+/// A node that has different parents in two merged phylogenies.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct MergeConflict {
+    pub name: String,
+    pub parents: Vec<String>,
+    pub other_parents: Vec<String>,
+}
+
+impl std::fmt::Display for MergeConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} vs. {}",
+            self.name,
+            self.parents.join(","),
+            self.other_parents.join(",")
+        )
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Branch
+
+/// A branch (edge) of a phylogeny, from a parent to a child node.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Branch {
+    pub parent: String,
+    pub child: String,
+    /// Length, in units of the phylogeny's branch lengths.
+    pub length: f64,
+    /// Support of the branch (ex. bootstrap), NaN if unknown.
+    ///
+    /// Since NaN is never less than a threshold, branches without support are
+    /// not collapsed by low support.
+    pub confidence: f64,
+}
+
+// ----------------------------------------------------------------------------
+// Phylogeny Config
+
+/// Limits on phylogeny searches, so that pathological graphs (ex. a cycle, or
+/// many nested recombinants) abort with an error, instead of hanging.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PhylogenyConfig {
+    /// Maximum number of paths found between two nodes.
+    pub max_paths: usize,
+    /// Maximum number of nodes visited by a single search.
+    pub max_visited: usize,
+}
+
+impl Default for PhylogenyConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PhylogenyConfig {
+    pub fn new() -> Self {
+        PhylogenyConfig {
+            max_paths: 10_000,
+            max_visited: 1_000_000,
+        }
+    }
+
+    /// Check the number of nodes visited by a search, against the limit.
+    pub fn check_visited(&self, visited: usize) -> Result<(), Report> {
+        if visited > self.max_visited {
+            return Err(eyre!(
+                "Phylogeny search visited more than the maximum of {} nodes.",
+                self.max_visited
+            ))
+            .suggestion(
+                "Does the phylogeny contain a cycle? Otherwise, increase PhylogenyConfig max_visited.",
+            );
+        }
+        Ok(())
+    }
+
+    /// Check the number of paths found by a search, against the limit.
+    pub fn check_paths(&self, paths: usize) -> Result<(), Report> {
+        if paths > self.max_paths {
+            return Err(eyre!(
+                "Phylogeny search found more than the maximum of {} paths.",
+                self.max_paths
+            ))
+            .suggestion(
+                "Are there many nested recombinants? Otherwise, increase PhylogenyConfig max_paths.",
+            );
+        }
+        Ok(())
+    }
+}
+
 // ----------------------------------------------------------------------------
 // Phylogeny
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Phylogeny {
-    pub graph: Graph<String, isize>,
+    pub graph: Graph<String, f64>,
+    // units of the edge weights
+    #[serde(default)]
+    pub branch_length: BranchLength,
+    // optional designation/first-seen dates of nodes
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub dates: BTreeMap<String, NaiveDate>,
+    // optional support (ex. bootstrap) of the branch leading to a node
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub confidence: BTreeMap<String, f64>,
     // we will parse recombinants on load/read
     #[serde(skip_serializing, skip_deserializing)]
     pub recombinants: Vec<String>,
     #[serde(skip_serializing, skip_deserializing)]
     pub recombinants_all: Vec<String>,
+    // search limits are runtime configuration, not part of the phylogeny
+    #[serde(skip_serializing, skip_deserializing)]
+    pub config: PhylogenyConfig,
+    // node colors of exports (ex. dot, graphml)
+    #[serde(skip_serializing, skip_deserializing)]
+    pub theme: Theme,
+    // include layout hints (rank, cluster) in exports (ex. dot, graphml)
+    #[serde(skip_serializing, skip_deserializing)]
+    pub layout_hints: bool,
+    // node indices by name, for constant time lookups (see get_node)
+    #[serde(skip_serializing, skip_deserializing)]
+    node_indices: HashMap<String, NodeIndex>,
 }
 
 impl Default for Phylogeny {
@@ -37,8 +210,15 @@ impl Phylogeny {
     pub fn new() -> Self {
         Phylogeny {
             graph: Graph::new(),
+            branch_length: BranchLength::None,
+            dates: BTreeMap::new(),
+            confidence: BTreeMap::new(),
             recombinants: Vec::new(),
             recombinants_all: Vec::new(),
+            config: PhylogenyConfig::new(),
+            theme: Theme::new(),
+            layout_hints: false,
+            node_indices: HashMap::new(),
         }
     }
 
@@ -137,7 +317,7 @@ impl Phylogeny {
         let is_recombinant = self.is_recombinant(name)?;
 
         // Delete the node
-        self.graph.remove_node(node).unwrap_or_default();
+        self.remove_node(node).unwrap_or_default();
 
         // If it was an interior node, connect parents and child
         children.iter().for_each(|c| {
@@ -145,7 +325,7 @@ impl Phylogeny {
             //debug!("Connecting child {c} to new parent(s): {parents:?}");
             parents.iter().for_each(|p| {
                 let p_node = self.get_node(p).expect("Parent {p} is not in graph.");
-                self.graph.add_edge(p_node, c_node, 1);
+                self.graph.add_edge(p_node, c_node, 1.0);
             })
         });
 
@@ -160,6 +340,553 @@ impl Phylogeny {
         Ok(())
     }
 
+    /// Set the root of the phylogeny to a named node.
+    ///
+    /// All edges are re-oriented to point away from the new root, preserving
+    /// branch lengths. Since the rest of rebar expects the top node to be named
+    /// 'root', a 'root' node is added above the new root (with a branch length
+    /// of 0). The previous 'root' node is removed if it's no longer needed, or
+    /// renamed if it's now an interior node. Phylogenies with recombinants can't
+    /// be re-rooted.
+    pub fn set_root(&mut self, name: &str) -> Result<(), Report> {
+        let new_root = self.get_node(name)?;
+        if name == ROOT && self.get_parents(name)?.is_empty() {
+            return Ok(());
+        }
+
+        let recombinants = self.get_recombinants()?;
+        if !recombinants.is_empty() {
+            return Err(eyre!("Phylogeny with recombinants can't be re-rooted."))
+                .suggestion(format!("Recombinants: {}", recombinants.join(", ")));
+        }
+
+        // ----------------------------------------------------------------
+        // Re-orient edges away from the new root
+
+        let mut edges = Vec::new();
+        let mut visited = HashSet::from([new_root]);
+        let mut queue = VecDeque::from([new_root]);
+        while let Some(node) = queue.pop_front() {
+            let neighbors = self.graph.neighbors_undirected(node).collect_vec();
+            for neighbor in neighbors {
+                if visited.contains(&neighbor) {
+                    continue;
+                }
+                let edge = self
+                    .graph
+                    .find_edge_undirected(node, neighbor)
+                    .map(|(edge, _direction)| edge)
+                    .ok_or_else(|| eyre!("Failed to find edge: {node:?} {neighbor:?}"))?;
+                edges.push((node, neighbor, self.graph[edge]));
+                visited.insert(neighbor);
+                queue.push_back(neighbor);
+            }
+        }
+        self.graph.clear_edges();
+        for (parent, child, weight) in edges {
+            self.graph.add_edge(parent, child, weight);
+        }
+
+        // ----------------------------------------------------------------
+        // Previous root
+
+        if name != ROOT && self.get_node(ROOT).is_ok() {
+            let parents = self.get_parents(ROOT)?;
+            let children = self.get_children(ROOT)?;
+            // a leaf, or a pass-through node, can be removed
+            if children.is_empty() {
+                let node = self.get_node(ROOT)?;
+                self.remove_node(node);
+            } else if parents.len() == 1 && children.len() == 1 {
+                self.suppress(ROOT)?;
+            }
+            // otherwise it's an interior node with multiple children
+            else {
+                let node = self.get_node(ROOT)?;
+                self.graph[node] = self.unique_name("node");
+                self.index_nodes();
+            }
+        }
+
+        // ----------------------------------------------------------------
+        // New root
+
+        if name != ROOT {
+            let root = self.add_node(ROOT.to_string());
+            let node = self.get_node(name)?;
+            let length = self.default_branch_length();
+            self.graph.add_edge(root, node, length);
+        }
+
+        self.recombinants = self.get_recombinants()?;
+        self.recombinants_all = self.get_recombinants_all()?;
+
+        Ok(())
+    }
+
+    /// Root the phylogeny on the branch leading to an outgroup.
+    ///
+    /// A new 'root' node is placed at the midpoint of the outgroup's branch, so
+    /// that the outgroup and the rest of the phylogeny are sister clades.
+    pub fn set_root_outgroup(&mut self, outgroup: &str) -> Result<(), Report> {
+        let parents = self.get_parents(outgroup)?;
+        let parent = match parents.as_slice() {
+            [parent] => parent.clone(),
+            [] => return Err(eyre!("Outgroup {outgroup} is already the root.")),
+            _ => return Err(eyre!("Outgroup {outgroup} can't be a recombinant.")),
+        };
+
+        // already rooted on the outgroup
+        if parent == ROOT && self.get_children(ROOT)?.len() == 2 {
+            return Ok(());
+        }
+
+        // split the outgroup branch with a new node
+        let parent_node = self.get_node(&parent)?;
+        let outgroup_node = self.get_node(outgroup)?;
+        let edge = self
+            .graph
+            .find_edge(parent_node, outgroup_node)
+            .ok_or_else(|| eyre!("Outgroup {outgroup} is not connected to {parent}."))?;
+        let length = self.graph[edge];
+        let (parent_length, outgroup_length) = match self.branch_length {
+            BranchLength::None => (1.0, 1.0),
+            // mutations can't be split in half
+            BranchLength::Mutations => {
+                ((length / 2.0).floor(), length - (length / 2.0).floor())
+            }
+            _ => (length / 2.0, length / 2.0),
+        };
+        self.graph.remove_edge(edge);
+        let midpoint = self.add_node(self.unique_name("node"));
+        self.graph.add_edge(parent_node, midpoint, parent_length);
+        self.graph.add_edge(midpoint, outgroup_node, outgroup_length);
+
+        // root on the midpoint, which is then replaced by the root
+        let midpoint_name = self.get_name(&midpoint)?;
+        self.set_root(&midpoint_name)?;
+        self.suppress_root_child(&midpoint_name)?;
+
+        Ok(())
+    }
+
+    /// Replace the only child of the root with the root itself.
+    fn suppress_root_child(&mut self, name: &str) -> Result<(), Report> {
+        let node = self.get_node(name)?;
+        let root = self.get_node(ROOT)?;
+        let children = self
+            .graph
+            .edges_directed(node, Direction::Outgoing)
+            .map(|edge| (edge.target(), *edge.weight()))
+            .collect_vec();
+        for (child, weight) in children {
+            self.graph.add_edge(root, child, weight);
+        }
+        let node = self.get_node(name)?;
+        self.remove_node(node);
+        Ok(())
+    }
+
+    /// Remove a node with a single parent and child, joining their branches.
+    pub fn suppress(&mut self, name: &str) -> Result<(), Report> {
+        let node = self.get_node(name)?;
+        let parent = self.graph.edges_directed(node, Direction::Incoming).collect_vec();
+        let child = self.graph.edges_directed(node, Direction::Outgoing).collect_vec();
+        if parent.len() != 1 || child.len() != 1 {
+            return Err(eyre!("Node {name} must have exactly one parent and child."));
+        }
+        let weight = match self.branch_length {
+            BranchLength::None => 1.0,
+            _ => parent[0].weight() + child[0].weight(),
+        };
+        let (parent, child) = (parent[0].source(), child[0].target());
+        self.graph.add_edge(parent, child, weight);
+        self.remove_node(node);
+        Ok(())
+    }
+
+    /// Collapse all unary nodes (one parent and one child), except the root.
+    ///
+    /// The branches of a collapsed node are joined, summing their lengths.
+    /// Returns the names of the collapsed nodes.
+    pub fn collapse_unary_nodes(&mut self) -> Result<Vec<String>, Report> {
+        let mut collapsed = Vec::new();
+        for name in self.get_names()? {
+            let node = self.get_node(&name)?;
+            let is_unary = self.graph.edges_directed(node, Direction::Incoming).count()
+                == 1
+                && self.graph.edges_directed(node, Direction::Outgoing).count() == 1;
+            if name != ROOT && is_unary {
+                self.suppress(&name)?;
+                self.dates.remove(&name);
+                self.confidence.remove(&name);
+                collapsed.push(name);
+            }
+        }
+        Ok(collapsed)
+    }
+
+    /// Get all branches, in graph order.
+    pub fn get_branches(&self) -> Result<Vec<Branch>, Report> {
+        self.graph
+            .edge_references()
+            .map(|edge| {
+                let child = self.get_name(&edge.target())?;
+                Ok(Branch {
+                    parent: self.get_name(&edge.source())?,
+                    confidence: self.confidence.get(&child).copied().unwrap_or(f64::NAN),
+                    child,
+                    length: *edge.weight(),
+                })
+            })
+            .collect()
+    }
+
+    /// Collapse internal branches that match a predicate into polytomies.
+    ///
+    /// ex. `phylogeny.collapse_branches(|b| b.confidence < 70.0)`
+    ///
+    /// The child node of a collapsed branch is removed, and its children are
+    /// attached to its parent, adding the collapsed branch length to theirs.
+    /// Branches are evaluated from the root down, so lengths include those of
+    /// collapsed ancestors. Tips, recombinants, and nodes that share a child
+    /// with their parent are never collapsed, so that recombination is
+    /// preserved. Returns the names of the collapsed nodes.
+    pub fn collapse_branches<F>(&mut self, predicate: F) -> Result<Vec<String>, Report>
+    where
+        F: Fn(&Branch) -> bool,
+    {
+        let order = toposort(&self.graph, None)
+            .map_err(|e| eyre!("Phylogeny contains a cycle at node: {:?}", e.node_id()))?
+            .iter()
+            .map(|node| self.get_name(node))
+            .collect::<Result<Vec<_>, Report>>()?;
+
+        let mut collapsed = Vec::new();
+        for name in order {
+            let node = self.get_node(&name)?;
+            let parent =
+                self.graph.edges_directed(node, Direction::Incoming).collect_vec();
+            let children = self
+                .graph
+                .edges_directed(node, Direction::Outgoing)
+                .map(|edge| (edge.target(), *edge.weight()))
+                .collect_vec();
+            let [parent] = parent.as_slice() else {
+                continue;
+            };
+            let (parent, length) = (parent.source(), *parent.weight());
+            let shares_child = children
+                .iter()
+                .any(|(child, _)| self.graph.find_edge(parent, *child).is_some());
+            if children.is_empty() || shares_child {
+                continue;
+            }
+            let branch = Branch {
+                parent: self.get_name(&parent)?,
+                child: name.clone(),
+                length,
+                confidence: self.confidence.get(&name).copied().unwrap_or(f64::NAN),
+            };
+            if !predicate(&branch) {
+                continue;
+            }
+
+            debug!("Collapsing branch: {} -> {name}", branch.parent);
+            for (child, weight) in children {
+                let weight = match self.branch_length {
+                    BranchLength::None => weight,
+                    _ => weight + length,
+                };
+                self.graph.add_edge(parent, child, weight);
+            }
+            self.remove_node(node);
+            self.dates.remove(&name);
+            self.confidence.remove(&name);
+            collapsed.push(name);
+        }
+
+        self.recombinants = self.get_recombinants()?;
+        self.recombinants_all = self.get_recombinants_all()?;
+
+        Ok(collapsed)
+    }
+
+    /// Remove all nodes that don't match a predicate, except the root.
+    ///
+    /// ex. `phylogeny.retain(|name| dataset.populations.contains_key(name))`
+    ///
+    /// The children of a removed node are connected to all of its parents,
+    /// adding the removed branch length to theirs, so that descendants keep
+    /// their closest retained ancestors. Nodes are removed from the root down,
+    /// so lengths include those of removed ancestors. A recombinant whose
+    /// parents are removed can be left with a single parent (ex. if both
+    /// parents descend from the same retained node). Returns the names of the
+    /// removed nodes.
+    pub fn retain<F>(&mut self, keep: F) -> Result<Vec<String>, Report>
+    where
+        F: Fn(&str) -> bool,
+    {
+        let order = toposort(&self.graph, None)
+            .map_err(|e| eyre!("Phylogeny contains a cycle at node: {:?}", e.node_id()))?
+            .iter()
+            .map(|node| self.get_name(node))
+            .collect::<Result<Vec<_>, Report>>()?;
+
+        let mut removed = Vec::new();
+        for name in order {
+            if name == ROOT || keep(&name) {
+                continue;
+            }
+            let node = self.get_node(&name)?;
+            let parents = self
+                .graph
+                .edges_directed(node, Direction::Incoming)
+                .map(|edge| (edge.source(), *edge.weight()))
+                .collect_vec();
+            let children = self
+                .graph
+                .edges_directed(node, Direction::Outgoing)
+                .map(|edge| (edge.target(), *edge.weight()))
+                .collect_vec();
+
+            debug!("Removing node: {name}");
+            for ((parent, length), (child, weight)) in
+                parents.iter().cartesian_product(&children)
+            {
+                if self.graph.find_edge(*parent, *child).is_some() {
+                    continue;
+                }
+                let weight = match self.branch_length {
+                    BranchLength::None => *weight,
+                    _ => weight + length,
+                };
+                self.graph.add_edge(*parent, *child, weight);
+            }
+            self.remove_node(node);
+            self.dates.remove(&name);
+            self.confidence.remove(&name);
+            removed.push(name);
+        }
+
+        self.recombinants = self.get_recombinants()?;
+        self.recombinants_all = self.get_recombinants_all()?;
+
+        Ok(removed)
+    }
+
+    /// Length of a new branch that doesn't represent any change (ex. a root).
+    fn default_branch_length(&self) -> f64 {
+        match self.branch_length {
+            BranchLength::None => 1.0,
+            _ => 0.0,
+        }
+    }
+
+    /// Check that branch lengths are consistent with their units.
+    pub fn validate_branch_lengths(&self) -> Result<(), Report> {
+        let unit = self.branch_length;
+        for edge in self.graph.edge_references() {
+            let length = *edge.weight();
+            let valid = length.is_finite()
+                && length >= 0.0
+                && match unit {
+                    BranchLength::None => length == 1.0,
+                    BranchLength::Mutations => length.fract() == 0.0,
+                    BranchLength::SubsPerSite => length <= 1.0,
+                    BranchLength::Years => true,
+                };
+            if !valid {
+                let parent = self.get_name(&edge.source())?;
+                let child = self.get_name(&edge.target())?;
+                return Err(eyre!(
+                    "Branch length {length} from {parent} to {child} is not valid for units: {unit}"
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Convert branch lengths to different units.
+    ///
+    /// Mutations and substitutions per site are converted with the genome
+    /// length. Any units can be converted to None, which discards the lengths.
+    /// Years can't be converted without a clock rate.
+    pub fn convert_branch_lengths(
+        &mut self,
+        unit: BranchLength,
+        genome_length: usize,
+    ) -> Result<(), Report> {
+        let genome_length = genome_length as f64;
+        let convert = match (self.branch_length, unit) {
+            (from, to) if from == to => return Ok(()),
+            (_, BranchLength::None) => |_length: f64, _genome_length: f64| 1.0,
+            (BranchLength::Mutations, BranchLength::SubsPerSite) => {
+                |length: f64, genome_length: f64| length / genome_length
+            }
+            (BranchLength::SubsPerSite, BranchLength::Mutations) => {
+                |length: f64, genome_length: f64| (length * genome_length).round()
+            }
+            (from, to) => {
+                return Err(eyre!(
+                    "Branch lengths can't be converted from {from} to {to}."
+                ))
+            }
+        };
+
+        if unit != BranchLength::None && genome_length == 0.0 {
+            return Err(eyre!(
+                "Genome length is required to convert branch lengths."
+            ));
+        }
+
+        self.graph
+            .edge_weights_mut()
+            .for_each(|length| *length = convert(*length, genome_length));
+        self.branch_length = unit;
+
+        Ok(())
+    }
+
+    /// Get a name that's not already in the phylogeny, ex. 'node1'.
+    pub fn unique_name(&self, prefix: &str) -> String {
+        let names = self.graph.node_weights().collect::<HashSet<_>>();
+        (1..)
+            .map(|i| format!("{prefix}{i}"))
+            .find(|name| !names.contains(name))
+            .unwrap_or_default()
+    }
+
+    /// Merge another phylogeny into this one.
+    ///
+    /// Nodes and edges are unioned. A node is in conflict if it has parents in
+    /// both phylogenies, and they differ. Conflicts are resolved according to the
+    /// policy, and are returned for reporting. Dates from the other phylogeny
+    /// are used only for nodes without a date, unless the policy is Replace.
+    /// Branch length units must match, unless the other phylogeny has none, in
+    /// which case its new branches have a length of 0.
+    pub fn merge(
+        &mut self,
+        other: &Phylogeny,
+        policy: ConflictPolicy,
+    ) -> Result<Vec<MergeConflict>, Report> {
+        // ----------------------------------------------------------------
+        // Branch lengths
+
+        // a phylogeny without branch lengths (ex. curated recombinant parents)
+        // can be merged into any other, but not the other way around
+        if self.branch_length != other.branch_length
+            && other.branch_length != BranchLength::None
+        {
+            return Err(eyre!(
+                "Phylogenies have different branch length units: {} and {}",
+                self.branch_length,
+                other.branch_length
+            ))
+            .suggestion("Please convert the branch lengths to the same units.");
+        }
+        let same_units = self.branch_length == other.branch_length;
+        let default_length = self.default_branch_length();
+        let new_length = |length: f64| if same_units { length } else { default_length };
+
+        // ----------------------------------------------------------------
+        // Identify conflicts, before making any changes
+
+        let mut conflicts = Vec::new();
+        for name in other.get_names()? {
+            let other_parents = other.get_parents(&name)?;
+            if self.get_node(&name).is_err() || other_parents.is_empty() {
+                continue;
+            }
+            let parents = self.get_parents(&name)?;
+            if !parents.is_empty()
+                && parents.iter().sorted().ne(other_parents.iter().sorted())
+            {
+                conflicts.push(MergeConflict {
+                    name,
+                    parents,
+                    other_parents,
+                });
+            }
+        }
+
+        if policy == ConflictPolicy::Error && !conflicts.is_empty() {
+            return Err(eyre!(
+                "Phylogenies have {} conflicting node(s):\n{}",
+                conflicts.len(),
+                conflicts.iter().join("\n")
+            ))
+            .suggestion(
+                "Please try a different conflict policy (keep, replace, union).",
+            );
+        }
+
+        // ----------------------------------------------------------------
+        // Add nodes and edges
+
+        // changes are made to a copy, so this phylogeny is unchanged on error
+        let mut merged = self.clone();
+
+        for name in other.get_names()? {
+            if merged.get_node(&name).is_err() {
+                merged.add_node(name.clone());
+            }
+        }
+
+        for name in other.get_names()? {
+            let node = merged.get_node(&name)?;
+            let conflict = conflicts.iter().any(|c| c.name == name);
+            match (conflict, policy) {
+                (true, ConflictPolicy::Keep) => continue,
+                (true, ConflictPolicy::Replace) => {
+                    let edges = merged
+                        .graph
+                        .edges_directed(node, Direction::Incoming)
+                        .map(|edge| edge.id())
+                        .sorted()
+                        .collect_vec();
+                    // remove highest index first, removal invalidates the last index
+                    edges.into_iter().rev().for_each(|edge| {
+                        merged.graph.remove_edge(edge);
+                    });
+                }
+                _ => (),
+            }
+
+            let other_node = other.get_node(&name)?;
+            for edge in other.graph.edges_directed(other_node, Direction::Incoming) {
+                let parent = other.get_name(&edge.source())?;
+                let parent_node = merged.get_node(&parent)?;
+                if merged.graph.find_edge(parent_node, node).is_none() {
+                    merged.graph.add_edge(parent_node, node, new_length(*edge.weight()));
+                }
+            }
+        }
+
+        // ----------------------------------------------------------------
+        // Dates
+
+        for (name, date) in &other.dates {
+            if policy == ConflictPolicy::Replace || !merged.dates.contains_key(name) {
+                merged.dates.insert(name.clone(), *date);
+            }
+        }
+
+        // ----------------------------------------------------------------
+        // Validate
+
+        toposort(&merged.graph, None).map_err(|cycle| {
+            let name = merged.get_name(&cycle.node_id()).unwrap_or_default();
+            eyre!("Merged phylogeny contains a cycle at node: {name}")
+        })?;
+
+        merged.recombinants = merged.get_recombinants()?;
+        merged.recombinants_all = merged.get_recombinants_all()?;
+        *self = merged;
+
+        Ok(conflicts)
+    }
+
     /// Prune a clade from the graph.
     ///
     /// Removes named node and all descendants.
@@ -174,25 +901,13 @@ impl Phylogeny {
 
     /// Read phylogeny from file.
     pub fn read(path: &Path) -> Result<Phylogeny, Report> {
-        let phylogeny = std::fs::read_to_string(path)
-            .wrap_err_with(|| format!("Failed to read file: {path:?}."))?;
-        let mut phylogeny: Phylogeny = serde_json::from_str(&phylogeny)
-            .wrap_err_with(|| format!("Failed to parse file: {path:?}."))?;
-
-        phylogeny.recombinants = phylogeny.get_recombinants()?;
-        phylogeny.recombinants_all = phylogeny.get_recombinants_all()?;
-
-        Ok(phylogeny)
+        let phylogeny = utils::read_to_string(path)?;
+        Phylogeny::from_json(&phylogeny)
+            .wrap_err_with(|| format!("Failed to parse file: {path:?}."))
     }
 
     /// Write phylogeny to file.
     pub fn write(&self, output_path: &Path) -> Result<(), Report> {
-        // Create output file
-        let mut file = File::create(output_path)?;
-
-        // .unwrap_or_else(|_| {
-        //     return Err(eyre!("Failed to create file: {:?}.", &output_path))
-        // });
         let ext = utils::path_to_ext(Path::new(output_path))?;
 
         // format conversion
@@ -200,30 +915,95 @@ impl Phylogeny {
             // ----------------------------------------------------------------
             // DOT file for graphviz
             "dot" => {
-                let mut output =
-                    format!("{}", Dot::with_config(&self.graph, &[Config::EdgeNoLabel]));
+                let hints = match self.layout_hints {
+                    true => self.get_layout_hints()?,
+                    false => BTreeMap::new(),
+                };
+                // add node colors, and dates as attributes if available
+                let get_node_attributes = |_graph, (node, name): (NodeIndex, &String)| {
+                    let color = self
+                        .get_node_color(name)
+                        .map(|color| format!("color = \"{color}\" "))
+                        .unwrap_or_default();
+                    let date = self
+                        .dates
+                        .get(name)
+                        .map(|date| format!("date = \"{date}\" "))
+                        .unwrap_or_default();
+                    // nodes in the same group are kept in a straight line
+                    let hint = hints
+                            .get(&node)
+                            .map(|hint| {
+                                let cluster = hint.cluster.replace('"', "\\\"");
+                                format!(
+                                    "rank = \"{}\" cluster = \"{cluster}\" group = \"{cluster}\" ",
+                                    hint.rank
+                                )
+                            })
+                            .unwrap_or_default();
+                    format!("{color}{date}{hint}")
+                };
+                let mut output = format!(
+                    "{}",
+                    Dot::with_attr_getters(
+                        &self.graph,
+                        &[Config::EdgeNoLabel],
+                        &|_graph, _edge| String::new(),
+                        &get_node_attributes,
+                    )
+                );
                 // set graph id (for cytoscape)
                 output = str::replace(&output, "digraph", "digraph G");
                 // set horizontal (Left to Right) format for tree-like visualizer
                 output =
                     str::replace(&output, "digraph {", "digraph {\n    rankdir=\"LR\";");
+                // align nodes of the same rank
+                if !hints.is_empty() {
+                    let ranks = hints
+                        .iter()
+                        .map(|(node, hint)| (hint.rank, node.index()))
+                        .into_group_map();
+                    let ranks = ranks
+                        .into_iter()
+                        .sorted()
+                        .map(|(_rank, nodes)| {
+                            format!(
+                                "    {{ rank = same; {}; }}\n",
+                                nodes.iter().join("; ")
+                            )
+                        })
+                        .join("");
+                    if let Some(end) = output.rfind('}') {
+                        output.insert_str(end, &ranks);
+                    }
+                }
                 output
             }
             // ----------------------------------------------------------------
+            // GML and GraphML for cytoscape and gephi
+            "gml" => self.to_gml()?,
+            "graphml" => self.to_graphml()?,
+            // ----------------------------------------------------------------
+            // Newick for tree viewers (ex. FigTree, ete3)
+            "nwk" | "newick" => format!("{}\n", self.to_newick()?),
+            // ----------------------------------------------------------------
+            // Mermaid for markdown docs
+            "mmd" | "mermaid" => self.to_mermaid()?,
+            // ----------------------------------------------------------------
             // JSON for rebar
-            "json" => serde_json::to_string_pretty(&self)
-                .unwrap_or_else(|_| panic!("Failed to parse: {self:?}")),
+            "json" => self.to_json()?,
             _ => {
                 return Err(eyre!(
                     "Phylogeny write for extension .{ext} is not supported."
                 )
-                .suggestion("Please try .json or .dot instead."))
+                .suggestion(
+                    "Please try .json, .dot, .gml, .graphml, .nwk, or .mmd instead.",
+                ))
             }
         };
 
         // Write to file
-        file.write_all(output.as_bytes())
-            .unwrap_or_else(|_| panic!("Failed to write file: {:?}.", &output_path));
+        utils::write_atomic(output_path, output)?;
 
         Ok(())
     }
@@ -241,6 +1021,9 @@ impl Phylogeny {
         // dfs.next(&self.graph);
         // Iterate over descendants
         while let Some(nx) = dfs.next(&self.graph) {
+            self.config
+                .check_visited(descendants.len() + 1)
+                .wrap_err_with(|| format!("Failed to get descendants of {name}."))?;
             // Get node name
             let nx_name = self.get_name(&nx)?;
             descendants.push(nx_name);
@@ -249,6 +1032,26 @@ impl Phylogeny {
         Ok(descendants)
     }
 
+    /// Get the depth of the phylogeny, the longest path (edges) from the root.
+    pub fn get_depth(&self) -> Result<usize, Report> {
+        // visit nodes in topological order, so parents are visited before children
+        let nodes = toposort(&self.graph, None)
+            .map_err(|cycle| eyre!("Phylogeny contains a cycle at node: {cycle:?}"))?;
+
+        let mut depths: BTreeMap<NodeIndex, usize> = BTreeMap::new();
+        for node in nodes {
+            let depth = self
+                .graph
+                .neighbors_directed(node, Direction::Incoming)
+                .map(|parent| depths[&parent] + 1)
+                .max()
+                .unwrap_or(0);
+            depths.insert(node, depth);
+        }
+
+        Ok(depths.into_values().max().unwrap_or(0))
+    }
+
     /// Get parent names of node
     pub fn get_parents(&self, name: &str) -> Result<Vec<String>, Report> {
         let mut parents = Vec::new();
@@ -311,45 +1114,96 @@ impl Phylogeny {
     /// Get all paths from the origin node to the destination node, always traveling
     /// in the specified direction (Incoming towards root, Outgoing towards tips)/
     /// petgraph must have this already implemented, but I can't find it in docs
+    ///
+    /// The search is limited by the phylogeny config (max_paths, max_visited).
     pub fn get_paths(
         &self,
         origin: &str,
         dest: &str,
         direction: petgraph::Direction,
     ) -> Result<Vec<Vec<String>>, Report> {
-        // container to hold the paths we've found, is a vector of vectors
-        // because there might be recombinants with multiple paths
-        let mut paths: Vec<Vec<String>> = Vec::new();
+        self.search_paths(origin, dest, direction)
+            .wrap_err_with(|| format!("Failed to get paths from {origin} to {dest}."))
+    }
 
+    /// Iterative depth-first search of paths for get_paths.
+    ///
+    /// The paths from each node to the destination are memoized, so that nodes
+    /// shared by multiple paths (ex. ancestors of recombinant parents) are only
+    /// searched once, and only counted once as visited. Paths are node indices
+    /// until the end of the search, when they're converted to names.
+    fn search_paths(
+        &self,
+        origin: &str,
+        dest: &str,
+        direction: petgraph::Direction,
+    ) -> Result<Vec<Vec<String>>, Report> {
         // check that the origin and dest actually exist in the graph
         let origin_node = self.get_node(origin)?;
-        let _dest_node = self.get_node(dest)?;
-
-        // Check if we've reached the destination
-        if origin == dest {
-            paths.push(vec![origin.to_string()]);
-        }
-        // Otherwise, continue the search!
-        else {
-            let mut neighbors =
-                self.graph.neighbors_directed(origin_node, direction).detach();
-            while let Some(parent_node) = neighbors.next_node(&self.graph) {
-                // convert the parent graph index to a string name
-                let parent_name = self.get_name(&parent_node)?;
-
-                // recursively get path of each parent to the destination
-                let mut parent_paths = self.get_paths(&parent_name, dest, direction)?;
+        let dest_node = self.get_node(dest)?;
+
+        // paths from each searched node to the destination
+        let mut paths: HashMap<NodeIndex, Vec<Vec<NodeIndex>>> = HashMap::new();
+        // nodes on the current path of the search, to detect cycles
+        let mut searching: HashSet<NodeIndex> = HashSet::new();
+        let mut stack = vec![origin_node];
+        let mut visited = 0;
+
+        while let Some(&node) = stack.last() {
+            if paths.contains_key(&node) {
+                stack.pop();
+                continue;
+            }
+            // Check if we've reached the destination
+            if node == dest_node {
+                paths.insert(node, vec![vec![node]]);
+                stack.pop();
+                continue;
+            }
 
-                // prepend the origin to the paths
-                parent_paths.iter_mut().for_each(|p| p.insert(0, origin.to_string()));
+            // on the first visit, search the neighbors before the node
+            if searching.insert(node) {
+                visited += 1;
+                self.config.check_visited(visited)?;
+                let mut unsearched = Vec::new();
+                for neighbor in self.graph.neighbors_directed(node, direction) {
+                    if searching.contains(&neighbor) {
+                        let name = self.get_name(&neighbor)?;
+                        return Err(eyre!("Phylogeny contains a cycle at node: {name}"));
+                    }
+                    if !paths.contains_key(&neighbor) {
+                        unsearched.push(neighbor);
+                    }
+                }
+                if !unsearched.is_empty() {
+                    stack.extend(unsearched);
+                    continue;
+                }
+            }
 
-                // update the paths container to return at end of function
-                for p in parent_paths {
-                    paths.push(p);
+            // on the last visit, prepend the node to the paths of its neighbors
+            let mut node_paths = Vec::new();
+            for neighbor in self.graph.neighbors_directed(node, direction) {
+                for neighbor_path in &paths[&neighbor] {
+                    let mut path = Vec::with_capacity(neighbor_path.len() + 1);
+                    path.push(node);
+                    path.extend(neighbor_path);
+                    node_paths.push(path);
                 }
+                self.config.check_paths(node_paths.len())?;
             }
+            paths.insert(node, node_paths);
+            searching.remove(&node);
+            stack.pop();
         }
 
+        let paths = paths
+            .remove(&origin_node)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|path| path.into_iter().map(|node| self.graph[node].clone()).collect())
+            .collect();
+
         Ok(paths)
     }
 
@@ -368,64 +1222,98 @@ impl Phylogeny {
     }
 
     /// Identify the most recent common ancestor shared between all node names.
+    ///
+    /// The common ancestor of children of a polytomy is the polytomy parent,
+    /// regardless of how many of its children are named. If a named node is an
+    /// ancestor of all others, it is the common ancestor. Recombination edges
+    /// are followed, see get_mrca.
     pub fn get_common_ancestor(&self, names: &[String]) -> Result<String, Report> {
+        self.get_mrca(names, false)
+    }
+
+    /// Identify the most recent common ancestor (MRCA) of multiple nodes.
+    ///
+    /// Recombinants have multiple paths to the root, so the ancestors of a node
+    /// are all nodes on any of its paths. The MRCA is the common ancestor with
+    /// the longest path from the root, ties are broken by name.
+    ///
+    /// If `ignore_recombination` is true, the edges from parents to a
+    /// recombinant are ignored, and each recombinant is the start of its own
+    /// lineage from the root. The MRCA of a recombinant and one of its parents
+    /// is then the root, rather than the parent.
+    pub fn get_mrca(
+        &self,
+        names: &[String],
+        ignore_recombination: bool,
+    ) -> Result<String, Report> {
         // if only one node name was provided, just return it
         if names.len() == 1 {
-            let common_ancestor = names[0].clone();
-            return Ok(common_ancestor);
+            return Ok(names[0].clone());
         }
+        let is_ignored = |node: NodeIndex| {
+            ignore_recombination
+                && self.graph.neighbors_directed(node, Direction::Incoming).count() > 1
+        };
 
-        // mass pile of all ancestors of all named nodes
-        let ancestors: Vec<_> = names
-            .iter()
-            .map(|pop| {
-                let paths = self.get_paths(pop, "root", Direction::Incoming)?;
-                let ancestors = paths.into_iter().flatten().unique().collect_vec();
-                debug!("{pop}: {ancestors:?}");
-                Ok(ancestors)
-            })
-            .collect::<Result<Vec<_>, Report>>()?
-            .into_iter()
-            .flatten()
-            .collect::<Vec<_>>();
-
-        // get ancestors shared by all sequences
-        let common_ancestors: Vec<_> = ancestors
-            .iter()
-            .unique()
-            .filter(|anc| {
-                let count = ancestors.iter().filter(|pop| pop == anc).count();
-                count == names.len()
-            })
-            .collect();
-
-        debug!("common_ancestors: {common_ancestors:?}");
+        // ancestors of each named node, including itself
+        let mut common_ancestors: Option<HashSet<NodeIndex>> = None;
+        for name in names {
+            let node = self.get_node(name)?;
+            let mut ancestors = HashSet::from([node]);
+            let mut queue = VecDeque::from([node]);
+            while let Some(node) = queue.pop_front() {
+                if is_ignored(node) {
+                    continue;
+                }
+                for parent in self.graph.neighbors_directed(node, Direction::Incoming) {
+                    if ancestors.insert(parent) {
+                        queue.push_back(parent);
+                    }
+                }
+            }
+            // recombinants that are ignored still descend from the root
+            if ignore_recombination {
+                ancestors.insert(self.get_node(ROOT)?);
+            }
+            debug!("{name}: {} ancestors", ancestors.len());
+            common_ancestors = Some(match common_ancestors {
+                Some(common) => common.intersection(&ancestors).copied().collect(),
+                None => ancestors,
+            });
+        }
+        let common_ancestors = common_ancestors.unwrap_or_default();
+
+        // get the depths (longest path from the root) of all nodes, visiting
+        // parents before children
+        let nodes = toposort(&self.graph, None)
+            .map_err(|cycle| eyre!("Phylogeny contains a cycle at node: {cycle:?}"))?;
+        let mut depths: BTreeMap<NodeIndex, usize> = BTreeMap::new();
+        for node in nodes {
+            let depth = match is_ignored(node) {
+                true => 1,
+                false => self
+                    .graph
+                    .neighbors_directed(node, Direction::Incoming)
+                    .map(|parent| depths[&parent] + 1)
+                    .max()
+                    .unwrap_or(0),
+            };
+            depths.insert(node, depth);
+        }
 
-        // get the depths (distance to root) of the common ancestors
-        let depths = common_ancestors
+        // get the deepest (ie. most recent common ancestor), ties are broken by
+        // name, since multiple paths to root (recombination) can tie
+        let mrca = common_ancestors
             .into_iter()
-            .map(|pop| {
-                let paths = self.get_paths(pop, "root", Direction::Incoming)?;
-                let longest_path = paths
-                    .into_iter()
-                    .max_by(|a, b| a.len().cmp(&b.len()))
-                    .unwrap_or_default();
-                let depth = longest_path.len();
-                debug!("{pop}: {depth}");
-                Ok((pop, depth))
-            })
-            .collect::<Result<Vec<_>, Report>>()?;
-
-        // get the deepest (ie. most recent common ancestor)
-        let deepest_ancestor = depths
+            .map(|node| Ok((self.get_name(&node)?, depths[&node])))
+            .collect::<Result<Vec<_>, Report>>()?
             .into_iter()
-            .max_by(|a, b| a.1.cmp(&b.1))
+            .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)))
+            .map(|(name, _depth)| name)
             .context("Failed to get common ancestor.")?;
+        debug!("mrca: {mrca}");
 
-        // tuple (population name, depth)
-        let common_ancestor = deepest_ancestor.0.to_string();
-
-        Ok(common_ancestor)
+        Ok(mrca)
     }
 
     /// Identify the most recent ancestor that is a recombinant.
@@ -449,7 +1337,50 @@ impl Phylogeny {
         Ok(recombinant)
     }
 
+    /// Add a named node to the graph, and to the index of node names.
+    pub fn add_node(&mut self, name: String) -> NodeIndex {
+        let node = self.graph.add_node(name.clone());
+        self.node_indices.entry(name).or_insert(node);
+        node
+    }
+
+    /// Remove a node from the graph, and from the index of node names.
+    ///
+    /// The graph moves its last node into the index of the removed node, so the
+    /// moved node is re-indexed.
+    pub fn remove_node(&mut self, node: NodeIndex) -> Option<String> {
+        let last = NodeIndex::new(self.graph.node_count().checked_sub(1)?);
+        let name = self.graph.remove_node(node)?;
+        if self.node_indices.get(&name) == Some(&node) {
+            self.node_indices.remove(&name);
+        }
+        if let Some(moved) = self.graph.node_weight(node) {
+            if self.node_indices.get(moved) == Some(&last) {
+                self.node_indices.insert(moved.clone(), node);
+            }
+        }
+        Some(name)
+    }
+
+    /// Rebuild the index of node names, after the graph was modified directly
+    /// (ex. graph.add_node, or renaming a node).
+    pub fn index_nodes(&mut self) {
+        self.node_indices.clear();
+        for (node, name) in self.graph.node_references() {
+            self.node_indices.entry(name.clone()).or_insert(node);
+        }
+    }
+
+    /// Get the index of a node name in the graph.
+    ///
+    /// Names are looked up in the index of node names. If the graph was modified
+    /// directly and the name isn't indexed, the graph is searched instead.
     pub fn get_node(&self, name: &str) -> Result<NodeIndex, Report> {
+        if let Some(node) = self.node_indices.get(name) {
+            if self.graph.node_weight(*node).is_some_and(|n| n == name) {
+                return Ok(*node);
+            }
+        }
         for (idx, n) in self.graph.node_references() {
             if n == name {
                 return Ok(idx);
@@ -459,11 +1390,9 @@ impl Phylogeny {
     }
 
     pub fn get_name(&self, node: &NodeIndex) -> Result<String, Report> {
-        for (idx, n) in self.graph.node_references() {
-            if &idx == node {
-                return Ok(n.clone());
-            }
-        }
-        Err(eyre!("Node {node:?} is not in the phylogeny."))
+        self.graph
+            .node_weight(*node)
+            .cloned()
+            .ok_or_else(|| eyre!("Node {node:?} is not in the phylogeny."))
     }
 }
@@ -0,0 +1,434 @@
+use crate::phylogeny::{BranchLength, Phylogeny, ROOT};
+use crate::utils;
+use color_eyre::eyre::{eyre, Report, Result, WrapErr};
+use color_eyre::Help;
+use log::warn;
+use petgraph::graph::NodeIndex;
+use petgraph::Direction;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+// ----------------------------------------------------------------------------
+// Newick Node
+
+/// A node parsed from a Newick string, before conversion to a phylogeny.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Node {
+    pub name: Option<String>,
+    pub length: Option<f64>,
+    pub children: Vec<Node>,
+    /// Tag of a hybrid node in extended Newick (ex. H1), which identifies all
+    /// occurrences of the node.
+    pub hybrid: Option<String>,
+}
+
+impl Node {
+    /// Count descendant nodes with and without a branch length.
+    pub fn count_lengths(&self) -> (usize, usize) {
+        self.children.iter().fold((0, 0), |(with, without), child| {
+            let (child_with, child_without) = child.count_lengths();
+            match child.length {
+                Some(_) => (with + child_with + 1, without + child_without),
+                None => (with + child_with, without + child_without + 1),
+            }
+        })
+    }
+
+    /// Collect the names of hybrid nodes by tag, from any of their occurrences.
+    pub fn hybrid_names(
+        &self,
+        names: &mut BTreeMap<String, Option<String>>,
+    ) -> Result<(), Report> {
+        if let Some(hybrid) = &self.hybrid {
+            let name = names.entry(hybrid.clone()).or_default();
+            match (&name, &self.name) {
+                (Some(name), Some(other)) if name != other => {
+                    return Err(eyre!(
+                        "Hybrid node #{hybrid} has multiple names: {name}, {other}"
+                    ))
+                }
+                (None, Some(other)) => *name = Some(other.clone()),
+                _ => (),
+            }
+        }
+        for child in &self.children {
+            child.hybrid_names(names)?;
+        }
+        Ok(())
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Parser
+
+/// Recursive descent parser of the Newick format.
+///
+/// Supports quoted labels ('...'), comments ([...]), whitespace between
+/// tokens, and the hybrid nodes of extended Newick (ex. D#H1).
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(newick: &'a str) -> Self {
+        Parser {
+            chars: newick.chars().peekable(),
+        }
+    }
+
+    /// Skip whitespace and comments.
+    fn skip(&mut self) {
+        while let Some(c) = self.chars.peek() {
+            if c.is_whitespace() {
+                self.chars.next();
+            } else if *c == '[' {
+                self.chars.by_ref().find(|c| *c == ']');
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip();
+        self.chars.peek().copied()
+    }
+
+    /// tree := subtree ';'
+    fn tree(&mut self) -> Result<Node, Report> {
+        let node = self.subtree()?;
+        match self.peek() {
+            Some(';') => Ok(node),
+            Some(c) => Err(eyre!("Expected ';' at end of tree, found: '{c}'")),
+            None => Err(eyre!("Expected ';' at end of tree.")),
+        }
+    }
+
+    /// subtree := ( '(' subtree (',' subtree)* ')' )? label? (':' length)?
+    fn subtree(&mut self) -> Result<Node, Report> {
+        let mut node = Node::default();
+
+        if self.peek() == Some('(') {
+            self.chars.next();
+            loop {
+                node.children.push(self.subtree()?);
+                match self.peek() {
+                    Some(',') => {
+                        self.chars.next();
+                    }
+                    Some(')') => {
+                        self.chars.next();
+                        break;
+                    }
+                    Some(c) => return Err(eyre!("Expected ',' or ')', found: '{c}'")),
+                    None => return Err(eyre!("Unexpected end of tree, missing ')'.")),
+                }
+            }
+        }
+
+        node.name = self.label()?;
+        if let Some((name, hybrid)) = node.name.as_deref().and_then(split_hybrid) {
+            node.hybrid = Some(hybrid.to_string());
+            node.name = (!name.is_empty()).then(|| name.to_string());
+        }
+
+        if self.peek() == Some(':') {
+            self.chars.next();
+            let length = self.token();
+            let length = length
+                .parse::<f64>()
+                .wrap_err_with(|| format!("Failed to parse branch length: '{length}'"))?;
+            node.length = Some(length);
+        }
+
+        Ok(node)
+    }
+
+    /// A quoted or unquoted label.
+    fn label(&mut self) -> Result<Option<String>, Report> {
+        let label = if self.peek() == Some('\'') {
+            self.chars.next();
+            let mut label = String::new();
+            loop {
+                match self.chars.next() {
+                    // two single quotes are an escaped quote
+                    Some('\'') if self.chars.peek() == Some(&'\'') => {
+                        self.chars.next();
+                        label.push('\'');
+                    }
+                    Some('\'') => break,
+                    Some(c) => label.push(c),
+                    None => return Err(eyre!("Unexpected end of tree, missing quote.")),
+                }
+            }
+            label
+        } else {
+            self.token()
+        };
+
+        Ok((!label.is_empty()).then_some(label))
+    }
+
+    /// Read characters until a Newick delimiter.
+    fn token(&mut self) -> String {
+        self.skip();
+        let mut token = String::new();
+        while let Some(c) = self.chars.peek() {
+            if "(),:;[".contains(*c) || c.is_whitespace() {
+                break;
+            }
+            token.push(*c);
+            self.chars.next();
+        }
+        token
+    }
+}
+
+/// Parse a Newick string into a tree of nodes.
+pub fn parse(newick: &str) -> Result<Node, Report> {
+    Parser::new(newick.trim()).tree()
+}
+
+// ----------------------------------------------------------------------------
+// Phylogeny
+
+impl Phylogeny {
+    /// Create a phylogeny from a Newick string, with an optional outgroup.
+    ///
+    /// An unnamed top node is named 'root', and other unnamed nodes are given
+    /// unique names (ex. 'node1'). Numeric labels of internal nodes are the
+    /// support of their branch (ex. bootstrap), and are also unnamed. A top
+    /// node with 3+ children is an unrooted tree, which should be rooted with
+    /// an outgroup. Branch lengths must be present on all branches or none, and
+    /// be valid for their units.
+    pub fn from_newick(
+        newick: &str,
+        outgroup: Option<&str>,
+        branch_length: BranchLength,
+    ) -> Result<Self, Report> {
+        let tree = parse(newick)?;
+
+        // branch lengths of all nodes except the top
+        let (with_length, without_length) = tree.count_lengths();
+        let branch_length = match (with_length, without_length) {
+            (0, _) if branch_length != BranchLength::None => {
+                warn!("Newick tree has no branch lengths, units will be: none");
+                BranchLength::None
+            }
+            (0, _) => BranchLength::None,
+            (_, 0) if branch_length == BranchLength::None => {
+                return Err(eyre!("Newick tree has branch lengths, but no units."))
+                    .suggestion("Please specify the branch length units.")
+            }
+            (_, 0) => branch_length,
+            _ => {
+                return Err(eyre!(
+                    "Newick tree has {without_length} branch(es) without a length."
+                ))
+                .suggestion("Branch lengths must be present on all branches or none.")
+            }
+        };
+
+        if tree.children.len() > 2 && outgroup.is_none() {
+            warn!(
+                "Newick tree appears to be unrooted (the top node has {} children), the top node will be used as the root.",
+                tree.children.len()
+            );
+        }
+
+        let mut phylogeny = Phylogeny::new();
+        phylogeny.branch_length = branch_length;
+        let mut root = tree;
+        if root.name.is_none() {
+            root.name = Some(ROOT.to_string());
+        }
+        let mut hybrid_names = BTreeMap::new();
+        root.hybrid_names(&mut hybrid_names)?;
+        let mut hybrids = BTreeMap::new();
+        phylogeny.add_newick_node(&root, None, &hybrid_names, &mut hybrids)?;
+
+        // rebar expects the top node to be named root
+        let top = root.name.unwrap_or_default();
+        if top != ROOT {
+            phylogeny.set_root(&top)?;
+        }
+
+        if let Some(outgroup) = outgroup {
+            phylogeny
+                .set_root_outgroup(outgroup)
+                .wrap_err_with(|| format!("Failed to root on outgroup: {outgroup}"))?;
+        }
+
+        phylogeny.validate_branch_lengths()?;
+        phylogeny.recombinants = phylogeny.get_recombinants()?;
+        phylogeny.recombinants_all = phylogeny.get_recombinants_all()?;
+
+        Ok(phylogeny)
+    }
+
+    /// Convert the phylogeny to a Newick string.
+    ///
+    /// Recombinants (nodes with multiple parents) use the extended Newick
+    /// hybrid notation, with the same label under each parent (ex. `D#H1`).
+    /// The recombinant and its descendants are written under its first parent,
+    /// and as a leaf under each other parent. Viewers without support for
+    /// hybrids (ex. FigTree) show the recombinant as duplicated tips. Branch
+    /// lengths are written unless the units are none.
+    pub fn to_newick(&self) -> Result<String, Report> {
+        let root = self.get_node(ROOT)?;
+        let mut hybrids = BTreeMap::new();
+        let newick = self.newick_subtree(root, None, &mut hybrids)?;
+        Ok(format!("{newick};"))
+    }
+
+    /// Convert a node (and its descendants) to a Newick subtree.
+    ///
+    /// Hybrid numbers of recombinants are assigned in the order they are
+    /// first written.
+    fn newick_subtree(
+        &self,
+        node: NodeIndex,
+        parent: Option<NodeIndex>,
+        hybrids: &mut BTreeMap<NodeIndex, usize>,
+    ) -> Result<String, Report> {
+        let name = self.get_name(&node)?;
+        let is_hybrid =
+            self.graph.neighbors_directed(node, Direction::Incoming).count() > 1;
+
+        // a recombinant that was already written is a leaf
+        let written = hybrids.contains_key(&node);
+        let label = if is_hybrid {
+            let num_hybrids = hybrids.len() + 1;
+            let number = *hybrids.entry(node).or_insert(num_hybrids);
+            quote(&format!("{name}#H{number}"))
+        } else {
+            quote(&name)
+        };
+
+        let mut newick = String::new();
+        if !written {
+            let children = self
+                .get_children(&name)?
+                .iter()
+                .map(|child| {
+                    let child = self.get_node(child)?;
+                    self.newick_subtree(child, Some(node), hybrids)
+                })
+                .collect::<Result<Vec<_>, Report>>()?;
+            if !children.is_empty() {
+                newick = format!("({})", children.join(","));
+            }
+        }
+        newick.push_str(&label);
+
+        if let Some(parent) = parent {
+            if self.branch_length != BranchLength::None {
+                let edge = self.graph.find_edge(parent, node).ok_or_else(|| {
+                    eyre!("Failed to find branch from parent of: {name}")
+                })?;
+                newick.push_str(&format!(":{}", self.graph[edge]));
+            }
+        }
+
+        Ok(newick)
+    }
+
+    /// Read phylogeny from a Newick file, with an optional outgroup.
+    pub fn read_newick(
+        path: &Path,
+        outgroup: Option<&str>,
+        branch_length: BranchLength,
+    ) -> Result<Self, Report> {
+        let newick = utils::read_to_string(path)?;
+        Phylogeny::from_newick(&newick, outgroup, branch_length)
+            .wrap_err_with(|| format!("Failed to parse file: {path:?}"))
+    }
+
+    /// Add a Newick node (and its descendants) as a child of a parent node.
+    ///
+    /// Hybrid nodes are added at their first occurrence, and connected to the
+    /// parent of each other occurrence. Unnamed hybrids are given unique names
+    /// (ex. 'hybrid1').
+    fn add_newick_node(
+        &mut self,
+        node: &Node,
+        parent: Option<NodeIndex>,
+        hybrid_names: &BTreeMap<String, Option<String>>,
+        hybrids: &mut BTreeMap<String, NodeIndex>,
+    ) -> Result<(), Report> {
+        // numeric labels of internal nodes are branch support (ex. bootstrap)
+        let confidence = match &node.name {
+            Some(name) if !node.children.is_empty() && node.hybrid.is_none() => {
+                name.parse::<f64>().ok()
+            }
+            _ => None,
+        };
+        // a recombinant (hybrid) that was already added is another parent
+        let hybrid = node.hybrid.as_ref().and_then(|hybrid| hybrids.get(hybrid));
+        if let (Some(index), Some(parent)) = (hybrid.copied(), parent) {
+            if self.graph.find_edge(parent, index).is_some() {
+                let name = self.get_name(&index)?;
+                return Err(eyre!("Hybrid node {name} has the same parent twice."));
+            }
+            self.graph.add_edge(parent, index, node.length.unwrap_or(1.0));
+            for child in &node.children {
+                self.add_newick_node(child, Some(index), hybrid_names, hybrids)?;
+            }
+            return Ok(());
+        }
+
+        let hybrid_name = node.hybrid.as_ref().and_then(|h| hybrid_names.get(h));
+        let name = match (&node.name, hybrid_name) {
+            (_, Some(None)) => self.unique_name("hybrid"),
+            (Some(_), None) if confidence.is_some() => self.unique_name("node"),
+            (_, Some(Some(name))) | (Some(name), None) => {
+                if self.get_node(name).is_ok() {
+                    return Err(eyre!("Duplicate node name in Newick tree: {name}"))
+                        .suggestion("Node names must be unique.");
+                }
+                name.clone()
+            }
+            (None, None) => self.unique_name("node"),
+        };
+
+        if let Some(confidence) = confidence {
+            self.confidence.insert(name.clone(), confidence);
+        }
+        let index = self.add_node(name);
+        if let Some(parent) = parent {
+            self.graph.add_edge(parent, index, node.length.unwrap_or(1.0));
+        }
+        if let Some(hybrid) = &node.hybrid {
+            hybrids.insert(hybrid.clone(), index);
+        }
+
+        for child in &node.children {
+            self.add_newick_node(child, Some(index), hybrid_names, hybrids)?;
+        }
+
+        Ok(())
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Functions
+
+/// Split a hybrid label of extended Newick into the name and tag (ex. D#H1).
+///
+/// The tag is the type of hybrid: hybridization (H), recombination (R), or
+/// lateral gene transfer (LGT), followed by a number. The name can be empty.
+fn split_hybrid(label: &str) -> Option<(&str, &str)> {
+    let (name, tag) = label.rsplit_once('#')?;
+    let number = ["LGT", "H", "R"].iter().find_map(|kind| tag.strip_prefix(kind))?;
+    let is_number = !number.is_empty() && number.chars().all(|c| c.is_ascii_digit());
+    is_number.then_some((name, tag))
+}
+
+/// Quote a Newick label, if it contains delimiters, whitespace, or quotes.
+fn quote(label: &str) -> String {
+    let needs_quotes = label.chars().any(|c| "(),:;[]'".contains(c) || c.is_whitespace());
+    match needs_quotes {
+        true => format!("'{}'", label.replace('\'', "''")),
+        false => label.to_string(),
+    }
+}
@@ -0,0 +1,107 @@
+use crate::phylogeny::{examples, Phylogeny, ROOT};
+use color_eyre::eyre::{eyre, Report, Result};
+use itertools::Itertools;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::collections::{BTreeMap, BTreeSet};
+
+// ----------------------------------------------------------------------------
+// Random Phylogeny
+
+impl Phylogeny {
+    /// Generate a random phylogeny (ancestral recombination graph), for tests
+    /// and benchmarks.
+    ///
+    /// A random bifurcating tree with `n_tips` tips (at least 2) is grown from
+    /// the root, by splitting random tips into nodes (P1, P2, ...). Then
+    /// `n_recombinants` recombinants (X1, X2, ...) are added as tips, each with
+    /// two random parents that are not ancestors of one another. Recombinants
+    /// can be parents of later recombinants (recursive recombination). The same
+    /// seed always generates the same phylogeny.
+    pub fn random(
+        n_tips: usize,
+        n_recombinants: usize,
+        seed: u64,
+    ) -> Result<Phylogeny, Report> {
+        if n_tips < 2 {
+            return Err(eyre!(
+                "A random phylogeny needs at least 2 tips, not {n_tips}."
+            ));
+        }
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut edges: Vec<(String, String)> = Vec::new();
+        let mut parents: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+        // grow a bifurcating tree, by splitting random tips
+        let mut num_nodes = 0;
+        let mut tips = vec![ROOT.to_string()];
+        while tips.len() < n_tips {
+            let tip = tips.swap_remove(rng.gen_range(0..tips.len()));
+            for _ in 0..2 {
+                num_nodes += 1;
+                let child = format!("P{num_nodes}");
+                add_edge(&tip, &child, &mut edges, &mut parents);
+                tips.push(child);
+            }
+        }
+
+        // add recombinants of unrelated parents
+        let mut nodes = (1..=num_nodes).map(|i| format!("P{i}")).collect_vec();
+        for i in 1..=n_recombinants {
+            let recombinant = format!("X{i}");
+            let parent_1 = nodes
+                .choose(&mut rng)
+                .cloned()
+                .ok_or_else(|| eyre!("Random phylogeny has no nodes."))?;
+            let related = ancestors(&parent_1, &parents);
+            let candidates = nodes
+                .iter()
+                .filter(|node| {
+                    **node != parent_1
+                        && !related.contains(*node)
+                        && !ancestors(node, &parents).contains(&parent_1)
+                })
+                .collect_vec();
+            let parent_2 = candidates
+                .choose(&mut rng)
+                .map(|node| node.to_string())
+                .ok_or_else(|| eyre!("No unrelated parent was found for {parent_1}."))?;
+            add_edge(&parent_1, &recombinant, &mut edges, &mut parents);
+            add_edge(&parent_2, &recombinant, &mut edges, &mut parents);
+            nodes.push(recombinant);
+        }
+
+        let edges = edges.iter().map(|(p, c)| (p.as_str(), c.as_str())).collect_vec();
+        examples::from_edges(&edges)
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Functions
+// ----------------------------------------------------------------------------
+
+/// Add an edge, and record the child's parent.
+fn add_edge(
+    parent: &str,
+    child: &str,
+    edges: &mut Vec<(String, String)>,
+    parents: &mut BTreeMap<String, Vec<String>>,
+) {
+    edges.push((parent.to_string(), child.to_string()));
+    parents.entry(child.to_string()).or_default().push(parent.to_string());
+}
+
+/// Get all ancestors of a node, from a map of node parents.
+fn ancestors(node: &str, parents: &BTreeMap<String, Vec<String>>) -> BTreeSet<String> {
+    let mut ancestors = BTreeSet::new();
+    let mut stack = vec![node.to_string()];
+    while let Some(node) = stack.pop() {
+        for parent in parents.get(&node).into_iter().flatten() {
+            if ancestors.insert(parent.clone()) {
+                stack.push(parent.clone());
+            }
+        }
+    }
+    ancestors
+}
@@ -0,0 +1,165 @@
+use crate::utils;
+use crate::utils::fmt;
+use clap::ValueEnum;
+use color_eyre::eyre::{eyre, Report, Result, WrapErr};
+use itertools::Itertools;
+use raqote::{
+    DrawOptions, DrawTarget, Path, PathBuilder, PathOp, Point, Source, StrokeStyle,
+    Winding,
+};
+
+// ----------------------------------------------------------------------------
+// Image Format
+// ----------------------------------------------------------------------------
+
+/// Image formats of plots.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum ImageFormat {
+    /// Raster image (.png).
+    #[default]
+    Png,
+    /// Vector image (.svg), which can be edited and scaled without blurring.
+    Svg,
+}
+
+impl std::fmt::Display for ImageFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let format = match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Svg => "svg",
+        };
+        write!(f, "{format}")
+    }
+}
+
+impl ImageFormat {
+    /// Get the image format of a path, from its extension.
+    pub fn from_path(path: &std::path::Path) -> Result<Self, Report> {
+        let ext = path.extension().unwrap_or_default().to_string_lossy();
+        ImageFormat::from_str(&ext, true)
+            .map_err(|_| eyre!("Unknown plot format {ext:?} of path: {path:?}"))
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Canvas
+// ----------------------------------------------------------------------------
+
+/// A drawing surface, that is rendered to a raster image, and recorded as
+/// vector (SVG) elements.
+pub struct Canvas {
+    pub target: DrawTarget,
+    pub width: f32,
+    pub height: f32,
+    /// SVG elements, in the order they were drawn.
+    pub elements: Vec<String>,
+}
+
+impl Canvas {
+    /// Create a canvas with a white background.
+    pub fn new(width: f32, height: f32) -> Self {
+        let mut target = DrawTarget::new(width as i32, height as i32);
+        let mut background = PathBuilder::new();
+        background.rect(0., 0., width, height);
+        let background = background.finish();
+        target.fill(&background, &super::constants::WHITE, &DrawOptions::new());
+
+        let elements = vec![format!(
+            r#"<rect width="{width}" height="{height}" fill="white"/>"#
+        )];
+        Canvas {
+            target,
+            width,
+            height,
+            elements,
+        }
+    }
+
+    /// Draw a closed polygon, with a fill and an outline.
+    pub fn polygon(
+        &mut self,
+        x_coords: &[f32],
+        y_coords: &[f32],
+        fill: &Source,
+        stroke: &Source,
+        stroke_style: &StrokeStyle,
+    ) {
+        // construct a list of path operations (drawing instructions)
+        let mut ops: Vec<PathOp> = Vec::new();
+        for (i, (x, y)) in x_coords.iter().zip(y_coords.iter()).enumerate() {
+            let point = Point::new(*x, *y);
+            let op = match i {
+                0 => PathOp::MoveTo(point),
+                _ => PathOp::LineTo(point),
+            };
+            ops.push(op);
+        }
+        ops.push(PathOp::Close);
+        let path = Path {
+            ops,
+            winding: Winding::EvenOdd,
+        };
+
+        // fill polygon
+        self.target.fill(&path, fill, &DrawOptions::new());
+        // outline polygon
+        self.target.stroke(&path, stroke, stroke_style, &DrawOptions::new());
+
+        let points = x_coords
+            .iter()
+            .zip(y_coords)
+            .map(|(x, y)| format!("{},{}", fmt::float(*x as f64), fmt::float(*y as f64)))
+            .join(" ");
+        self.elements.push(format!(
+            r#"<polygon points="{points}" {} {} stroke-width="{}"/>"#,
+            svg_paint("fill", fill),
+            svg_paint("stroke", stroke),
+            stroke_style.width,
+        ));
+    }
+
+    /// Write the canvas to a file, in the format of its extension (png or svg).
+    pub fn write(&self, output_path: &std::path::Path) -> Result<(), Report> {
+        // write to a temporary file first, so that an interrupted plot
+        // doesn't leave a partial image behind
+        let tmp_file = utils::create_temp_file(output_path)?;
+        match ImageFormat::from_path(output_path)? {
+            ImageFormat::Png => self
+                .target
+                .write_png(tmp_file.path())
+                .map_err(|e| eyre!("{e:?}"))
+                .wrap_err_with(|| format!("Failed to write plot: {output_path:?}"))?,
+            ImageFormat::Svg => std::fs::write(tmp_file.path(), self.to_svg())
+                .wrap_err_with(|| format!("Failed to write plot: {output_path:?}"))?,
+        }
+        utils::persist_atomic(tmp_file, output_path)?;
+        Ok(())
+    }
+
+    /// Convert the canvas to an SVG document.
+    pub fn to_svg(&self) -> String {
+        let (width, height) = (self.width, self.height);
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\">\n{}\n</svg>\n",
+            self.elements.join("\n")
+        )
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Functions
+// ----------------------------------------------------------------------------
+
+/// Convert a raqote source to an SVG paint attribute (ex. fill, stroke).
+fn svg_paint(attribute: &str, source: &Source) -> String {
+    match source {
+        Source::Solid(color) if color.a > 0 => format!(
+            r#"{attribute}="rgb({},{},{})" {attribute}-opacity="{}""#,
+            color.r,
+            color.g,
+            color.b,
+            fmt::float(color.a as f64 / 255.)
+        ),
+        _ => format!(r#"{attribute}="none""#),
+    }
+}
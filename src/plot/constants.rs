@@ -8,33 +8,6 @@ pub const FONT_BOLD: &[u8] =
 
 pub const ALPHABET: [char; 4] = ['A', 'C', 'G', 'T'];
 
-// D3 Palettes: https://github.com/d3/d3-3.x-api-reference/blob/master/Ordinal-Scales.md#categorical-colors
-pub const PALETTE_DARK: [[u8; 4]; 9] = [
-    [31, 119, 180, 255],  // dark blue
-    [255, 127, 14, 255],  // dark orange
-    [44, 160, 44, 255],   // dark green
-    [214, 39, 39, 255],   // dark red
-    [148, 103, 189, 255], // dark purple
-    [140, 86, 59, 255],   // dark brown
-    [227, 119, 195, 255], // dark pink
-    //[127, 127, 127, 255], // dark grey
-    [188, 189, 34, 255], // dark green alt
-    [23, 189, 207, 255], // dark teal
-];
-
-pub const PALETTE_LIGHT: [[u8; 4]; 9] = [
-    [174, 199, 232, 255], // light blue
-    [255, 187, 120, 255], // light orange
-    [152, 223, 138, 255], // light green
-    [255, 152, 150, 255], // light red
-    [197, 176, 213, 255], // light purple
-    [196, 156, 148, 255], // light brown
-    [247, 182, 210, 255], // light pink
-    //[199, 199, 199, 255], // light grey
-    [219, 219, 141, 255], // light green alt
-    [158, 218, 229, 255], // light teal
-];
-
 pub const WHITE: Source = Source::Solid(SolidSource {
     r: 255,
     g: 255,
@@ -1,16 +1,22 @@
+pub mod canvas;
 pub mod constants;
 pub mod polygon;
 pub mod text;
 
 use crate::cli;
+use crate::plot::canvas::Canvas;
+use crate::recombination::Breakpoint;
+use crate::run::layout::{Layout, Manifest};
 use crate::utils::table::Table;
-use color_eyre::eyre::{eyre, Report, Result};
+use crate::utils::theme::Theme;
+use color_eyre::eyre::{eyre, Report, Result, WrapErr};
 use color_eyre::Help;
 use itertools::Itertools;
 use log::{debug, info, warn};
 use raqote::*;
+use std::collections::BTreeMap;
 use std::fs::create_dir_all;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 /// Plot rebar output
 pub fn plot(args: &cli::plot::Args) -> Result<(), Report> {
@@ -25,14 +31,12 @@ pub fn plot(args: &cli::plot::Args) -> Result<(), Report> {
     // ------------------------------------------------------------------------
     // Check Mandatory Paths
 
-    let linelist = &run_dir.join("linelist.tsv");
-    if !linelist.exists() {
-        return Err(eyre!(
-            "Linelist file {linelist:?} does not exist in --run-dir {run_dir:?}."
-        ));
-    }
-    let barcodes_dir = &run_dir.join("barcodes");
-    if !linelist.exists() {
+    let layout = Layout::new(run_dir);
+    let linelist = &layout
+        .find_linelist()
+        .wrap_err_with(|| format!("Failed to find linelist in --run-dir {run_dir:?}."))?;
+    let barcodes_dir = &layout.barcodes_dir();
+    if !barcodes_dir.exists() {
         return Err(eyre!(
             "Barcodes directory {barcodes_dir:?} does not exist in --run-dir {run_dir:?}."
         ));
@@ -45,7 +49,7 @@ pub fn plot(args: &cli::plot::Args) -> Result<(), Report> {
     }
 
     // create plot directory if it doesn't exist
-    let output_dir = args.output_dir.clone().unwrap_or(run_dir.join("plots"));
+    let output_dir = args.output_dir.clone().unwrap_or(layout.plots_dir());
     if !output_dir.exists() {
         info!("Creating plot directory: {output_dir:?}");
         create_dir_all(&output_dir)?;
@@ -54,7 +58,7 @@ pub fn plot(args: &cli::plot::Args) -> Result<(), Report> {
     // ------------------------------------------------------------------------
     // List of Barcodes Files
 
-    let mut barcodes_files: Vec<std::path::PathBuf> = Vec::new();
+    let mut barcodes_files: Vec<PathBuf> = Vec::new();
 
     // Input File Specified
     let barcodes_file = &args.barcodes_file;
@@ -78,6 +82,44 @@ pub fn plot(args: &cli::plot::Args) -> Result<(), Report> {
         }
     }
 
+    // ------------------------------------------------------------------------
+    // Plot Barcodes Stacked By Parents (Optional)
+
+    if args.by_parents {
+        let linelist_table = Table::read(linelist)?;
+        let unique_key_i = linelist_table.header_position("unique_key")?;
+        let parents_i = linelist_table.header_position("parents")?;
+
+        // group barcodes files by the parents of their unique key, they are
+        // not also plotted individually
+        let mut groups: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+        for barcodes_file in barcodes_files.drain(..) {
+            let unique_key =
+                barcodes_file.file_stem().unwrap_or_default().to_string_lossy();
+            match linelist_table.rows.iter().find(|row| row[unique_key_i] == unique_key) {
+                Some(row) => groups.entry(row[parents_i].clone()).or_default().push(barcodes_file),
+                None => warn!("Barcodes unique key ({unique_key}) was not found in the linelist, skipping: {barcodes_file:?}"),
+            }
+        }
+
+        for (parents, barcodes_files) in groups {
+            let output_path =
+                output_dir.join(format!("{}.{}", parents.replace(',', "_"), args.format));
+            info!(
+                "Plotting {} barcodes file(s) with parents {parents}: {output_path:?}",
+                barcodes_files.len()
+            );
+            create_comparative(
+                &barcodes_files,
+                linelist,
+                args.annotations.as_deref(),
+                &output_path,
+                args.all_coords,
+                &args.theme,
+            )?;
+        }
+    }
+
     // ------------------------------------------------------------------------
     // Plot Each Barcodes
 
@@ -88,13 +130,14 @@ pub fn plot(args: &cli::plot::Args) -> Result<(), Report> {
             .expect("Failed to get file stem of {barcodes_file:?}")
             .to_str()
             .expect("Failed to convert file of stem {barcodes_file:?} to str.");
-        let output_path = output_dir.join(format!("{}.png", output_prefix));
+        let output_path = output_dir.join(format!("{output_prefix}.{}", args.format));
         let result = create(
             &barcodes_file,
             linelist,
             args.annotations.as_deref(),
             &output_path,
             args.all_coords,
+            &args.theme,
         );
         match result {
             Ok(()) => (),
@@ -106,30 +149,40 @@ pub fn plot(args: &cli::plot::Args) -> Result<(), Report> {
         }
     }
 
+    // ------------------------------------------------------------------------
+    // Update Manifest
+
+    // only update the manifest of versioned output directories
+    let manifest_path = layout.manifest();
+    if manifest_path.exists() && args.output_dir.is_none() {
+        info!("Updating manifest: {manifest_path:?}");
+        let mut manifest = Manifest::from_layout(&layout)?;
+        manifest.date_created = Manifest::read(&manifest_path)?.date_created;
+        manifest.write(&manifest_path)?;
+    }
+
     info!("Done.");
     Ok(())
 }
 
-#[allow(unused_variables)]
 pub fn create(
     barcodes_path: &Path,
     linelist_path: &Path,
     annotations_path: Option<&Path>,
     output_path: &Path,
     all_coords: bool,
+    theme: &Theme,
 ) -> Result<(), Report> {
     // ------------------------------------------------------------------------
     // Import Data
     // ------------------------------------------------------------------------
 
-    let mut barcodes = Table::read(barcodes_path)?;
+    let barcodes = Table::read(barcodes_path)?;
 
     let unique_key = barcodes_path.file_stem().unwrap().to_str().unwrap();
 
     // filter the linelist to the current key
-    let mut linelist = Table::read(linelist_path)?;
-    //linelist = linelist.filter("unique_key", unique_key)?;
-    linelist = linelist.filter("unique_key", unique_key)?;
+    let linelist = Table::read(linelist_path)?.filter("unique_key", unique_key)?;
     if linelist.rows.is_empty() {
         return Err(
             eyre!("The barcodes unique key ({unique_key}) was not found in the linelist: {linelist_path:?}")
@@ -144,6 +197,149 @@ pub fn create(
         annotations = Table::read(annotations_path)?
     }
 
+    draw(
+        barcodes,
+        linelist,
+        &annotations,
+        output_path,
+        all_coords,
+        theme,
+    )
+}
+
+/// Plot samples from multiple barcodes files with the same parents, in one figure.
+///
+/// The barcodes are combined (see combine_barcodes), so that samples with
+/// different breakpoints are aligned on the same coordinates, and share the
+/// annotation track. Breakpoints of all samples are drawn, and the regions are
+/// those of the first sample.
+pub fn create_comparative(
+    barcodes_paths: &[PathBuf],
+    linelist_path: &Path,
+    annotations_path: Option<&Path>,
+    output_path: &Path,
+    all_coords: bool,
+    theme: &Theme,
+) -> Result<(), Report> {
+    let unique_keys = barcodes_paths
+        .iter()
+        .filter_map(|path| {
+            path.file_stem().map(|stem| stem.to_string_lossy().to_string())
+        })
+        .collect_vec();
+
+    // filter the linelist to the current keys
+    let linelist = Table::read(linelist_path)?
+        .filter_column("unique_key", |key| unique_keys.iter().any(|k| k == key))?;
+    let parents_i = linelist.header_position("parents")?;
+    let parents =
+        linelist.rows.iter().map(|row| row[parents_i].clone()).unique().collect_vec();
+    if parents.len() != 1 {
+        return Err(eyre!(
+            "Barcodes must have the same parents to be plotted together, found: {}",
+            parents.join(" ")
+        ));
+    }
+    let parents = parents[0].split(',').map(String::from).collect_vec();
+
+    let barcodes = barcodes_paths
+        .iter()
+        .map(|path| Table::read(path))
+        .collect::<Result<Vec<_>, Report>>()?;
+    let barcodes = combine_barcodes(&barcodes, &parents)?;
+
+    // optional import data
+    let mut annotations = Table::new();
+    if let Some(annotations_path) = annotations_path {
+        annotations = Table::read(annotations_path)?
+    }
+
+    draw(
+        barcodes,
+        linelist,
+        &annotations,
+        output_path,
+        all_coords,
+        theme,
+    )
+}
+
+/// Combine barcodes tables of samples with the same parents.
+///
+/// Coordinates are the union of all tables. A sample without a coordinate has
+/// the base of its parents if they agree, otherwise it is missing ('N'). The
+/// origin is '?' if the samples disagree.
+pub fn combine_barcodes(tables: &[Table], parents: &[String]) -> Result<Table, Report> {
+    let mut combined = Table::new();
+    combined.headers =
+        vec!["coord", "origin", "Reference"].into_iter().map(String::from).collect_vec();
+    combined.headers.extend(parents.iter().cloned());
+    let num_fixed = combined.headers.len();
+    for table in tables {
+        let samples = table.headers.iter().skip(num_fixed);
+        combined.headers.extend(
+            samples.filter(|s| !combined.headers.contains(s)).cloned().collect_vec(),
+        );
+    }
+
+    // rows of each table by coordinate
+    let mut rows: BTreeMap<usize, Vec<(&Table, &Vec<String>)>> = BTreeMap::new();
+    for table in tables {
+        let coord_i = table.header_position("coord")?;
+        for row in &table.rows {
+            let coord = row[coord_i].parse::<usize>().wrap_err_with(|| {
+                format!("Failed to parse barcodes coord: {}", row[coord_i])
+            })?;
+            rows.entry(coord).or_default().push((table, row));
+        }
+    }
+
+    for (coord, coord_rows) in rows {
+        let mut row = vec![String::new(); combined.headers.len()];
+        let mut origins = Vec::new();
+        for (table, table_row) in &coord_rows {
+            for (header, value) in table.headers.iter().zip(table_row.iter()) {
+                if header == "origin" {
+                    origins.push(value.clone());
+                } else {
+                    let i = combined.header_position(header)?;
+                    row[i] = value.clone();
+                }
+            }
+        }
+        row[0] = coord.to_string();
+        row[1] = match origins.into_iter().unique().collect_vec() {
+            origins if origins.len() == 1 => origins[0].clone(),
+            _ => "?".to_string(),
+        };
+
+        // samples without this coord have the base of their parents
+        let parent_bases = row[3..num_fixed].iter().unique().collect_vec();
+        let base = match parent_bases[..] {
+            [base] => base.clone(),
+            _ => "N".to_string(),
+        };
+        row.iter_mut()
+            .skip(num_fixed)
+            .filter(|b| b.is_empty())
+            .for_each(|b| *b = base.clone());
+
+        combined.rows.push(row);
+    }
+
+    Ok(combined)
+}
+
+/// Draw a plot of barcodes, for the samples in the linelist.
+#[allow(unused_variables)]
+fn draw(
+    mut barcodes: Table,
+    mut linelist: Table,
+    annotations: &Table,
+    output_path: &Path,
+    all_coords: bool,
+    theme: &Theme,
+) -> Result<(), Report> {
     // check for mandatory columns and header pos
     let genome_length_i = linelist.header_position("genome_length")?;
     let breakpoints_i = linelist.header_position("breakpoints")?;
@@ -163,7 +359,7 @@ pub fn create(
 
     // check if we should include/exclude private mutations
     if !all_coords {
-        barcodes.rows.retain(|row| row[origin_i] != "private");
+        barcodes = barcodes.filter_column("origin", |origin| origin != "private")?;
     }
 
     // get coords
@@ -181,11 +377,11 @@ pub fn create(
     // If multiple parents weren't confidently identified
     if parents.is_empty() {
         return Err(eyre!(
-            "No parents (origin) were confidently identified in barcodes file: {barcodes_path:?}"
+            "No parents (origin) were confidently identified in barcodes for plot: {output_path:?}"
         ));
     }
 
-    if parents.len() > constants::PALETTE_DARK.len() {
+    if parents.len() > theme.palette_dark.len() {
         return Err(eyre!("There are more parents than colors in the palette!")
             .suggestion(format!(
                 "Are you sure you want to plot recombination involving {} parents?",
@@ -343,17 +539,8 @@ pub fn create(
     // Canvas
     // ------------------------------------------------------------------------
 
-    let mut canvas = DrawTarget::new(canvas_width as i32, canvas_height as i32);
-
-    // ------------------------------------------------------------------------
-    // Background
-    // ------------------------------------------------------------------------
-
-    // draw white background
-    let mut background = PathBuilder::new();
-    background.rect(0., 0., canvas_width, canvas_height);
-    let background = background.finish();
-    canvas.fill(&background, &constants::WHITE, &DrawOptions::new());
+    // the canvas has a white background
+    let mut canvas = Canvas::new(canvas_width, canvas_height);
 
     // ------------------------------------------------------------------------
     // Regions
@@ -446,7 +633,7 @@ pub fn create(
 
         // color
         let parent_i = parents.iter().position(|p| *p == parent).unwrap();
-        let [r, g, b, a] = constants::PALETTE_DARK[parent_i];
+        let [r, g, b, a] = theme.palette_dark[parent_i].0;
         let color = Source::Solid(SolidSource { r, g, b, a });
 
         // draw region box
@@ -506,29 +693,28 @@ pub fn create(
 
     // special pallete for annotations, that interleaves dark and light
     // skip colors reserverd for parents, x 2 for interleaved palette
-    // todo!() raise error if no colors left, parents consumed it all
-    let annot_palette = constants::PALETTE_DARK
+    // if parents consumed all the colors, reuse the whole palette
+    let num_reserved = if parents.len() < theme.palette_dark.len() {
+        parents.len()
+    } else {
+        0
+    };
+    let annot_palette = theme
+        .palette_dark
         .iter()
-        .zip(constants::PALETTE_LIGHT.iter())
-        .skip(parents.len())
-        .flat_map(|(dark, light)| vec![*dark, *light])
+        .zip(theme.palette_light.iter())
+        .skip(num_reserved)
+        .flat_map(|(dark, light)| vec![dark.0, light.0])
         .collect_vec();
 
     for (i, row) in annotations.rows.iter().enumerate() {
         let abbrev_i = annotations.header_position("abbreviation")?;
-        let start_i = annotations.header_position("start")?;
-        let end_i = annotations.header_position("end")?;
-
         let abbreviation = &annotations.rows[i][abbrev_i];
-        let start = annotations.rows[i][start_i].parse::<usize>()?;
-        let end = annotations.rows[i][end_i].parse::<usize>()?;
+        let start = annotations.get_as::<usize>(i, "start")?;
+        let end = annotations.get_as::<usize>(i, "end")?;
 
         // use colors from the color palette that are not reserved for pops
-        let mut color_i = i;
-        if color_i >= annot_palette.len() {
-            color_i -= annot_palette.len();
-        }
-        let [r, g, b, a] = annot_palette[color_i];
+        let [r, g, b, a] = annot_palette[i % annot_palette.len()];
         let color = Source::Solid(SolidSource { r, g, b, a });
         // draw the region box, leave X_INC gap at top for annotation labels
         // convert genomic coordinates to pixel coordinates
@@ -702,7 +888,10 @@ pub fn create(
     let breakpoints = linelist
         .rows
         .iter()
-        .flat_map(|row| row[breakpoints_i].split(','))
+        .map(|row| Breakpoint::parse_list(&row[breakpoints_i]))
+        .collect::<Result<Vec<_>, Report>>()?
+        .into_iter()
+        .flatten()
         .unique()
         .collect_vec();
 
@@ -717,10 +906,8 @@ pub fn create(
 
     for (i, breakpoint) in breakpoints.iter().enumerate() {
         // get the region start/end
-        let breakpoint_parts = breakpoint.split('-').collect_vec();
-        // check if breakpoints is single coordinate
-        let mut prev_region_end = breakpoint_parts[0].parse::<f32>()?;
-        let mut next_region_start = breakpoint_parts[1].parse::<f32>()?;
+        let mut prev_region_end = breakpoint.start as f32;
+        let mut next_region_start = breakpoint.end as f32;
 
         if prev_region_end == next_region_start {
             prev_region_end -= 1.0;
@@ -913,7 +1100,7 @@ pub fn create(
             }
             // is this a parent?
             else if parents.contains(population) {
-                let [r, g, b, a] = get_base_rgba(&pop_base, &ref_base, pop_i - 1);
+                let [r, g, b, a] = get_base_rgba(&pop_base, &ref_base, pop_i - 1, theme);
                 pop_color = Source::Solid(SolidSource { r, g, b, a });
             }
             // otherwise, it's a sequence
@@ -930,7 +1117,8 @@ pub fn create(
                 // color by origin if exact
                 if origins.len() == 1 {
                     let parent_i = origins[0];
-                    let [r, g, b, a] = get_base_rgba(&pop_base, &ref_base, parent_i);
+                    let [r, g, b, a] =
+                        get_base_rgba(&pop_base, &ref_base, parent_i, theme);
                     pop_color = Source::Solid(SolidSource { r, g, b, a });
                 }
                 // otherwise, just make it white to show ambiguous origins
@@ -1064,7 +1252,7 @@ pub fn create(
         let box_y = y + (constants::X_INC / 2.) - (sub_box_w / 2.);
         let draw_x = vec![box_x, box_x, box_x + sub_box_w, box_x + sub_box_w];
         let draw_y = vec![box_y, box_y + sub_box_w, box_y + sub_box_w, box_y];
-        let [r, g, b, a] = constants::PALETTE_DARK[i];
+        let [r, g, b, a] = theme.palette_dark[i].0;
         let color = Source::Solid(SolidSource { r, g, b, a });
         polygon::draw_raqote(
             &mut canvas,
@@ -1089,7 +1277,7 @@ pub fn create(
         let box_y = y + (constants::X_INC / 2.) - (sub_box_w / 2.);
         let draw_x = vec![box_x, box_x, box_x + sub_box_w, box_x + sub_box_w];
         let draw_y = vec![box_y, box_y + sub_box_w, box_y + sub_box_w, box_y];
-        let [r, g, b, a] = constants::PALETTE_LIGHT[i];
+        let [r, g, b, a] = theme.palette_light[i].0;
         let color = Source::Solid(SolidSource { r, g, b, a });
         polygon::draw_raqote(
             &mut canvas,
@@ -1141,13 +1329,19 @@ pub fn create(
     // Export
     // ------------------------------------------------------------------------
 
-    canvas.write_png(output_path).unwrap();
+    // the format is chosen by the extension of the output path
+    canvas.write(output_path)?;
 
     Ok(())
 }
 
 /// Get the background color (RGBA) of a nucleotide base
-pub fn get_base_rgba(base: &String, ref_base: &String, pal_i: usize) -> [u8; 4] {
+pub fn get_base_rgba(
+    base: &String,
+    ref_base: &String,
+    pal_i: usize,
+    theme: &Theme,
+) -> [u8; 4] {
     // default WHITE
     let mut rgba = [255, 255, 255, 255];
 
@@ -1156,11 +1350,11 @@ pub fn get_base_rgba(base: &String, ref_base: &String, pal_i: usize) -> [u8; 4]
 
     // same as reference, light palette
     if base == ref_base {
-        rgba = constants::PALETTE_LIGHT[pal_i];
+        rgba = theme.palette_light[pal_i].0;
     }
     // mutation, dark palette
     else if constants::ALPHABET.contains(&base_char) {
-        rgba = constants::PALETTE_DARK[pal_i];
+        rgba = theme.palette_dark[pal_i].0;
     }
 
     rgba
@@ -1,6 +1,7 @@
 // This module was inspired by @J-Cake's custom implementation of text rendering
 // Source: https://gist.github.com/J-Cake/ddccf99d3f7d6fc947fc60204aa41e09#file-text-rs
 
+use crate::plot::canvas::Canvas;
 use crate::plot::constants;
 use color_eyre::eyre::{eyre, Report, Result, WrapErr};
 use image::{imageops, ImageBuffer, Rgba};
@@ -166,7 +167,7 @@ pub fn to_raqote_data(
 
 //#[derive(Debug)]
 pub struct DrawRaqoteArgs<'canvas> {
-    pub canvas: &'canvas mut raqote::DrawTarget,
+    pub canvas: &'canvas mut Canvas,
     pub text: String,
     pub font_style: FontStyle,
     pub font_size: f32,
@@ -179,7 +180,7 @@ pub struct DrawRaqoteArgs<'canvas> {
 }
 
 impl<'canvas> DrawRaqoteArgs<'canvas> {
-    pub fn from_canvas(canvas: &'canvas mut raqote::DrawTarget) -> Self {
+    pub fn from_canvas(canvas: &'canvas mut Canvas) -> Self {
         DrawRaqoteArgs {
             canvas,
             text: String::new(),
@@ -233,7 +234,39 @@ pub fn draw_raqote(
         height: image.height() as i32,
         data: &data,
     };
-    args.canvas.draw_image_at(point.x, point.y, &draw_image, &raqote::DrawOptions::new());
+    args.canvas.target.draw_image_at(
+        point.x,
+        point.y,
+        &draw_image,
+        &raqote::DrawOptions::new(),
+    );
+
+    // svg text is centered in the box of the rendered text
+    let center_x = x + (image.width() as f32 / 2.);
+    let center_y = y + (image.height() as f32 / 2.);
+    let [r, g, b, a] = args.color.0;
+    let weight = match args.font_style {
+        FontStyle::Regular => "normal",
+        FontStyle::Bold => "bold",
+    };
+    let transform = match args.rotate {
+        0 => String::new(),
+        rotate => format!(r#" transform="rotate({rotate} {center_x} {center_y})""#),
+    };
+    args.canvas.elements.push(format!(
+        r#"<text x="{center_x}" y="{center_y}" font-family="DejaVu Sans, sans-serif" font-size="{}" font-weight="{weight}" text-anchor="middle" dominant-baseline="central" fill="rgb({r},{g},{b})" fill-opacity="{}"{transform}>{}</text>"#,
+        args.font_size,
+        a as f32 / 255.,
+        escape(&args.text),
+    ));
 
     Ok(image)
 }
+
+/// Escape text for XML (ex. svg).
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,36 @@
+// ----------------------------------------------------------------------------
+// Prelude
+// ----------------------------------------------------------------------------
+
+// Commonly used types and functions, for using rebar as a library:
+//
+//     use rebar::prelude::*;
+//
+// These paths are kept stable, even if the modules they are defined in move.
+
+// ----------------------------------------------------------------------------
+// Dataset
+
+pub use crate::dataset::barcodes::BarcodeClasses;
+pub use crate::dataset::load::dataset as load_dataset;
+pub use crate::dataset::{Dataset, SearchResult};
+pub use crate::phylogeny::{BranchLength, Phylogeny};
+pub use crate::sequence::scoring::ScoringModel;
+pub use crate::sequence::{Sequence, Substitution};
+
+// ----------------------------------------------------------------------------
+// Run
+
+pub use crate::cli::run::{Args as RunArgs, Input as RunInput};
+pub use crate::export::sink::{OutputFormat, OutputSink};
+pub use crate::run::input::{Record, Source};
+pub use crate::run::{run, run_with_sinks};
+
+// ----------------------------------------------------------------------------
+// Results
+
+pub use crate::recombination::search::Explanation;
+pub use crate::recombination::{Breakpoint, Recombination, Region};
+pub use crate::run::failures::Failure;
+pub use crate::run::stats::Summary as RunSummary;
+pub use crate::utils::table::Table;
@@ -0,0 +1,237 @@
+use crate::dataset::SearchResult;
+use crate::recombination::Recombination;
+use crate::run::input::Source;
+use crate::sequence::Sequence;
+use crate::utils;
+use crate::utils::fmt;
+use crate::utils::table::{SortType, Table};
+use bio::alignment::pairwise::banded::Aligner;
+use bio::alignment::sparse::{hash_kmers, HashMapFx};
+use bio::alignment::AlignmentOperation;
+use bio::alphabets::dna::revcomp;
+use bio::io::{fasta, fastq};
+use color_eyre::eyre::{Report, Result, WrapErr};
+use itertools::Itertools;
+use log::{debug, info};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Length of exact k-mer matches that seed the read alignment.
+pub const KMER_LENGTH: usize = 11;
+/// Width of the band around the seeds.
+pub const BAND_WIDTH: usize = 25;
+/// Minimum alignment score per base of the read, for a read to be mapped.
+pub const MIN_SCORE_PER_BASE: f64 = 0.5;
+/// Separator between the sample and read name in sequence ids.
+pub const READ_SEPARATOR: char = '/';
+
+// ----------------------------------------------------------------------------
+// Functions
+// ----------------------------------------------------------------------------
+
+/// Read unaligned reads (fasta or fastq) as (id, sequence) pairs.
+pub fn read_reads(path: &Path) -> Result<Vec<(String, Vec<u8>)>, Report> {
+    let ext = utils::path_to_ext(path)?;
+    let reads = if ["fastq", "fq"].contains(&ext.as_str()) {
+        fastq::Reader::new(utils::open_bufread(path)?)
+            .records()
+            .map(|r| r.map(|r| (r.id().to_string(), r.seq().to_ascii_uppercase())))
+            .collect::<Result<Vec<_>, _>>()
+            .wrap_err_with(|| format!("Failed to parse reads: {path:?}"))?
+    } else {
+        utils::read_fasta(path)?
+            .records()
+            .map(|r| r.map(|r| (r.id().to_string(), r.seq().to_ascii_uppercase())))
+            .collect::<Result<Vec<_>, _>>()
+            .wrap_err_with(|| format!("Failed to parse reads: {path:?}"))?
+    };
+    Ok(reads)
+}
+
+/// Map a read to the reference, in whichever orientation aligns best.
+///
+/// Returns the read in reference coordinates, where bases not covered by
+/// the read are missing ('N'), deletions are '-', and insertions are removed.
+/// Returns None if the read does not map to the reference.
+pub fn map_read<F: bio::alignment::pairwise::MatchFunc>(
+    read: &[u8],
+    reference: &[u8],
+    reference_kmers: &HashMapFx<&[u8], Vec<u32>>,
+    aligner: &mut Aligner<F>,
+) -> Option<Vec<u8>> {
+    let read_revcomp = revcomp(read);
+    let forward = aligner.semiglobal_with_prehash(read, reference, reference_kmers);
+    let reverse =
+        aligner.semiglobal_with_prehash(&read_revcomp, reference, reference_kmers);
+    let (read, alignment) = if reverse.score > forward.score {
+        (read_revcomp.as_slice(), reverse)
+    } else {
+        (read, forward)
+    };
+
+    if (alignment.score as f64) < MIN_SCORE_PER_BASE * read.len() as f64 {
+        return None;
+    }
+
+    let mut mapped = vec![b'N'; reference.len()];
+    let (mut x, mut y) = (alignment.xstart, alignment.ystart);
+    for op in &alignment.operations {
+        match op {
+            AlignmentOperation::Match | AlignmentOperation::Subst => {
+                mapped[y] = read[x];
+                x += 1;
+                y += 1;
+            }
+            AlignmentOperation::Del => {
+                mapped[y] = b'-';
+                y += 1;
+            }
+            AlignmentOperation::Ins => x += 1,
+            AlignmentOperation::Xclip(n) => x += n,
+            AlignmentOperation::Yclip(_) => (),
+        }
+    }
+
+    Some(mapped)
+}
+
+/// Map unaligned reads of a sample to the reference, and parse as sequences.
+///
+/// Sequence ids are the sample and read name (ex. 'sample/read1').
+pub fn parse_reads(
+    path: &Path,
+    reference: &Sequence,
+    mask: &Vec<usize>,
+) -> Result<Vec<Sequence>, Report> {
+    let sample = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.split('.').next())
+        .unwrap_or_default()
+        .to_string();
+    let reads = read_reads(path)?;
+
+    let reference_seq = reference.seq.iter().map(|b| *b as u8).collect_vec();
+    let reference_kmers = hash_kmers(&reference_seq, KMER_LENGTH);
+    let score = |a: u8, b: u8| match (a, b) {
+        (b'N', _) | (_, b'N') => 0i32,
+        _ if a == b => 1i32,
+        _ => -1i32,
+    };
+
+    let mapped = reads
+        .par_iter()
+        .map_init(
+            || Aligner::new(-5, -1, score, KMER_LENGTH, BAND_WIDTH),
+            |aligner, (id, read)| {
+                let mapped = map_read(read, &reference_seq, &reference_kmers, aligner);
+                if mapped.is_none() {
+                    debug!("Read {id} of sample {sample} did not map to the reference.");
+                }
+                mapped.map(|seq| (id, seq))
+            },
+        )
+        .collect::<Vec<_>>();
+
+    let num_mapped = mapped.iter().flatten().count();
+    info!(
+        "Sample {sample}: {num_mapped}/{} reads mapped.",
+        reads.len()
+    );
+
+    mapped
+        .into_iter()
+        .flatten()
+        .map(|(id, seq)| {
+            let id = format!("{sample}{READ_SEPARATOR}{id}");
+            let record = fasta::Record::with_attrs(&id, None, &seq);
+            Sequence::from_record(record, Some(reference), mask)
+        })
+        .collect()
+}
+
+/// Summarize the classification of reads by sample.
+///
+/// Only sequences read from --reads are summarized, by the sample in their id.
+/// Reports the number of reads classified, the number and fraction that are
+/// recombinant, and the number of reads per population and parents. Many
+/// reads from different parents spanning a breakpoint, rather than
+/// recombinant reads, suggest a co-infection.
+pub fn summary(results: &[(SearchResult, Recombination)]) -> Result<Table, Report> {
+    let mut table = Table::new();
+    table.headers = vec![
+        "sample",
+        "reads",
+        "recombinant_reads",
+        "recombinant_fraction",
+        "populations",
+        "parents",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect_vec();
+
+    // group reads by sample, other inputs (ex. alignment records named
+    // 'hCoV-19/USA/...') are not samples, even if their id has a separator
+    let mut samples: BTreeMap<&str, Vec<&(SearchResult, Recombination)>> =
+        BTreeMap::new();
+    for result in results {
+        let sequence = result.1.sequence;
+        if sequence.source != Some(Source::Reads) {
+            continue;
+        }
+        // sample names are file names, so the first separator ends the sample
+        let Some((sample, _read)) = sequence.id.split_once(READ_SEPARATOR) else {
+            continue;
+        };
+        samples.entry(sample).or_default().push(result);
+    }
+
+    let format_counts = |counts: Vec<String>| {
+        counts
+            .into_iter()
+            .counts()
+            .into_iter()
+            .sorted_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)))
+            .map(|(name, count)| format!("{name}:{count}"))
+            .join(";")
+    };
+
+    for (sample, results) in samples {
+        let reads = results
+            .iter()
+            .filter(|(best_match, _)| !best_match.consensus_population.is_empty())
+            .collect_vec();
+        let recombinant_reads =
+            reads.iter().filter(|(_, r)| r.recombinant.is_some()).count();
+        let recombinant_fraction = if reads.is_empty() {
+            0.0
+        } else {
+            recombinant_reads as f64 / reads.len() as f64
+        };
+        let populations = format_counts(
+            reads.iter().map(|(b, _)| b.consensus_population.clone()).collect(),
+        );
+        let parents = format_counts(
+            reads
+                .iter()
+                .filter(|(_, r)| !r.parents.is_empty())
+                .map(|(_, r)| r.parents.join(","))
+                .collect(),
+        );
+
+        table.rows.push(vec![
+            sample.to_string(),
+            reads.len().to_string(),
+            recombinant_reads.to_string(),
+            fmt::float(recombinant_fraction),
+            populations,
+            parents,
+        ]);
+    }
+    // samples are often numbered (ex. sample2, sample10)
+    table.sort_rows(&[("sample", SortType::Natural)])?;
+
+    Ok(table)
+}
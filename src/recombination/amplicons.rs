@@ -0,0 +1,270 @@
+use crate::recombination::{missing_fraction, Breakpoint};
+use crate::sequence::Sequence;
+use crate::utils;
+use color_eyre::eyre::{eyre, Report, Result, WrapErr};
+use color_eyre::Help;
+use itertools::Itertools;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+// ----------------------------------------------------------------------------
+// Primer
+// ----------------------------------------------------------------------------
+
+/// A primer of a primer scheme, by genomic coordinates (1-based, inclusive).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Primer {
+    pub name: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+// ----------------------------------------------------------------------------
+// Amplicon
+// ----------------------------------------------------------------------------
+
+/// An amplicon of a primer scheme, by genomic coordinates (1-based, inclusive).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Amplicon {
+    pub name: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl std::fmt::Display for Amplicon {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}:{}-{}", self.name, self.start, self.end)
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Dropout
+// ----------------------------------------------------------------------------
+
+/// An amplicon that is missing from a sequence (ex. amplicon dropout).
+///
+/// The dropout is the region of the amplicon that isn't covered by its
+/// neighbors, which is entirely missing. The window is the span of the
+/// amplicon and its neighbors, where parental regions might be truncated.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dropout {
+    pub amplicon: String,
+    pub start: usize,
+    pub end: usize,
+    pub window_start: usize,
+    pub window_end: usize,
+}
+
+impl Dropout {
+    /// Number of missing bases of the dropout.
+    pub fn len(&self) -> usize {
+        self.end - self.start + 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.end < self.start
+    }
+
+    /// Return true if a region (start and end coordinates) overlaps the window
+    /// of the dropout.
+    pub fn affects(&self, start: usize, end: usize) -> bool {
+        start <= self.window_end && end >= self.window_start
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Primer Scheme
+// ----------------------------------------------------------------------------
+
+/// The primers and amplicons of a primer scheme (ex. ARTIC), sorted by
+/// coordinates.
+#[derive(Clone, Debug, Default)]
+pub struct PrimerScheme {
+    pub primers: Vec<Primer>,
+    pub amplicons: Vec<Amplicon>,
+}
+
+impl PrimerScheme {
+    pub fn new() -> Self {
+        PrimerScheme {
+            primers: Vec::new(),
+            amplicons: Vec::new(),
+        }
+    }
+
+    /// Read a primer scheme from a BED file.
+    ///
+    /// Records are (at least) chrom, start (0-based), end (exclusive), and name.
+    /// Primers named `<scheme>_<amplicon>_LEFT` or `_RIGHT` (with an optional
+    /// suffix, ex. `_LEFT_alt1`) are grouped into amplicons, from the start of
+    /// their left primers to the end of their right primers. Otherwise, each
+    /// record is an amplicon.
+    pub fn read(path: &Path) -> Result<Self, Report> {
+        let text = utils::read_to_string(path)?;
+        PrimerScheme::from_bed(&text)
+            .wrap_err_with(|| format!("Failed to parse primer scheme: {path:?}"))
+    }
+
+    /// Parse a primer scheme from the text of a BED file, see read.
+    pub fn from_bed(text: &str) -> Result<Self, Report> {
+        let mut primers = Vec::new();
+        // amplicon coordinates by name, extended by each of its primers
+        let mut amplicons: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim_end_matches('\r');
+            if line.trim().is_empty()
+                || line.starts_with('#')
+                || line.starts_with("track")
+            {
+                continue;
+            }
+            let fields = line.split('\t').collect_vec();
+            let [_chrom, start, end, name, ..] = fields[..] else {
+                return Err(eyre!("Line {} has less than 4 columns: {line}", i + 1))
+                    .suggestion("BED columns are chrom, start, end, and name.");
+            };
+            let parse = |value: &str| {
+                value.parse::<usize>().wrap_err_with(|| {
+                    format!("Line {} has an invalid coordinate: {value}", i + 1)
+                })
+            };
+            let (start, end) = (parse(start)? + 1, parse(end)?);
+            if end < start {
+                return Err(eyre!("Line {} ends before it starts: {line}", i + 1));
+            }
+            let amplicon = amplicon_name(name);
+            let coords = amplicons.entry(amplicon).or_insert((start, end));
+            *coords = (coords.0.min(start), coords.1.max(end));
+            primers.push(Primer {
+                name: name.to_string(),
+                start,
+                end,
+            });
+        }
+
+        let primers = primers
+            .into_iter()
+            .sorted_by_key(|primer| (primer.start, primer.end))
+            .collect_vec();
+
+        let amplicons = amplicons
+            .into_iter()
+            .map(|(name, (start, end))| Amplicon { name, start, end })
+            .sorted_by_key(|amplicon| (amplicon.start, amplicon.end))
+            .collect_vec();
+        Ok(PrimerScheme { primers, amplicons })
+    }
+
+    /// Mask the primer binding sites of a sequence as missing data, as they
+    /// often carry artifacts of the primers rather than the genome.
+    ///
+    /// Returns the number of substitutions that were masked.
+    pub fn mask_primers(&self, sequence: &mut Sequence) -> usize {
+        let coords = self
+            .primers
+            .iter()
+            .flat_map(|primer| primer.start..=primer.end)
+            .filter(|coord| *coord <= sequence.genome_length)
+            .collect::<BTreeSet<_>>();
+        let num_substitutions = sequence.substitutions.len();
+        sequence.substitutions.retain(|sub| !coords.contains(&sub.coord));
+        sequence.deletions.retain(|del| !coords.contains(&del.coord));
+        for coord in &coords {
+            if let Some(base) = sequence.seq.get_mut(coord - 1) {
+                *base = 'N';
+            }
+        }
+        sequence.missing = sequence
+            .missing
+            .iter()
+            .chain(coords.iter())
+            .copied()
+            .unique()
+            .sorted()
+            .collect_vec();
+        num_substitutions - sequence.substitutions.len()
+    }
+
+    /// Find the adjacent amplicons whose boundary contains a breakpoint.
+    ///
+    /// The boundary is where the amplicons overlap (or the gap between
+    /// them), which is where the primers bind. Breakpoints that fall within a
+    /// boundary might be artifacts of amplicons from different templates
+    /// (ex. co-infection or contamination) rather than recombination.
+    pub fn boundary(&self, breakpoint: &Breakpoint) -> Option<(&Amplicon, &Amplicon)> {
+        self.amplicons.iter().tuple_windows().find(|(prev, next)| {
+            let start = prev.end.min(next.start);
+            let end = prev.end.max(next.start);
+            breakpoint.start >= start && breakpoint.end <= end
+        })
+    }
+
+    /// Identify the amplicons that dropped out of a sequence.
+    ///
+    /// An amplicon dropped out if all bases that are not covered by its
+    /// neighbors are missing.
+    pub fn dropouts(&self, sequence: &Sequence) -> Vec<Dropout> {
+        let mut dropouts = Vec::new();
+        for (i, amplicon) in self.amplicons.iter().enumerate() {
+            let prev = i.checked_sub(1).and_then(|i| self.amplicons.get(i));
+            let next = self.amplicons.get(i + 1);
+            let start = prev.map(|p| p.end + 1).unwrap_or(1).max(amplicon.start);
+            let end = next
+                .map(|n| n.start.saturating_sub(1))
+                .unwrap_or(usize::MAX)
+                .min(amplicon.end)
+                .min(sequence.genome_length);
+            if end < start || missing_fraction(sequence, start, end) < 1.0 {
+                continue;
+            }
+            dropouts.push(Dropout {
+                amplicon: amplicon.name.clone(),
+                start,
+                end,
+                window_start: prev.unwrap_or(amplicon).start,
+                window_end: next.unwrap_or(amplicon).end,
+            });
+        }
+        dropouts
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Functions
+// ----------------------------------------------------------------------------
+
+/// Get the amplicon name of a primer (ex. nCoV-2019_1_LEFT_alt1 is
+/// nCoV-2019_1), or the name itself if it's not a primer.
+fn amplicon_name(name: &str) -> String {
+    let parts = name.split('_').collect_vec();
+    match parts.iter().position(|part| *part == "LEFT" || *part == "RIGHT") {
+        Some(i) if i > 0 => parts[..i].join("_"),
+        _ => name.to_string(),
+    }
+}
+
+/// Adjust the min_consecutive and min_length of a region (start and end
+/// coordinates) for amplicon dropouts.
+///
+/// The missing bases of dropouts whose window overlaps the region count
+/// towards its length, and min_consecutive is scaled by the fraction of the
+/// region and dropouts that was observed (at least 1). Returns the adjusted
+/// (min_consecutive, min_length), and the dropouts that affected the region.
+pub fn adjust_thresholds(
+    start: usize,
+    end: usize,
+    dropouts: &[Dropout],
+    min_consecutive: usize,
+    min_length: usize,
+) -> (usize, usize, Vec<&Dropout>) {
+    let affected = dropouts.iter().filter(|d| d.affects(start, end)).collect_vec();
+    if affected.is_empty() {
+        return (min_consecutive, min_length, affected);
+    }
+    let missing: usize = affected.iter().map(|d| d.len()).sum();
+    let observed = end - start + 1;
+    let scaled = (min_consecutive * observed).div_ceil(observed + missing);
+    let min_consecutive = scaled.max(min_consecutive.min(1));
+    let min_length = min_length.saturating_sub(missing);
+    (min_consecutive, min_length, affected)
+}
@@ -0,0 +1,151 @@
+use crate::recombination::{Breakpoint, Recombination};
+use crate::utils::table::{SortType, Table};
+use color_eyre::eyre::{Report, Result};
+use itertools::Itertools;
+use std::collections::BTreeMap;
+
+/// Minimum number of sequences with the same parents to check consistency.
+pub const MIN_CLUSTER_SIZE: usize = 2;
+
+// ----------------------------------------------------------------------------
+// Breakpoint Cluster
+
+/// Recombinant sequences with the same parents, and their representative
+/// breakpoints.
+#[derive(Clone, Debug)]
+pub struct Cluster {
+    pub parents: Vec<String>,
+    pub sequences: Vec<String>,
+    /// Breakpoint intervals shared by the most sequences in the cluster.
+    pub breakpoints: Vec<Breakpoint>,
+    /// Sequences with breakpoints inconsistent with the representatives.
+    pub inconsistent: BTreeMap<String, Vec<Breakpoint>>,
+}
+
+// ----------------------------------------------------------------------------
+// Functions
+// ----------------------------------------------------------------------------
+
+/// Identify the representative of a set of breakpoint intervals.
+///
+/// The representative is the intersection of the largest set of intervals that
+/// overlap. Returns the representative, and the indices of overlapping intervals.
+pub fn representative(intervals: &[&Breakpoint]) -> (Breakpoint, Vec<usize>) {
+    // the coordinate covered by the most intervals, ties to the left
+    let coord = intervals
+        .iter()
+        .map(|b| b.start)
+        .sorted()
+        .dedup()
+        .max_by(|a, b| {
+            let covered = |c: &usize| intervals.iter().filter(|b| b.contains(*c)).count();
+            covered(a).cmp(&covered(b)).then(b.cmp(a))
+        })
+        .unwrap_or_default();
+
+    let members = intervals.iter().positions(|b| b.contains(coord)).collect_vec();
+    // members all contain the coordinate, so they always intersect
+    let breakpoint = members
+        .iter()
+        .map(|i| *intervals[*i])
+        .reduce(|a, b| a.intersection(&b).unwrap_or(a))
+        .unwrap_or(Breakpoint::new(coord, coord));
+    (breakpoint, members)
+}
+
+/// Group recombinant sequences by their parents, and flag inconsistent breakpoints.
+///
+/// Sequences with the same parents are expected to share breakpoints. A sequence
+/// is inconsistent if it has a different number of breakpoints than most of the
+/// cluster, or a breakpoint that does not overlap the representative. These are
+/// likely borderline calls, that should be reviewed before designation.
+pub fn clusters(recombinations: &[&Recombination]) -> Vec<Cluster> {
+    let mut groups: BTreeMap<Vec<String>, Vec<&Recombination>> = BTreeMap::new();
+    recombinations.iter().filter(|r| r.recombinant.is_some()).for_each(|r| {
+        groups.entry(r.parents.iter().cloned().sorted().collect()).or_default().push(r)
+    });
+
+    let mut clusters = Vec::new();
+    for (parents, group) in groups {
+        if group.len() < MIN_CLUSTER_SIZE {
+            continue;
+        }
+
+        // most sequences are expected to have the same number of breakpoints
+        let num_breakpoints = group
+            .iter()
+            .map(|r| r.breakpoints.len())
+            .counts()
+            .into_iter()
+            .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)))
+            .map(|(n, _)| n)
+            .unwrap_or_default();
+
+        let sequences = group.iter().map(|r| r.sequence.id.clone()).collect_vec();
+        let mut inconsistent = BTreeMap::new();
+        let (expected, unexpected): (Vec<&Recombination>, Vec<&Recombination>) =
+            group.into_iter().partition(|r| r.breakpoints.len() == num_breakpoints);
+        for r in unexpected {
+            inconsistent.insert(r.sequence.id.clone(), r.breakpoints.clone());
+        }
+
+        let mut breakpoints = Vec::new();
+        for i in 0..num_breakpoints {
+            let intervals = expected.iter().map(|r| &r.breakpoints[i]).collect_vec();
+            let (breakpoint, members) = representative(&intervals);
+            for (j, r) in expected.iter().enumerate() {
+                if !members.contains(&j) {
+                    inconsistent.insert(r.sequence.id.clone(), r.breakpoints.clone());
+                }
+            }
+            breakpoints.push(breakpoint);
+        }
+
+        clusters.push(Cluster {
+            parents,
+            sequences,
+            breakpoints,
+            inconsistent,
+        });
+    }
+
+    clusters
+}
+
+/// Summarize breakpoint clusters as a table.
+pub fn table(clusters: &[Cluster]) -> Result<Table, Report> {
+    let mut table = Table::new();
+    table.headers = vec![
+        "parents",
+        "sequences",
+        "breakpoints",
+        "consistent",
+        "inconsistent",
+        "inconsistent_breakpoints",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect_vec();
+
+    for cluster in clusters {
+        let inconsistent_breakpoints = cluster
+            .inconsistent
+            .iter()
+            .map(|(id, breakpoints)| format!("{id}:{}", breakpoints.iter().join(",")))
+            .join(";");
+        table.rows.push(vec![
+            cluster.parents.join(","),
+            cluster.sequences.len().to_string(),
+            cluster.breakpoints.iter().join(","),
+            (cluster.sequences.len() - cluster.inconsistent.len()).to_string(),
+            cluster.inconsistent.len().to_string(),
+            inconsistent_breakpoints,
+        ]);
+    }
+    table.sort_rows(&[
+        ("breakpoints", SortType::Natural),
+        ("parents", SortType::Natural),
+    ])?;
+
+    Ok(table)
+}
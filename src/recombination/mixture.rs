@@ -0,0 +1,236 @@
+use crate::dataset::{Dataset, SearchResult};
+use crate::recombination::{Recombination, UNKNOWN_PARENT};
+use crate::sequence::Sequence;
+use color_eyre::eyre::{Report, Result};
+use itertools::Itertools;
+use std::fmt;
+
+/// Fraction of discriminating sites that are ambiguous, to suggest a co-infection.
+pub const MAX_AMBIGUOUS_FRACTION: f64 = 0.2;
+/// Minimum number of ambiguous sites to analyze a non-recombinant sequence.
+pub const MIN_AMBIGUOUS_SITES: usize = 3;
+/// Number of parental allele switches tolerated beyond the breakpoints.
+pub const MAX_EXTRA_SWITCHES: usize = 2;
+
+// ----------------------------------------------------------------------------
+// Mixture Status
+
+/// Whether mixed parental alleles suggest a recombinant or co-infection.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Status {
+    LikelyRecombinant,
+    LikelyCoinfection,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let status = match self {
+            Status::LikelyRecombinant => "likely_recombinant",
+            Status::LikelyCoinfection => "likely_coinfection",
+        };
+        write!(f, "{status}")
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Mixture
+
+/// Analysis of the parental alleles of a sequence at discriminating sites.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mixture {
+    pub status: Status,
+    pub parents: Vec<String>,
+    /// Sites where the parents have different bases, and the sequence has data.
+    pub sites: usize,
+    /// Sites where the sequence has an ambiguous base, that includes both parents.
+    pub ambiguous: usize,
+    /// Number of times the parental origin switches between consecutive sites.
+    pub switches: usize,
+}
+
+impl fmt::Display for Mixture {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let parents = self.parents.join(" and ");
+        let rationale = if self.status == Status::LikelyCoinfection
+            && self.ambiguous as f64 > MAX_AMBIGUOUS_FRACTION * self.sites as f64
+        {
+            format!("sites that discriminate {parents} are ambiguous")
+        } else if self.status == Status::LikelyCoinfection {
+            format!("alleles of {parents} are interleaved")
+        } else {
+            format!("alleles of {parents} are clustered")
+        };
+        let rationale = format!(
+            "{rationale} (sites: {}, ambiguous: {}, switches: {})",
+            self.sites, self.ambiguous, self.switches
+        );
+        write!(f, "{}: {rationale}", self.status)
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Functions
+// ----------------------------------------------------------------------------
+
+/// Get the bases of an IUPAC nucleotide code.
+pub fn iupac_bases(code: char) -> Vec<char> {
+    let bases = match code {
+        'R' => "AG",
+        'Y' => "CT",
+        'S' => "CG",
+        'W' => "AT",
+        'K' => "GT",
+        'M' => "AC",
+        'B' => "CGT",
+        'D' => "AGT",
+        'H' => "ACT",
+        'V' => "ACG",
+        'A' | 'C' | 'G' | 'T' => return vec![code],
+        _ => "",
+    };
+    bases.chars().collect()
+}
+
+/// Get the base of a population at a coordinate.
+///
+/// Populations are compact, so the base is looked up by coordinate, rather
+/// than by scanning the substitutions (see Sequence::base).
+fn population_base(dataset: &Dataset, population: &str, coord: usize) -> Option<char> {
+    let sequence = dataset.populations.get(population)?;
+    Some(sequence.base(coord, &dataset.reference))
+}
+
+/// Identify the population that best explains the ambiguous bases of a
+/// sequence, in addition to the best match.
+fn secondary_population(
+    dataset: &Dataset,
+    sequence: &Sequence,
+    population: &str,
+) -> Option<String> {
+    let ambiguous = sequence
+        .missing
+        .iter()
+        .filter(|coord| iupac_bases(sequence.seq[*coord - 1]).len() > 1)
+        .copied()
+        .collect_vec();
+    if ambiguous.len() < MIN_AMBIGUOUS_SITES {
+        return None;
+    }
+
+    // count the ambiguous sites where a population has the other base
+    let counts = dataset.populations.keys().filter(|p| *p != population).map(|other| {
+        let count = ambiguous
+            .iter()
+            .filter(|coord| {
+                let bases = iupac_bases(sequence.seq[**coord - 1]);
+                let base = population_base(dataset, population, **coord);
+                let other_base = population_base(dataset, other, **coord);
+                base != other_base
+                    && base.is_some_and(|b| bases.contains(&b))
+                    && other_base.is_some_and(|b| bases.contains(&b))
+            })
+            .count();
+        (other, count)
+    });
+    counts
+        .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(a.0)))
+        .filter(|(_, count)| *count >= MIN_AMBIGUOUS_SITES)
+        .map(|(other, _)| other.clone())
+}
+
+/// Distinguish a co-infection (or contamination) from a true recombinant.
+///
+/// A true recombinant has parental alleles clustered into regions, while a
+/// co-infection has ambiguous bases, or parental alleles interleaved across
+/// the genome. Sequences that are not recombinant are only analyzed if they
+/// have ambiguous bases that are explained by a second population. This is
+/// run once per sequence by the search workers (see Recombination::mixture).
+pub fn analyze(
+    dataset: &Dataset,
+    best_match: &SearchResult,
+    recombination: &Recombination,
+) -> Result<Option<Mixture>, Report> {
+    let sequence = recombination.sequence;
+
+    let parents = if recombination.parents.len() >= 2 {
+        recombination
+            .parents
+            .iter()
+            .filter(|p| *p != UNKNOWN_PARENT && dataset.populations.contains_key(*p))
+            .cloned()
+            .collect_vec()
+    } else {
+        let population = &best_match.consensus_population;
+        if !dataset.populations.contains_key(population) {
+            return Ok(None);
+        }
+        match secondary_population(dataset, sequence, population) {
+            Some(other) => vec![population.clone(), other],
+            None => return Ok(None),
+        }
+    };
+    if parents.len() < 2 {
+        return Ok(None);
+    }
+
+    // parental origin at each discriminating site, None if ambiguous
+    let coords = parents
+        .iter()
+        .flat_map(|p| dataset.populations[p].substitutions.iter().map(|s| s.coord))
+        .unique()
+        .sorted()
+        .collect_vec();
+    let mut origins: Vec<Option<&String>> = Vec::new();
+    for coord in coords {
+        let parent_bases = parents
+            .iter()
+            .map(|p| population_base(dataset, p, coord))
+            .collect::<Option<Vec<_>>>()
+            .unwrap_or_default();
+        if parent_bases.iter().unique().count() < 2 {
+            continue;
+        }
+        let bases = iupac_bases(sequence.seq[coord - 1]);
+        let matches = parents
+            .iter()
+            .zip(parent_bases)
+            .filter_map(|(p, base)| bases.contains(&base).then_some(p))
+            .collect_vec();
+        match matches.len() {
+            0 => continue,
+            1 => origins.push(Some(matches[0])),
+            _ => origins.push(None),
+        }
+    }
+
+    let sites = origins.len();
+    let ambiguous = origins.iter().filter(|o| o.is_none()).count();
+    let switches = origins
+        .iter()
+        .flatten()
+        .tuple_windows()
+        .filter(|(prev, next)| prev != next)
+        .count();
+
+    let max_switches = recombination.breakpoints.len().max(1) + MAX_EXTRA_SWITCHES;
+    let status = if ambiguous as f64 > MAX_AMBIGUOUS_FRACTION * sites as f64
+        || switches > max_switches
+    {
+        Status::LikelyCoinfection
+    } else {
+        Status::LikelyRecombinant
+    };
+
+    // only report a non-recombinant if it looks like a co-infection
+    if recombination.parents.len() < 2 && status != Status::LikelyCoinfection {
+        return Ok(None);
+    }
+
+    Ok(Some(Mixture {
+        status,
+        parents,
+        sites,
+        ambiguous,
+        switches,
+    }))
+}
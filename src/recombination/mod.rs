@@ -1,19 +1,30 @@
+pub mod amplicons;
+pub mod consistency;
+pub mod mixture;
+pub mod pairs;
 pub mod search;
+pub mod temporal;
 pub mod validate;
 
 use crate::cli::run;
 use crate::dataset::SearchResult;
-use crate::sequence::{Sequence, Substitution};
+use crate::recombination::amplicons::{Dropout, PrimerScheme};
+use crate::sequence::{parsimony, Sequence, Substitution};
+use crate::utils::fmt;
 use crate::utils::table::Table;
-use color_eyre::eyre::{eyre, Report, Result};
+use color_eyre::eyre::{eyre, Report, Result, WrapErr};
 use color_eyre::Help;
 use indoc::formatdoc;
 use itertools::Itertools;
 use log::debug;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
+use std::str::FromStr;
 use strum::{EnumIter, EnumProperty};
 
+/// Placeholder name for a recombination parent that is not in the dataset.
+pub const UNKNOWN_PARENT: &str = "unknown";
+
 // ----------------------------------------------------------------------------
 // Structs
 // ----------------------------------------------------------------------------
@@ -23,6 +34,7 @@ use strum::{EnumIter, EnumProperty};
 
 #[derive(Clone, Debug, Serialize)]
 pub struct Recombination<'seq> {
+    #[serde(skip_serializing)]
     pub sequence: &'seq Sequence,
     pub unique_key: String,
     pub recombinant: Option<String>,
@@ -36,9 +48,20 @@ pub struct Recombination<'seq> {
     pub conflict_ref: BTreeMap<String, Vec<Substitution>>,
     pub conflict_alt: BTreeMap<String, Vec<Substitution>>,
     pub private: BTreeMap<String, Vec<Substitution>>,
+    pub reversions: BTreeMap<String, Vec<Substitution>>,
     pub score: BTreeMap<String, isize>,
     #[serde(skip_serializing)]
     pub table: Table,
+    #[serde(skip_serializing)]
+    pub profile: search::Profile,
+    /// Warnings about the detection of this sequence, for the outputs.
+    pub warnings: Vec<String>,
+    /// Why the sequence is not recombinant, with --explain-negatives.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub explanation: Option<search::Explanation>,
+    /// Whether mixed parental alleles suggest a recombinant or co-infection.
+    #[serde(skip_serializing)]
+    pub mixture: Option<mixture::Mixture>,
 }
 
 impl<'seq> Recombination<'seq> {
@@ -58,53 +81,57 @@ impl<'seq> Recombination<'seq> {
             conflict_ref: BTreeMap::new(),
             conflict_alt: BTreeMap::new(),
             private: BTreeMap::new(),
+            reversions: BTreeMap::new(),
             score: BTreeMap::new(),
+            profile: search::Profile::new(),
+            warnings: Vec::new(),
+            explanation: None,
+            mixture: None,
         }
     }
 
-    pub fn pretty_print_parsimony(&self) -> String {
-        let mut score = String::new();
-        let mut support = String::new();
-        let mut conflict_ref = String::new();
-        let mut conflict_alt = String::new();
-        let mut private = String::new();
-
-        self.parents.iter().for_each(|pop| {
-            // score
-            let msg = format!("  - {pop}: {}\n", &self.score[pop]);
-            score.push_str(&msg);
-
-            // support
-            let subs = self.support.get(pop).unwrap();
-            let msg = format!("  - {pop} ({}): {}\n", subs.len(), subs.iter().join(", "));
-            support.push_str(&msg);
-
-            // conflict_ref
-            let subs = self.conflict_ref.get(pop).unwrap();
-            let msg = format!("  - {pop} ({}): {}\n", subs.len(), subs.iter().join(", "));
-            conflict_ref.push_str(&msg);
-
-            // conflict_alt
-            let subs = self.conflict_alt.get(pop).unwrap();
-            let msg = format!("  - {pop} ({}): {}\n", subs.len(), subs.iter().join(", "));
-            conflict_alt.push_str(&msg);
-
-            // private
-            let subs = self.private.get(pop).unwrap();
-            let msg = format!("  - {pop} ({}): {}\n", subs.len(), subs.iter().join(", "));
-            private.push_str(&msg);
-        });
+    /// Pretty-print the parsimony of the parents (yaml-like), with at most
+    /// `max_items` parents and substitutions per section.
+    pub fn pretty_print_parsimony(&self, max_items: usize) -> String {
+        let parents = self.parents.iter().collect_vec();
+        let score = parsimony::pretty_print_scores(&parents, &self.score, max_items);
+        let section =
+            |section| parsimony::pretty_print_section(&parents, section, max_items);
 
         formatdoc!(
-            "score:\n{score}
-            support:\n{support}
-            conflict_ref:\n{conflict_ref}
-            conflict_alt:\n{conflict_alt}
-            private:\n{private}
-            "
+            "score:\n  {score}
+            support:\n  {}
+            conflict_ref:\n  {}
+            conflict_alt:\n  {}
+            private:\n  {}
+            ",
+            section(&self.support),
+            section(&self.conflict_ref),
+            section(&self.conflict_alt),
+            section(&self.private),
         )
     }
 
+    /// Summarize the parsimony of the parents on one line, for logs.
+    pub fn one_line_parsimony(&self) -> String {
+        self.parents
+            .iter()
+            .map(|pop| {
+                let count = |section: &BTreeMap<String, Vec<Substitution>>| {
+                    section.get(pop).map(Vec::len).unwrap_or_default()
+                };
+                format!(
+                    "{pop} (score: {}, support: {}, conflict_ref: {}, conflict_alt: {}, private: {})",
+                    self.score.get(pop).copied().unwrap_or_default(),
+                    count(&self.support),
+                    count(&self.conflict_ref),
+                    count(&self.conflict_alt),
+                    count(&self.private),
+                )
+            })
+            .join(", ")
+    }
+
     pub fn get_substitution_origins(
         &self,
         best_match: &SearchResult,
@@ -197,7 +224,11 @@ impl std::fmt::Display for Hypothesis {
 // Breakpoint
 
 /// Recombination breakpoint intervals (left and right inclusive)
-#[derive(Clone, Debug, Deserialize, Serialize)]
+///
+/// Breakpoints are ordered by start, then end.
+#[derive(
+    Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize,
+)]
 pub struct Breakpoint {
     pub start: usize,
     pub end: usize,
@@ -209,6 +240,73 @@ impl std::fmt::Display for Breakpoint {
     }
 }
 
+impl FromStr for Breakpoint {
+    type Err = Report;
+
+    /// Parse a breakpoint from its display format (ex. 12-15), or a single
+    /// coordinate (ex. 12).
+    fn from_str(text: &str) -> Result<Self, Report> {
+        let (start, end) = text.split_once('-').unwrap_or((text, text));
+        let start =
+            start.parse().wrap_err_with(|| eyre!("Invalid breakpoint: {text}"))?;
+        let end = end.parse().wrap_err_with(|| eyre!("Invalid breakpoint: {text}"))?;
+        if start > end {
+            return Err(eyre!("Invalid breakpoint: {text}"))
+                .suggestion("Breakpoint start must be less than or equal to the end.");
+        }
+        Ok(Breakpoint { start, end })
+    }
+}
+
+impl Breakpoint {
+    pub fn new(start: usize, end: usize) -> Self {
+        Breakpoint { start, end }
+    }
+
+    /// Parse breakpoints from a comma-separated list (ex. 12-15,20-20).
+    pub fn parse_list(text: &str) -> Result<Vec<Breakpoint>, Report> {
+        text.split(',').filter(|b| !b.is_empty()).map(Breakpoint::from_str).collect()
+    }
+
+    /// Number of coordinates in the interval (ie. its uncertainty).
+    pub fn width(&self) -> usize {
+        self.end - self.start + 1
+    }
+
+    /// Whether a coordinate is in the interval.
+    pub fn contains(&self, coord: usize) -> bool {
+        self.start <= coord && coord <= self.end
+    }
+
+    /// Whether two intervals share any coordinates.
+    pub fn overlaps(&self, other: &Breakpoint) -> bool {
+        self.start <= other.end && other.start <= self.end
+    }
+
+    /// Coordinates shared by two intervals, if they overlap.
+    pub fn intersection(&self, other: &Breakpoint) -> Option<Breakpoint> {
+        self.overlaps(other).then(|| {
+            Breakpoint::new(self.start.max(other.start), self.end.min(other.end))
+        })
+    }
+
+    /// Number of bases between two intervals, 0 if they overlap.
+    pub fn distance(&self, other: &Breakpoint) -> usize {
+        if self.overlaps(other) {
+            0
+        } else if self.end < other.start {
+            other.start - self.end
+        } else {
+            self.start - other.end
+        }
+    }
+
+    /// Largest shift of the start or end between two intervals.
+    pub fn shift(&self, other: &Breakpoint) -> usize {
+        self.start.abs_diff(other.start).max(self.end.abs_diff(other.end))
+    }
+}
+
 // ----------------------------------------------------------------------------
 // Direction
 
@@ -229,6 +327,11 @@ pub struct Region {
     pub origin: String,
     #[serde(skip_serializing)]
     pub substitutions: Vec<Substitution>,
+    #[serde(default, skip_serializing)]
+    pub reversions: Vec<Substitution>,
+    /// Fraction of the region that is missing data in the sequence.
+    #[serde(default, serialize_with = "fmt::serialize_float")]
+    pub missing: f64,
 }
 
 impl std::fmt::Display for Region {
@@ -250,6 +353,7 @@ impl std::fmt::Display for Region {
 ///  * `parents` | `&Vec<dataset::SearchResult>` | Any known recombination parents so far.
 ///  * `parent_candidate` |  Option<&SearchResult> |  An unknown recombination parent to evaluate.
 ///  * `reference` |
+///  * `primer_scheme` | Option<&PrimerScheme> | Amplicons, to adjust regions for dropouts.
 ///  * `args` | &run::Args | CLI run parameters.
 ///
 pub fn detect_recombination<'seq>(
@@ -257,9 +361,11 @@ pub fn detect_recombination<'seq>(
     parents: &Vec<SearchResult>,
     parent_candidate: Option<&SearchResult>,
     reference: &Sequence,
+    primer_scheme: Option<&PrimerScheme>,
     args: &run::Args,
 ) -> Result<Recombination<'seq>, Report> {
     let mut recombination = Recombination::new(sequence);
+    let dropouts = primer_scheme.map(|s| s.dropouts(sequence)).unwrap_or_default();
 
     // if no parent candidates were provided, just use the first parent
     let parent_candidate = match parent_candidate {
@@ -391,8 +497,7 @@ pub fn detect_recombination<'seq>(
         }
     }
 
-    let mut table_no_private = table.clone();
-    table_no_private.rows.retain(|row| row[origin_col_i] != "private");
+    let table_no_private = table.filter_column("origin", |origin| origin != "private")?;
 
     // Debugging Table
     debug!(
@@ -406,14 +511,21 @@ pub fn detect_recombination<'seq>(
 
     // First: 5' -> 3', filter separately on min_consecutive then min_length
     let mut regions_5p = identify_regions(&table_no_private)?;
+    regions_5p = merge_reversions(&regions_5p, &table_no_private, args.max_reversions)?;
     regions_5p = filter_regions(
         &regions_5p,
         Direction::Forward,
         args.min_consecutive,
         0_usize,
+        &dropouts,
+    )?;
+    regions_5p = filter_regions(
+        &regions_5p,
+        Direction::Forward,
+        0_usize,
+        args.min_length,
+        &dropouts,
     )?;
-    regions_5p =
-        filter_regions(&regions_5p, Direction::Forward, 0_usize, args.min_length)?;
     debug!(
         "regions_5p: {}",
         serde_json::to_string(&regions_5p).unwrap()
@@ -421,14 +533,21 @@ pub fn detect_recombination<'seq>(
 
     // Second: 3' -> 5', filter separately on min_consecutive then min_length
     let mut regions_3p = identify_regions(&table_no_private)?;
+    regions_3p = merge_reversions(&regions_3p, &table_no_private, args.max_reversions)?;
     regions_3p = filter_regions(
         &regions_3p,
         Direction::Reverse,
         args.min_consecutive,
         0_usize,
+        &dropouts,
+    )?;
+    regions_3p = filter_regions(
+        &regions_3p,
+        Direction::Reverse,
+        0_usize,
+        args.min_length,
+        &dropouts,
     )?;
-    regions_3p =
-        filter_regions(&regions_3p, Direction::Reverse, 0_usize, args.min_length)?;
     debug!(
         "regions_3p: {}",
         serde_json::to_string(&regions_3p).unwrap()
@@ -444,12 +563,60 @@ pub fn detect_recombination<'seq>(
         Direction::Forward,
         args.min_consecutive,
         args.min_length,
+        &dropouts,
     )?;
     debug!(
         "regions_intersect: {}",
         serde_json::to_string(&regions_intersect).unwrap()
     );
 
+    // Split regions that span long stretches of missing data, as the parental
+    // origin of the sites within them is unreliable.
+    if let Some(max_missing) = args.max_missing_in_region {
+        regions_intersect = split_missing(
+            &regions_intersect,
+            sequence,
+            max_missing,
+            args.min_consecutive,
+            args.min_length,
+            &dropouts,
+            &mut recombination.warnings,
+        )?;
+        debug!(
+            "regions_split_missing: {}",
+            serde_json::to_string(&regions_intersect).unwrap()
+        );
+    }
+    regions_intersect.values_mut().for_each(|region| {
+        region.missing = missing_fraction(sequence, region.start, region.end);
+    });
+
+    // Report regions that are only long enough because of amplicon dropouts
+    for region in regions_intersect.values() {
+        let (min_consecutive, min_length, affected) = amplicons::adjust_thresholds(
+            region.start,
+            region.end,
+            &dropouts,
+            args.min_consecutive,
+            args.min_length,
+        );
+        let region_length = (region.end - region.start) + 1;
+        if affected.is_empty()
+            || (region.substitutions.len() >= args.min_consecutive
+                && region_length >= args.min_length)
+        {
+            continue;
+        }
+        recombination.warnings.push(format!(
+            "region {region} kept by amplicon dropout ({}): min_consecutive={}->{}, min_length={}->{}",
+            affected.iter().map(|d| &d.amplicon).join(","),
+            args.min_consecutive,
+            min_consecutive,
+            args.min_length,
+            min_length,
+        ));
+    }
+
     // Make sure all the prev_parents + parent_candidate have at least 1 region
     let region_origins = regions_intersect
         .values()
@@ -517,6 +684,18 @@ pub fn detect_recombination<'seq>(
         serde_json::to_string(&breakpoints).unwrap()
     );
 
+    // Report breakpoints at amplicon boundaries, which might be artifacts
+    if let Some(primer_scheme) = primer_scheme {
+        for breakpoint in &breakpoints {
+            if let Some((prev, next)) = primer_scheme.boundary(breakpoint) {
+                recombination.warnings.push(format!(
+                    "breakpoint {breakpoint} coincides with amplicon boundary ({},{})",
+                    prev.name, next.name
+                ));
+            }
+        }
+    }
+
     // --------------------------------------------------------------------
     // Update
     // --------------------------------------------------------------------
@@ -552,6 +731,10 @@ pub fn detect_recombination<'seq>(
         // private
         let mut private = search_result.private.clone();
         private.retain(|s| coordinates.contains(&s.coord));
+        // reversions, parent substitutions in this region that the sequence lacks
+        let mut parent_subs = search_result.substitutions.clone();
+        parent_subs.retain(|s| coordinates.contains(&s.coord));
+        let reversions = sequence.reversions(&parent_subs);
         // score
         let score = support.len() as isize
             - conflict_alt.len() as isize
@@ -561,6 +744,7 @@ pub fn detect_recombination<'seq>(
         recombination.conflict_ref.insert(pop.to_owned(), conflict_ref);
         recombination.conflict_alt.insert(pop.to_owned(), conflict_alt);
         recombination.private.insert(pop.to_owned(), private);
+        recombination.reversions.insert(pop.to_owned(), reversions);
         recombination.score.insert(pop.to_owned(), score);
     }
 
@@ -569,7 +753,7 @@ pub fn detect_recombination<'seq>(
     // convoluted debug message
     debug!(
         "Parsimony Summary:\n{}",
-        recombination.pretty_print_parsimony()
+        recombination.pretty_print_parsimony(parsimony::MAX_DISPLAY_ITEMS)
     );
 
     Ok(recombination)
@@ -605,6 +789,8 @@ pub fn identify_regions(table: &Table) -> Result<BTreeMap<usize, Region>, Report
                 end: coord,
                 origin: origin.clone(),
                 substitutions,
+                reversions: Vec::new(),
+                missing: 0.0,
             };
             regions.insert(start, region);
         }
@@ -621,12 +807,98 @@ pub fn identify_regions(table: &Table) -> Result<BTreeMap<usize, Region>, Report
     Ok(regions)
 }
 
+/// Merge parental regions that are broken up by reversions.
+///
+/// A reversion is a site where the sequence has the reference base, but the
+/// parent of the surrounding region has a substitution. A region made up of only
+/// reversions, that is flanked by regions of the same origin, is merged into the
+/// flanking regions as long as the merged region has no more than `max_reversions`.
+///
+/// Reversions are recorded as the back mutation from the parent's base (ex. C10T
+/// of the parent becomes T10C), like [Sequence::reversions]. A region whose
+/// reference bases are not substitutions of the flanking parent (ex. they are
+/// substitutions of another parent) is not merged.
+pub fn merge_reversions(
+    regions: &BTreeMap<usize, Region>,
+    table: &Table,
+    max_reversions: usize,
+) -> Result<BTreeMap<usize, Region>, Report> {
+    let mut regions_merge: BTreeMap<usize, Region> = BTreeMap::new();
+    let regions = regions.values().collect_vec();
+    let mut start_prev: Option<usize> = None;
+    let mut i = 0;
+
+    let coord_col_i = table.header_position("coord")?;
+    let rows = table
+        .rows
+        .iter()
+        .map(|row| Ok((row[coord_col_i].parse::<usize>()?, row)))
+        .collect::<Result<BTreeMap<_, _>, Report>>()?;
+
+    // the back mutation of a substitution of the parent, if the sequence has
+    // the reference base instead
+    let back_mutation = |sub: &Substitution, parent: &str| -> Result<_, Report> {
+        let parent_col_i = table.header_position(parent)?;
+        let parent_base = rows
+            .get(&sub.coord)
+            .and_then(|row| row[parent_col_i].chars().next())
+            .unwrap_or(sub.reference);
+        let is_reversion = sub.alt == sub.reference && parent_base != sub.reference;
+        Ok(is_reversion.then_some(Substitution {
+            coord: sub.coord,
+            reference: parent_base,
+            alt: sub.reference,
+        }))
+    };
+
+    while i < regions.len() {
+        let region = regions[i];
+
+        // is this region only reversions of the previous region's parent,
+        // with a next region of the same parent?
+        if let (Some(start_prev), Some(next)) = (start_prev, regions.get(i + 1)) {
+            let prev = regions_merge.get_mut(&start_prev).unwrap();
+            let reversions = region
+                .substitutions
+                .iter()
+                .map(|sub| back_mutation(sub, &prev.origin))
+                .collect::<Result<Option<Vec<_>>, Report>>()?;
+            if let Some(reversions) = reversions {
+                let num_reversions =
+                    prev.reversions.len() + reversions.len() + next.reversions.len();
+                if prev.origin == next.origin && num_reversions <= max_reversions {
+                    debug!(
+                        "Merging reversions {} into region {prev}.",
+                        reversions.iter().join(",")
+                    );
+                    prev.reversions.extend(reversions);
+                    prev.reversions.extend(next.reversions.clone());
+                    prev.substitutions.extend(next.substitutions.clone());
+                    prev.end = next.end;
+                    i += 2;
+                    continue;
+                }
+            }
+        }
+
+        regions_merge.insert(region.start, region.to_owned());
+        start_prev = Some(region.start);
+        i += 1;
+    }
+
+    Ok(regions_merge)
+}
+
 /// Filter recombinant regions based on the length and consecutive bases.
+///
+/// The thresholds are relaxed for regions next to amplicon dropouts, see
+/// [amplicons::adjust_thresholds].
 pub fn filter_regions(
     regions: &BTreeMap<usize, Region>,
     direction: Direction,
     min_consecutive: usize,
     min_length: usize,
+    dropouts: &[Dropout],
 ) -> Result<BTreeMap<usize, Region>, Report> {
     let mut regions_filter = BTreeMap::new();
     let mut origin_prev: Option<String> = None;
@@ -641,11 +913,20 @@ pub fn filter_regions(
         let region = regions.get(start).unwrap();
         let num_consecutive = region.substitutions.len();
         let region_length = (region.end - region.start) + 1;
+        let (region_min_consecutive, region_min_length, _) = amplicons::adjust_thresholds(
+            region.start,
+            region.end,
+            dropouts,
+            min_consecutive,
+            min_length,
+        );
 
         // start of new region, either first or origin changes
         if origin_prev.is_none() || origin_prev != Some(region.origin.clone()) {
             // is the new parental region long enough?
-            if num_consecutive >= min_consecutive && region_length >= min_length {
+            if num_consecutive >= region_min_consecutive
+                && region_length >= region_min_length
+            {
                 regions_filter.insert(region.start, region.to_owned());
                 origin_prev = Some(region.origin.clone());
                 start_prev = Some(region.start);
@@ -659,6 +940,7 @@ pub fn filter_regions(
                     if let Some(start_prev) = start_prev {
                         let region_update = regions_filter.get_mut(&start_prev).unwrap();
                         region_update.substitutions.extend(region.substitutions.clone());
+                        region_update.reversions.extend(region.reversions.clone());
                         region_update.end = region.end;
                     }
                 }
@@ -669,6 +951,8 @@ pub fn filter_regions(
                             regions_filter.get(&start_prev).unwrap().to_owned();
                         region_new.substitutions.extend(region.substitutions.clone());
                         region_new.substitutions.sort();
+                        region_new.reversions.extend(region.reversions.clone());
+                        region_new.reversions.sort();
                         region_new.start = region.start;
 
                         // remove old region from filtered map
@@ -718,11 +1002,22 @@ pub fn intersect_regions(
             let start = subs_intersect.iter().min().map(|sub| sub.coord).unwrap();
             let end = subs_intersect.iter().max().map(|sub| sub.coord).unwrap();
 
+            // find the shared reversions
+            let reversions = r1
+                .reversions
+                .iter()
+                .filter(|sub| r2.reversions.contains(sub))
+                .filter(|sub| sub.coord >= start && sub.coord <= end)
+                .cloned()
+                .collect::<Vec<_>>();
+
             let region = Region {
                 start,
                 end,
                 origin: r1.origin.clone(),
                 substitutions: subs_intersect,
+                reversions,
+                missing: 0.0,
             };
             regions_intersect.insert(start, region);
         }
@@ -733,25 +1028,120 @@ pub fn intersect_regions(
     Ok(regions_intersect)
 }
 
+/// Calculate the fraction of coordinates (inclusive) that are missing in a sequence.
+pub fn missing_fraction(sequence: &Sequence, start: usize, end: usize) -> f64 {
+    if end < start {
+        return 0.0;
+    }
+    let num_missing =
+        sequence.missing.iter().filter(|coord| (start..=end).contains(*coord)).count();
+    num_missing as f64 / (end - start + 1) as f64
+}
+
+/// Split parental regions at stretches of missing data.
+///
+/// A region with more than `max_missing` (fraction) missing data is split
+/// between consecutive substitutions whose gap exceeds `max_missing`. Pieces
+/// that no longer satisfy `min_consecutive` and `min_length` (adjusted for
+/// amplicon dropouts) are removed, with a warning.
+pub fn split_missing(
+    regions: &BTreeMap<usize, Region>,
+    sequence: &Sequence,
+    max_missing: f64,
+    min_consecutive: usize,
+    min_length: usize,
+    dropouts: &[Dropout],
+    warnings: &mut Vec<String>,
+) -> Result<BTreeMap<usize, Region>, Report> {
+    let mut regions_split = BTreeMap::new();
+
+    for region in regions.values() {
+        if missing_fraction(sequence, region.start, region.end) <= max_missing {
+            regions_split.insert(region.start, region.clone());
+            continue;
+        }
+
+        // group substitutions into pieces separated by missing stretches
+        let mut pieces: Vec<Vec<Substitution>> = Vec::new();
+        for sub in region.substitutions.iter().sorted() {
+            match pieces.last_mut() {
+                Some(piece)
+                    if missing_fraction(
+                        sequence,
+                        piece.last().unwrap().coord + 1,
+                        sub.coord - 1,
+                    ) <= max_missing =>
+                {
+                    piece.push(*sub)
+                }
+                _ => pieces.push(vec![*sub]),
+            }
+        }
+
+        for substitutions in pieces {
+            let start = substitutions.first().unwrap().coord;
+            let end = substitutions.last().unwrap().coord;
+            let (min_consecutive, min_length, _) = amplicons::adjust_thresholds(
+                start,
+                end,
+                dropouts,
+                min_consecutive,
+                min_length,
+            );
+            if substitutions.len() < min_consecutive || (end - start) + 1 < min_length {
+                debug!(
+                    "Removing region {start}-{end}|{} split by missing data.",
+                    region.origin
+                );
+                warnings.push(format!(
+                    "region {start}-{end}|{} discarded: too much missing data",
+                    region.origin
+                ));
+                continue;
+            }
+            let reversions = region
+                .reversions
+                .iter()
+                .filter(|sub| sub.coord >= start && sub.coord <= end)
+                .cloned()
+                .collect_vec();
+            let region = Region {
+                start,
+                end,
+                origin: region.origin.clone(),
+                substitutions,
+                reversions,
+                missing: missing_fraction(sequence, start, end),
+            };
+            regions_split.insert(start, region);
+        }
+    }
+
+    Ok(regions_split)
+}
+
 /// Identify breakpoint intervals in recombination regions.
 pub fn identify_breakpoints(
     regions: &BTreeMap<usize, Region>,
 ) -> Result<Vec<Breakpoint>, Report> {
     let mut breakpoints: Vec<Breakpoint> = Vec::new();
     let mut end_prev: Option<usize> = None;
+    let mut origin_prev: Option<&String> = None;
 
     for region in regions.values() {
         // a breakpoint is only possible if we already found a prev region
+        // of a different origin (ex. split by missing data)
+        if origin_prev == Some(&region.origin) {
+            end_prev = Some(region.end);
+            continue;
+        }
+        origin_prev = Some(&region.origin);
         if let Some(end_prev) = end_prev {
             // breakpoint intervals are non-inclusive of regions
             // but what happens if we know the precise bases...
             let start = end_prev + 1;
             let end = region.start - 1;
-            let breakpoint = if start < end {
-                Breakpoint { start, end }
-            } else {
-                Breakpoint { start, end: start }
-            };
+            let breakpoint = Breakpoint::new(start, end.max(start));
             breakpoints.push(breakpoint);
         }
 
@@ -0,0 +1,164 @@
+use crate::sequence::Sequence;
+use color_eyre::eyre::{eyre, Report, Result};
+use itertools::Itertools;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+
+// ----------------------------------------------------------------------------
+// Pair Profile
+// ----------------------------------------------------------------------------
+
+/// Informative sites between a pair of populations.
+///
+/// The coordinates where the two populations have different bases, assuming
+/// the reference base where a population has no substitution.
+pub type PairProfile = Arc<Vec<usize>>;
+
+/// Maximum number of pair profiles held by the cache.
+///
+/// Once full, new profiles are still returned but no longer cached.
+pub const MAX_PAIRS: usize = 100_000;
+
+/// Identify the informative sites between two population sequences.
+///
+/// Sites that are missing or deleted in either population are excluded, since
+/// there is no base to compare.
+pub fn informative_sites(a: &Sequence, b: &Sequence) -> Vec<usize> {
+    let uncomparable = a
+        .missing
+        .iter()
+        .chain(b.missing.iter())
+        .cloned()
+        .chain(a.deletions.iter().chain(b.deletions.iter()).map(|d| d.coord))
+        .collect::<HashSet<_>>();
+    a.substitutions
+        .iter()
+        .filter(|sub| !b.substitutions.contains(sub))
+        .chain(b.substitutions.iter().filter(|sub| !a.substitutions.contains(sub)))
+        .map(|sub| sub.coord)
+        .filter(|coord| !uncomparable.contains(coord))
+        .unique()
+        .sorted()
+        .collect_vec()
+}
+
+// ----------------------------------------------------------------------------
+// Pair Cache
+// ----------------------------------------------------------------------------
+
+/// A thread-safe cache of parent pair profiles.
+///
+/// Profiles are symmetric, so pairs are stored in sorted order. The cache
+/// holds at most [`MAX_PAIRS`] profiles.
+#[derive(Debug, Default)]
+pub struct PairCache {
+    profiles: RwLock<HashMap<(String, String), PairProfile>>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+    precomputed: AtomicUsize,
+    screened: AtomicUsize,
+    disabled: AtomicBool,
+}
+
+/// Summary statistics of the parent pair cache.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct PairCacheStats {
+    pub pairs: usize,
+    pub precomputed: usize,
+    pub hits: usize,
+    pub misses: usize,
+    pub screened: usize,
+}
+
+impl fmt::Display for PairCacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "pairs: {}, precomputed: {}, hits: {}, misses: {}, screened: {}",
+            self.pairs, self.precomputed, self.hits, self.misses, self.screened
+        )
+    }
+}
+
+impl PairCache {
+    pub fn new() -> Self {
+        PairCache::default()
+    }
+
+    fn key(a: &str, b: &str) -> (String, String) {
+        if a <= b {
+            (a.to_string(), b.to_string())
+        } else {
+            (b.to_string(), a.to_string())
+        }
+    }
+
+    /// Get the profile of a pair, if it has been cached.
+    pub fn get(&self, a: &str, b: &str) -> Result<Option<PairProfile>, Report> {
+        let profiles =
+            self.profiles.read().map_err(|e| eyre!("Pair cache is poisoned: {e}"))?;
+        let profile = profiles.get(&PairCache::key(a, b)).cloned();
+        let counter = if profile.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        Ok(profile)
+    }
+
+    /// Add the profile of a pair to the cache, if it is not full.
+    pub fn insert(
+        &self,
+        a: &str,
+        b: &str,
+        profile: Vec<usize>,
+        precomputed: bool,
+    ) -> Result<PairProfile, Report> {
+        if self.disabled.load(Ordering::Relaxed) {
+            return Ok(Arc::new(profile));
+        }
+        let mut profiles =
+            self.profiles.write().map_err(|e| eyre!("Pair cache is poisoned: {e}"))?;
+        let key = PairCache::key(a, b);
+        if profiles.len() >= MAX_PAIRS && !profiles.contains_key(&key) {
+            return Ok(Arc::new(profile));
+        }
+        let profile = profiles.entry(key).or_insert_with(|| {
+            if precomputed {
+                self.precomputed.fetch_add(1, Ordering::Relaxed);
+            }
+            Arc::new(profile)
+        });
+        Ok(profile.clone())
+    }
+
+    /// Clear the cache, and stop caching new pairs (ex. to reduce memory).
+    pub fn disable(&self) -> Result<(), Report> {
+        self.disabled.store(true, Ordering::Relaxed);
+        let mut profiles =
+            self.profiles.write().map_err(|e| eyre!("Pair cache is poisoned: {e}"))?;
+        *profiles = HashMap::new();
+        Ok(())
+    }
+
+    /// Record the number of candidate parents removed by pre-screening.
+    pub fn add_screened(&self, n: usize) {
+        self.screened.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> Result<PairCacheStats, Report> {
+        let profiles =
+            self.profiles.read().map_err(|e| eyre!("Pair cache is poisoned: {e}"))?;
+        Ok(PairCacheStats {
+            pairs: profiles.len(),
+            precomputed: self.precomputed.load(Ordering::Relaxed),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            screened: self.screened.load(Ordering::Relaxed),
+        })
+    }
+}
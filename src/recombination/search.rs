@@ -1,13 +1,37 @@
 use crate::cli::run;
 use crate::dataset::{Dataset, SearchResult};
-use crate::recombination::{detect_recombination, validate, Hypothesis, Recombination};
+use crate::recombination::{
+    detect_recombination, validate, Hypothesis, Recombination, UNKNOWN_PARENT,
+};
 use crate::sequence::Sequence;
 use color_eyre::eyre::{eyre, Report, Result};
 use itertools::Itertools;
 use log::{debug, warn};
+use serde::Serialize;
 use std::collections::BTreeMap;
+use std::time::Duration;
 use strum::IntoEnumIterator;
 
+// ----------------------------------------------------------------------------
+// Search Profile
+
+/// Runtime and complexity of the search for a single sequence.
+#[derive(Clone, Debug, Default)]
+pub struct Profile {
+    /// Wall time of the consensus and parent searches.
+    pub runtime: Duration,
+    /// Number of candidate parents checked for recombination.
+    pub candidate_parents: usize,
+    /// Number of parent search iterations, across all hypotheses.
+    pub search_iterations: usize,
+}
+
+impl Profile {
+    pub fn new() -> Self {
+        Profile::default()
+    }
+}
+
 // ----------------------------------------------------------------------------
 // Functions
 // ----------------------------------------------------------------------------
@@ -15,7 +39,8 @@ use strum::IntoEnumIterator;
 /// Search for primary and secondary recombination parents.
 ///
 /// Uses a recursion_limit for safety. It is not intended to
-/// run this wrapper function more than once recursively.
+/// run this wrapper function more than once recursively. The search
+/// complexity is recorded in the profile, whether or not it succeeds.
 #[allow(clippy::needless_if)]
 pub fn all_parents<'seq>(
     sequence: &'seq Sequence,
@@ -23,6 +48,7 @@ pub fn all_parents<'seq>(
     best_match: &mut SearchResult,
     populations: &[&String],
     args: &run::Args,
+    profile: &mut Profile,
 ) -> Result<Recombination<'seq>, Report> {
     // copy args, we don't want to modify the original global parameters
     let mut args = args.clone();
@@ -37,30 +63,7 @@ pub fn all_parents<'seq>(
     // ------------------------------------------------------------------------
 
     // // Edge Cases: Manually specified in the organism's dataset.
-    let mut edge_case = false;
-
-    // only apply edge cases if the user didn't request a naive search
-    if !args.naive {
-        let edge_case_search = dataset
-            .edge_cases
-            .iter()
-            .find(|e| e.population.as_ref() == best_match.recombinant.as_ref());
-
-        if let Some(edge_case_args) = edge_case_search {
-            debug!("Applying edge case parameters: {edge_case_args:?}");
-            edge_case = true;
-            args = args.apply_edge_case(edge_case_args)?;
-
-            if let Some(parents) = &edge_case_args.parents {
-                let parents_expand = dataset.expand_populations(parents)?;
-                populations.retain(|pop| parents_expand.contains(pop));
-            }
-            if let Some(knockout) = &edge_case_args.knockout {
-                let knockout = dataset.expand_populations(knockout)?;
-                populations.retain(|pop| !knockout.contains(pop));
-            }
-        }
-    }
+    let edge_case = apply_edge_case(dataset, best_match, &mut args, &mut populations)?;
 
     // ----------------------------------------------------------------------------
     // Hypothesis Testing
@@ -174,8 +177,19 @@ pub fn all_parents<'seq>(
         if let Ok(primary_parent) = primary_search {
             debug!("Primary Parent Search was successful.");
             debug!("Secondary Parent(s) Search.");
-            let secondary_search =
-                secondary_parents(sequence, dataset, &[primary_parent], &hyp_args);
+            let primary_parent = [primary_parent];
+            let mut secondary_search =
+                secondary_parents(sequence, dataset, &primary_parent, &hyp_args, profile);
+
+            // if no secondary parent was found, optionally fall back to an unknown one
+            if secondary_search.is_err() && args.allow_unknown_parent {
+                debug!(
+                    "Secondary Parent(s) Search was unsuccessful, trying unknown parent."
+                );
+                profile.candidate_parents += 1;
+                secondary_search =
+                    unknown_parent(sequence, dataset, &primary_parent, &hyp_args);
+            }
 
             if let Ok((recombination, parents)) = secondary_search {
                 debug!("Secondary Parent(s) Search was successful.");
@@ -235,6 +249,8 @@ pub fn all_parents<'seq>(
     if hypotheses.is_empty() {
         return Err(eyre!("No evidence for any recombination hypotheses."));
     }
+    // ties between hypotheses with different results are surfaced as warnings
+    let mut warnings = Vec::new();
     // single hypothesis
     let best_hypothesis = if hypotheses.len() == 1 {
         hypotheses.first_key_value().unwrap().0.clone()
@@ -294,6 +310,19 @@ pub fn all_parents<'seq>(
             .filter_map(|(hyp, r)| (r == best_hyp_rank).then_some(hyp))
             .next()
             .unwrap();
+        // only a warning if the tied hypotheses disagree on the parents or breakpoints
+        let num_results = best_hypotheses
+            .iter()
+            .filter_map(|hyp| hypotheses[hyp].0.as_ref())
+            .map(|r| (&r.parents, &r.breakpoints))
+            .unique()
+            .count();
+        if num_results > 1 {
+            warnings.push(format!(
+                "tie broken by hypothesis order: {best_hypothesis:?} chosen from {}",
+                best_hypotheses.iter().map(|hyp| format!("{hyp:?}")).join(",")
+            ));
+        }
 
         best_hypothesis.to_owned()
     };
@@ -314,6 +343,7 @@ pub fn all_parents<'seq>(
     // Recombinant attributes
 
     recombination.edge_case = edge_case;
+    recombination.warnings.extend(warnings);
 
     // Decide on novel vs known recombinant at this point
     recombination.recombinant = if let Some(recombinant) = &best_match.recombinant {
@@ -327,8 +357,8 @@ pub fn all_parents<'seq>(
         } else {
             *best_match = primary_parent;
             debug!(
-                "Changing best match consensus to primary parent:\n{}",
-                best_match.pretty_print()
+                "Changing best match consensus to primary parent: {}",
+                best_match.one_line()
             );
             Some("novel".to_string())
         }
@@ -348,12 +378,50 @@ pub fn all_parents<'seq>(
     Ok(recombination)
 }
 
+/// Apply the dataset edge case of the best match's recombinant (if any) to the
+/// search parameters and populations.
+///
+/// Returns true if an edge case was applied. Edge cases are not applied to a
+/// naive search.
+fn apply_edge_case(
+    dataset: &Dataset,
+    best_match: &SearchResult,
+    args: &mut run::Args,
+    populations: &mut Vec<&String>,
+) -> Result<bool, Report> {
+    // only apply edge cases if the user didn't request a naive search
+    if args.naive {
+        return Ok(false);
+    }
+    let edge_case_search = dataset
+        .edge_cases
+        .iter()
+        .find(|e| e.population.as_ref() == best_match.recombinant.as_ref());
+    let Some(edge_case_args) = edge_case_search else {
+        return Ok(false);
+    };
+
+    debug!("Applying edge case parameters: {edge_case_args:?}");
+    *args = args.apply_edge_case(edge_case_args)?;
+
+    if let Some(parents) = &edge_case_args.parents {
+        let parents_expand = dataset.expand_populations(parents)?;
+        populations.retain(|pop| parents_expand.contains(pop));
+    }
+    if let Some(knockout) = &edge_case_args.knockout {
+        let knockout = dataset.expand_populations(knockout)?;
+        populations.retain(|pop| !knockout.contains(pop));
+    }
+    Ok(true)
+}
+
 // Search for the secondary recombination parent(s).
 pub fn secondary_parents<'seq>(
     sequence: &'seq Sequence,
     dataset: &Dataset,
     parents: &[SearchResult],
     args: &run::Args,
+    profile: &mut Profile,
 ) -> Result<(Recombination<'seq>, Vec<SearchResult>), Report> {
     // Initialize our 'Recombination' result, that we will modify and update
     // as we iterate through potential parents
@@ -376,6 +444,12 @@ pub fn secondary_parents<'seq>(
     // Keep track of how many parent search iterations we've done
     let mut num_iter = 0;
 
+    // Missing amplicons of the sequence, which relax the region thresholds
+    let has_dropouts = dataset
+        .primer_scheme
+        .as_ref()
+        .is_some_and(|scheme| !scheme.dropouts(sequence).is_empty());
+
     loop {
         // --------------------------------------------------------------------
         // Loop Break Check: Simple
@@ -406,6 +480,7 @@ pub fn secondary_parents<'seq>(
         }
 
         num_iter += 1;
+        profile.search_iterations += 1;
         debug!("Parent #{}: Iteration {num_iter}", num_parents + 1);
 
         // --------------------------------------------------------------------
@@ -550,6 +625,41 @@ pub fn secondary_parents<'seq>(
 
         include_populations.retain(|pop| conflict_alt_populations.contains(pop));
 
+        // --------------------------------------------------------------------
+        // PRE-SCREEN POPULATIONS
+
+        // The primary and secondary parent must each contribute a region of
+        // min_consecutive sites, so they need to differ at twice as many sites.
+        // Only possible when the primary parent is an actual population of the
+        // dataset, rather than the consensus of its top populations.
+        // Amplicon dropouts relax min_consecutive of the regions they affect
+        // (see amplicons::adjust_thresholds), down to a single site.
+        // Only profiles already in the pair cache are consulted (see
+        // --precompute-pairs), the pre-screen does not compute them itself.
+        if let [primary] = parents.as_slice() {
+            let primary_population = &primary.consensus_population;
+            if primary.top_populations.contains(primary_population)
+                && dataset.populations.contains_key(primary_population)
+            {
+                let min_consecutive = if has_dropouts {
+                    args.min_consecutive.min(1)
+                } else {
+                    args.min_consecutive
+                };
+                let min_sites = 2 * min_consecutive.max(1);
+                let num_populations = include_populations.len();
+                include_populations.retain(|pop| {
+                    match dataset.pair_cache.get(primary_population, pop) {
+                        Ok(Some(profile)) => profile.len() >= min_sites,
+                        _ => true,
+                    }
+                });
+                let num_screened = num_populations - include_populations.len();
+                dataset.pair_cache.add_screened(num_screened);
+                debug!("Pre-screened populations with too few informative sites: {num_screened}");
+            }
+        }
+
         // trunclate list for display
         let display_populations = if include_populations.len() <= 10 {
             include_populations.iter().join(", ")
@@ -602,11 +712,13 @@ pub fn secondary_parents<'seq>(
                     .retain(|pop| **pop != parent_candidate.consensus_population);
 
                 // check for recombination
+                profile.candidate_parents += 1;
                 let detect_result = detect_recombination(
                     sequence,
                     &parents,
                     Some(&parent_candidate),
                     &dataset.reference,
+                    dataset.primer_scheme.as_ref(),
                     args,
                 );
 
@@ -624,3 +736,277 @@ pub fn secondary_parents<'seq>(
         }
     }
 }
+
+/// Search for an unknown recombination parent, that is not in the dataset.
+///
+/// The unknown parent is a placeholder, whose substitutions are those in the
+/// sequence that are not resolved by the known parents.
+pub fn unknown_parent<'seq>(
+    sequence: &'seq Sequence,
+    dataset: &Dataset,
+    parents: &[SearchResult],
+    args: &run::Args,
+) -> Result<(Recombination<'seq>, Vec<SearchResult>), Report> {
+    let parent_substitutions =
+        parents.iter().flat_map(|parent| &parent.substitutions).unique().collect_vec();
+    let substitutions = sequence
+        .substitutions
+        .iter()
+        .filter(|sub| !parent_substitutions.contains(sub))
+        .cloned()
+        .collect_vec();
+    debug!(
+        "Unknown parent substitutions: {}",
+        substitutions.iter().join(", ")
+    );
+
+    if substitutions.len() < args.min_subs {
+        return Err(eyre!(
+            "Unresolved substitutions ({}) are less than the minimum ({}).",
+            substitutions.len(),
+            args.min_subs
+        ));
+    }
+
+    let population = UNKNOWN_PARENT.to_string();
+    let mut unknown = SearchResult::new(sequence);
+    unknown.consensus_population = population.clone();
+    unknown.top_populations = vec![population.clone()];
+    unknown.top_populations_total = 1;
+    unknown.support.insert(population.clone(), substitutions.clone());
+    unknown.conflict_ref.insert(population.clone(), Vec::new());
+    unknown.conflict_alt.insert(population.clone(), Vec::new());
+    unknown.score.insert(population, substitutions.len() as isize);
+    unknown.substitutions = substitutions;
+
+    let mut parents = parents.to_vec();
+    let recombination = detect_recombination(
+        sequence,
+        &parents,
+        Some(&unknown),
+        &dataset.reference,
+        dataset.primer_scheme.as_ref(),
+        args,
+    )?;
+    parents.push(unknown);
+
+    Ok((recombination, parents))
+}
+
+// ----------------------------------------------------------------------------
+// Negatives
+// ----------------------------------------------------------------------------
+
+/// Why a sequence was not called recombinant (--explain-negatives).
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Explanation {
+    /// The best secondary parent candidate, if one was found.
+    pub candidate: Option<String>,
+    /// The threshold that rejected the candidate (ex. min_length=500).
+    pub threshold: Option<String>,
+    pub detail: String,
+}
+
+/// Explain why a sequence was not called recombinant.
+///
+/// The best secondary parent candidate is the population that best matches
+/// the sequence at the sites that the primary parent doesn't
+/// resolve. Each detection threshold is then relaxed one at a time, to find
+/// the one that rejected the candidate.
+pub fn explain_negative(
+    sequence: &Sequence,
+    dataset: &Dataset,
+    best_match: &SearchResult,
+    populations: &[&String],
+    args: &run::Args,
+) -> Result<Explanation, Report> {
+    let mut args = args.clone();
+    let mut populations = populations.to_vec();
+    let edge_case = apply_edge_case(dataset, best_match, &mut args, &mut populations)?;
+
+    if best_match.consensus_population.is_empty() {
+        return Ok(Explanation {
+            detail: "No population matched the sequence.".to_string(),
+            ..Default::default()
+        });
+    }
+
+    // the primary parent is the best match, unless it is not one of the
+    // populations being searched (as in the parent search)
+    let primary_search = |populations: &Vec<&String>| {
+        if populations.contains(&&best_match.consensus_population) {
+            Ok(best_match.clone())
+        } else {
+            dataset.search(sequence, Some(populations), None)
+        }
+    };
+
+    // a designated recombinant is first searched for among its designated
+    // parents, then among all populations
+    if let (false, Some(recombinant)) = (args.naive, &best_match.recombinant) {
+        let designated_parents = dataset
+            .phylogeny
+            .get_parents(recombinant)?
+            .iter()
+            .filter_map(|p| dataset.get_ancestor_with_sequence(p).ok())
+            .collect_vec();
+        let mut designated_populations = populations.clone();
+        designated_populations.retain(|pop| designated_parents.contains(pop));
+        if let Ok(primary) = primary_search(&designated_populations) {
+            let explanation = explain_candidate(
+                sequence,
+                dataset,
+                &primary,
+                &designated_populations,
+                &args,
+            );
+            if explanation.candidate.is_some() {
+                return Ok(with_edge_case(explanation, edge_case));
+            }
+        }
+    }
+
+    match primary_search(&populations) {
+        Ok(primary) => {
+            let explanation =
+                explain_candidate(sequence, dataset, &primary, &populations, &args);
+            Ok(with_edge_case(explanation, edge_case))
+        }
+        Err(_) => Ok(Explanation {
+            detail: "No parent population matched the sequence.".to_string(),
+            ..Default::default()
+        }),
+    }
+}
+
+/// Explain why the best secondary parent candidate of a primary parent was
+/// rejected, by relaxing each detection threshold.
+/// Note when the rejecting threshold was set by a dataset edge case.
+fn with_edge_case(mut explanation: Explanation, edge_case: bool) -> Explanation {
+    if edge_case && explanation.threshold.is_some() {
+        explanation.detail += " The threshold is set by a dataset edge case.";
+    }
+    explanation
+}
+
+fn explain_candidate(
+    sequence: &Sequence,
+    dataset: &Dataset,
+    primary: &SearchResult,
+    populations: &[&String],
+    args: &run::Args,
+) -> Explanation {
+    let mut explanation = Explanation::default();
+    let mut populations = populations.to_vec();
+    let primary_population = &primary.consensus_population;
+
+    // sites that are not resolved by the primary parent
+    let conflict_alt = sequence
+        .substitutions
+        .iter()
+        .filter(|sub| !primary.substitutions.contains(sub))
+        .map(|sub| sub.coord);
+    let conflict_ref = primary.conflict_ref.get(primary_population).into_iter().flatten();
+    let coordinates = conflict_alt
+        .chain(conflict_ref.map(|sub| sub.coord))
+        .unique()
+        .sorted()
+        .collect_vec();
+    if coordinates.is_empty() {
+        explanation.detail = format!(
+            "All substitutions are resolved by the primary parent {primary_population}."
+        );
+        return explanation;
+    }
+
+    // ------------------------------------------------------------------------
+    // Candidate
+
+    populations.retain(|pop| *pop != primary_population);
+    let Ok(candidate) = dataset.search(sequence, Some(&populations), Some(&coordinates))
+    else {
+        explanation.detail = format!(
+            "No secondary parent candidate matches the {} site(s) unresolved by {primary_population}.",
+            coordinates.len()
+        );
+        return explanation;
+    };
+    explanation.candidate = Some(candidate.consensus_population.clone());
+
+    // ------------------------------------------------------------------------
+    // Thresholds
+
+    let parents = vec![primary.clone()];
+    let detect = |args: &run::Args| {
+        detect_recombination(
+            sequence,
+            &parents,
+            Some(&candidate),
+            &dataset.reference,
+            dataset.primer_scheme.as_ref(),
+            args,
+        )
+    };
+    let error = match detect(args) {
+        Ok(_) => {
+            explanation.detail = format!(
+                "Recombination with {} passes all thresholds, but was not the best hypothesis.",
+                candidate.consensus_population
+            );
+            return explanation;
+        }
+        Err(e) => e,
+    };
+
+    // thresholds that are already disabled can't be relaxed further
+    let relaxed = [
+        (args.min_consecutive > 0).then(|| {
+            let relaxed = run::Args {
+                min_consecutive: 0,
+                ..args.clone()
+            };
+            (format!("min_consecutive={}", args.min_consecutive), relaxed)
+        }),
+        (args.min_length > 0).then(|| {
+            let relaxed = run::Args {
+                min_length: 0,
+                ..args.clone()
+            };
+            (format!("min_length={}", args.min_length), relaxed)
+        }),
+        (args.min_subs > 0).then(|| {
+            let relaxed = run::Args {
+                min_subs: 0,
+                ..args.clone()
+            };
+            (format!("min_subs={}", args.min_subs), relaxed)
+        }),
+        Some((
+            format!("max_reversions={}", args.max_reversions),
+            run::Args {
+                max_reversions: usize::MAX,
+                ..args.clone()
+            },
+        )),
+        args.max_missing_in_region.map(|max| {
+            let relaxed = run::Args {
+                max_missing_in_region: None,
+                ..args.clone()
+            };
+            (format!("max_missing_in_region={max}"), relaxed)
+        }),
+    ];
+    for (threshold, relaxed_args) in relaxed.iter().flatten() {
+        if detect(relaxed_args).is_ok() {
+            explanation.threshold = Some(threshold.clone());
+            explanation.detail = error.to_string();
+            return explanation;
+        }
+    }
+
+    explanation.detail = format!(
+        "{error} No single threshold rejected {}.",
+        candidate.consensus_population
+    );
+    explanation
+}
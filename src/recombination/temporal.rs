@@ -0,0 +1,56 @@
+use crate::dataset::Dataset;
+use crate::recombination::Recombination;
+use chrono::NaiveDate;
+use itertools::Itertools;
+use std::fmt;
+
+// ----------------------------------------------------------------------------
+// Temporal Flag
+
+/// A recombination parent that was first seen after the sequence was collected.
+///
+/// A recombinant can't predate its parents, so these are usually artifacts
+/// (ex. contamination, or a parent that is too similar to an older population).
+#[derive(Clone, Debug, PartialEq)]
+pub struct TemporalFlag {
+    pub parent: String,
+    /// The first-seen date of the parent, from the dataset.
+    pub first_seen: NaiveDate,
+    /// The collection date of the sequence, from the --metadata.
+    pub collection_date: NaiveDate,
+}
+
+impl fmt::Display for TemporalFlag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "parent {} first seen {} after collection {}",
+            self.parent, self.first_seen, self.collection_date
+        )
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Functions
+
+/// Check that the parents of a recombinant were seen before its collection date.
+///
+/// Returns the parents that postdate the sequence, which is empty if the
+/// sequence has no collection date, or the parents have no dates.
+pub fn check(dataset: &Dataset, recombination: &Recombination) -> Vec<TemporalFlag> {
+    let Some(collection_date) = recombination.sequence.collection_date else {
+        return Vec::new();
+    };
+    recombination
+        .parents
+        .iter()
+        .filter_map(|parent| {
+            let first_seen = *dataset.phylogeny.dates.get(parent)?;
+            (first_seen > collection_date).then(|| TemporalFlag {
+                parent: parent.clone(),
+                first_seen,
+                collection_date,
+            })
+        })
+        .collect_vec()
+}
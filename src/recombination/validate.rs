@@ -89,10 +89,32 @@ impl FromStr for Details {
 // ----------------------------------------------------------------------------
 
 /// Validate a best match and recombination to expected dataset values.
+///
+/// Failures are logged as warnings.
 pub fn validate(
     dataset: &Dataset,
     best_match: &SearchResult,
     recombination: &Recombination,
+) -> Result<Option<Validate>, Report> {
+    let validate = compare(dataset, best_match, recombination)?;
+    if let Some(validate) = &validate {
+        if matches!(validate.status, Status::Fail) {
+            warn!(
+                "{} failed validation: {details}",
+                best_match.sequence_id.replace("population_", ""),
+                details = validate.details.iter().join(", ")
+            );
+        }
+    }
+    Ok(validate)
+}
+
+/// Compare a best match and recombination to expected dataset values, without
+/// logging (see validate).
+pub fn compare(
+    dataset: &Dataset,
+    best_match: &SearchResult,
+    recombination: &Recombination,
 ) -> Result<Option<Validate>, Report> {
     // Use the sequence ID as the expected population
     let expected_population = best_match.sequence_id.replace("population_", "");
@@ -172,10 +194,6 @@ pub fn validate(
             {
                 validate.details.push(Details::IncorrectRecombinant);
             }
-            warn!(
-                "{expected_population} failed validation: {details}",
-                details = validate.details.iter().join(", ")
-            );
             validate
         };
 
@@ -0,0 +1,190 @@
+use crate::cli::run;
+use crate::dataset::{Dataset, SearchResult};
+use crate::recombination::search::{self, Profile};
+use crate::recombination::validate::{self, Status, Validate};
+use crate::recombination::Recombination;
+use crate::sequence::Sequence;
+use crate::utils::table::Table;
+use color_eyre::eyre::{Report, Result};
+use itertools::Itertools;
+
+// ----------------------------------------------------------------------------
+// Detection
+// ----------------------------------------------------------------------------
+
+/// How a designated recombinant population was detected, in a self-test run.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Detection {
+    /// Detected by a naive search (--naive).
+    Naive,
+    /// Detected with its designated parents, without an edge case.
+    DesignatedParents,
+    /// Detected only with its edge case.
+    EdgeCase,
+    /// Not detected, even with its designated parents and edge case.
+    Fail,
+}
+
+impl std::fmt::Display for Detection {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let detection = match self {
+            Detection::Naive => "naive",
+            Detection::DesignatedParents => "designated_parents",
+            Detection::EdgeCase => "edge_case",
+            Detection::Fail => "fail",
+        };
+        write!(f, "{detection}")
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Edge Case Stat
+// ----------------------------------------------------------------------------
+
+/// Validation of a designated recombinant population, with and without a
+/// naive search.
+#[derive(Clone, Debug)]
+pub struct EdgeCaseStat {
+    pub population: String,
+    /// Parameters of the dataset edge case that differ from the run (ex.
+    /// min_length=250), if the population has an edge case.
+    pub edge_case: Option<Vec<String>>,
+    pub naive: Validate,
+    pub validate: Validate,
+}
+
+impl EdgeCaseStat {
+    /// Classify how the population was detected.
+    pub fn detection(&self) -> Detection {
+        match (&self.naive.status, &self.validate.status) {
+            (Status::Pass, _) => Detection::Naive,
+            (Status::Fail, Status::Pass) if self.edge_case.is_some() => {
+                Detection::EdgeCase
+            }
+            (Status::Fail, Status::Pass) => Detection::DesignatedParents,
+            (Status::Fail, Status::Fail) => Detection::Fail,
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Functions
+// ----------------------------------------------------------------------------
+
+/// Compare the detection of a designated recombinant population (ex.
+/// population_XBB) with a naive search.
+///
+/// Returns None if the sequence is not a dataset population, or the population
+/// is not a designated recombinant.
+pub fn stat(
+    sequence: &Sequence,
+    dataset: &Dataset,
+    best_match: &SearchResult,
+    recombination: &Recombination,
+    populations: &[&String],
+    args: &run::Args,
+) -> Result<Option<EdgeCaseStat>, Report> {
+    let Some(population) = sequence.id.strip_prefix("population_") else {
+        return Ok(None);
+    };
+    if !dataset.phylogeny.recombinants.iter().any(|r| r == population) {
+        return Ok(None);
+    }
+    let Some(validate) = validate::compare(dataset, best_match, recombination)? else {
+        return Ok(None);
+    };
+
+    // the parent search of a naive run, without edge cases or designated parents
+    let naive_args = run::Args {
+        naive: true,
+        ..args.clone()
+    };
+    let mut naive_match = best_match.clone();
+    let mut profile = Profile::new();
+    let naive = search::all_parents(
+        sequence,
+        dataset,
+        &mut naive_match,
+        populations,
+        &naive_args,
+        &mut profile,
+    )
+    .unwrap_or_else(|_| Recombination::new(sequence));
+    let Some(naive) = validate::compare(dataset, &naive_match, &naive)? else {
+        return Ok(None);
+    };
+
+    let edge_case = dataset
+        .edge_cases
+        .iter()
+        .find(|e| e.population.as_deref() == Some(population))
+        .map(|edge_case| edge_case_parameters(args, edge_case));
+
+    Ok(Some(EdgeCaseStat {
+        population: population.to_string(),
+        edge_case,
+        naive,
+        validate,
+    }))
+}
+
+/// Get the parameters of an edge case that differ from the run args.
+fn edge_case_parameters(args: &run::Args, edge_case: &run::Args) -> Vec<String> {
+    let edge_case = args.apply_edge_case(edge_case).unwrap_or_else(|_| args.clone());
+    let parameters = [
+        ("max_iter", args.max_iter, edge_case.max_iter),
+        ("max_parents", args.max_parents, edge_case.max_parents),
+        (
+            "min_consecutive",
+            args.min_consecutive,
+            edge_case.min_consecutive,
+        ),
+        ("min_length", args.min_length, edge_case.min_length),
+        ("min_subs", args.min_subs, edge_case.min_subs),
+    ];
+    let mut changed = parameters
+        .into_iter()
+        .filter(|(_name, run, edge_case)| run != edge_case)
+        .map(|(name, _run, edge_case)| format!("{name}={edge_case}"))
+        .collect_vec();
+    if let Some(parents) = &edge_case.parents {
+        changed.push(format!("parents={}", parents.join("|")));
+    }
+    if edge_case.naive {
+        changed.push("naive=true".to_string());
+    }
+    changed
+}
+
+/// Create a table of edge case statistics (qc/edge_cases.tsv), sorted by
+/// population.
+pub fn table(stats: &[EdgeCaseStat]) -> Table {
+    let mut table = Table::new();
+    table.headers = vec![
+        "population",
+        "detection",
+        "edge_case",
+        "edge_case_parameters",
+        "naive_validate",
+        "naive_validate_details",
+        "validate",
+        "validate_details",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect_vec();
+
+    for stat in stats.iter().sorted_by(|a, b| a.population.cmp(&b.population)) {
+        table.rows.push(vec![
+            stat.population.clone(),
+            stat.detection().to_string(),
+            stat.edge_case.is_some().to_string(),
+            stat.edge_case.as_ref().map(|e| e.join(",")).unwrap_or_default(),
+            stat.naive.status.to_string(),
+            stat.naive.details.iter().join(","),
+            stat.validate.status.to_string(),
+            stat.validate.details.iter().join(","),
+        ]);
+    }
+    table
+}
@@ -0,0 +1,83 @@
+use crate::utils::table::Table;
+use color_eyre::eyre::Report;
+use itertools::Itertools;
+use std::any::Any;
+
+// ----------------------------------------------------------------------------
+// Failure
+// ----------------------------------------------------------------------------
+
+/// The stage of a run in which a sequence failed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Stage {
+    /// Reading the sequence from the input alignment.
+    Parse,
+    /// Searching for the best match and recombination parents.
+    Search,
+}
+
+impl std::fmt::Display for Stage {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let stage = match self {
+            Stage::Parse => "parse",
+            Stage::Search => "search",
+        };
+        write!(f, "{stage}")
+    }
+}
+
+/// A sequence that failed, and was skipped instead of stopping the run.
+#[derive(Clone, Debug)]
+pub struct Failure {
+    /// Sequence id, or the record number if the id couldn't be read.
+    pub strain: String,
+    pub stage: Stage,
+    /// The error chain, from the outermost error to the root cause.
+    pub error: String,
+}
+
+impl Failure {
+    /// Create a failure from an error report, keeping the full error chain.
+    pub fn new(strain: &str, stage: Stage, report: &Report) -> Self {
+        Failure {
+            strain: strain.to_string(),
+            stage,
+            error: report.chain().map(|e| e.to_string()).join(": "),
+        }
+    }
+
+    /// Create a failure from the payload of a panic.
+    pub fn from_panic(strain: &str, stage: Stage, payload: &(dyn Any + Send)) -> Self {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        Failure {
+            strain: strain.to_string(),
+            stage,
+            error: format!("panicked: {message}"),
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Functions
+// ----------------------------------------------------------------------------
+
+/// Create a table of failed sequences (qc/failures.tsv).
+pub fn table(failures: &[Failure]) -> Table {
+    let mut table = Table::new();
+    table.headers =
+        vec!["strain", "stage", "error"].into_iter().map(String::from).collect_vec();
+    for failure in failures {
+        // errors are kept on a single line, so that rows are not split
+        let error = failure.error.split_whitespace().join(" ");
+        table.rows.push(vec![
+            failure.strain.clone(),
+            failure.stage.to_string(),
+            error,
+        ]);
+    }
+    table
+}
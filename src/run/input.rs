@@ -0,0 +1,146 @@
+use crate::cli;
+use crate::dataset::Dataset;
+use crate::reads;
+use crate::sequence::Sequence;
+use crate::utils;
+use color_eyre::eyre::{Report, Result, WrapErr};
+use itertools::{Either, Itertools};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::iter;
+
+// ----------------------------------------------------------------------------
+// Source
+// ----------------------------------------------------------------------------
+
+/// The input a sequence was read from, which is carried into the results.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Source {
+    /// A dataset population (--populations).
+    Population,
+    /// A record of the input alignment (--alignment).
+    Alignment,
+    /// A read of an input sample (--reads).
+    Reads,
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let source = match self {
+            Source::Population => "population",
+            Source::Alignment => "alignment",
+            Source::Reads => "reads",
+        };
+        write!(f, "{source}")
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Records
+// ----------------------------------------------------------------------------
+
+/// A sequence read from an input, or the id and error of one that couldn't be.
+///
+/// These are skipped and reported as failures, unless --fail-fast.
+pub type Record = Result<Sequence, (String, Report)>;
+
+/// Chain the inputs of a run into one stream of records, tagged by source.
+///
+/// Records are read in order from the dataset populations (--populations),
+/// the alignment (--alignment), and then the reads (--reads). The alignment
+/// and reads are read lazily, as the stream is consumed. The populations are
+/// expanded up front, so that the stream doesn't borrow the dataset, which
+/// can still change before the search (ex. --knockout). Errors that stop the
+/// whole input (ex. a fasta that can't be parsed) are returned as the outer
+/// error.
+pub fn chain<'a>(
+    input: &'a cli::run::Input,
+    dataset: &Dataset,
+    mask: &'a Vec<usize>,
+) -> Result<impl Iterator<Item = Result<Record, Report>> + Send + 'a, Report> {
+    // ------------------------------------------------------------------------
+    // Dataset Populations
+
+    let populations = match &input.populations {
+        Some(populations) => {
+            info!("Parsing input populations: {populations:?}");
+            dataset.expand_populations(populations)?
+        }
+        None => Vec::new(),
+    };
+
+    // limit the amount of warnings emitted
+    let mut num_warnings = 0;
+    let max_warnings = 10;
+
+    let populations = populations.into_iter().filter_map(move |p| {
+        let Some(sequence) = dataset.populations.get(&p) else {
+            match num_warnings.cmp(&max_warnings) {
+                Ordering::Less => {
+                    warn!("Population {p} is not in the dataset populations fasta.")
+                }
+                Ordering::Equal => warn!("... (Additional warnings ommitted)"),
+                Ordering::Greater => (),
+            }
+            num_warnings += 1;
+            return None;
+        };
+        debug!("Adding population {p} to query sequences.");
+        let mut sequence = sequence.clone();
+        sequence.expand(&dataset.reference);
+        sequence.id = format!("population_{}", sequence.id);
+        sequence.source = Some(Source::Population);
+        Some(Ok(Ok(sequence)))
+    });
+    let populations = populations.collect_vec();
+
+    // ------------------------------------------------------------------------
+    // Alignment
+
+    let reference = dataset.reference.clone();
+    let alignment = match &input.alignment {
+        Some(alignment) => {
+            info!("Loading query alignment: {alignment:?}");
+            let records = utils::read_fasta(alignment)?.records();
+            let records = records.map(move |result| {
+                let record = result.wrap_err_with(|| {
+                    format!("Unable to parse alignment: {alignment:?}")
+                })?;
+                let id = record.id().to_string();
+                // ex. records that are not aligned to the reference
+                let record = Sequence::from_record(record, Some(&reference), mask)
+                    .map(|mut sequence| {
+                        sequence.source = Some(Source::Alignment);
+                        sequence
+                    })
+                    .map_err(|e| (id, e));
+                Ok(record)
+            });
+            Either::Left(records)
+        }
+        None => Either::Right(iter::empty()),
+    };
+
+    // ------------------------------------------------------------------------
+    // Reads
+
+    let reference = dataset.reference.clone();
+    let reads = input.reads.iter().flatten().flat_map(move |path| {
+        info!("Mapping query reads: {path:?}");
+        let records: Vec<Record> = match reads::parse_reads(path, &reference, mask) {
+            Ok(sample) => sample
+                .into_iter()
+                .map(|mut sequence| {
+                    sequence.source = Some(Source::Reads);
+                    Ok(sequence)
+                })
+                .collect(),
+            Err(e) => vec![Err((path.to_string_lossy().to_string(), e))],
+        };
+        records.into_iter().map(Ok)
+    });
+
+    Ok(populations.into_iter().chain(alignment).chain(reads))
+}
@@ -0,0 +1,350 @@
+use crate::utils;
+use chrono::prelude::*;
+use color_eyre::eyre::{eyre, Report, Result, WrapErr};
+use color_eyre::Help;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::default::Default;
+use std::fs::create_dir_all;
+use std::path::{Path, PathBuf};
+
+/// Version of the run output directory layout.
+///
+/// Increment this whenever files or directories are renamed, moved, or removed
+/// so that downstream tooling can detect incompatible outputs.
+pub const LAYOUT_VERSION: usize = 1;
+
+/// File name of the manifest at the root of the output directory.
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Output directory name that is replaced by a date-stamped run name.
+pub const AUTO_OUTPUT_DIR: &str = "auto";
+
+/// Name of the link to the most recent date-stamped output directory.
+pub const LATEST_LINK: &str = "latest";
+
+// ----------------------------------------------------------------------------
+// Layout
+// ----------------------------------------------------------------------------
+
+/// The output directory layout of rebar run.
+///
+/// ```text
+/// <output-dir>/
+/// ├── manifest.json
+/// ├── run_args.json
+/// ├── summary.json
+/// ├── auspice.json
+/// ├── linelist/
+/// │   ├── linelist.tsv
+/// │   ├── linelist.<json|parquet>
+/// │   ├── genes.tsv
+/// │   └── reads.tsv
+/// ├── barcodes/
+/// │   └── <unique_key>.tsv
+/// ├── plots/
+/// │   └── <unique_key>.<png|svg>
+/// ├── samples/
+/// │   └── <strain>.json
+/// ├── qc/
+/// │   ├── breakpoints.tsv
+/// │   ├── edge_cases.tsv
+/// │   ├── failures.tsv
+/// │   ├── negatives.tsv
+/// │   └── novelty.tsv
+/// └── debug/
+/// ```
+#[derive(Clone, Debug)]
+pub struct Layout {
+    pub root: PathBuf,
+}
+
+impl Layout {
+    pub fn new(root: &Path) -> Self {
+        Layout {
+            root: root.to_path_buf(),
+        }
+    }
+
+    /// Directories that must exist in every output directory.
+    pub fn dirs(&self) -> Vec<PathBuf> {
+        vec![
+            self.linelist_dir(),
+            self.barcodes_dir(),
+            self.plots_dir(),
+            self.qc_dir(),
+            self.debug_dir(),
+        ]
+    }
+
+    /// Directories that only exist in some output directories (ex. samples).
+    pub fn optional_dirs(&self) -> Vec<PathBuf> {
+        vec![self.samples_dir()]
+    }
+
+    /// Create all directories of the layout.
+    pub fn create(&self) -> Result<(), Report> {
+        for dir in self.dirs() {
+            create_dir_all(&dir)
+                .wrap_err_with(|| format!("Failed to create directory: {dir:?}"))?;
+        }
+        Ok(())
+    }
+
+    pub fn manifest(&self) -> PathBuf {
+        self.root.join(MANIFEST_FILE)
+    }
+
+    pub fn run_args(&self) -> PathBuf {
+        self.root.join("run_args.json")
+    }
+
+    pub fn summary(&self) -> PathBuf {
+        self.root.join("summary.json")
+    }
+
+    pub fn auspice(&self) -> PathBuf {
+        self.root.join("auspice.json")
+    }
+
+    pub fn linelist_dir(&self) -> PathBuf {
+        self.root.join("linelist")
+    }
+
+    pub fn linelist(&self) -> PathBuf {
+        self.linelist_dir().join("linelist.tsv")
+    }
+
+    /// The linelist in an additional format, by extension (ex. json).
+    pub fn linelist_as(&self, extension: &str) -> PathBuf {
+        self.linelist().with_extension(extension)
+    }
+
+    pub fn genes(&self) -> PathBuf {
+        self.linelist_dir().join("genes.tsv")
+    }
+
+    pub fn reads(&self) -> PathBuf {
+        self.linelist_dir().join("reads.tsv")
+    }
+
+    pub fn barcodes_dir(&self) -> PathBuf {
+        self.root.join("barcodes")
+    }
+
+    pub fn plots_dir(&self) -> PathBuf {
+        self.root.join("plots")
+    }
+
+    pub fn samples_dir(&self) -> PathBuf {
+        self.root.join("samples")
+    }
+
+    pub fn qc_dir(&self) -> PathBuf {
+        self.root.join("qc")
+    }
+
+    pub fn breakpoints(&self) -> PathBuf {
+        self.qc_dir().join("breakpoints.tsv")
+    }
+
+    pub fn edge_cases(&self) -> PathBuf {
+        self.qc_dir().join("edge_cases.tsv")
+    }
+
+    pub fn failures(&self) -> PathBuf {
+        self.qc_dir().join("failures.tsv")
+    }
+
+    pub fn negatives(&self) -> PathBuf {
+        self.qc_dir().join("negatives.tsv")
+    }
+
+    pub fn novelty(&self) -> PathBuf {
+        self.qc_dir().join("novelty.tsv")
+    }
+
+    pub fn debug_dir(&self) -> PathBuf {
+        self.root.join("debug")
+    }
+
+    /// Find the linelist, falling back to the pre-layout location (root).
+    pub fn find_linelist(&self) -> Result<PathBuf, Report> {
+        let linelist = self.linelist();
+        let legacy = self.root.join("linelist.tsv");
+        if linelist.exists() {
+            Ok(linelist)
+        } else if legacy.exists() {
+            Ok(legacy)
+        } else {
+            Err(eyre!("Linelist file {linelist:?} does not exist."))
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Auto Output Directory
+// ----------------------------------------------------------------------------
+
+/// Check if an output directory should be named automatically (--output-dir auto).
+pub fn is_auto(output_dir: &Path) -> bool {
+    output_dir.file_name().is_some_and(|name| name == AUTO_OUTPUT_DIR)
+}
+
+/// Name a date-stamped output directory, in place of --output-dir auto.
+///
+/// The name is rebar_<dataset>_<tag>_<timestamp> (local time), in the parent
+/// directory of auto. A suffix is added if the directory already exists, so
+/// previous runs are never overwritten.
+pub fn auto_output_dir(output_dir: &Path, name: &str, tag: &str) -> PathBuf {
+    let parent = output_dir.parent().unwrap_or(Path::new(""));
+    let timestamp = Local::now().format("%Y-%m-%d_%H-%M-%S");
+    let run_name = utils::sanitize_file_name(&format!("rebar_{name}_{tag}_{timestamp}"));
+
+    let mut path = parent.join(&run_name);
+    let mut num_duplicates = 0;
+    while path.exists() {
+        num_duplicates += 1;
+        path = parent.join(format!("{run_name}_{num_duplicates}"));
+    }
+    path
+}
+
+/// Point the latest link at a date-stamped output directory, replacing the
+/// link to a previous run.
+///
+/// The link is relative, so that the parent directory can be moved.
+pub fn link_latest(output_dir: &Path) -> Result<(), Report> {
+    let parent = output_dir.parent().unwrap_or(Path::new(""));
+    let link = parent.join(LATEST_LINK);
+    let target = output_dir
+        .file_name()
+        .ok_or_else(|| eyre!("Output directory has no name: {output_dir:?}"))?;
+
+    if link.symlink_metadata().is_ok() {
+        if !link.is_symlink() {
+            return Err(eyre!("Failed to link latest run, {link:?} is not a link."))
+                .suggestion("Please rename or remove it, to link the latest run.");
+        }
+        std::fs::remove_file(&link)
+            .wrap_err_with(|| format!("Failed to remove link: {link:?}"))?;
+    }
+
+    #[cfg(unix)]
+    let result = std::os::unix::fs::symlink(target, &link);
+    #[cfg(windows)]
+    let result = std::os::windows::fs::symlink_dir(target, &link);
+    result.wrap_err_with(|| format!("Failed to create link: {link:?}"))?;
+
+    Ok(())
+}
+
+// ----------------------------------------------------------------------------
+// Manifest
+// ----------------------------------------------------------------------------
+
+/// A record of all files written to a run output directory.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Manifest {
+    pub layout_version: usize,
+    pub cli_version: String,
+    pub date_created: DateTime<Utc>,
+    pub files: Vec<ManifestFile>,
+}
+
+/// A file in the manifest, with a path relative to the output directory.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct ManifestFile {
+    pub path: PathBuf,
+    pub bytes: u64,
+}
+
+impl Default for Manifest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Manifest {
+    pub fn new() -> Self {
+        Manifest {
+            layout_version: LAYOUT_VERSION,
+            cli_version: env!("CARGO_PKG_VERSION").to_string(),
+            date_created: Utc::now(),
+            files: Vec::new(),
+        }
+    }
+
+    /// Create a manifest from all files currently in the output directory.
+    pub fn from_layout(layout: &Layout) -> Result<Self, Report> {
+        let mut manifest = Manifest::new();
+
+        let mut paths = vec![layout.run_args(), layout.summary(), layout.auspice()];
+        for dir in layout.dirs().into_iter().chain(layout.optional_dirs()) {
+            if !dir.exists() {
+                continue;
+            }
+            let files = std::fs::read_dir(&dir)
+                .wrap_err_with(|| format!("Failed to read directory: {dir:?}"))?;
+            for result in files {
+                let path = result?.path();
+                if path.is_file() {
+                    paths.push(path);
+                }
+            }
+        }
+
+        manifest.files = paths
+            .into_iter()
+            .filter(|path| path.exists())
+            .sorted()
+            .map(|path| {
+                let bytes = path.metadata()?.len();
+                // always record forward slashes, regardless of operating system
+                let path = path
+                    .strip_prefix(&layout.root)?
+                    .iter()
+                    .map(|c| c.to_string_lossy())
+                    .join("/");
+                let path = PathBuf::from(path);
+                Ok(ManifestFile { path, bytes })
+            })
+            .collect::<Result<Vec<_>, Report>>()?;
+
+        Ok(manifest)
+    }
+
+    /// Read manifest from file.
+    pub fn read(path: &Path) -> Result<Manifest, Report> {
+        let manifest = utils::read_to_string(path)?;
+        let mut manifest: Manifest = serde_json::from_str(&manifest)
+            .wrap_err_with(|| format!("Failed to parse file: {path:?}"))?;
+        // manifest might have been written on windows
+        manifest
+            .files
+            .iter_mut()
+            .for_each(|file| file.path = utils::normalize_separators(&file.path));
+
+        if manifest.layout_version > LAYOUT_VERSION {
+            return Err(eyre!(
+                "Output layout version {} is newer than supported version {LAYOUT_VERSION}.",
+                manifest.layout_version
+            ))
+            .suggestion("Please update rebar to read this output directory.");
+        }
+
+        Ok(manifest)
+    }
+
+    /// Write manifest to file.
+    pub fn write(&self, path: &Path) -> Result<(), Report> {
+        // parse to string
+        let output = serde_json::to_string_pretty(self)
+            .wrap_err_with(|| format!("Failed to parse: {self:?}"))?;
+
+        // write to file
+        utils::write_atomic(path, format!("{}\n", output))?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,162 @@
+use color_eyre::eyre::{eyre, Report, Result};
+use color_eyre::Help;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use sysinfo::{Pid, System};
+
+/// Time between samples of the memory usage.
+pub const SAMPLE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Fraction of --max-memory at which the run switches to low-memory strategies.
+pub const LOW_MEMORY_FRACTION: f64 = 0.8;
+
+/// Number of bytes in a megabyte (MB).
+pub const BYTES_PER_MB: u64 = 1_000_000;
+
+// ----------------------------------------------------------------------------
+// Memory State
+// ----------------------------------------------------------------------------
+
+/// The memory state of a run, relative to its limit.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum State {
+    /// Below the low-memory threshold (or no limit).
+    Normal,
+    /// Above the low-memory threshold, use lower-memory strategies.
+    LowMemory,
+    /// Above the limit, stop the run.
+    Exceeded,
+}
+
+// ----------------------------------------------------------------------------
+// Memory Usage
+// ----------------------------------------------------------------------------
+
+/// Summary of the memory usage of a run.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Usage {
+    /// Peak resident set size (RSS), in megabytes.
+    pub peak_rss_mb: u64,
+    /// Number of memory samples taken.
+    pub samples: usize,
+    /// Whether the run switched to low-memory strategies.
+    pub low_memory: bool,
+}
+
+// ----------------------------------------------------------------------------
+// Memory Monitor
+// ----------------------------------------------------------------------------
+
+/// Periodically samples the resident set size (RSS) of the process, and
+/// compares it to an optional limit (--max-memory).
+///
+/// Samples are taken by a single thread, while workers and the writer read the
+/// state to decide which strategies to use.
+#[derive(Debug)]
+pub struct Monitor {
+    /// Limit in bytes.
+    limit: Option<u64>,
+    pid: Option<Pid>,
+    system: Mutex<System>,
+    peak: AtomicU64,
+    samples: AtomicUsize,
+    state: Mutex<State>,
+}
+
+impl Monitor {
+    /// Create a monitor, with an optional limit in megabytes.
+    pub fn new(max_memory: Option<u64>) -> Self {
+        Monitor {
+            limit: max_memory.map(|mb| mb * BYTES_PER_MB),
+            pid: sysinfo::get_current_pid().ok(),
+            system: Mutex::new(System::new()),
+            peak: AtomicU64::new(0),
+            samples: AtomicUsize::new(0),
+            state: Mutex::new(State::Normal),
+        }
+    }
+
+    /// Get the current resident set size (RSS) of the process, in bytes.
+    ///
+    /// Returns None if memory usage is not supported on this platform.
+    pub fn rss(&self) -> Result<Option<u64>, Report> {
+        let Some(pid) = self.pid else {
+            return Ok(None);
+        };
+        let mut system =
+            self.system.lock().map_err(|e| eyre!("Memory monitor is poisoned: {e}"))?;
+        if !system.refresh_process(pid) {
+            return Ok(None);
+        }
+        Ok(system.process(pid).map(|process| process.memory()))
+    }
+
+    /// Sample the memory usage, and update the peak and state.
+    ///
+    /// Returns the new state, if this sample changed it. The state only
+    /// escalates, since memory is rarely returned to the operating system.
+    pub fn sample(&self) -> Result<Option<State>, Report> {
+        let Some(rss) = self.rss()? else {
+            return Ok(None);
+        };
+        self.samples.fetch_add(1, Ordering::Relaxed);
+        self.peak.fetch_max(rss, Ordering::Relaxed);
+
+        let Some(limit) = self.limit else {
+            return Ok(None);
+        };
+        let sampled = if rss > limit {
+            State::Exceeded
+        } else if rss as f64 > limit as f64 * LOW_MEMORY_FRACTION {
+            State::LowMemory
+        } else {
+            State::Normal
+        };
+        let mut state =
+            self.state.lock().map_err(|e| eyre!("Memory monitor is poisoned: {e}"))?;
+        if sampled > *state {
+            *state = sampled;
+            Ok(Some(sampled))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Get the current memory state.
+    pub fn state(&self) -> Result<State, Report> {
+        let state =
+            self.state.lock().map_err(|e| eyre!("Memory monitor is poisoned: {e}"))?;
+        Ok(*state)
+    }
+
+    /// Return an error if the memory limit was exceeded.
+    pub fn check(&self) -> Result<(), Report> {
+        if self.state()? == State::Exceeded {
+            let limit = self.limit.unwrap_or_default() / BYTES_PER_MB;
+            let peak = self.peak.load(Ordering::Relaxed) / BYTES_PER_MB;
+            return Err(eyre!(
+                "Memory usage ({peak} MB) exceeded --max-memory ({limit} MB)."
+            ))
+            .suggestion(
+                "Please split the input into smaller batches, reduce --threads or \
+                --queue-size, or increase --max-memory.",
+            );
+        }
+        Ok(())
+    }
+
+    /// Summarize the memory usage, if any samples were taken.
+    pub fn usage(&self) -> Result<Option<Usage>, Report> {
+        let samples = self.samples.load(Ordering::Relaxed);
+        if samples == 0 {
+            return Ok(None);
+        }
+        Ok(Some(Usage {
+            peak_rss_mb: self.peak.load(Ordering::Relaxed) / BYTES_PER_MB,
+            samples,
+            low_memory: self.state()? >= State::LowMemory,
+        }))
+    }
+}
@@ -1,36 +1,92 @@
+pub mod edge_cases;
+pub mod failures;
+pub mod input;
+pub mod layout;
+pub mod memory;
+pub mod order;
+pub mod stats;
+
 use crate::cli;
 use crate::dataset;
 use crate::export;
+use crate::reads;
 use crate::recombination;
 
 use crate::dataset::{attributes::Name, SearchResult};
+use crate::export::sink::OutputSink;
 use crate::recombination::Recombination;
+use crate::run::failures::{Failure, Stage};
+use crate::run::layout::{Layout, Manifest};
 use crate::sequence::Sequence;
-use bio::io::fasta;
+use crate::utils;
+use chrono::NaiveDate;
 use color_eyre::eyre::{eyre, Report, Result, WrapErr};
-use indicatif::{style::ProgressStyle, ProgressBar};
+use color_eyre::Help;
+use indicatif::style::ProgressStyle;
 use itertools::Itertools;
 use log::{debug, info, warn};
-use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
-use std::fs::{create_dir_all, File};
-use std::io::Write;
+use std::collections::{HashMap, HashSet};
+use std::fs::create_dir_all;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use typed_arena::Arena;
 
 /// Run rebar on input alignment and/or dataset population(s)
 pub fn run(args: &mut cli::run::Args) -> Result<(), Report> {
+    run_with_sinks(args, Vec::new())
+}
+
+/// Run rebar, and also stream results to custom output sinks.
+///
+/// Sinks receive results in the same order as the linelist, after the
+/// built-in outputs (ex. to write results directly to a database).
+pub fn run_with_sinks(
+    args: &mut cli::run::Args,
+    mut sinks: Vec<Box<dyn OutputSink>>,
+) -> Result<(), Report> {
+    let run_start = Instant::now();
+
     // copy args for export/seralizing
-    let args_export = args.clone();
+    let mut args_export = args.clone();
 
-    // Warn if the directory already exists
-    if !args.output_dir.exists() {
-        info!("Creating output directory: {:?}", &args.output_dir);
-        create_dir_all(&args.output_dir)?;
-    } else {
-        warn!(
-            "Proceed with caution! --output-dir {:?} already exists.",
-            args.output_dir
-        );
+    // Check linelist columns before the (potentially long) run
+    if let Some(columns) = &args.linelist_columns {
+        export::check_linelist_columns(columns, args.profile)?;
     }
 
+    if args.queue_size == Some(0) {
+        return Err(eyre!("Invalid --queue-size: 0"))
+            .suggestion("Please provide a queue size of at least 1.");
+    }
+
+    if let Some(max_missing) = args.max_missing_in_region {
+        if !(0.0..=1.0).contains(&max_missing) {
+            return Err(eyre!("Invalid --max-missing-in-region: {max_missing}"))
+                .suggestion("Please provide a fraction between 0 and 1.");
+        }
+    }
+
+    // read the previous summary before the (potentially long) run
+    let previous_summary = match &args.previous_summary {
+        Some(path) => {
+            if !(args.novelty_fold_change >= 1.0 && args.novelty_fold_change.is_finite())
+            {
+                return Err(eyre!(
+                    "Invalid --novelty-fold-change: {}",
+                    args.novelty_fold_change
+                ))
+                .suggestion("Please provide a fold change of at least 1.");
+            }
+            Some(stats::Summary::read_previous(path)?)
+        }
+        None => None,
+    };
+
     // check how many threads are available on the system
     let default_thread_pool =
         rayon::ThreadPoolBuilder::new().build().expect("Failed to build thread pool.");
@@ -40,9 +96,11 @@ pub fn run(args: &mut cli::run::Args) -> Result<(), Report> {
     );
 
     // warn the user if they requested more than their system has available
-    // if so, default to the system threads
+    // if so, default to the system threads, as with --threads 0
     let mut num_threads = args.threads;
-    if args.threads > default_thread_pool.current_num_threads() {
+    if args.threads == 0 {
+        num_threads = default_thread_pool.current_num_threads();
+    } else if args.threads > default_thread_pool.current_num_threads() {
         warn!(
             "Requested --threads {} is greater than the available threads.",
             args.threads
@@ -66,73 +124,108 @@ pub fn run(args: &mut cli::run::Args) -> Result<(), Report> {
     // Collect files in dataset_dir into a dataset object
     // This mainly includes parent populations sequences
     //   and optionally a phylogenetic representation.
-    let mut dataset = dataset::load::dataset(&args.dataset_dir, &args.mask)?;
-
-    // init a container to hold query sequences, dataset
-    // populations and/or sequences from an input alignment
-    let mut sequences = Vec::new();
-    // keep track of ids we've seen to remove duplicates later
-    let mut ids_seen = Vec::new();
+    // With --self-dataset, the input alignment is the dataset.
+    let mut dataset = if args.self_dataset {
+        let alignment = args
+            .input
+            .alignment
+            .as_ref()
+            .ok_or_else(|| eyre!("An --alignment is required for --self-dataset."))?;
+        dataset::load::alignment(alignment, &args.mask)?
+    } else {
+        let dataset_dir = args
+            .dataset_dir
+            .as_ref()
+            .ok_or_else(|| eyre!("A --dataset-dir or --dataset-url is required."))?;
+        if args.strict {
+            let summary_path = dataset_dir.join("summary.json");
+            info!("Validating dataset summary (strict): {summary_path:?}");
+            let summary = dataset::attributes::Summary::read_strict(&summary_path)?;
+            summary
+                .verify_checksums(dataset_dir)
+                .wrap_err_with(|| format!("Failed strict validation: {dataset_dir:?}"))?;
+        }
+        dataset::load::dataset(dataset_dir, &args.mask)?
+    };
+    dataset.name_matching = args.name_matching;
 
-    // ------------------------------------------------------------------------
-    // Parse Input Populations
-    // ------------------------------------------------------------------------
+    // name the output directory after the dataset, with --output-dir auto
+    let auto_output_dir = layout::is_auto(&args.output_dir);
+    if auto_output_dir {
+        let (name, tag) = (dataset.name.to_string(), dataset.tag.to_string());
+        args.output_dir = layout::auto_output_dir(&args.output_dir, &name, &tag);
+        args_export.output_dir = args.output_dir.clone();
+    }
 
-    // this step is pretty fast, don't really need a progress bar here
+    // Warn if the directory already exists
+    if !args.output_dir.exists() {
+        info!("Creating output directory: {:?}", &args.output_dir);
+        create_dir_all(&args.output_dir)?;
+    } else {
+        warn!(
+            "Proceed with caution! --output-dir {:?} already exists.",
+            args.output_dir
+        );
+    }
+    let layout = Layout::new(&args.output_dir);
+    layout.create()?;
 
-    if let Some(populations) = &args.input.populations {
-        info!("Parsing input populations: {populations:?}");
+    // sequences that failed are skipped and reported, unless --fail-fast
+    let mut failures: Vec<Failure> = Vec::new();
 
-        // limit the amount of warnings emitted
-        let mut num_warnings = 0;
-        let max_warnings = 10;
+    // ------------------------------------------------------------------------
+    // Dataset As Of Date
+    // ------------------------------------------------------------------------
 
-        dataset.expand_populations(populations)?.into_iter().for_each(|p| {
-            if !dataset.populations.contains_key(&p) {
-                if num_warnings < max_warnings {
-                    warn!("Population {p} is not in the dataset populations fasta.");
-                } else {
-                    warn!("... (Additional warnings ommitted)");
-                }
-                num_warnings += 1;
-            } else {
-                debug!("Adding population {p} to query sequences.");
-                let mut sequence = dataset.populations.get(&p).unwrap().clone();
-                sequence.id = format!("population_{}", sequence.id);
-                ids_seen.push(sequence.id.clone());
-                sequences.push(sequence.clone());
-            }
-        });
+    // this happens before parsing input populations, so that populations from
+    // the future can't be queried either
+    if let Some(as_of) = &args.as_of {
+        info!("Restricting dataset to populations as of: {as_of}");
+        if dataset.phylogeny.dates.is_empty() {
+            warn!("--as-of was supplied, but the dataset has no population dates.");
+        }
+        let removed = dataset.as_of(as_of)?;
+        info!(
+            "Removed {} population(s) dated after {as_of}.",
+            removed.len()
+        );
+        let undated = dataset
+            .populations
+            .keys()
+            .filter(|pop| !dataset.phylogeny.dates.contains_key(*pop))
+            .count();
+        if undated > 0 {
+            warn!("{undated} population(s) have no date and were retained.");
+        }
     }
 
     // ------------------------------------------------------------------------
-    // Parse Input Alignment
+    // Parse Input Sequences
     // ------------------------------------------------------------------------
 
-    if let Some(alignment) = &args.input.alignment {
-        info!("Loading query alignment: {:?}", alignment);
-        let alignment_reader = fasta::Reader::from_file(alignment)
-            .map_err(|e| eyre!(e))
-            .wrap_err("Failed to read file: {alignment:?}")?;
+    // the dataset populations, alignment, and reads are read as one stream,
+    // and each sequence is tagged with its source. The stream is read by the
+    // search workers as they are ready for the next sequence, so that parsed
+    // sequences don't queue up ahead of the search.
+    let (input, mask) = (args.input.clone(), args.mask.clone());
+    let records = input::chain(&input, &dataset, &mask)?;
 
-        for result in alignment_reader.records() {
-            let record = result.wrap_err("Unable to parse alignment: {alignment:?}")?;
-            let sequence =
-                Sequence::from_record(record, Some(&dataset.reference), &args.mask)?;
+    // ------------------------------------------------------------------------
+    // Sample Metadata (Optional)
 
-            // check for duplicates
-            if ids_seen.contains(&sequence.id) {
+    // dates are assigned to sequences as they are read
+    let collection_dates = match &args.metadata {
+        Some(metadata) => {
+            info!("Reading metadata: {metadata:?}");
+            if dataset.phylogeny.dates.is_empty() {
                 warn!(
-                    "Sequence {} is duplicated, retaining first one.",
-                    sequence.id
+                    "--metadata was supplied, but the dataset has no population dates."
                 );
-                continue;
-            } else {
-                ids_seen.push(sequence.id.clone());
-                sequences.push(sequence);
             }
+            Some(read_collection_dates(metadata)?)
         }
-    }
+        None => None,
+    };
 
     // ------------------------------------------------------------------------
     // Parse and expand input parents
@@ -148,33 +241,66 @@ pub fn run(args: &mut cli::run::Args) -> Result<(), Report> {
 
     if let Some(knockout) = &args.knockout {
         info!("Performing dataset knockout: {knockout:?}");
+        let knockout = knockout.iter().map(String::as_str).collect_vec();
+        let knockout_expanded = dataset.knockout(&knockout)?;
+        debug!("Expanded dataset knockout: {knockout_expanded:?}");
+        info!(
+            "Removed {} population(s) from the dataset.",
+            knockout_expanded.len()
+        );
+        args.knockout = Some(knockout_expanded);
+    }
+
+    // ------------------------------------------------------------------------
+    // Approximate Search Index (Optional)
+    // ------------------------------------------------------------------------
 
-        // Check to make sure wildcards are used in all knockouts
-        // Weird things happend if you don't!
-        let knockout_no_wildcard =
-            knockout.iter().filter(|p| !p.contains('*')).collect_vec();
-        if !knockout_no_wildcard.is_empty() {
-            warn!("Proceed with caution! Weird things can happen when you run a knockout without descendants (no '*'): {knockout_no_wildcard:?}.")
+    if let Some(k) = args.approximate {
+        if k == 0 {
+            return Err(eyre!("Invalid --approximate: {k}"))
+                .suggestion("Please provide at least 1 population to score exactly.");
         }
+        info!("Building MinHash index of population substitutions.");
+        dataset.minhash = Some(dataset::minhash::MinHashIndex::from_populations(
+            &dataset.populations,
+        ));
+    }
 
-        // Expanded populations (in case wildcard * is provided)
-        let knockout_expanded = dataset.expand_populations(knockout)?;
-        debug!("Expanded dataset knockout: {knockout:?}");
-        debug!("Removing knockout populations from the fasta.");
-        dataset.populations.retain(|id, _| !knockout_expanded.contains(id));
+    // ------------------------------------------------------------------------
+    // Barcode Classes (Optional)
+    // ------------------------------------------------------------------------
 
-        debug!("Removing knockout populations from the mutations.");
-        dataset.mutations.iter_mut().for_each(|(_sub, populations)| {
-            populations.retain(|p| !knockout_expanded.contains(p));
-        });
+    if args.collapse_barcodes {
+        let classes =
+            dataset::barcodes::BarcodeClasses::from_populations(&dataset.populations);
+        info!(
+            "Collapsed {} populations with identical barcodes into {} classes.",
+            classes.num_collapsed() + classes.collapsed().count(),
+            classes.collapsed().count()
+        );
+        dataset.barcode_classes = Some(classes);
+    }
 
-        if !dataset.phylogeny.is_empty() {
-            for p in &knockout_expanded {
-                dataset.phylogeny.remove(p)?;
-            }
-        }
+    // ------------------------------------------------------------------------
+    // Primer Scheme (Optional)
+    // ------------------------------------------------------------------------
 
-        args.knockout = Some(knockout_expanded);
+    if let Some(primer_bed) = &args.primer_bed {
+        info!("Reading primer scheme: {primer_bed:?}");
+        let scheme = recombination::amplicons::PrimerScheme::read(primer_bed)?;
+        if scheme.amplicons.is_empty() {
+            return Err(eyre!("No amplicons were found in: {primer_bed:?}"))
+                .suggestion("Please check that --primer-bed is tab-separated.");
+        }
+        info!(
+            "Found {} primers in {} amplicons of the primer scheme.",
+            scheme.primers.len(),
+            scheme.amplicons.len()
+        );
+        dataset.primer_scheme = Some(scheme);
+    } else if args.mask_primers {
+        return Err(eyre!("--mask-primers requires a primer scheme."))
+            .suggestion("Please provide the primer scheme with --primer-bed.");
     }
 
     // ------------------------------------------------------------------------
@@ -184,7 +310,8 @@ pub fn run(args: &mut cli::run::Args) -> Result<(), Report> {
     info!("Running recombination search.");
 
     // this step is the slowest, use progress bar and parallel threads
-    let progress_bar = ProgressBar::new(sequences.len() as u64);
+    // the number of sequences grows as the input stream is read
+    let progress_bar = utils::logging::progress_bar(0);
     progress_bar.set_style(progress_bar_style);
 
     // adjust search populations based on args.parents and args.knockout
@@ -197,29 +324,200 @@ pub fn run(args: &mut cli::run::Args) -> Result<(), Report> {
     if let Some(populations) = &args.knockout {
         parent_search_populations.retain(|pop| !populations.contains(pop))
     }
+    // if args.max_date supplied on the CLI
+    if let Some(max_date) = &args.max_date {
+        let dates = &dataset.phylogeny.dates;
+        if dates.is_empty() {
+            warn!("--max-date was supplied, but the dataset has no population dates.");
+        }
+        parent_search_populations
+            .retain(|pop| dates.get(*pop).map(|date| date <= max_date).unwrap_or(true));
+        info!(
+            "Number of parent populations dated on or before {max_date}: {}",
+            parent_search_populations.len()
+        );
+    }
+
+    // with --self-dataset, a sequence can't be its own parent
+    let sequence_search_populations = |sequence: &Sequence| {
+        let mut populations = parent_search_populations.clone();
+        if args.self_dataset {
+            populations.retain(|pop| **pop != sequence.id);
+        }
+        populations
+    };
+
+    // ------------------------------------------------------------------------
+    // Input Sequences
+
+    // the input stream is shared by the workers, along with the index of the
+    // next sequence in the input order, and the ids seen to skip duplicates
+    let inputs = Mutex::new((records.fuse(), 0, HashSet::new()));
+    // the sequences read by each worker, which the results borrow until the
+    // outputs after the linelist are written
+    let mut arenas = (0..num_threads).map(|_| Arena::new()).collect_vec();
+    let (num_dated, num_masked) = (AtomicUsize::new(0), AtomicUsize::new(0));
+
+    // Read the next sequence from the input stream, with its index.
+    // Sequences that couldn't be parsed are returned as failures, so that
+    // they are written in order with the other results.
+    type Input = (usize, Result<Sequence, Failure>);
+    let next_input = || -> Result<Option<Input>, Report> {
+        let mut inputs =
+            inputs.lock().map_err(|e| eyre!("Input queue is poisoned: {e}"))?;
+        let (records, next_index, ids_seen) = &mut *inputs;
+        for record in records {
+            let sequence = match record? {
+                Ok(sequence) => Ok(sequence),
+                Err((id, e)) => Err(Failure::new(&id, Stage::Parse, &e)),
+            };
+            // check for duplicates
+            if let Ok(sequence) = &sequence {
+                if !ids_seen.insert(sequence.id.clone()) {
+                    warn!(
+                        "Sequence {} is duplicated, retaining first one.",
+                        sequence.id
+                    );
+                    continue;
+                }
+            }
+            let index = *next_index;
+            *next_index += 1;
+            progress_bar.inc_length(1);
+            return Ok(Some((index, sequence)));
+        }
+        Ok(None)
+    };
+
+    // Assign the collection date and mask primers of a sequence, once read.
+    let prepare = |sequence: &mut Sequence| {
+        let date = collection_dates.as_ref().and_then(|dates| dates.get(&sequence.id));
+        if let Some(date) = date {
+            sequence.collection_date = Some(*date);
+            num_dated.fetch_add(1, Ordering::Relaxed);
+        }
+        if let Some(scheme) = &dataset.primer_scheme {
+            if args.mask_primers {
+                num_masked.fetch_add(scheme.mask_primers(sequence), Ordering::Relaxed);
+            }
+        }
+    };
+
+    // ------------------------------------------------------------------------
+    // Best Match (Consensus)
+    //
+    // search for the best match in the dataset to each sequence.
+    // this will represent the consensus population call.
+
+    // Consensus populations observed in multiple sequences are likely to be
+    // the primary parent of many searches, so with --precompute-pairs they are
+    // profiled against all candidate parents once they are seen again.
+    let consensus_counts = Mutex::new(HashMap::new());
+    let num_precomputed = AtomicUsize::new(0);
+    if args.precompute_pairs {
+        info!("Precomputing parent pairs of frequently observed populations.");
+    }
+
+    // the search time is kept for the profile of each sequence
+    // a search that panics is a failure of the sequence, not the run
+    let best_match = |sequence: &Sequence| {
+        debug!("Identifying best match (consensus population).");
+        let start = Instant::now();
+        let populations =
+            args.self_dataset.then(|| sequence_search_populations(sequence));
+        let search_result =
+            panic::catch_unwind(AssertUnwindSafe(|| match args.approximate {
+                Some(k) => dataset.search_approximate(sequence, populations.as_ref(), k),
+                None => dataset.search(sequence, populations.as_ref(), None),
+            }))
+            .map_err(|e| Failure::from_panic(&sequence.id, Stage::Search, e.as_ref()))?;
+        let runtime = start.elapsed();
 
-    // Search for the best match and recombination parents for each sequence.
-    // This loop/closure is structured weirdly for rayon compatability, and the
+        if let (true, Ok(result)) = (args.precompute_pairs, &search_result) {
+            let population = &result.consensus_population;
+            let mut counts = consensus_counts
+                .lock()
+                .map_err(|e| eyre!("Consensus counts are poisoned: {e}"))
+                .map_err(|e| Failure::new(&sequence.id, Stage::Search, &e))?;
+            let count = counts.entry(population.clone()).or_insert(0);
+            *count += 1;
+            if *count == 2 {
+                drop(counts);
+                let num_pairs = dataset
+                    .precompute_pairs(&[population], &parent_search_populations)
+                    .map_err(|e| Failure::new(&sequence.id, Stage::Search, &e))?;
+                num_precomputed.fetch_add(num_pairs, Ordering::Relaxed);
+            }
+        }
+
+        Ok::<_, Failure>((search_result, runtime))
+    };
+
+    // collect run statistics across threads
+    let accumulator = stats::Accumulator::new();
+    // designated recombinants that need edge cases, with --edge-case-stats
+    let edge_case_stats = Mutex::new(Vec::new());
+    // searches of sequences whose content is duplicated (ex. the same sample
+    // under multiple ids), by content hash
+    let search_memo: Mutex<HashMap<String, Option<(SearchResult, Recombination)>>> =
+        Mutex::new(HashMap::new());
+    let num_reused = AtomicUsize::new(0);
+
+    // Search for the recombination parents of a sequence.
+    // This closure is structured weirdly for thread compatability, and the
     // fact that we need to return multiple types of objects
-    let results: Vec<(SearchResult, Recombination)> = sequences
-        .par_iter()
-        .map(|sequence| {
-            // initialize with default results, regardless of whether our
-            // searches "succeed", we're going to return standardized data
-            // structures to build our exports upon (ex. linelist columns)
-            // which will include the "negative" results
-            let mut best_match = SearchResult::new(sequence);
-            let mut recombination = Recombination::new(sequence);
-
-            // ------------------------------------------------------------------------
-            // Best Match (Consensus)
-            //
-            // search for the best match in the dataset to this sequence.
-            // this will represent the consensus population call.
-
-            debug!("Identifying best match (consensus population).");
-            let search_result = dataset.search(sequence, None, None);
+    let search_parents = |sequence, best_match_search| -> Result<_, Report> {
+        let (search_result, runtime): (Result<SearchResult, Report>, Duration) =
+            best_match_search;
+        let start = Instant::now();
+        // initialize with default results, regardless of whether our
+        // searches "succeed", we're going to return standardized data
+        // structures to build our exports upon (ex. linelist columns)
+        // which will include the "negative" results
+        let mut best_match = SearchResult::new(sequence);
+        let mut recombination = Recombination::new(sequence);
+        let parent_search_populations = sequence_search_populations(sequence);
+        let mut profile = recombination::search::Profile::new();
 
+        // reuse the search of a sequence with the same content, which only
+        // applies to sequences that matched, since the search depends on its id
+        // with --self-dataset or without a match (ex. population_B).
+        // The first sequence with a content only records its hash, and the
+        // search is kept once the content is seen again, so that the searches
+        // of unique sequences aren't copied.
+        let content_hash = search_result
+            .is_ok()
+            .then(|| sequence.content_hash())
+            .filter(|_hash| !args.self_dataset);
+        let (memoized, memoize) = match &content_hash {
+            Some(hash) => {
+                let mut memo = search_memo
+                    .lock()
+                    .map_err(|e| eyre!("Search memo is poisoned: {e}"))?;
+                match memo.get(hash) {
+                    Some(memoized) => (memoized.clone(), memoized.is_none()),
+                    None => {
+                        memo.insert(hash.clone(), None);
+                        (None, false)
+                    }
+                }
+            }
+            None => (None, false),
+        };
+
+        if let Some((memo_match, memo_recombination)) = memoized {
+            debug!("Reusing the search of a sequence with the same content.");
+            num_reused.fetch_add(1, Ordering::Relaxed);
+            best_match = memo_match;
+            best_match.sequence_id = sequence.id.clone();
+            recombination = memo_recombination;
+            if let Some(header) = recombination.table.headers.last_mut() {
+                if *header == recombination.sequence.id {
+                    *header = sequence.id.clone();
+                }
+            }
+            recombination.sequence = sequence;
+        } else {
             // if we found a match, proceed with recombinant search
             if let Ok(search_result) = search_result {
                 best_match = search_result;
@@ -231,6 +529,7 @@ pub fn run(args: &mut cli::run::Args) -> Result<(), Report> {
                     &mut best_match,
                     &parent_search_populations,
                     args,
+                    &mut profile,
                 );
                 match parent_search {
                     Ok(search_result) => recombination = search_result,
@@ -249,47 +548,417 @@ pub fn run(args: &mut cli::run::Args) -> Result<(), Report> {
                 }
             }
 
-            progress_bar.inc(1);
+            if args.explain_negatives && recombination.recombinant.is_none() {
+                let explanation = recombination::search::explain_negative(
+                    sequence,
+                    &dataset,
+                    &best_match,
+                    &parent_search_populations,
+                    args,
+                );
+                match explanation {
+                    Ok(explanation) => recombination.explanation = Some(explanation),
+                    Err(e) => debug!("Failed to explain negative result. {e}"),
+                }
+            }
+
+            // mixture, co-infection vs recombination
+            recombination.mixture =
+                recombination::mixture::analyze(&dataset, &best_match, &recombination)?;
+
+            if let Some(hash) = content_hash.filter(|_hash| memoize) {
+                let memo = (best_match.clone(), recombination.clone());
+                search_memo
+                    .lock()
+                    .map_err(|e| eyre!("Search memo is poisoned: {e}"))?
+                    .insert(hash, Some(memo));
+            }
+        }
+
+        if args.edge_case_stats {
+            let stat = edge_cases::stat(
+                sequence,
+                &dataset,
+                &best_match,
+                &recombination,
+                &parent_search_populations,
+                args,
+            );
+            match stat {
+                Ok(Some(stat)) => edge_case_stats
+                    .lock()
+                    .map_err(|e| eyre!("Edge case statistics are poisoned: {e}"))?
+                    .push(stat),
+                Ok(None) => (),
+                Err(e) => debug!("Failed to compare with a naive search. {e}"),
+            }
+        }
+
+        // truncate after the parent search, which checks the top populations
+        if let Some(max) = args.max_top_populations {
+            best_match.truncate_top_populations(max);
+        }
+
+        profile.runtime = runtime + start.elapsed();
+        recombination.profile = profile;
+
+        if let Err(e) = accumulator.record(&best_match, &recombination) {
+            warn!("Failed to record statistics for {}: {e}", sequence.id);
+        }
+        progress_bar.inc(1);
+
+        Ok((best_match, recombination))
+    };
+
+    // The linelist is written as results finish. Workers read sequences in
+    // input order, and results are re-sequenced into input order before
+    // writing, unless --unordered.
+    let outpath_linelist = layout.linelist();
+    info!("Exporting linelist: {outpath_linelist:?}");
+    let linelist_writer = export::LinelistWriter::new(
+        &outpath_linelist,
+        args.linelist_columns.as_deref(),
+        args.profile,
+        args.locale_safe,
+    )?;
+    // additional formats, followed by custom sinks
+    let mut builtin_sinks: Vec<Box<dyn OutputSink>> = vec![Box::new(linelist_writer)];
+    for format in args.output_format.iter().unique() {
+        let path = layout.linelist_as(&format.to_string());
+        info!("Exporting linelist ({format}): {path:?}");
+        builtin_sinks.push(format.sink(
+            &path,
+            args.linelist_columns.as_deref(),
+            args.profile,
+        )?);
+    }
+    if args.explain_negatives {
+        let path = layout.negatives();
+        info!("Exporting negative explanations: {path:?}");
+        builtin_sinks.push(Box::new(export::sink::NegativesWriter::new(&path)?));
+    }
+    if args.sample_outputs {
+        let dir = layout.samples_dir();
+        info!("Exporting sample results: {dir:?}");
+        builtin_sinks.push(Box::new(export::sink::SampleSink::new(&dir)?));
+    }
+    sinks.splice(0..0, builtin_sinks);
+
+    // results in flight between the workers and writer are bounded by the capacity
+    let capacity = args.queue_size.unwrap_or(num_threads * order::BUFFER_PER_THREAD);
+    debug!("Result queue size: {capacity}");
+    let resequencer = order::Resequencer::new(capacity, !args.unordered);
+    let (sender, receiver) = mpsc::sync_channel(capacity);
+
+    // memory usage is sampled with --profile, and guarded with --max-memory
+    let monitor = memory::Monitor::new(args.max_memory);
+    let sample_memory = args.profile || args.max_memory.is_some();
+    if args.max_memory.is_some() && monitor.rss()?.is_none() {
+        warn!("Memory usage can't be measured on this platform, --max-memory will be ignored.");
+    }
+    let (stop_sampler, sampler_stopped) = mpsc::channel::<()>();
+    let fail_fast = args.fail_fast;
 
-            (best_match, recombination)
-        })
-        .collect();
+    let (results, discarded) = thread::scope(|scope| {
+        let (search_parents, resequencer, progress_bar) =
+            (&search_parents, &resequencer, &progress_bar);
+        let (next_input, prepare, best_match) = (&next_input, &prepare, &best_match);
+        let (monitor, dataset) = (&monitor, &dataset);
+
+        // near the limit, disable the pair cache and shrink the queue,
+        // over the limit, stop the workers
+        let sampler = sample_memory.then(|| {
+            scope.spawn(move || {
+                loop {
+                    match monitor.sample()? {
+                        Some(memory::State::LowMemory) => {
+                            warn!("Memory usage is approaching --max-memory, switching to low-memory mode.");
+                            dataset.pair_cache.disable()?;
+                            resequencer.shrink(num_threads);
+                        }
+                        Some(memory::State::Exceeded) => {
+                            warn!("Memory usage exceeded --max-memory, stopping the search.");
+                            resequencer.close();
+                        }
+                        _ => (),
+                    }
+                    let stopped = sampler_stopped.recv_timeout(memory::SAMPLE_INTERVAL);
+                    if !matches!(stopped, Err(RecvTimeoutError::Timeout)) {
+                        break;
+                    }
+                }
+                Ok::<(), Report>(())
+            })
+        });
+        let workers = arenas
+            .iter_mut()
+            .map(|arena| {
+                let sender = sender.clone();
+                scope.spawn(move || {
+                    let arena: &Arena<Sequence> = arena;
+                    loop {
+                        // an input that can't be read stops the run
+                        let input = match next_input() {
+                            Ok(input) => input,
+                            Err(e) => {
+                                resequencer.close();
+                                return Err(e);
+                            }
+                        };
+                        let Some((index, sequence)) = input else {
+                            break;
+                        };
+                        if !resequencer.wait(index)? {
+                            break;
+                        }
+                        let result = sequence.and_then(|mut sequence| {
+                            prepare(&mut sequence);
+                            let sequence = &*arena.alloc(sequence);
+                            let best_match = best_match(sequence)?;
+                            panic::catch_unwind(AssertUnwindSafe(|| {
+                                search_parents(sequence, best_match)
+                            }))
+                            .map_err(|e| {
+                                Failure::from_panic(
+                                    &sequence.id,
+                                    Stage::Search,
+                                    e.as_ref(),
+                                )
+                            })?
+                            .map_err(|e| Failure::new(&sequence.id, Stage::Search, &e))
+                        });
+                        if let Err(failure) = &result {
+                            progress_bar.inc(1);
+                            if fail_fast {
+                                resequencer.close();
+                                return Err(eyre!(
+                                    "Sequence {} failed: {}",
+                                    failure.strain,
+                                    failure.error
+                                ));
+                            }
+                        }
+                        if sender.send((index, result)).is_err() {
+                            break;
+                        }
+                    }
+                    Ok::<(), Report>(())
+                })
+            })
+            .collect_vec();
+        drop(sender);
+
+        // write results as they are ready, stop the workers if writing fails
+        // in low-memory mode, results are discarded once they are written
+        let mut results: Vec<(SearchResult, Recombination)> = Vec::new();
+        let mut discarded = false;
+        let written = (|| {
+            for (index, result) in &receiver {
+                for result in resequencer.push(index, result)? {
+                    let (best_match, recombination) = match result {
+                        Ok(result) => result,
+                        Err(failure) => {
+                            warn!(
+                                "Sequence {} failed: {}",
+                                failure.strain, failure.error
+                            );
+                            failures.push(failure);
+                            continue;
+                        }
+                    };
+                    for sink in sinks.iter_mut() {
+                        sink.on_result(&best_match, &recombination, dataset)?;
+                    }
+                    if monitor.state()? == memory::State::Normal {
+                        results.push((best_match, recombination));
+                    } else if !discarded {
+                        warn!("Discarding results after they are written, to reduce memory.");
+                        results = Vec::new();
+                        discarded = true;
+                    }
+                }
+            }
+            Ok::<(), Report>(())
+        })();
+        resequencer.close();
+        drop(receiver);
+
+        for worker in workers {
+            worker.join().map_err(|_| eyre!("Worker thread panicked."))??;
+        }
+        drop(stop_sampler);
+        if let Some(sampler) = sampler {
+            sampler.join().map_err(|_| eyre!("Memory sampler thread panicked."))??;
+        }
+        written?;
+        Ok::<_, Report>((results, discarded))
+    })?;
 
     progress_bar.finish();
+    monitor.check()?;
+
+    if collection_dates.is_some() {
+        let num_dated = num_dated.into_inner();
+        info!(
+            "Found collection dates for {num_dated}/{} sequences.",
+            progress_bar.length().unwrap_or(0)
+        );
+    }
+    if args.mask_primers {
+        let num_masked = num_masked.into_inner();
+        info!("Masked {num_masked} substitution(s) at primer binding sites.");
+    }
+    let num_reused = num_reused.into_inner();
+    if num_reused > 0 {
+        info!("Reused the searches of {num_reused} sequence(s) with duplicated content.");
+    }
+    if args.precompute_pairs {
+        let num_populations = consensus_counts
+            .into_inner()
+            .map_err(|e| eyre!("Consensus counts are poisoned: {e}"))?
+            .values()
+            .filter(|count| **count > 1)
+            .count();
+        let num_pairs = num_precomputed.into_inner();
+        info!("Precomputed {num_pairs} parent pairs for {num_populations} frequently observed populations.");
+    }
+
+    info!("Parent pair cache: {}", dataset.pair_cache.stats()?);
+
+    let mut summary = accumulator.flush()?;
+    info!(
+        "Sequences: {}, matched: {}, recombinant: {}",
+        summary.sequences, summary.matches, summary.recombinants
+    );
+    summary.failures = failures.len();
+    if !failures.is_empty() {
+        warn!("{} sequence(s) failed, and were skipped.", failures.len());
+    }
+    summary.memory = monitor.usage()?;
+    if let Some(usage) = &summary.memory {
+        info!("Peak memory (RSS): {} MB", usage.peak_rss_mb);
+    }
 
     // ------------------------------------------------------------------------
     // Export CLI args
 
-    let outpath_args = args.output_dir.join("run_args.json");
+    let outpath_args = layout.run_args();
     info!("Exporting CLI Run Args: {outpath_args:?}");
-    // create output file
-    let mut file = File::create(&outpath_args)
-        .wrap_err_with(|| format!("Failed to create file: {outpath_args:?}"))?;
-
     // parse to string
     let output = serde_json::to_string_pretty(&args_export)
         .wrap_err_with(|| "Failed to parse mutations.".to_string())?;
 
     // write to file
-    file.write_all(format!("{}\n", output).as_bytes())
-        .wrap_err_with(|| format!("Failed to write file: {outpath_args:?}"))?;
+    utils::write_atomic(&outpath_args, format!("{}\n", output))?;
+
+    // ------------------------------------------------------------------------
+    // Export Summary
+
+    let outpath_summary = layout.summary();
+    info!("Exporting summary: {outpath_summary:?}");
+    summary.write(&outpath_summary)?;
+
+    // ------------------------------------------------------------------------
+    // Export Novelty (QC)
+
+    if let Some(previous_summary) = &previous_summary {
+        let outpath_novelty = layout.novelty();
+        info!("Exporting novelty report: {outpath_novelty:?}");
+        let novelty = summary.novelty(previous_summary, args.novelty_fold_change);
+        let [new, increased, decreased] = ["new", "increased", "decreased"]
+            .map(|status| novelty.filter("status", status).map(|t| t.rows.len()));
+        let [new, increased, decreased] = [new?, increased?, decreased?];
+        info!("Recombinants since the previous run: {new} new, {increased} increased, {decreased} decreased.");
+        novelty.write(&outpath_novelty)?;
+    }
+
+    // ------------------------------------------------------------------------
+    // Export Edge Cases (QC)
+
+    if args.edge_case_stats {
+        let outpath_edge_cases = layout.edge_cases();
+        info!("Exporting edge case statistics: {outpath_edge_cases:?}");
+        let stats = edge_case_stats
+            .into_inner()
+            .map_err(|e| eyre!("Edge case statistics are poisoned: {e}"))?;
+        let counts = stats.iter().map(|stat| stat.detection().to_string()).counts();
+        let counts = counts.iter().sorted().map(|(d, n)| format!("{d}: {n}")).join(", ");
+        info!("Designated recombinants detected by: {counts}");
+        edge_cases::table(&stats).write(&outpath_edge_cases)?;
+    }
 
     // ------------------------------------------------------------------------
     // Export Linelist (single)
 
-    let outpath_linelist = args.output_dir.join("linelist.tsv");
-    info!("Exporting linelist: {outpath_linelist:?}");
+    for sink in sinks {
+        sink.on_finish()?;
+    }
+
+    // ------------------------------------------------------------------------
+    // Export Failures (QC)
 
-    let linelist_table = export::linelist(&results, &dataset)?;
-    //let linelist_table = export::linelist(&best_matches, &recombinations, &dataset)?;
-    linelist_table.write(&outpath_linelist)?;
+    let outpath_failures = layout.failures();
+    info!("Exporting failures: {outpath_failures:?}");
+    failures::table(&failures).write(&outpath_failures)?;
+
+    // the remaining outputs need all results, which were discarded
+    if discarded {
+        warn!("Results were discarded to reduce memory, skipping the genes, breakpoints, reads, Auspice, and barcodes outputs.");
+        let outpath_manifest = layout.manifest();
+        info!("Exporting manifest: {outpath_manifest:?}");
+        Manifest::from_layout(&layout)?.write(&outpath_manifest)?;
+        if auto_output_dir {
+            info!("Linking latest run: {:?}", &args.output_dir);
+            layout::link_latest(&args.output_dir)?;
+        }
+        return finish(&summary, run_start.elapsed(), &outpath_failures);
+    }
 
     // ------------------------------------------------------------------------
-    // Export Barcodes (multiple, collected by recombinant)
+    // Export Genes (optional, requires annotations)
+
+    if !dataset.annotations.rows.is_empty() {
+        let outpath_genes = layout.genes();
+        info!("Exporting genes: {outpath_genes:?}");
+        export::genes(&results, &dataset)?.write(&outpath_genes)?;
+    }
 
-    let outdir_barcodes = args.output_dir.join("barcodes");
+    // ------------------------------------------------------------------------
+    // Export Breakpoint Consistency (QC)
 
-    create_dir_all(&outdir_barcodes)?;
+    let outpath_breakpoints = layout.breakpoints();
+    info!("Checking breakpoint consistency: {outpath_breakpoints:?}");
+    let recombinations = results.iter().map(|(_b, r)| r).collect_vec();
+    let clusters = recombination::consistency::clusters(&recombinations);
+    let num_inconsistent: usize = clusters.iter().map(|c| c.inconsistent.len()).sum();
+    if num_inconsistent > 0 {
+        warn!("{num_inconsistent} sequence(s) have breakpoints inconsistent with other sequences with the same parents.");
+    }
+    recombination::consistency::table(&clusters)?.write(&outpath_breakpoints)?;
+
+    // ------------------------------------------------------------------------
+    // Export Reads (optional, requires input reads)
+
+    if args.input.reads.is_some() {
+        let outpath_reads = layout.reads();
+        info!("Exporting reads summary: {outpath_reads:?}");
+        reads::summary(&results)?.write(&outpath_reads)?;
+    }
+
+    // ------------------------------------------------------------------------
+    // Export Auspice JSON
+
+    let outpath_auspice = layout.auspice();
+    info!("Exporting Auspice JSON: {outpath_auspice:?}");
+    let auspice = export::auspice(&results, &dataset)?;
+    let output = serde_json::to_string_pretty(&auspice)
+        .wrap_err_with(|| "Failed to parse Auspice JSON.".to_string())?;
+    utils::write_atomic(&outpath_auspice, format!("{}\n", output))?;
+
+    // ------------------------------------------------------------------------
+    // Export Barcodes (multiple, collected by recombinant)
+
+    let outdir_barcodes = layout.barcodes_dir();
 
     // get unique keys of recombinants identified
     let unique_keys = results
@@ -318,6 +987,72 @@ pub fn run(args: &mut cli::run::Args) -> Result<(), Report> {
         barcode_table.write(&barcode_table_path)?;
     }
 
+    // ------------------------------------------------------------------------
+    // Export Manifest
+
+    let outpath_manifest = layout.manifest();
+    info!("Exporting manifest: {outpath_manifest:?}");
+    Manifest::from_layout(&layout)?.write(&outpath_manifest)?;
+
+    // link the latest run, once all outputs are written
+    if auto_output_dir {
+        info!("Linking latest run: {:?}", &args.output_dir);
+        layout::link_latest(&args.output_dir)?;
+    }
+
     info!("Done.");
+    finish(&summary, run_start.elapsed(), &outpath_failures)
+}
+
+/// Print the exit summary of a run, and fail if any sequences failed.
+///
+/// All outputs are written before, so that the results of the other
+/// sequences can still be used.
+fn finish(
+    summary: &stats::Summary,
+    runtime: Duration,
+    outpath_failures: &Path,
+) -> Result<(), Report> {
+    // always printed, regardless of verbosity, for pipeline logs
+    eprintln!("{}", summary.exit_line(runtime));
+    if summary.failures > 0 {
+        return Err(eyre!(
+            "{} sequence(s) failed: {outpath_failures:?}",
+            summary.failures
+        ))
+        .suggestion("The other sequences were processed, and their outputs written.")
+        .suggestion("To stop at the first failure instead, use --fail-fast.");
+    }
     Ok(())
 }
+
+/// Read the collection dates of sequences from metadata, by strain.
+///
+/// Dates that are incomplete or unknown (ex. 2021-05, or ?) are skipped.
+pub fn read_collection_dates(path: &Path) -> Result<HashMap<String, NaiveDate>, Report> {
+    let metadata = utils::table::Table::read(path)?;
+    let strain_i = metadata
+        .header_position("strain")
+        .suggestion("The metadata requires a 'strain' column of sequence ids.")?;
+    let date_i = metadata
+        .header_position("date")
+        .suggestion("The metadata requires a 'date' column (YYYY-MM-DD).")?;
+
+    let mut dates = HashMap::new();
+    let mut num_skipped = 0;
+    for row in &metadata.rows {
+        let date = row
+            .get(date_i)
+            .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok());
+        match (row.get(strain_i), date) {
+            (Some(strain), Some(date)) => {
+                dates.insert(strain.clone(), date);
+            }
+            _ => num_skipped += 1,
+        }
+    }
+    if num_skipped > 0 {
+        warn!("Skipped {num_skipped} metadata row(s) without a complete date: {path:?}");
+    }
+    Ok(dates)
+}
@@ -0,0 +1,106 @@
+use color_eyre::eyre::{eyre, Report, Result};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex};
+
+/// Number of results that can be buffered per thread, waiting to be written.
+pub const BUFFER_PER_THREAD: usize = 64;
+
+// ----------------------------------------------------------------------------
+// Resequencer
+// ----------------------------------------------------------------------------
+
+#[derive(Debug)]
+struct State<T> {
+    /// Index of the next result to write.
+    next: usize,
+    /// Results that finished before the next result, waiting to be written.
+    buffer: BTreeMap<usize, T>,
+    /// Whether the writer has stopped (ex. on error), and workers should too.
+    closed: bool,
+}
+
+/// Re-sequence results from parallel workers into input order, for the writer.
+///
+/// Memory is bounded by the capacity: a worker must wait before starting an
+/// input that is more than `capacity` ahead of the next result to write. Since
+/// workers start inputs in order, the next result is always in progress by a
+/// worker that is not waiting, and the window always advances.
+///
+/// When unordered, results are passed to the writer as soon as they finish.
+#[derive(Debug)]
+pub struct Resequencer<T> {
+    capacity: AtomicUsize,
+    ordered: bool,
+    state: Mutex<State<T>>,
+    window: Condvar,
+}
+
+impl<T> Resequencer<T> {
+    pub fn new(capacity: usize, ordered: bool) -> Self {
+        Resequencer {
+            capacity: AtomicUsize::new(capacity.max(1)),
+            ordered,
+            state: Mutex::new(State {
+                next: 0,
+                buffer: BTreeMap::new(),
+                closed: false,
+            }),
+            window: Condvar::new(),
+        }
+    }
+
+    /// Wait until an input index is within the window, before starting it.
+    ///
+    /// Returns false if the writer has stopped, and the input should be skipped.
+    pub fn wait(&self, index: usize) -> Result<bool, Report> {
+        let state =
+            self.state.lock().map_err(|e| eyre!("Resequencer is poisoned: {e}"))?;
+        let state = self
+            .window
+            .wait_while(state, |state| {
+                let capacity = self.capacity.load(Ordering::Relaxed);
+                self.ordered && !state.closed && index >= state.next + capacity
+            })
+            .map_err(|e| eyre!("Resequencer is poisoned: {e}"))?;
+        Ok(!state.closed)
+    }
+
+    /// Add a finished result, and return the results that are ready to write.
+    pub fn push(&self, index: usize, result: T) -> Result<Vec<T>, Report> {
+        if !self.ordered {
+            return Ok(vec![result]);
+        }
+        let mut state =
+            self.state.lock().map_err(|e| eyre!("Resequencer is poisoned: {e}"))?;
+        state.buffer.insert(index, result);
+
+        let mut ready = Vec::new();
+        loop {
+            let next = state.next;
+            let Some(result) = state.buffer.remove(&next) else {
+                break;
+            };
+            ready.push(result);
+            state.next += 1;
+        }
+        if !ready.is_empty() {
+            self.window.notify_all();
+        }
+        Ok(ready)
+    }
+
+    /// Reduce the capacity, so that fewer results are buffered (ex. to reduce
+    /// memory). Inputs that were already started are not affected.
+    pub fn shrink(&self, capacity: usize) {
+        self.capacity.fetch_min(capacity.max(1), Ordering::Relaxed);
+    }
+
+    /// Stop the writer, releasing any waiting workers.
+    pub fn close(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.closed = true;
+        }
+        self.window.notify_all();
+    }
+}
@@ -0,0 +1,277 @@
+use crate::dataset::SearchResult;
+use crate::recombination::Recombination;
+use crate::run::layout::Layout;
+use crate::run::memory;
+use crate::utils;
+use crate::utils::fmt;
+use crate::utils::table::Table;
+use color_eyre::eyre::{eyre, Report, Result, WrapErr};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Number of shards for the per-lineage tallies.
+pub const NUM_SHARDS: usize = 16;
+
+// ----------------------------------------------------------------------------
+// Run Statistics Accumulator
+// ----------------------------------------------------------------------------
+
+/// Statistics collected across worker threads during a run.
+///
+/// Counters are atomic, and lineage tallies are sharded by name so that
+/// threads recording different lineages rarely wait on the same lock.
+#[derive(Debug)]
+pub struct Accumulator {
+    sequences: AtomicUsize,
+    matches: AtomicUsize,
+    recombinants: AtomicUsize,
+    edge_cases: AtomicUsize,
+    populations: Vec<Mutex<HashMap<String, usize>>>,
+    recombinant_populations: Vec<Mutex<HashMap<String, usize>>>,
+}
+
+impl Default for Accumulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Accumulator {
+    pub fn new() -> Self {
+        let shards = || (0..NUM_SHARDS).map(|_| Mutex::new(HashMap::new())).collect();
+        Accumulator {
+            sequences: AtomicUsize::new(0),
+            matches: AtomicUsize::new(0),
+            recombinants: AtomicUsize::new(0),
+            edge_cases: AtomicUsize::new(0),
+            populations: shards(),
+            recombinant_populations: shards(),
+        }
+    }
+
+    /// Increment the tally of a lineage in its shard.
+    fn tally(
+        shards: &[Mutex<HashMap<String, usize>>],
+        lineage: &str,
+    ) -> Result<(), Report> {
+        let mut hasher = DefaultHasher::new();
+        lineage.hash(&mut hasher);
+        let shard = (hasher.finish() as usize) % shards.len();
+        let mut counts = shards[shard]
+            .lock()
+            .map_err(|e| eyre!("Statistics shard is poisoned: {e}"))?;
+        *counts.entry(lineage.to_string()).or_insert(0) += 1;
+        Ok(())
+    }
+
+    /// Record the results of a single sequence.
+    pub fn record(
+        &self,
+        best_match: &SearchResult,
+        recombination: &Recombination,
+    ) -> Result<(), Report> {
+        self.sequences.fetch_add(1, Ordering::Relaxed);
+
+        if !best_match.consensus_population.is_empty() {
+            self.matches.fetch_add(1, Ordering::Relaxed);
+            Accumulator::tally(&self.populations, &best_match.consensus_population)?;
+        }
+        if let Some(recombinant) = &recombination.recombinant {
+            self.recombinants.fetch_add(1, Ordering::Relaxed);
+            Accumulator::tally(&self.recombinant_populations, recombinant)?;
+        }
+        if recombination.edge_case {
+            self.edge_cases.fetch_add(1, Ordering::Relaxed);
+        }
+
+        Ok(())
+    }
+
+    /// Merge the counters and shards into a run summary.
+    pub fn flush(&self) -> Result<Summary, Report> {
+        let merge = |shards: &[Mutex<HashMap<String, usize>>]| {
+            let mut counts = BTreeMap::new();
+            for shard in shards {
+                let shard = shard
+                    .lock()
+                    .map_err(|e| eyre!("Statistics shard is poisoned: {e}"))?;
+                counts.extend(shard.iter().map(|(k, v)| (k.clone(), *v)));
+            }
+            Ok::<_, Report>(counts)
+        };
+
+        Ok(Summary {
+            sequences: self.sequences.load(Ordering::Relaxed),
+            matches: self.matches.load(Ordering::Relaxed),
+            recombinants: self.recombinants.load(Ordering::Relaxed),
+            edge_cases: self.edge_cases.load(Ordering::Relaxed),
+            failures: 0,
+            populations: merge(&self.populations)?,
+            recombinant_populations: merge(&self.recombinant_populations)?,
+            memory: None,
+        })
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Run Summary
+// ----------------------------------------------------------------------------
+
+/// Summary statistics of a run.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Summary {
+    /// Number of sequences processed.
+    pub sequences: usize,
+    /// Number of sequences matched to a dataset population.
+    pub matches: usize,
+    /// Number of sequences with recombination detected.
+    pub recombinants: usize,
+    /// Number of sequences handled as an edge case.
+    pub edge_cases: usize,
+    /// Number of sequences that failed, and were skipped (qc/failures.tsv).
+    #[serde(default)]
+    pub failures: usize,
+    /// Number of sequences per consensus population.
+    pub populations: BTreeMap<String, usize>,
+    /// Number of sequences per recombinant.
+    pub recombinant_populations: BTreeMap<String, usize>,
+    /// Memory usage, if sampled (--profile or --max-memory).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory: Option<memory::Usage>,
+}
+
+impl Summary {
+    pub fn new() -> Self {
+        Summary::default()
+    }
+
+    /// A single line summary of a finished run, for pipeline logs.
+    ///
+    /// ex. `rebar_summary status=success processed=5 matched=5 unmatched=0
+    /// failed=0 recombinants=2 edge_cases=0 runtime_s=0.25`
+    ///
+    /// The status is partial if any sequences failed (and were skipped).
+    /// Unmatched sequences are those without a best match. The peak memory
+    /// (peak_rss_mb) is appended if it was sampled.
+    pub fn exit_line(&self, runtime: Duration) -> String {
+        let status = if self.failures == 0 {
+            "success"
+        } else {
+            "partial"
+        };
+        let mut line = format!(
+            "rebar_summary status={status} processed={} matched={} unmatched={} failed={} recombinants={} edge_cases={} runtime_s={}",
+            self.sequences,
+            self.matches,
+            self.sequences.saturating_sub(self.matches),
+            self.failures,
+            self.recombinants,
+            self.edge_cases,
+            fmt::float(runtime.as_secs_f64()),
+        );
+        if let Some(memory) = &self.memory {
+            line.push_str(&format!(" peak_rss_mb={}", memory.peak_rss_mb));
+        }
+        line
+    }
+
+    /// Read the summary of a previous run, from its summary.json or output
+    /// directory.
+    pub fn read_previous(path: &Path) -> Result<Summary, Report> {
+        let path = match path.is_dir() {
+            true => Layout::new(path).summary(),
+            false => path.to_path_buf(),
+        };
+        Summary::read(&path)
+            .wrap_err_with(|| format!("Failed to read previous summary: {path:?}"))
+    }
+
+    /// Report recombinants that are new, or changed, since a previous run.
+    ///
+    /// Recombinants are compared by their proportion of sequences, since runs
+    /// can have different numbers of sequences. A recombinant is new if it
+    /// was not in the previous run, and increased (or decreased) if its
+    /// proportion changed by at least the fold change. Recombinants that are
+    /// no longer found have decreased. Unchanged recombinants are omitted.
+    pub fn novelty(&self, previous: &Summary, min_fold_change: f64) -> Table {
+        let mut table = Table::new();
+        table.headers = vec![
+            "recombinant",
+            "status",
+            "previous_count",
+            "current_count",
+            "previous_proportion",
+            "current_proportion",
+            "fold_change",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect_vec();
+
+        let proportion = |count: usize, total: usize| match total {
+            0 => 0.0,
+            _ => count as f64 / total as f64,
+        };
+        let recombinants = previous
+            .recombinant_populations
+            .keys()
+            .chain(self.recombinant_populations.keys())
+            .unique()
+            .sorted();
+
+        for recombinant in recombinants {
+            let previous_count =
+                previous.recombinant_populations.get(recombinant).copied().unwrap_or(0);
+            let current_count =
+                self.recombinant_populations.get(recombinant).copied().unwrap_or(0);
+            let previous_proportion = proportion(previous_count, previous.sequences);
+            let current_proportion = proportion(current_count, self.sequences);
+            // infinite if the recombinant is new, 0 if it is no longer found
+            let fold_change = current_proportion / previous_proportion;
+
+            let status = if previous_count == 0 {
+                "new"
+            } else if fold_change >= min_fold_change {
+                "increased"
+            } else if fold_change <= 1.0 / min_fold_change {
+                "decreased"
+            } else {
+                continue;
+            };
+            table.rows.push(vec![
+                recombinant.to_string(),
+                status.to_string(),
+                previous_count.to_string(),
+                current_count.to_string(),
+                fmt::float(previous_proportion),
+                fmt::float(current_proportion),
+                fmt::float(fold_change),
+            ]);
+        }
+
+        table
+    }
+
+    /// Read summary from file.
+    pub fn read(path: &Path) -> Result<Summary, Report> {
+        let summary = utils::read_to_string(path)?;
+        let summary = serde_json::from_str(&summary)
+            .wrap_err_with(|| format!("Failed to parse file: {path:?}"))?;
+        Ok(summary)
+    }
+
+    /// Write summary to file.
+    pub fn write(&self, path: &Path) -> Result<(), Report> {
+        let output = serde_json::to_string_pretty(self)
+            .wrap_err_with(|| format!("Failed to parse: {self:?}"))?;
+        utils::write_atomic(path, format!("{}\n", output))?;
+        Ok(())
+    }
+}
@@ -1,10 +1,17 @@
 pub mod parsimony;
+pub mod scoring;
+pub mod translate;
 
-use bio::io::fasta;
+use crate::run::input::Source;
+use crate::utils;
+use chrono::NaiveDate;
 use color_eyre::eyre::{eyre, Report, Result, WrapErr};
 use color_eyre::Help;
+use log::warn;
+use openssl::sha::Sha256;
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
+use std::collections::BTreeMap;
 use std::default::Default;
 use std::path::Path;
 use std::str::FromStr;
@@ -75,9 +82,20 @@ impl FromStr for Substitution {
     type Err = Report;
 
     fn from_str(text: &str) -> Result<Self, Report> {
-        let reference = text.chars().next().unwrap();
-        let alt = text.chars().nth(text.len() - 1).unwrap();
-        let coord = text[1..text.len() - 1].parse().unwrap();
+        let chars: Vec<char> = text.chars().collect();
+        let (reference, alt) = match (chars.first(), chars.last()) {
+            (Some(reference), Some(alt)) if chars.len() >= 3 => (*reference, *alt),
+            _ => {
+                return Err(eyre!("Invalid substitution: {text}"))
+                    .suggestion("Substitutions are <ref><coord><alt>, ex. C22995A")
+            }
+        };
+        let coord = chars[1..chars.len() - 1]
+            .iter()
+            .collect::<String>()
+            .parse()
+            .map_err(|e| eyre!("Invalid substitution coordinate: {text}. {e}"))
+            .suggestion("Substitutions are <ref><coord><alt>, ex. C22995A")?;
         let substitution = Substitution {
             reference,
             alt,
@@ -125,6 +143,16 @@ pub struct Sequence {
     pub substitutions: Vec<Substitution>,
     pub deletions: Vec<Deletion>,
     pub missing: Vec<usize>,
+    /// Ambiguous bases (IUPAC codes other than N) of missing coordinates, only
+    /// for compact sequences without bases (see Sequence::compact).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub ambiguous: BTreeMap<usize, char>,
+    /// The input the sequence was read from, for query sequences.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<Source>,
+    /// The collection date of the sample, for query sequences with --metadata.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collection_date: Option<NaiveDate>,
 }
 
 impl Sequence {
@@ -137,6 +165,9 @@ impl Sequence {
             substitutions: Vec::new(),
             deletions: Vec::new(),
             missing: Vec::new(),
+            ambiguous: BTreeMap::new(),
+            source: None,
+            collection_date: None,
         }
     }
 
@@ -147,7 +178,13 @@ impl Sequence {
     ) -> Result<Self, Report> {
         let mut sample = Sequence::new();
         sample.id = record.id().to_string();
-        sample.seq = record.seq().iter().map(|b| *b as char).collect();
+        sample.seq = record.seq().iter().map(|b| normalize_base(*b as char)).collect();
+
+        let num_uracil =
+            record.seq().iter().filter(|b| b.eq_ignore_ascii_case(&b'U')).count();
+        if num_uracil > 0 {
+            warn!("Sequence {} has {num_uracil} uracil (U) base(s), which were converted to thymine (T).", sample.id);
+        }
 
         // check mask coord
         for bases in mask {
@@ -225,16 +262,137 @@ impl Sequence {
 
         Ok(sample)
     }
+
+    /// Drop the bases of the sequence, to store it sparsely.
+    ///
+    /// The substitutions, deletions, and missing data are kept, so the bases
+    /// can still be looked up relative to the reference (see Sequence::base).
+    /// This is used for dataset populations, as large genomes (ex. bacterial
+    /// core-genome alignments) would otherwise store every base of every
+    /// population.
+    pub fn compact(&mut self) {
+        if self.seq.is_empty() {
+            return;
+        }
+        self.ambiguous = self
+            .missing
+            .iter()
+            .filter_map(|coord| {
+                let base = *self.seq.get(coord - 1)?;
+                (base != 'N').then_some((*coord, base))
+            })
+            .collect();
+        self.seq = Vec::new();
+    }
+
+    /// Restore the bases of a compact sequence, from the reference.
+    pub fn expand(&mut self, reference: &Sequence) {
+        if !self.seq.is_empty() || self.genome_length == 0 {
+            return;
+        }
+        self.seq =
+            (1..=self.genome_length).map(|coord| self.base(coord, reference)).collect();
+        self.ambiguous.clear();
+    }
+
+    /// Get the base at a coordinate (1-based).
+    ///
+    /// Compact sequences are looked up by their substitutions, deletions, and
+    /// missing data, and are otherwise the reference base.
+    pub fn base(&self, coord: usize, reference: &Sequence) -> char {
+        match self.seq.get(coord - 1) {
+            Some(base) => *base,
+            None => self.variant_base(coord).unwrap_or(reference.seq[coord - 1]),
+        }
+    }
+
+    /// Get the base of a compact sequence at a coordinate, if it differs from
+    /// the reference.
+    fn variant_base(&self, coord: usize) -> Option<char> {
+        if let Ok(i) = self.substitutions.binary_search_by_key(&coord, |sub| sub.coord) {
+            Some(self.substitutions[i].alt)
+        } else if let Ok(i) = self.deletions.binary_search_by_key(&coord, |del| del.coord)
+        {
+            Some(self.deletions[i].alt)
+        } else if self.missing.binary_search(&coord).is_ok() {
+            Some(self.ambiguous.get(&coord).copied().unwrap_or('N'))
+        } else {
+            None
+        }
+    }
+
+    /// Get a hash of the content of the sequence, which is independent of its id.
+    ///
+    /// The hash is the first 16 hex characters of a SHA-256 digest of the genome
+    /// length, substitutions, deletions, and missing bases (with their IUPAC
+    /// code, since ambiguous bases are used to detect mixtures). Sequences with
+    /// the same hash have the same results, and the hash is stable between runs
+    /// and sequence renames.
+    pub fn content_hash(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(format!("{}\n", self.genome_length).as_bytes());
+        for substitution in &self.substitutions {
+            hasher.update(format!("{substitution},").as_bytes());
+        }
+        hasher.update(b"\n");
+        for deletion in &self.deletions {
+            hasher.update(format!("{deletion},").as_bytes());
+        }
+        hasher.update(b"\n");
+        for coord in &self.missing {
+            let base = match self.seq.get(coord - 1) {
+                Some(base) => *base,
+                None => self.ambiguous.get(coord).copied().unwrap_or('N'),
+            };
+            hasher.update(format!("{coord}{base},").as_bytes());
+        }
+        utils::to_hex(&hasher.finish()[..8])
+    }
+
+    /// Identify reversions of population substitutions in the sequence.
+    ///
+    /// A reversion is a population substitution where the sequence has the
+    /// reference base instead. Reversions are returned as the back mutation
+    /// (ex. C10T becomes T10C).
+    pub fn reversions(&self, substitutions: &[Substitution]) -> Vec<Substitution> {
+        substitutions
+            .iter()
+            .filter(|sub| match self.seq.get(sub.coord - 1) {
+                Some(base) => *base == sub.reference,
+                None => {
+                    sub.coord <= self.genome_length
+                        && self.variant_base(sub.coord).is_none()
+                }
+            })
+            .map(|sub| Substitution {
+                coord: sub.coord,
+                reference: sub.alt,
+                alt: sub.reference,
+            })
+            .collect()
+    }
 }
 
 // ----------------------------------------------------------------------------
 // Functions
 // ----------------------------------------------------------------------------
 
+/// Normalize a base from a fasta record.
+///
+/// Lowercase bases are converted to uppercase, unknown bases (?) to missing
+/// data (N), and uracil (U) to thymine (T).
+pub fn normalize_base(base: char) -> char {
+    match base.to_ascii_uppercase() {
+        '?' => 'N',
+        'U' => 'T',
+        base => base,
+    }
+}
+
 /// Read first record of fasta path into sequence record.
 pub fn read_reference(path: &Path, mask: &Vec<usize>) -> Result<Sequence, Report> {
     // start reading in the reference as fasta, raise error if file doesn't exist
-    let reader = fasta::Reader::from_file(path).expect("Unable to read reference");
+    let reader = utils::read_fasta(path)?;
 
     // parse just the first record from the reference
     // 1. raise error if record iterator doesn't work
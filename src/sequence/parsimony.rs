@@ -3,6 +3,11 @@ use color_eyre::eyre::{Report, Result};
 use indoc::formatdoc;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+/// Maximum number of items to display per section, when pretty-printing.
+pub const MAX_DISPLAY_ITEMS: usize = 10;
 
 // ----------------------------------------------------------------------------
 // Population Parsimony Summary
@@ -77,16 +82,29 @@ impl Summary {
         Ok(parsimony_summary)
     }
 
-    pub fn pretty_print(&self) -> String {
+    /// Pretty-print the summary (yaml-like), with at most `max_items`
+    /// substitutions per section.
+    pub fn pretty_print(&self, max_items: usize) -> String {
         formatdoc!(
             "score:\n  {}
             support:\n  {}
             conflict_ref:\n  {}
             conflict_alt:\n  {}",
             self.score,
-            self.support.iter().join(", "),
-            self.conflict_ref.iter().join(", "),
-            self.conflict_alt.iter().join(", "),
+            join_capped(&self.support, max_items),
+            join_capped(&self.conflict_ref, max_items),
+            join_capped(&self.conflict_alt, max_items),
+        )
+    }
+
+    /// Summarize on one line, with counts only, for logs.
+    pub fn one_line(&self) -> String {
+        format!(
+            "score: {}, support: {}, conflict_ref: {}, conflict_alt: {}",
+            self.score,
+            self.support.len(),
+            self.conflict_ref.len(),
+            self.conflict_alt.len(),
         )
     }
 }
@@ -96,3 +114,64 @@ impl Default for Summary {
         Self::new()
     }
 }
+
+// ----------------------------------------------------------------------------
+// Functions
+// ----------------------------------------------------------------------------
+
+/// Join items for display, with at most `max_items` followed by a count of the rest.
+pub fn join_capped<T: Display>(items: &[T], max_items: usize) -> String {
+    let mut display = items.iter().take(max_items).join(", ");
+    if items.len() > max_items {
+        let more = format!("... (+{} more)", items.len() - max_items);
+        display = if display.is_empty() {
+            more
+        } else {
+            format!("{display}, {more}")
+        };
+    }
+    display
+}
+
+/// Pretty-print the scores of populations, as yaml-like list items.
+///
+/// Populations are displayed in the given order, with at most `max_items`.
+pub fn pretty_print_scores(
+    populations: &[&String],
+    scores: &BTreeMap<String, isize>,
+    max_items: usize,
+) -> String {
+    let mut lines = populations
+        .iter()
+        .take(max_items)
+        .map(|pop| format!("- {pop}: {}", scores.get(*pop).copied().unwrap_or_default()))
+        .collect_vec();
+    if populations.len() > max_items {
+        lines.push(format!("- ... (+{} more)", populations.len() - max_items));
+    }
+    lines.join("\n  ")
+}
+
+/// Pretty-print a section of substitutions by population, as yaml-like list items.
+///
+/// Populations are displayed in the given order, with at most `max_items`
+/// populations, and `max_items` substitutions per population. Populations
+/// missing from the section are displayed with no substitutions.
+pub fn pretty_print_section(
+    populations: &[&String],
+    section: &BTreeMap<String, Vec<Substitution>>,
+    max_items: usize,
+) -> String {
+    let mut lines = populations
+        .iter()
+        .take(max_items)
+        .map(|pop| {
+            let subs = section.get(*pop).map(Vec::as_slice).unwrap_or_default();
+            format!("- {pop} ({}): {}", subs.len(), join_capped(subs, max_items))
+        })
+        .collect_vec();
+    if populations.len() > max_items {
+        lines.push(format!("- ... (+{} more)", populations.len() - max_items));
+    }
+    lines.join("\n  ")
+}
@@ -0,0 +1,36 @@
+use crate::sequence::parsimony;
+use std::fmt;
+use std::sync::Arc;
+
+// ----------------------------------------------------------------------------
+// Scoring Model
+
+/// A model to score how well a candidate population matches a sequence.
+///
+/// Candidates with the highest score are the top populations of a search,
+/// for both the best match and the recombination parents. Implement this
+/// trait to experiment with alternative scoring (ex. likelihood-based), and
+/// set it on the dataset (`Dataset::scoring`) before searching.
+pub trait ScoringModel: fmt::Debug + Send + Sync {
+    /// Score a candidate from its support and conflicts, higher is better.
+    fn score(&self, summary: &parsimony::Summary) -> f64;
+}
+
+/// The default model, parsimony (support - conflict_ref - conflict_alt).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Parsimony;
+
+impl ScoringModel for Parsimony {
+    fn score(&self, summary: &parsimony::Summary) -> f64 {
+        summary.score as f64
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Functions
+// ----------------------------------------------------------------------------
+
+/// The default scoring model, parsimony.
+pub fn default_model() -> Arc<dyn ScoringModel> {
+    Arc::new(Parsimony)
+}
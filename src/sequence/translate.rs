@@ -0,0 +1,150 @@
+use crate::sequence::Sequence;
+use crate::utils::table::Table;
+use color_eyre::eyre::{Report, Result, WrapErr};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Standard genetic code, for codons in TCAG order (ex. TTT, TTC, TTA, ...).
+const CODON_TABLE: &[u8; 64] =
+    b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG";
+
+// ----------------------------------------------------------------------------
+// Amino Acid Substitution
+
+/// A protein-level substitution in a gene (ex. S:D614G).
+///
+/// A deleted codon has the alt '-', and a stop codon '*'.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct AminoAcidSubstitution {
+    pub gene: String,
+    pub coord: usize,
+    pub reference: char,
+    pub alt: char,
+}
+
+impl std::fmt::Display for AminoAcidSubstitution {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}{}{}",
+            self.gene, self.reference, self.coord, self.alt
+        )
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Functions
+// ----------------------------------------------------------------------------
+
+/// Translate a codon with the standard genetic code.
+///
+/// A fully deleted codon ('---') is '-'. Codons with ambiguous bases, or
+/// partially deleted, are 'X'.
+pub fn translate_codon(codon: &[char]) -> char {
+    if codon == ['-', '-', '-'] {
+        return '-';
+    }
+    let index = codon.iter().try_fold(0, |index, base| {
+        let base = match base.to_ascii_uppercase() {
+            'T' | 'U' => 0,
+            'C' => 1,
+            'A' => 2,
+            'G' => 3,
+            _ => return None,
+        };
+        Some(index * 4 + base)
+    });
+    match index {
+        Some(index) if codon.len() == 3 => CODON_TABLE[index] as char,
+        _ => 'X',
+    }
+}
+
+/// Translate a nucleotide sequence, in frame from the first base.
+///
+/// Incomplete codons at the end are ignored.
+pub fn translate(sequence: &[char]) -> String {
+    sequence.chunks_exact(3).map(translate_codon).collect()
+}
+
+/// Identify the protein-level substitutions of a sequence, in annotated genes.
+///
+/// Genes are read from the annotations (gene, start, end) in the forward
+/// strand and reference frame. Only codons with a nucleotide substitution or
+/// deletion are translated. Deletions and insertions do not shift the frame
+/// of the following codons, since the sequence is aligned to the reference.
+/// Codons with missing or ambiguous bases, or partially deleted (frameshifts),
+/// are conservatively skipped.
+pub fn substitutions(
+    sequence: &Sequence,
+    reference: &Sequence,
+    annotations: &Table,
+) -> Result<Vec<AminoAcidSubstitution>, Report> {
+    if annotations.rows.is_empty() {
+        return Ok(Vec::new());
+    }
+    let gene_i = annotations.header_position("gene")?;
+
+    // the sequence is the reference, with its substitutions and deletions
+    let mut alts = BTreeMap::new();
+    sequence.substitutions.iter().for_each(|s| {
+        alts.insert(s.coord, s.alt);
+    });
+    sequence.deletions.iter().for_each(|d| {
+        alts.insert(d.coord, '-');
+    });
+    let missing: BTreeSet<usize> = sequence.missing.iter().copied().collect();
+
+    let mut aa_substitutions = Vec::new();
+    for (i, row) in annotations.rows.iter().enumerate() {
+        let gene = &row[gene_i];
+        let [start, end] = ["start", "end"].map(|header| {
+            annotations
+                .get_as::<usize>(i, header)
+                .wrap_err_with(|| format!("Failed to parse gene coordinate: {gene}"))
+        });
+        let (start, end) = (start?, end?);
+
+        // first coordinate of each codon with a mutation
+        let codons = alts
+            .range(start..=end)
+            .map(|(coord, _alt)| coord - (coord - start) % 3)
+            .filter(|codon_start| codon_start + 2 <= end)
+            .unique()
+            .collect_vec();
+
+        for codon_start in codons {
+            let coords = codon_start..codon_start + 3;
+            if coords.clone().any(|coord| missing.contains(&coord)) {
+                continue;
+            }
+            let Some(ref_codon) = coords
+                .clone()
+                .map(|coord| reference.seq.get(coord - 1).copied())
+                .collect::<Option<Vec<char>>>()
+            else {
+                continue;
+            };
+            let alt_codon = coords
+                .clone()
+                .zip(&ref_codon)
+                .map(|(coord, r)| alts.get(&coord).copied().unwrap_or(*r))
+                .collect_vec();
+
+            let (reference, alt) =
+                (translate_codon(&ref_codon), translate_codon(&alt_codon));
+            if reference == alt || reference == 'X' || alt == 'X' {
+                continue;
+            }
+            aa_substitutions.push(AminoAcidSubstitution {
+                gene: gene.clone(),
+                coord: (codon_start - start) / 3 + 1,
+                reference,
+                alt,
+            });
+        }
+    }
+
+    Ok(aa_substitutions)
+}
@@ -1,15 +1,23 @@
 use crate::cli;
 use crate::dataset;
+use crate::dataset::Dataset;
 use crate::recombination;
+use crate::utils;
+use crate::utils::table::Table;
 
-use color_eyre::eyre::{eyre, Report, Result, WrapErr};
+use color_eyre::eyre::{eyre, Report, Result};
+use color_eyre::Help;
 use itertools::Itertools;
 use log::{debug, info};
-use rand::Rng;
-use std::fs::{create_dir_all, File};
-use std::io::Write;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::BTreeMap;
+use std::fs::create_dir_all;
 
 /// Simulate recombination.
+///
+/// Writes the recombinant sequences (simulated.fasta) and a truth table of
+/// their parents, breakpoints, and regions (truth.tsv).
 pub fn simulate(args: &cli::simulate::Args) -> Result<(), Report> {
     // create output directory if it doesn't exist
     if !args.output_dir.exists() {
@@ -24,89 +32,199 @@ pub fn simulate(args: &cli::simulate::Args) -> Result<(), Report> {
     let genome_length = dataset.reference.genome_length;
 
     // Check to make sure all parents are in dataset
-    let parents = args.parents.clone();
-    for parent in &parents {
-        if !dataset.populations.contains_key(parent.as_str()) {
-            return Err(eyre!(
-                "Parent {parent} is not the dataset populations fasta."
+    let mut parents = Vec::new();
+    for parent in &args.parents {
+        let parent = dataset.canonical_name(parent)?;
+        if !dataset.populations.contains_key(&parent) {
+            return Err(dataset::not_found(
+                &parent,
+                "dataset populations fasta",
+                dataset.populations.keys(),
             ));
         }
+        parents.push(parent);
+    }
+    if parents.len() < 2 {
+        return Err(eyre!(
+            "At least 2 --parents are required to simulate recombination."
+        ));
+    }
+    if genome_length <= parents.len() {
+        return Err(eyre!(
+            "The genome ({genome_length} bases) is too short for {} parents.",
+            parents.len()
+        ));
     }
 
     // ------------------------------------------------------------------------
     // Breakpoints
 
-    let mut breakpoints = if let Some(breakpoints) = &args.breakpoints {
+    if args.num_sequences == 0 {
+        return Err(eyre!("--num-sequences must be at least 1."));
+    }
+    if let Some(breakpoints) = &args.breakpoints {
+        validate_breakpoints(breakpoints, parents.len(), genome_length)?;
         info!("Using manual breakpoints: {breakpoints:?}");
-        breakpoints.clone()
-    } else {
-        let mut breakpoints = Vec::new();
-        let mut num_breakpoints_remaining = parents.len() - 1;
-        let mut start = 1;
-        let mut rng = rand::thread_rng();
-        while num_breakpoints_remaining > 0 {
-            // save some coordinates for future breakpoints
-            let end = genome_length - num_breakpoints_remaining;
-            let coord = rng.gen_range(start..end);
-            breakpoints.push(coord);
-            start = coord + 1;
-            num_breakpoints_remaining -= 1;
+        if args.num_sequences > 1 {
+            return Err(eyre!(
+                "--num-sequences {} requires random breakpoints.",
+                args.num_sequences
+            ))
+            .suggestion("Please remove --breakpoints, or simulate 1 sequence.");
         }
-        info!("Using random breakpoints: {breakpoints:?}");
-        breakpoints
-    };
+    }
 
-    let unique_key = format!(
-        "simulate_{}_{}",
-        &parents.iter().join("_"),
-        &breakpoints.iter().map(|start| format!("{start}-{}", start + 1)).join("_"),
-    );
-    info!("Unique Key: {unique_key:?}");
+    // the seed is reported, so that random breakpoints can be reproduced
+    let seed = args.seed.unwrap_or_else(|| rand::thread_rng().gen());
+    if args.breakpoints.is_none() {
+        info!("Using random breakpoints, with seed: {seed}");
+    }
+    let mut rng = StdRng::seed_from_u64(seed);
 
     // ------------------------------------------------------------------------
-    // Regions
+    // Sequences
 
-    breakpoints.push(genome_length);
-    let mut regions = Vec::new();
+    let mut fasta = Vec::new();
+    let mut truth = Table::new();
+    truth.headers = vec!["strain", "parents", "breakpoints", "regions"]
+        .into_iter()
+        .map(String::from)
+        .collect_vec();
+
+    let mut keys_seen: BTreeMap<String, usize> = BTreeMap::new();
+    for _ in 0..args.num_sequences {
+        let breakpoints = match &args.breakpoints {
+            Some(breakpoints) => breakpoints.clone(),
+            None => random_breakpoints(&mut rng, parents.len(), genome_length),
+        };
+
+        // the crossover is between the end of one region and start of the next
+        let breakpoints_display = breakpoints
+            .iter()
+            .map(|end| recombination::Breakpoint::new(*end, end + 1))
+            .collect_vec();
+
+        let mut unique_key = format!(
+            "simulate_{}_{}",
+            &parents.iter().join("_"),
+            &breakpoints_display.iter().join("_"),
+        );
+        // random breakpoints can be drawn more than once
+        let num_duplicates = keys_seen.entry(unique_key.clone()).or_insert(0);
+        if *num_duplicates > 0 {
+            unique_key = format!("{unique_key}_{num_duplicates}");
+        }
+        *num_duplicates += 1;
+        info!("Unique Key: {unique_key:?}");
+
+        let (regions, sequence) = recombine(&dataset, &parents, &breakpoints)?;
+
+        fasta.push(format!(">{unique_key}\n{sequence}"));
+        truth.rows.push(vec![
+            unique_key,
+            parents.iter().join(","),
+            breakpoints_display.iter().join(","),
+            regions.iter().join(","),
+        ]);
+    }
+
+    let output_path = args.output_dir.join("simulated.fasta");
+    info!("Exporting fasta: {output_path:?}");
+    utils::write_atomic(&output_path, format!("{}\n", fasta.join("\n")))?;
+
+    let output_path = args.output_dir.join("truth.tsv");
+    info!("Exporting truth table: {output_path:?}");
+    truth.write(&output_path)?;
+
+    Ok(())
+}
+
+/// Check that breakpoints are increasing coordinates within the genome, one
+/// less than the number of parents.
+pub fn validate_breakpoints(
+    breakpoints: &[usize],
+    num_parents: usize,
+    genome_length: usize,
+) -> Result<(), Report> {
+    if breakpoints.len() != num_parents - 1 {
+        return Err(eyre!(
+            "{} --breakpoints were provided for {num_parents} parents.",
+            breakpoints.len()
+        ))
+        .suggestion("Please provide one less breakpoint than the number of parents.");
+    }
+    let increasing = breakpoints.iter().tuple_windows().all(|(a, b)| a < b);
+    let in_genome = breakpoints.iter().all(|b| *b >= 1 && *b < genome_length);
+    if !increasing || !in_genome {
+        return Err(eyre!("Invalid --breakpoints: {breakpoints:?}")).suggestion(format!(
+            "Breakpoints must be increasing coordinates from 1 to {}.",
+            genome_length - 1
+        ));
+    }
+    Ok(())
+}
+
+/// Choose random breakpoints for the parents, in increasing order.
+pub fn random_breakpoints(
+    rng: &mut StdRng,
+    num_parents: usize,
+    genome_length: usize,
+) -> Vec<usize> {
+    let mut breakpoints = Vec::new();
+    let mut num_breakpoints_remaining = num_parents - 1;
     let mut start = 1;
+    while num_breakpoints_remaining > 0 {
+        // save some coordinates for future breakpoints
+        let end = genome_length - num_breakpoints_remaining;
+        let coord = rng.gen_range(start..end);
+        breakpoints.push(coord);
+        start = coord + 1;
+        num_breakpoints_remaining -= 1;
+    }
+    breakpoints
+}
 
-    for (origin, end) in parents.into_iter().zip(breakpoints.into_iter()) {
+/// Create a recombinant sequence of the parents, with regions ending at the
+/// breakpoints.
+pub fn recombine(
+    dataset: &Dataset,
+    parents: &[String],
+    breakpoints: &[usize],
+) -> Result<(Vec<recombination::Region>, String), Report> {
+    let genome_length = dataset.reference.genome_length;
+    let ends = breakpoints.iter().copied().chain([genome_length]);
+
+    let mut regions = Vec::new();
+    let mut start = 1;
+    for (origin, end) in parents.iter().zip(ends) {
         let region = recombination::Region {
             start,
             end,
-            origin,
+            origin: origin.clone(),
             substitutions: Vec::new(),
+            reversions: Vec::new(),
+            missing: 0.0,
         };
         regions.push(region);
         start = end + 1;
     }
     debug!("Regions: {regions:?}");
 
-    // ------------------------------------------------------------------------
-    // Sequences
-
-    let sequence: String = regions
+    let sequence = regions
         .iter()
         .map(|region| {
-            let sequence = dataset.populations.get(&region.origin).unwrap_or_else(|| {
-                panic!(
+            let sequence = dataset.populations.get(&region.origin).ok_or_else(|| {
+                eyre!(
                     "Failed to find region origin {} in dataset populations.",
-                    &region.origin
+                    region.origin
                 )
-            });
+            })?;
             // Reminder, -1 to coordinates since they are 1-based
-            sequence.seq[region.start - 1..=region.end - 1].iter().collect::<String>()
+            Ok((region.start..=region.end)
+                .map(|coord| sequence.base(coord, &dataset.reference))
+                .collect::<String>())
         })
-        .collect();
+        .collect::<Result<String, Report>>()?;
 
-    let output_path = args.output_dir.join(format!("{unique_key}.fasta"));
-    info!("Exporting fasta: {output_path:?}");
-    let mut output_file = File::create(&output_path)
-        .wrap_err_with(|| format!("Unable to create file: {output_path:?}"))?;
-    let lines = format!(">{unique_key}\n{sequence}");
-    output_file
-        .write_all(lines.as_bytes())
-        .wrap_err_with(|| format!("Unable to write file: {output_path:?}"))?;
-
-    Ok(())
+    Ok((regions, sequence))
 }
@@ -0,0 +1,47 @@
+use serde::Serializer;
+
+/// Number of decimal places of floats in outputs (ex. fractions, runtimes).
+///
+/// Branch lengths are not rounded, as they are data of the dataset rather
+/// than results.
+pub const PRECISION: usize = 2;
+
+// ----------------------------------------------------------------------------
+// Functions
+// ----------------------------------------------------------------------------
+
+/// Round a float to a number of decimal places, with ties away from zero.
+///
+/// Negative zero is rounded to zero, so that outputs never have -0.00.
+pub fn round(value: f64, precision: usize) -> f64 {
+    let scale = 10_f64.powi(precision as i32);
+    let rounded = (value * scale).round() / scale;
+    match rounded == 0.0 {
+        true => 0.0,
+        false => rounded,
+    }
+}
+
+/// Format a float with a fixed number of decimal places (PRECISION), for
+/// text outputs (ex. tsv, svg, logs). Values that are not finite are NA.
+pub fn float(value: f64) -> String {
+    float_with(value, PRECISION)
+}
+
+/// Format a float with a fixed number of decimal places.
+pub fn float_with(value: f64, precision: usize) -> String {
+    if !value.is_finite() {
+        return "NA".to_string();
+    }
+    format!("{:.precision$}", round(value, precision))
+}
+
+/// Serialize a float rounded to PRECISION decimal places, for JSON outputs.
+///
+/// ex. `#[serde(serialize_with = "fmt::serialize_float")]`
+pub fn serialize_float<S>(value: &f64, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_f64(round(*value, PRECISION))
+}
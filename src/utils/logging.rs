@@ -0,0 +1,52 @@
+use crate::cli::Verbosity;
+use chrono::Utc;
+use indicatif::{ProgressBar, ProgressDrawTarget};
+use serde_json::json;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether progress bars are hidden (ex. --quiet or --json-logs).
+static HIDE_PROGRESS: AtomicBool = AtomicBool::new(false);
+
+// ----------------------------------------------------------------------------
+// Functions
+// ----------------------------------------------------------------------------
+
+/// Initialize the global logger, and whether progress bars are drawn.
+///
+/// Logs are always written to stderr, so stdout is reserved for outputs. With
+/// `quiet`, only errors are logged. With `json_logs`, each event is written as a
+/// single line of JSON, for log collectors. Progress bars are hidden in both cases.
+pub fn init(verbosity: &Verbosity, quiet: bool, json_logs: bool) {
+    let level = if quiet {
+        Verbosity::Error.to_string()
+    } else {
+        verbosity.to_string()
+    };
+
+    let mut builder = env_logger::Builder::new();
+    builder.parse_filters(&level).target(env_logger::Target::Stderr);
+    if json_logs {
+        builder.format(|buf, record| {
+            let event = json!({
+                "timestamp": Utc::now().to_rfc3339(),
+                "level": record.level().to_string(),
+                "target": record.target(),
+                "message": record.args().to_string(),
+            });
+            writeln!(buf, "{event}")
+        });
+    }
+    builder.init();
+
+    HIDE_PROGRESS.store(quiet || json_logs, Ordering::Relaxed);
+}
+
+/// Create a progress bar, which is hidden if progress is silenced.
+pub fn progress_bar(len: u64) -> ProgressBar {
+    if HIDE_PROGRESS.load(Ordering::Relaxed) {
+        ProgressBar::with_draw_target(Some(len), ProgressDrawTarget::hidden())
+    } else {
+        ProgressBar::new(len)
+    }
+}
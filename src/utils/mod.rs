@@ -1,8 +1,14 @@
-pub mod remote_file;
+pub mod fmt;
+pub mod logging;
 pub mod table;
+pub mod tar;
+pub mod theme;
+pub mod versioned_file;
+pub mod zip;
 
 use crate::dataset::attributes::Tag;
-use crate::utils::remote_file::RemoteFile;
+use crate::utils::versioned_file::VersionedFile;
+use bio::io::fasta;
 use chrono::prelude::*;
 use color_eyre::eyre::{eyre, Report, Result, WrapErr};
 use color_eyre::Help;
@@ -10,13 +16,127 @@ use itertools::Itertools;
 use log::{debug, warn};
 use reqwest::header::{ACCESS_CONTROL_EXPOSE_HEADERS, USER_AGENT};
 use std::collections::BTreeMap;
-use std::fs::{remove_file, write, File};
-use std::io::Read;
+use std::fs::{create_dir_all, remove_file, File};
+use std::io::{BufRead, BufReader, Read, Write};
 use std::path::{Path, PathBuf};
-use tempfile::TempDir;
-//use zip::ZipArchive;
+use tempfile::{NamedTempFile, TempDir};
 use zstd::stream::read::Decoder;
 
+/// Byte order mark, which some editors (ex. Excel, Notepad) prepend to UTF-8 files.
+pub const BOM: &str = "\u{feff}";
+
+/// Open a file for buffered reading, skipping a leading byte order mark.
+pub fn open_bufread(path: &Path) -> Result<BufReader<File>, Report> {
+    let file =
+        File::open(path).wrap_err_with(|| format!("Failed to read file: {path:?}"))?;
+    let mut reader = BufReader::new(file);
+    let has_bom = reader
+        .fill_buf()
+        .wrap_err_with(|| format!("Failed to read file: {path:?}"))?
+        .starts_with(BOM.as_bytes());
+    if has_bom {
+        reader.consume(BOM.len());
+    }
+    Ok(reader)
+}
+
+/// Read a file to string, skipping a leading byte order mark.
+pub fn read_to_string(path: &Path) -> Result<String, Report> {
+    let mut contents = String::new();
+    open_bufread(path)?
+        .read_to_string(&mut contents)
+        .wrap_err_with(|| format!("Failed to read file: {path:?}"))?;
+    Ok(contents)
+}
+
+/// Open a FASTA file for reading, skipping a leading byte order mark.
+///
+/// Trailing carriage returns (CRLF) are trimmed from headers and sequences by
+/// the reader.
+pub fn read_fasta(path: &Path) -> Result<fasta::Reader<BufReader<File>>, Report> {
+    Ok(fasta::Reader::from_bufread(open_bufread(path)?))
+}
+
+/// Convert Windows path separators (\\) to forward slashes.
+///
+/// Used for relative paths recorded in output files (ex. manifest.json), so
+/// that output directories can be moved between operating systems.
+pub fn normalize_separators(path: &Path) -> PathBuf {
+    PathBuf::from(path.to_string_lossy().replace('\\', "/"))
+}
+
+/// Replace characters that are not safe in file names with underscores.
+pub fn sanitize_file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c.is_ascii_alphanumeric() || "-_.".contains(c) {
+            true => c,
+            false => '_',
+        })
+        .collect()
+}
+
+/// Format bytes as a lowercase hex string (ex. checksums).
+pub fn to_hex(bytes: &[u8]) -> String {
+    use std::fmt::Write;
+    bytes.iter().fold(String::with_capacity(bytes.len() * 2), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+/// Create the parent directory of a file path, if it doesn't exist.
+pub fn create_parent_dir(path: &Path) -> Result<(), Report> {
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() && !parent.exists() {
+            create_dir_all(parent)
+                .wrap_err_with(|| format!("Failed to create directory: {parent:?}"))?;
+        }
+    }
+    Ok(())
+}
+
+/// Create a temporary file in the same directory as the output path.
+///
+/// The temporary file is on the same filesystem as the output, so that it can
+/// be atomically renamed with [`persist_atomic`]. It is deleted if dropped
+/// before being persisted.
+pub fn create_temp_file(path: &Path) -> Result<NamedTempFile, Report> {
+    create_parent_dir(path)?;
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    // keep the extension, for writers that parse the format from the path
+    let suffix = path.extension().map(|ext| format!(".{}", ext.to_string_lossy()));
+    tempfile::Builder::new()
+        .prefix(".rebar-")
+        .suffix(suffix.as_deref().unwrap_or(""))
+        .tempfile_in(dir)
+        .wrap_err_with(|| format!("Failed to create temporary file for: {path:?}"))
+}
+
+/// Atomically move a temporary file to the output path.
+pub fn persist_atomic(file: NamedTempFile, path: &Path) -> Result<(), Report> {
+    file.persist(path)
+        .map_err(|e| e.error)
+        .wrap_err_with(|| format!("Failed to write file: {path:?}"))?;
+    Ok(())
+}
+
+/// Write contents to a file atomically, via a temporary file and rename.
+///
+/// Concurrent or interrupted runs will never leave a partially written file at
+/// the output path, it either has the previous contents or the new contents.
+pub fn write_atomic<C: AsRef<[u8]>>(path: &Path, contents: C) -> Result<(), Report> {
+    let mut file = create_temp_file(path)?;
+    file.write_all(contents.as_ref())
+        .wrap_err_with(|| format!("Failed to write file: {path:?}"))?;
+    file.as_file()
+        .sync_all()
+        .wrap_err_with(|| format!("Failed to write file: {path:?}"))?;
+    persist_atomic(file, path)
+}
+
 /// Download file from url to path, with optional decompression.
 pub async fn download_file(
     url: &str,
@@ -38,13 +158,11 @@ pub async fn download_file(
         let tmp_dir = TempDir::new()?;
         let tmp_path = PathBuf::from(tmp_dir.path()).join(format!("tmpfile.{ext}"));
         let content = response.bytes().await?;
-        write(&tmp_path, content)
-            .wrap_err_with(|| eyre!("Unable to write file: {tmp_path:?}"))?;
+        write_atomic(&tmp_path, content)?;
         decompress_file(&tmp_path, output_path, true)?;
     } else {
         let content = response.text().await?;
-        write(output_path, content)
-            .wrap_err_with(|| eyre!("Unable to write file: {output_path:?}"))?;
+        write_atomic(output_path, content)?;
     }
 
     Ok(())
@@ -105,7 +223,7 @@ pub async fn download_github(
     remote_path: &str,
     output_path: &Path,
     sha: &Option<String>,
-) -> Result<RemoteFile, Report> {
+) -> Result<VersionedFile, Report> {
     // GitHub API Setup
     let github_token: Option<String> = std::env::var("GITHUB_TOKEN").ok();
     let github_username = std::env::var("GITHUB_USERNAME").unwrap_or("".to_string());
@@ -170,23 +288,12 @@ pub async fn download_github(
     let download_url =
         format!("https://raw.githubusercontent.com/{repo}/{sha}/{remote_path}");
 
-    // Identify decompression mode
-    // TBD! todo!() make this an enum of implemented decompression types
-    let ext = path_to_ext(Path::new(&download_url))?;
-    let decompress = ext == "zst";
-
-    // Download the file
-    debug!("Downloading file: {download_url} to {output_path:?}");
-    download_file(&download_url, output_path, decompress).await?;
-
-    // Store all the information about the remote file for the dataset summary
-    let remote_file = RemoteFile {
-        url: download_url,
-        sha,
-        local_path: output_path.to_path_buf(),
-        date_created,
-        date_downloaded: Utc::now(),
-    };
+    // Download the file, and store all the information about the remote file
+    // for the dataset summary
+    let mut remote_file = VersionedFile::from_url(&download_url, output_path);
+    remote_file.sha = sha;
+    remote_file.date_created = date_created;
+    remote_file.fetch().await?;
     debug!("Downloaded file: {remote_file:?}");
 
     Ok(remote_file)
@@ -204,19 +311,20 @@ pub fn decompress_file(input: &Path, output: &Path, inplace: bool) -> Result<(),
             let mut decoder = Decoder::new(reader)?;
             let mut buffer = String::new();
             decoder.read_to_string(&mut buffer)?;
-            write(output, buffer)
-                .wrap_err(format!("Unable to write file: {:?}", output))?;
+            write_atomic(output, buffer)?;
+
+            if inplace {
+                remove_file(input)?;
+            }
+        }
+        // archives are extracted into the output directory
+        "zip" => {
+            zip::extract(input, output)?;
 
             if inplace {
                 remove_file(input)?;
             }
         }
-        // "zip" => {
-        //     let file = File::open(input)?;
-        //     let mut archive = ZipArchive::new(file)?;
-        //     archive.extract(output)
-        //         .wrap_err(format!("Unable to write file: {:?}", output))?;
-        // }
         _ => return Err(eyre!("Decompression for .{ext:?} is not implemented yet.")),
     };
 
@@ -270,3 +378,42 @@ pub fn path_to_ext(path: &Path) -> Result<String, Report> {
 
     Ok(ext)
 }
+
+/// Levenshtein (edit) distance between two strings.
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b = b.chars().collect_vec();
+    // distances from the previous row, initialized to the empty prefix of a
+    let mut prev = (0..=b.len()).collect_vec();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut curr = vec![i + 1; b.len() + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let cost = usize::from(a_char != *b_char);
+            curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        prev = curr;
+    }
+
+    prev[b.len()]
+}
+
+/// Suggest the closest candidates to a query, for did-you-mean messages.
+///
+/// Candidates are returned in order of increasing edit distance, up to a
+/// maximum distance scaled by the length of the query.
+pub fn suggest<'a, I>(query: &str, candidates: I) -> Vec<String>
+where
+    I: IntoIterator<Item = &'a String>,
+{
+    let max_distance = (query.chars().count() / 3).max(1);
+    let max_suggestions = 3;
+
+    candidates
+        .into_iter()
+        .map(|candidate| (levenshtein(query, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .sorted()
+        .take(max_suggestions)
+        .map(|(_, candidate)| candidate.to_string())
+        .collect_vec()
+}
@@ -1,10 +1,132 @@
 use crate::utils;
 use color_eyre::eyre::{eyre, Report, Result, WrapErr};
 use itertools::Itertools;
+use std::cmp::Ordering;
 use std::default::Default;
-use std::fs::File;
-use std::io::{BufRead, BufReader, Write};
+use std::fmt::Display;
+use std::io::BufRead;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+// ----------------------------------------------------------------------------
+// Sort Type
+
+/// How to compare the values of a column when sorting.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SortType {
+    /// Compare values as text.
+    #[default]
+    Lexical,
+    /// Compare runs of digits as numbers, and other characters as text.
+    ///
+    /// Useful for genomic coordinates (ex. breakpoints) and names with numbers,
+    /// where '2000-3000' sorts before '10000-11000' and 'BA.2' before 'BA.10'.
+    Natural,
+    /// Compare values as numbers, values that are not numbers sort last.
+    Numeric,
+}
+
+impl SortType {
+    /// Compare two values by the sort type.
+    pub fn compare(&self, a: &str, b: &str) -> Ordering {
+        match self {
+            SortType::Lexical => a.cmp(b),
+            SortType::Natural => natural_cmp(a, b),
+            SortType::Numeric => {
+                match (a.trim().parse::<f64>(), b.trim().parse::<f64>()) {
+                    (Ok(a), Ok(b)) => a.total_cmp(&b),
+                    (Ok(_), Err(_)) => Ordering::Less,
+                    (Err(_), Ok(_)) => Ordering::Greater,
+                    (Err(_), Err(_)) => a.cmp(b),
+                }
+            }
+        }
+    }
+}
+
+/// Compare strings naturally, where runs of digits are compared as numbers.
+///
+/// Values that are naturally equal (ex. '01' and '1') fall back to lexical
+/// order, so that sorting is deterministic.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    // split into runs of digits and non-digits
+    let chunks = |s: &str| {
+        s.chars()
+            .group_by(|c| c.is_ascii_digit())
+            .into_iter()
+            .map(|(is_digit, chunk)| (is_digit, chunk.collect::<String>()))
+            .collect_vec()
+    };
+    let (a_chunks, b_chunks) = (chunks(a), chunks(b));
+
+    for (a_chunk, b_chunk) in a_chunks.iter().zip(b_chunks.iter()) {
+        let ordering = match (a_chunk, b_chunk) {
+            ((true, a_digits), (true, b_digits)) => {
+                // compare without parsing, so that long numbers can't overflow
+                let a_digits = a_digits.trim_start_matches('0');
+                let b_digits = b_digits.trim_start_matches('0');
+                a_digits.len().cmp(&b_digits.len()).then(a_digits.cmp(b_digits))
+            }
+            ((_, a_text), (_, b_text)) => a_text.cmp(b_text),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    a_chunks.len().cmp(&b_chunks.len()).then(a.cmp(b))
+}
+
+/// Quote a value that spreadsheets would otherwise convert, as text.
+///
+/// Values with digits and no letters that are not whole numbers (ex. the
+/// breakpoint '12-12', the date '2023-11-30', the decimal '0.5', or the list
+/// '0.25,0.75') are converted to dates or locale numbers when opened in Excel.
+/// These are enclosed in double quotes, with inner quotes doubled.
+pub fn locale_safe(value: &str) -> String {
+    let has_digit = value.chars().any(|c| c.is_ascii_digit());
+    let has_letter = value.chars().any(|c| c.is_ascii_alphabetic());
+    let is_integer = value.chars().all(|c| c.is_ascii_digit());
+    if has_digit && !has_letter && !is_integer {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Split a line of delimited text into values, where values enclosed in double
+/// quotes can contain the delimiter and doubled inner quotes.
+///
+/// Quotes that don't start a value are kept as is (ex. `5'-"UTR"`).
+pub fn split_quoted(line: &str, delim: char) -> Vec<String> {
+    let mut values = Vec::new();
+    let mut value = String::new();
+    // whether the current value is enclosed in quotes, and if we're inside them
+    let (mut is_quoted, mut in_quotes) = (false, false);
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                chars.next();
+                value.push('"');
+            }
+            '"' if in_quotes => in_quotes = false,
+            '"' if value.is_empty() && !is_quoted => {
+                (is_quoted, in_quotes) = (true, true)
+            }
+            c if c == delim && !in_quotes => {
+                values.push(std::mem::take(&mut value));
+                is_quoted = false;
+            }
+            c => value.push(c),
+        }
+    }
+    values.push(value);
+    values
+}
+
+// ----------------------------------------------------------------------------
+// Table
 
 #[derive(Debug, Clone)]
 pub struct Table {
@@ -28,39 +150,62 @@ impl Table {
         }
     }
 
+    /// Read a table from a file, with a header line.
+    ///
+    /// The delimiter is from the file extension (tsv, csv, or txt), see
+    /// [Table::from_reader] for how lines are parsed.
     pub fn read(path: &Path) -> Result<Table, Report> {
-        let mut table = Table::new();
+        Table::read_with_headers(path, true)
+    }
+
+    /// Read a table from a file without a header line, where every line is a
+    /// row, and columns are accessed by position.
+    pub fn read_headerless(path: &Path) -> Result<Table, Report> {
+        Table::read_with_headers(path, false)
+    }
 
+    fn read_with_headers(path: &Path, has_headers: bool) -> Result<Table, Report> {
         // lookup delimiter from file extension
         let delim = utils::path_to_delim(path)?;
+        // attempt to open the file path, skipping a byte order mark
+        let reader = utils::open_bufread(path)?;
+        let mut table = Table::from_reader(reader, delim, has_headers)
+            .wrap_err_with(|| format!("Failed to read table: {path:?}"))?;
+        table.path = path.to_path_buf();
+        Ok(table)
+    }
+
+    /// Read a table from delimited text (ex. a file or bytes in memory).
+    ///
+    /// Values may be enclosed in double quotes (ex. linelists written with
+    /// --locale-safe), to contain the delimiter or doubled inner quotes.
+    /// Windows line endings and a leading byte order mark are removed. If
+    /// `has_headers` is false, the headers are empty and every line is a row.
+    pub fn from_reader<R: BufRead>(
+        reader: R,
+        delim: char,
+        has_headers: bool,
+    ) -> Result<Table, Report> {
+        let mut table = Table::new();
 
-        // attempt to open the file path
-        let file =
-            File::open(path).wrap_err_with(|| eyre!("Failed to read file: {path:?}"))?;
-        // read in the lines
-        let lines = BufReader::new(file).lines();
-        //.map_err(|e| eyre!(e))
-        //.wrap_err_with(|| eyre!("Failed to parse file: {path:?}"))?;
-
-        for line in lines.flatten() {
-            let row = line
-                .split(delim)
-                .collect_vec()
-                .into_iter()
-                .map(String::from)
-                .collect_vec();
-            // if headers are empty, this is the first line, write headers
-            if table.headers.is_empty() {
+        for (i, line) in reader.lines().enumerate() {
+            let line = line.wrap_err_with(|| format!("Failed to read line {}", i + 1))?;
+            // windows line endings (CRLF)
+            let line = line.strip_suffix('\r').unwrap_or(&line);
+            let line = if i == 0 {
+                line.trim_start_matches(utils::BOM)
+            } else {
+                line
+            };
+            let row = split_quoted(line, delim);
+            // the first line is the headers, otherwise regular row
+            if has_headers && i == 0 {
                 table.headers = row;
-            }
-            // otherwise regular row
-            else {
+            } else {
                 table.rows.push(row);
             }
         }
 
-        table.path = path.to_path_buf();
-
         Ok(table)
     }
 
@@ -72,68 +217,217 @@ impl Table {
         Ok(pos)
     }
 
-    pub fn filter(&self, header: &str, pattern: &str) -> Result<Table, Report> {
-        let mut table = Table::new();
+    /// Parse the value of a column in a row (0-based, after the headers).
+    ///
+    /// ex. `let start = table.get_as::<usize>(0, "start")?;`
+    pub fn get_as<U>(&self, row: usize, header: &str) -> Result<U, Report>
+    where
+        U: FromStr,
+        U::Err: Display,
+    {
+        let header_i = self.header_position(header)?;
+        self.parse_value(row, header_i)
+    }
+
+    /// Parse all values of a column, in order of the rows.
+    ///
+    /// ex. `let starts = table.get_column_as::<usize>("start")?;`
+    pub fn get_column_as<U>(&self, header: &str) -> Result<Vec<U>, Report>
+    where
+        U: FromStr,
+        U::Err: Display,
+    {
         let header_i = self.header_position(header)?;
-        table.headers = self.headers.clone();
-        table.rows = self
+        (0..self.rows.len()).map(|row| self.parse_value(row, header_i)).collect()
+    }
+
+    /// Parse a value by its row and column position, with the row number and
+    /// column name in errors.
+    fn parse_value<U>(&self, row: usize, header_i: usize) -> Result<U, Report>
+    where
+        U: FromStr,
+        U::Err: Display,
+    {
+        let header = &self.headers[header_i];
+        let value = self
             .rows
+            .get(row)
+            .ok_or_else(|| {
+                eyre!("Row {} was not found in table: {:?}.", row + 1, self.path)
+            })?
+            .get(header_i)
+            .ok_or_else(|| {
+                eyre!(
+                    "Row {} has no value for column '{header}' in table: {:?}.",
+                    row + 1,
+                    self.path
+                )
+            })?;
+        value.parse::<U>().map_err(|e| {
+            eyre!(
+                "Failed to parse row {} of column '{header}' as {}: '{value}' ({e}) in table: {:?}.",
+                row + 1,
+                std::any::type_name::<U>(),
+                self.path
+            )
+        })
+    }
+
+    /// Filter rows where the value of a column matches a pattern exactly.
+    pub fn filter(&self, header: &str, pattern: &str) -> Result<Table, Report> {
+        self.filter_column(header, |value| value == pattern)
+    }
+
+    /// Filter rows where the value of a column satisfies a predicate.
+    ///
+    /// ex. `linelist.filter_column("recombinant", |value| !value.is_empty())?`
+    pub fn filter_column<F>(&self, header: &str, predicate: F) -> Result<Table, Report>
+    where
+        F: Fn(&str) -> bool,
+    {
+        let header_i = self.header_position(header)?;
+        self.check_column(header_i)?;
+        Ok(self.filter_rows(|row| predicate(&row[header_i])))
+    }
+
+    /// Filter rows that satisfy a predicate, as a new table.
+    pub fn filter_rows<F>(&self, predicate: F) -> Table
+    where
+        F: Fn(&[String]) -> bool,
+    {
+        Table {
+            headers: self.headers.clone(),
+            rows: self.rows.iter().filter(|row| predicate(row)).cloned().collect_vec(),
+            path: self.path.clone(),
+        }
+    }
+
+    /// Select and reorder columns, as a new table.
+    pub fn select_columns(&self, headers: &[&str]) -> Result<Table, Report> {
+        let positions = headers
             .iter()
-            .filter(|row| row[header_i] == pattern)
-            .cloned()
-            .collect_vec();
+            .map(|header| {
+                let header_i = self.header_position(header)?;
+                self.check_column(header_i)?;
+                Ok(header_i)
+            })
+            .collect::<Result<Vec<_>, Report>>()?;
+        Ok(Table {
+            headers: headers.iter().map(|header| header.to_string()).collect_vec(),
+            rows: self
+                .rows
+                .iter()
+                .map(|row| positions.iter().map(|i| row[*i].clone()).collect_vec())
+                .collect_vec(),
+            path: self.path.clone(),
+        })
+    }
+
+    /// Sort rows by columns, in order of priority.
+    ///
+    /// The sort is stable, rows with equal values keep their original order.
+    pub fn sort_rows(&mut self, columns: &[(&str, SortType)]) -> Result<(), Report> {
+        let columns = columns
+            .iter()
+            .map(|(header, sort_type)| {
+                let header_i = self.header_position(header)?;
+                self.check_column(header_i)?;
+                Ok((header_i, *sort_type))
+            })
+            .collect::<Result<Vec<_>, Report>>()?;
+        self.rows.sort_by(|a, b| {
+            columns.iter().fold(Ordering::Equal, |ordering, (i, sort_type)| {
+                ordering.then_with(|| sort_type.compare(&a[*i], &b[*i]))
+            })
+        });
+        Ok(())
+    }
+
+    /// Sort rows by a column, as a new table.
+    ///
+    /// ex. `linelist.sort_by_column("breakpoints", SortType::Natural)?`
+    pub fn sort_by_column(
+        &self,
+        header: &str,
+        sort_type: SortType,
+    ) -> Result<Table, Report> {
+        let mut table = self.clone();
+        table.sort_rows(&[(header, sort_type)])?;
         Ok(table)
     }
 
+    /// Check that every row has a value for a column, by its position.
+    fn check_column(&self, header_i: usize) -> Result<(), Report> {
+        match self.rows.iter().position(|row| row.get(header_i).is_none()) {
+            Some(row) => Err(eyre!(
+                "Row {} has no value for column '{}' in table: {:?}.",
+                row + 1,
+                self.headers[header_i],
+                self.path
+            )),
+            None => Ok(()),
+        }
+    }
+
     /// write to file
     pub fn write(&self, path: &Path) -> Result<(), Report> {
-        let mut file = File::create(path)
-            .wrap_err_with(|| format!("Unable to create file: {path:?}"))?;
-
         // Parse line delimiter from file extension
-        let delim = utils::path_to_delim(path)?.to_string();
+        let delim = utils::path_to_delim(path)?;
+        utils::write_atomic(path, self.to_delimited(delim))
+    }
+
+    /// Convert table to delimited text (ex. tsv), with a header line.
+    pub fn to_delimited(&self, delim: char) -> String {
+        let delim = delim.to_string();
 
-        // write headers
-        let line = format!("{}\n", self.headers.iter().join(&delim));
-        file.write_all(line.as_bytes())
-            .wrap_err_with(|| format!("Unable to write table headers: {line}"))?;
+        // headers
+        let mut output = format!("{}\n", self.headers.iter().join(&delim));
 
-        // write regular rows
+        // regular rows
         for row in &self.rows {
-            let line = format!("{}\n", row.iter().join(&delim));
-            file.write_all(line.as_bytes())
-                .wrap_err_with(|| format!("Unable to write table rows: {line}"))?;
+            output.push_str(&format!("{}\n", row.iter().join(&delim)));
         }
 
-        Ok(())
+        output
     }
 
-    /// Convert table to markdown format
+    /// Convert table to markdown format, with centered columns.
     ///
-    /// TBD: error handling for empty rows!
+    /// Pipes in values are escaped (ex. the region '1-11|A'), so that they
+    /// don't split columns. Rows with a different number of values than the
+    /// headers are an error.
     pub fn to_markdown(&self) -> Result<String, Report> {
-        // get the maximum width of each column
-        let col_widths = self
-            // iterate through columns/headers
-            .headers
+        for (row_i, row) in self.rows.iter().enumerate() {
+            if row.len() != self.headers.len() {
+                return Err(eyre!(
+                    "Row {} has {} values, but there are {} columns in table: {:?}",
+                    row_i + 1,
+                    row.len(),
+                    self.headers.len(),
+                    self.path
+                ));
+            }
+        }
+        let escape = |value: &str| value.replace('|', "\\|");
+        let headers = self.headers.iter().map(|h| escape(h)).collect_vec();
+        let rows = self
+            .rows
+            .iter()
+            .map(|row| row.iter().map(|value| escape(value)).collect_vec())
+            .collect_vec();
+
+        // get the maximum width (in characters) of each column, +2 to add space
+        // on either side
+        let col_widths = headers
             .iter()
             .enumerate()
             .map(|(col_i, header)| {
-                self
-                    // iterate through this column's rows,
-                    // get max string width, +2 to add space on either side
-                    .rows
-                    .iter()
-                    .map(|row| {
-                        let cell_width = (*row[col_i]).len();
-                        if cell_width >= header.len() {
-                            cell_width + 2
-                        } else {
-                            header.len() + 2
-                        }
-                    })
+                rows.iter()
+                    .map(|row| row[col_i].chars().count())
+                    .chain([header.chars().count()])
                     .max()
-                    .unwrap_or(header.len() + 2)
+                    .unwrap_or_default()
+                    + 2
             })
             .collect_vec();
 
@@ -142,8 +436,7 @@ impl Table {
         let mut header_frame = String::from("|");
 
         // Create the header line
-        for it in self.headers.iter().zip(col_widths.iter()) {
-            let (header, col_width) = it;
+        for (header, col_width) in headers.iter().zip(col_widths.iter()) {
             let cell = format!("{:^width$}|", header, width = col_width);
             markdown.push_str(&cell);
 
@@ -155,10 +448,10 @@ impl Table {
         markdown.push('\n');
 
         // Create the row lines
-        for row in &self.rows {
+        for row in &rows {
             markdown.push('|');
-            for (col_i, col_width) in col_widths.iter().enumerate() {
-                let cell = format!("{:^width$}|", row[col_i], width = col_width);
+            for (value, col_width) in row.iter().zip(col_widths.iter()) {
+                let cell = format!("{:^width$}|", value, width = col_width);
                 markdown.push_str(&cell);
             }
             markdown.push('\n');
@@ -0,0 +1,67 @@
+use crate::utils;
+use color_eyre::eyre::{eyre, Report, Result};
+use color_eyre::Help;
+use std::path::Path;
+
+/// Size of tar headers and data blocks.
+const BLOCK_SIZE: usize = 512;
+
+// ----------------------------------------------------------------------------
+// Functions
+// ----------------------------------------------------------------------------
+
+/// Write files to a tar archive (ustar), by their path in the archive.
+///
+/// Files are written with read/write permissions for the owner, and the
+/// current time. Parent directories are created when the archive is extracted.
+pub fn write(path: &Path, files: &[(String, Vec<u8>)]) -> Result<(), Report> {
+    let mtime = chrono::Utc::now().timestamp().max(0) as u64;
+    let mut archive = Vec::new();
+    for (name, contents) in files {
+        archive.extend(header(name, contents.len(), mtime)?);
+        archive.extend(contents);
+        let padding = (BLOCK_SIZE - contents.len() % BLOCK_SIZE) % BLOCK_SIZE;
+        archive.extend(vec![0; padding]);
+    }
+    // the end of the archive is marked by two empty blocks
+    archive.extend(vec![0; BLOCK_SIZE * 2]);
+
+    utils::write_atomic(path, archive)
+}
+
+/// Create the header block of a file in a tar archive (ustar).
+fn header(name: &str, size: usize, mtime: u64) -> Result<Vec<u8>, Report> {
+    // names longer than the name field are split into a prefix and name
+    let (prefix, name) = match name.len() {
+        0..=100 => ("", name),
+        _ => name
+            .char_indices()
+            .filter(|(i, c)| *c == '/' && *i <= 155 && name.len() - i - 1 <= 100)
+            .map(|(i, _c)| (&name[..i], &name[i + 1..]))
+            .next()
+            .ok_or_else(|| eyre!("File name is too long for a tar archive: {name}"))
+            .suggestion("Please use a shorter name.")?,
+    };
+
+    let mut header = vec![0; BLOCK_SIZE];
+    let mut set_field = |offset: usize, value: &[u8]| {
+        header[offset..offset + value.len()].copy_from_slice(value);
+    };
+    set_field(0, name.as_bytes());
+    set_field(100, b"0000644\0");
+    set_field(108, b"0000000\0");
+    set_field(116, b"0000000\0");
+    set_field(124, format!("{size:011o}\0").as_bytes());
+    set_field(136, format!("{mtime:011o}\0").as_bytes());
+    // the checksum is calculated as if its own field was spaces
+    set_field(148, b"        ");
+    set_field(156, b"0");
+    set_field(257, b"ustar\0");
+    set_field(263, b"00");
+    set_field(345, prefix.as_bytes());
+
+    let checksum: u32 = header.iter().map(|b| *b as u32).sum();
+    header[148..156].copy_from_slice(format!("{checksum:06o}\0 ").as_bytes());
+
+    Ok(header)
+}
@@ -0,0 +1,182 @@
+use crate::utils;
+use color_eyre::eyre::{eyre, Report, Result, WrapErr};
+use color_eyre::Help;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Names of the built-in themes.
+pub const THEMES: &[&str] = &["default", "colorblind"];
+
+// ----------------------------------------------------------------------------
+// Color
+
+/// An RGBA color, written as hex (ex. #1f77b4, or #1f77b480 with alpha).
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Color(pub [u8; 4]);
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let [r, g, b, a] = self.0;
+        write!(f, "#{r:02x}{g:02x}{b:02x}")?;
+        if a != 255 {
+            write!(f, "{a:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Color {
+    type Err = Report;
+
+    fn from_str(text: &str) -> Result<Self, Report> {
+        let hex = text.trim().trim_start_matches('#');
+        let channels = (0..hex.len())
+            .step_by(2)
+            .map(|i| hex.get(i..i + 2).and_then(|c| u8::from_str_radix(c, 16).ok()))
+            .collect::<Option<Vec<u8>>>();
+        match channels.as_deref() {
+            Some([r, g, b]) => Ok(Color([*r, *g, *b, 255])),
+            Some([r, g, b, a]) => Ok(Color([*r, *g, *b, *a])),
+            _ => Err(eyre!("Invalid color: {text}"))
+                .suggestion("Colors are hex RGB or RGBA, ex. #1f77b4"),
+        }
+    }
+}
+
+impl TryFrom<String> for Color {
+    type Error = Report;
+
+    fn try_from(text: String) -> Result<Self, Report> {
+        Color::from_str(&text)
+    }
+}
+
+impl From<Color> for String {
+    fn from(color: Color) -> Self {
+        color.to_string()
+    }
+}
+
+impl Color {
+    /// Blend the color with white, by a fraction (0: unchanged, 1: white).
+    pub fn lighten(&self, fraction: f32) -> Self {
+        let [r, g, b, a] = self.0;
+        let blend = |c: u8| (c as f32 + (255. - c as f32) * fraction).round() as u8;
+        Color([blend(r), blend(g), blend(b), a])
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Theme
+
+/// Colors of all visual outputs (plots and phylogeny exports).
+///
+/// Parents in plots are colored in palette order, with the dark palette for
+/// mutations and the light palette for reference bases. Annotations use the
+/// remaining colors.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct Theme {
+    /// Node color of the phylogeny root.
+    pub root: Color,
+    /// Node color of recombinants.
+    pub recombinant: Color,
+    /// Node color of non-recombinants.
+    pub non_recombinant: Color,
+    pub palette_dark: Vec<Color>,
+    pub palette_light: Vec<Color>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FromStr for Theme {
+    type Err = Report;
+
+    /// Get a built-in theme by name, or read a theme from a JSON file.
+    fn from_str(name: &str) -> Result<Self, Report> {
+        match name {
+            "default" => Ok(Theme::new()),
+            "colorblind" => Ok(Theme::colorblind()),
+            _ if Path::new(name).exists() => Theme::read(Path::new(name)),
+            _ => Err(eyre!(
+                "Unknown theme: {name}, themes are one of: {}, or a JSON file.",
+                THEMES.join(", ")
+            )),
+        }
+    }
+}
+
+impl Theme {
+    /// The default theme, with D3 (category20) palettes.
+    pub fn new() -> Self {
+        let hex = |colors: &[&str]| {
+            colors
+                .iter()
+                .map(|c| Color::from_str(c).unwrap_or(Color([0, 0, 0, 255])))
+                .collect_vec()
+        };
+        Theme {
+            root: Color([0, 0, 0, 255]),
+            recombinant: Color([228, 26, 28, 255]),
+            non_recombinant: Color([55, 126, 184, 255]),
+            palette_dark: hex(&[
+                "#1f77b4", "#ff7f0e", "#2ca02c", "#d62727", "#9467bd", "#8c563b",
+                "#e377c3", "#bcbd22", "#17bdcf",
+            ]),
+            palette_light: hex(&[
+                "#aec7e8", "#ffbb78", "#98df8a", "#ff9896", "#c5b0d5", "#c49c94",
+                "#f7b6d2", "#dbdb8d", "#9edae5",
+            ]),
+        }
+    }
+
+    /// A colorblind-safe theme, with the Okabe-Ito palette.
+    pub fn colorblind() -> Self {
+        let palette_dark = [
+            "#0072b2", "#e69f00", "#009e73", "#cc79a7", "#56b4e9", "#d55e00",
+        ]
+        .iter()
+        .map(|c| Color::from_str(c).unwrap_or(Color([0, 0, 0, 255])))
+        .collect_vec();
+        Theme {
+            root: Color([0, 0, 0, 255]),
+            recombinant: Color([213, 94, 0, 255]),
+            non_recombinant: Color([0, 114, 178, 255]),
+            palette_light: palette_dark.iter().map(|c| c.lighten(0.6)).collect_vec(),
+            palette_dark,
+        }
+    }
+
+    /// Read a theme from a JSON file.
+    ///
+    /// Colors that are not in the file keep their default.
+    pub fn read(path: &Path) -> Result<Theme, Report> {
+        let theme = utils::read_to_string(path)?;
+        let theme: Theme = serde_json::from_str(&theme)
+            .wrap_err_with(|| format!("Failed to parse theme: {path:?}"))?;
+        theme.validate()?;
+        Ok(theme)
+    }
+
+    /// Check that the palettes are usable.
+    pub fn validate(&self) -> Result<(), Report> {
+        if self.palette_dark.is_empty()
+            || self.palette_dark.len() != self.palette_light.len()
+        {
+            return Err(eyre!(
+                "Theme palettes must have the same number of colors, found {} dark and {} light.",
+                self.palette_dark.len(),
+                self.palette_light.len()
+            ));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,273 @@
+use crate::utils;
+use chrono::prelude::*;
+use color_eyre::eyre::{eyre, Report, Result, WrapErr};
+use color_eyre::Help;
+use log::debug;
+use openssl::sha::Sha256;
+use serde::{Deserialize, Serialize};
+use std::default::Default;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use zstd::stream::read::Decoder;
+
+// ----------------------------------------------------------------------------
+// Compression
+
+/// Compression of a file, identified by its extension.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    #[default]
+    None,
+    Zstd,
+}
+
+impl std::fmt::Display for Compression {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let compression = match self {
+            Compression::None => "none",
+            Compression::Zstd => "zstd",
+        };
+        write!(f, "{compression}")
+    }
+}
+
+impl Compression {
+    /// Identify compression from a path or url (ex. populations.fasta.zst).
+    pub fn from_path(path: &Path) -> Self {
+        match utils::path_to_ext(path).unwrap_or_default().as_str() {
+            "zst" => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Format
+
+/// Format of a file's (decompressed) contents.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Fasta,
+    Json,
+    Tsv,
+    Csv,
+    Text,
+    #[default]
+    Unknown,
+}
+
+impl std::fmt::Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let format = match self {
+            Format::Fasta => "fasta",
+            Format::Json => "json",
+            Format::Tsv => "tsv",
+            Format::Csv => "csv",
+            Format::Text => "text",
+            Format::Unknown => "unknown",
+        };
+        write!(f, "{format}")
+    }
+}
+
+impl Format {
+    /// Identify format from the extension of a path or url, ignoring compression
+    /// (ex. populations.fasta.zst is fasta).
+    pub fn from_path(path: &Path) -> Self {
+        let path = match Compression::from_path(path) {
+            Compression::None => path.to_path_buf(),
+            _ => path.with_extension(""),
+        };
+        match utils::path_to_ext(&path).unwrap_or_default().as_str() {
+            "fasta" | "fa" | "fna" => Format::Fasta,
+            "json" => Format::Json,
+            "tsv" => Format::Tsv,
+            "csv" => Format::Csv,
+            "txt" => Format::Text,
+            _ => Format::Unknown,
+        }
+    }
+
+    /// Identify format from the first line of the contents.
+    pub fn sniff(line: &str) -> Self {
+        let line = line.trim_start_matches(utils::BOM).trim_start();
+        if line.starts_with('>') {
+            Format::Fasta
+        } else if line.starts_with('{') || line.starts_with('[') {
+            Format::Json
+        } else if line.contains('\t') {
+            Format::Tsv
+        } else if line.contains(',') {
+            Format::Csv
+        } else {
+            Format::Text
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Versioned File
+
+/// A dataset file, and the version of its source.
+///
+/// Records where the file came from (url and commit sha), where it was saved,
+/// and how to read it, so that a dataset can be re-created exactly from its
+/// summary.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct VersionedFile {
+    pub url: String,
+    pub sha: String,
+    pub local_path: PathBuf,
+    pub date_created: DateTime<Utc>,
+    pub date_downloaded: DateTime<Utc>,
+    /// Compression of the source file, it is decompressed on fetch.
+    #[serde(default)]
+    pub compression: Compression,
+    #[serde(default)]
+    pub format: Format,
+    /// SHA-256 checksum (hex) of the local file, as fetched from its source.
+    #[serde(default)]
+    pub checksum: Option<String>,
+}
+
+impl Default for VersionedFile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VersionedFile {
+    pub fn new() -> Self {
+        VersionedFile {
+            url: String::new(),
+            sha: String::new(),
+            local_path: PathBuf::new(),
+            date_created: DateTime::default(),
+            date_downloaded: DateTime::default(),
+            compression: Compression::None,
+            format: Format::Unknown,
+            checksum: None,
+        }
+    }
+
+    /// Create a file from its source url (or path), and its local path.
+    ///
+    /// Compression and format are identified from the url extension.
+    pub fn from_url(url: &str, local_path: &Path) -> Self {
+        VersionedFile {
+            url: url.to_string(),
+            local_path: local_path.to_path_buf(),
+            compression: Compression::from_path(Path::new(url)),
+            format: Format::from_path(Path::new(url)),
+            ..Default::default()
+        }
+    }
+
+    /// Whether the source is a remote url, rather than a local file.
+    pub fn is_remote(&self) -> bool {
+        self.url.starts_with("http://") || self.url.starts_with("https://")
+    }
+
+    /// Download (or copy) the source to the local path, decompressing if needed.
+    ///
+    /// If the format could not be identified from the url, it is sniffed from
+    /// the downloaded contents. If the file already has a checksum (ex. from a
+    /// summary snapshot), the fetched file must match it.
+    pub async fn fetch(&mut self) -> Result<(), Report> {
+        if self.url.is_empty() {
+            return Err(eyre!("File has no source url: {:?}", self.local_path));
+        }
+        self.compression = Compression::from_path(Path::new(&self.url));
+        let decompress = self.compression != Compression::None;
+
+        debug!("Fetching file: {} to {:?}", self.url, self.local_path);
+        if self.is_remote() {
+            utils::download_file(&self.url, &self.local_path, decompress).await?;
+        } else if decompress {
+            utils::decompress_file(Path::new(&self.url), &self.local_path, false)?;
+        } else {
+            utils::create_parent_dir(&self.local_path)?;
+            std::fs::copy(&self.url, &self.local_path)
+                .wrap_err_with(|| format!("Failed to copy file: {}", self.url))
+                .suggestion("Does the source file still exist?")?;
+        }
+        self.date_downloaded = Utc::now();
+
+        let checksum = checksum(&self.local_path)?;
+        if let Some(expected) = &self.checksum {
+            if expected != &checksum {
+                return Err(eyre!(
+                    "Checksum of {} does not match: expected {expected}, found {checksum}",
+                    self.url
+                ))
+                .suggestion("Has the source file changed since the summary was created?");
+            }
+        }
+        self.checksum = Some(checksum);
+
+        if self.format == Format::Unknown {
+            let mut line = String::new();
+            self.open()?.read_line(&mut line)?;
+            self.format = Format::sniff(&line);
+        }
+
+        Ok(())
+    }
+
+    /// Open the local file for buffered reading.
+    ///
+    /// The contents are decompressed if the local path is compressed, and a
+    /// leading byte order mark is skipped.
+    pub fn open(&self) -> Result<Box<dyn BufRead>, Report> {
+        let path = &self.local_path;
+        let reader: Box<dyn BufRead> = match Compression::from_path(path) {
+            Compression::None => Box::new(utils::open_bufread(path)?),
+            Compression::Zstd => {
+                let file = std::fs::File::open(path)
+                    .wrap_err_with(|| format!("Failed to read file: {path:?}"))?;
+                let decoder = Decoder::new(file)
+                    .wrap_err_with(|| format!("Failed to decompress file: {path:?}"))?;
+                let mut reader = BufReader::new(decoder);
+                let has_bom = reader.fill_buf()?.starts_with(utils::BOM.as_bytes());
+                if has_bom {
+                    reader.consume(utils::BOM.len());
+                }
+                Box::new(reader)
+            }
+        };
+        Ok(reader)
+    }
+
+    /// Read the local file to string.
+    pub fn read_to_string(&self) -> Result<String, Report> {
+        let mut contents = String::new();
+        self.open()?
+            .read_to_string(&mut contents)
+            .wrap_err_with(|| format!("Failed to read file: {:?}", self.local_path))?;
+        Ok(contents)
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Functions
+// ----------------------------------------------------------------------------
+
+/// Calculate the SHA-256 checksum (hex) of a file.
+pub fn checksum(path: &Path) -> Result<String, Report> {
+    let mut file = std::fs::File::open(path)
+        .wrap_err_with(|| format!("Failed to read file: {path:?}"))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let n = file
+            .read(&mut buffer)
+            .wrap_err_with(|| format!("Failed to read file: {path:?}"))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+    }
+    Ok(utils::to_hex(&hasher.finish()))
+}
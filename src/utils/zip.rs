@@ -0,0 +1,127 @@
+use color_eyre::eyre::{eyre, Report, Result, WrapErr};
+use color_eyre::Help;
+use flate2::read::DeflateDecoder;
+use std::fs::{create_dir_all, write};
+use std::io::Read;
+use std::path::{Component, Path};
+
+/// Signature of the end of central directory record.
+const END_SIGNATURE: u32 = 0x0605_4b50;
+/// Signature of a central directory file header.
+const CENTRAL_SIGNATURE: u32 = 0x0201_4b50;
+/// Signature of a local file header.
+const LOCAL_SIGNATURE: u32 = 0x0403_4b50;
+/// Size of the end of central directory record, without its comment.
+const END_SIZE: usize = 22;
+
+// ----------------------------------------------------------------------------
+// Functions
+// ----------------------------------------------------------------------------
+
+/// Extract a zip archive into a directory.
+///
+/// Files that are stored or deflated are supported, which covers the archives
+/// of common tools (ex. Nextclade dataset bundles). Zip64 and encrypted
+/// archives are not supported.
+pub fn extract(path: &Path, output_dir: &Path) -> Result<(), Report> {
+    let archive =
+        std::fs::read(path).wrap_err_with(|| format!("Failed to read file: {path:?}"))?;
+    extract_bytes(&archive, output_dir)
+        .wrap_err_with(|| format!("Failed to extract zip archive: {path:?}"))
+        .suggestion("Please unzip the archive first.")
+}
+
+/// Extract the files of a zip archive, by the central directory.
+fn extract_bytes(archive: &[u8], output_dir: &Path) -> Result<(), Report> {
+    // the end record is last, followed by a comment of up to 65535 bytes
+    let end = (0..=archive.len().saturating_sub(END_SIZE))
+        .rev()
+        .take(u16::MAX as usize + 1)
+        .find(|i| read_u32(archive, *i).ok() == Some(END_SIGNATURE))
+        .ok_or_else(|| eyre!("No end of central directory record was found."))?;
+    let num_entries = read_u16(archive, end + 10)? as usize;
+    let mut offset = read_u32(archive, end + 16)? as usize;
+
+    create_dir_all(output_dir)?;
+    for _ in 0..num_entries {
+        if read_u32(archive, offset)? != CENTRAL_SIGNATURE {
+            return Err(eyre!("Invalid central directory header at byte {offset}."));
+        }
+        let method = read_u16(archive, offset + 10)?;
+        let compressed_size = read_u32(archive, offset + 20)?;
+        let size = read_u32(archive, offset + 24)?;
+        let name_len = read_u16(archive, offset + 28)? as usize;
+        let extra_len = read_u16(archive, offset + 30)? as usize;
+        let comment_len = read_u16(archive, offset + 32)? as usize;
+        let local_offset = read_u32(archive, offset + 42)? as usize;
+        let name = read_bytes(archive, offset + 46, name_len)?;
+        let name = String::from_utf8_lossy(name).to_string();
+        offset += 46 + name_len + extra_len + comment_len;
+
+        if [compressed_size, size, local_offset as u32].contains(&u32::MAX) {
+            return Err(eyre!("Zip64 archives are not supported: {name}"));
+        }
+
+        // entries must stay inside the output directory
+        let relative = Path::new(&name);
+        if relative.components().any(|c| !matches!(c, Component::Normal(_))) {
+            return Err(eyre!("Zip entry is outside the archive: {name}"));
+        }
+        let output_path = output_dir.join(relative);
+        if name.ends_with('/') {
+            create_dir_all(&output_path)?;
+            continue;
+        }
+
+        // the data follows the local header, which has its own extra field
+        if read_u32(archive, local_offset)? != LOCAL_SIGNATURE {
+            return Err(eyre!("Invalid local file header: {name}"));
+        }
+        let local_name_len = read_u16(archive, local_offset + 26)? as usize;
+        let local_extra_len = read_u16(archive, local_offset + 28)? as usize;
+        let data_offset = local_offset + 30 + local_name_len + local_extra_len;
+        let data = read_bytes(archive, data_offset, compressed_size as usize)?;
+
+        let contents = match method {
+            0 => data.to_vec(),
+            8 => {
+                let mut contents = Vec::with_capacity(size as usize);
+                DeflateDecoder::new(data)
+                    .read_to_end(&mut contents)
+                    .wrap_err_with(|| format!("Failed to decompress: {name}"))?;
+                contents
+            }
+            _ => {
+                return Err(eyre!(
+                    "Zip compression method {method} is not supported: {name}"
+                ))
+            }
+        };
+
+        if let Some(parent) = output_path.parent() {
+            create_dir_all(parent)?;
+        }
+        write(&output_path, contents)?;
+    }
+
+    Ok(())
+}
+
+/// Read bytes of an archive, erroring if the archive is truncated.
+fn read_bytes(archive: &[u8], offset: usize, len: usize) -> Result<&[u8], Report> {
+    archive
+        .get(offset..offset + len)
+        .ok_or_else(|| eyre!("Zip archive is truncated at byte {offset}."))
+}
+
+/// Read a little-endian u16 of an archive.
+fn read_u16(archive: &[u8], offset: usize) -> Result<u16, Report> {
+    let bytes = read_bytes(archive, offset, 2)?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+/// Read a little-endian u32 of an archive.
+fn read_u32(archive: &[u8], offset: usize) -> Result<u32, Report> {
+    let bytes = read_bytes(archive, offset, 4)?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
@@ -0,0 +1,205 @@
+use crate::cli;
+use crate::dataset::{load, Dataset};
+use crate::export;
+use crate::recombination::UNKNOWN_PARENT;
+use crate::run::layout::Layout;
+use crate::utils;
+use crate::utils::table::Table;
+use color_eyre::eyre::{eyre, Report, Result, WrapErr};
+use color_eyre::Help;
+use itertools::Itertools;
+use log::{info, warn};
+use std::path::PathBuf;
+
+/// Validate a run output directory against the current schema.
+///
+/// Checks that the run args can be parsed, that the linelist has the expected
+/// columns, that coordinates are within the genome, and that populations and
+/// parents exist in the dataset. This detects stale outputs, for example after
+/// a dataset upgrade.
+pub fn validate_output(args: &cli::validate_output::Args) -> Result<(), Report> {
+    let output_dir = &args.output_dir;
+    if !output_dir.exists() {
+        return Err(eyre!("Output directory {output_dir:?} does not exist."));
+    }
+    let layout = Layout::new(output_dir);
+
+    let mut problems = Vec::new();
+
+    // ------------------------------------------------------------------------
+    // Run Args
+
+    let run_args_path = layout.run_args();
+    info!("Reading run args: {run_args_path:?}");
+    let run_args = utils::read_to_string(&run_args_path)?;
+    let run_args_value: serde_json::Value = serde_json::from_str(&run_args)
+        .wrap_err_with(|| format!("Failed to parse file: {run_args_path:?}"))?;
+    let run_args: cli::run::Args = serde_json::from_value(run_args_value.clone())
+        .wrap_err_with(|| format!("Failed to parse file: {run_args_path:?}"))
+        .suggestion("The output might be from an incompatible version of rebar.")?;
+    // paths are not deserialized into the run args (ex. for edge cases)
+    let run_dataset_dir = run_args_value["dataset_dir"].as_str().map(PathBuf::from);
+    let run_alignment = run_args_value["input"]["alignment"].as_str().map(PathBuf::from);
+
+    // ------------------------------------------------------------------------
+    // Linelist Columns
+
+    let linelist_path = layout.find_linelist()?;
+    info!("Reading linelist: {linelist_path:?}");
+    let linelist = Table::read(&linelist_path)?;
+
+    let expected = match &run_args.linelist_columns {
+        Some(columns) => columns.clone(),
+        None => export::default_linelist_columns(run_args.profile),
+    };
+    for column in &expected {
+        if !linelist.headers.contains(column) {
+            problems.push(format!("Missing linelist column: {column}"));
+        }
+    }
+    for column in &linelist.headers {
+        if !export::all_linelist_columns().contains(&column.as_str()) {
+            problems.push(format!("Unknown linelist column: {column}"));
+        }
+    }
+    for (i, row) in linelist.rows.iter().enumerate() {
+        if row.len() != linelist.headers.len() {
+            problems.push(format!(
+                "Linelist row {} has {} columns, expected {}.",
+                i + 1,
+                row.len(),
+                linelist.headers.len()
+            ));
+        }
+    }
+
+    // ------------------------------------------------------------------------
+    // Dataset
+
+    let mask = vec![0, 0];
+    let dataset = match (&args.dataset_dir, &run_dataset_dir) {
+        (Some(dataset_dir), _) | (None, Some(dataset_dir)) => {
+            Some(load::dataset(dataset_dir, &mask)?)
+        }
+        (None, None) if run_args.self_dataset => match &run_alignment {
+            Some(alignment) => Some(load::alignment(alignment, &mask)?),
+            None => None,
+        },
+        (None, None) => None,
+    };
+    if dataset.is_none() {
+        warn!("No dataset was found, populations and parents will not be checked.");
+    }
+
+    // ------------------------------------------------------------------------
+    // Linelist Values
+
+    // rows that have the expected columns
+    let rows = linelist
+        .rows
+        .iter()
+        .filter(|row| row.len() == linelist.headers.len())
+        .collect_vec();
+
+    for row in rows {
+        let value =
+            |column: &str| linelist.header_position(column).ok().map(|i| row[i].as_str());
+        let strain = value("strain").unwrap_or("NA");
+
+        // genome length, from the linelist or the dataset
+        let genome_length = match value("genome_length") {
+            Some(length) => length.parse::<usize>().ok(),
+            None => dataset.as_ref().map(|d| d.reference.genome_length),
+        };
+        if let (Some(dataset), Some(length)) = (&dataset, genome_length) {
+            if dataset.reference.genome_length != length {
+                problems.push(format!(
+                    "{strain}: genome length {length} does not match the dataset ({}).",
+                    dataset.reference.genome_length
+                ));
+            }
+        }
+
+        // coordinates, ex. 12-12 (breakpoints) and 1-11|A (regions)
+        let mut origins = Vec::new();
+        for column in ["breakpoints", "regions"] {
+            let Some(value) = value(column) else { continue };
+            for coords in value.split(',').filter(|c| !c.is_empty()) {
+                let (coords, origin) = match coords.split_once('|') {
+                    Some((coords, origin)) => (coords, Some(origin)),
+                    None => (coords, None),
+                };
+                origins.extend(origin);
+                if let Err(e) = check_coordinates(coords, genome_length) {
+                    problems.push(format!("{strain}: {column} {e}"));
+                }
+            }
+        }
+
+        // populations and parents exist in the dataset
+        let Some(dataset) = &dataset else { continue };
+        let parents = value("parents")
+            .map(|p| p.split(',').filter(|p| !p.is_empty()).collect_vec())
+            .unwrap_or_default();
+        let populations = value("population")
+            .into_iter()
+            .filter(|p| !p.is_empty())
+            .chain(parents)
+            .chain(origins)
+            .unique();
+        for population in populations {
+            if !population_exists(dataset, population) {
+                problems.push(format!(
+                    "{strain}: population {population} is not in the dataset."
+                ));
+            }
+        }
+    }
+
+    // ------------------------------------------------------------------------
+    // Report
+
+    if !problems.is_empty() {
+        problems.iter().for_each(|p| warn!("{p}"));
+        return Err(eyre!(
+            "Output directory {output_dir:?} failed validation with {} problem(s).",
+            problems.len()
+        ))
+        .suggestion(
+            "The output might be stale, please re-run with the current dataset.",
+        );
+    }
+
+    info!("Output directory {output_dir:?} passed validation.");
+
+    Ok(())
+}
+
+/// Check that a start-end coordinate range is within the genome.
+fn check_coordinates(coords: &str, genome_length: Option<usize>) -> Result<(), Report> {
+    let (start, end) = coords
+        .split_once('-')
+        .ok_or_else(|| eyre!("{coords} is not a start-end range."))?;
+    let start = start.parse::<usize>().wrap_err_with(|| eyre!("{coords} is invalid."))?;
+    let end = end.parse::<usize>().wrap_err_with(|| eyre!("{coords} is invalid."))?;
+
+    if start == 0 || start > end {
+        return Err(eyre!("{coords} is an invalid range."));
+    }
+    if let Some(genome_length) = genome_length {
+        if end > genome_length {
+            return Err(eyre!(
+                "{coords} exceeds the genome length ({genome_length})."
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Check if a population is in the dataset, or is a placeholder.
+fn population_exists(dataset: &Dataset, population: &str) -> bool {
+    population == UNKNOWN_PARENT
+        || dataset.populations.contains_key(population)
+        || dataset.phylogeny.get_node(population).is_ok()
+}
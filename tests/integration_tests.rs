@@ -1,30 +1,173 @@
 use rebar::cli;
-use rebar::dataset::attributes::{Name, Tag};
+use rebar::dataset::attributes::{Name, NameMatching, Summary as DatasetSummary, Tag};
+use rebar::dataset::barcodes::BarcodeClasses;
 use rebar::dataset::download;
-use rebar::plot::plot;
-use rebar::run::run;
+use rebar::dataset::edge_case;
+use rebar::dataset::load;
+use rebar::dataset::matrix::export_matrix;
+use rebar::dataset::ncbi::GenBank;
+use rebar::dataset::nextclade;
+use rebar::dataset::phylogeny::export_phylogeny;
+use rebar::dataset::selection::Selection;
+use rebar::dataset::self_test::{self, Accuracy};
+use rebar::dataset::signature::Signature;
+use rebar::dataset::toy1;
+use rebar::dataset::toy_mlst::scheme::Scheme;
+use rebar::dataset::{Dataset, SearchResult};
+use rebar::export::sink::{OutputFormat, OutputSink};
+use rebar::extract_case::extract_case;
+use rebar::inspect::inspect;
+use rebar::phylogeny::{
+    examples, BranchLength, ConflictPolicy, Phylogeny, PhylogenyConfig,
+};
+use rebar::plot::canvas::ImageFormat;
+use rebar::plot::{combine_barcodes, plot};
+use rebar::prelude::{RunArgs, RunInput};
+use rebar::recombination::mixture::{Mixture, Status as MixtureStatus};
+use rebar::recombination::{self, Breakpoint, Recombination, Region};
+use rebar::run::order::Resequencer;
+use rebar::run::stats::Summary;
+use rebar::run::{run, run_with_sinks};
+use rebar::sequence::parsimony;
+use rebar::sequence::scoring::ScoringModel;
+use rebar::sequence::translate;
+use rebar::sequence::Sequence;
+use rebar::simulate::simulate;
+use rebar::utils::fmt;
+use rebar::utils::table::{self, SortType, Table};
+use rebar::utils::theme::Theme;
+use rebar::validate_output::validate_output;
 
+use chrono::NaiveDate;
 use color_eyre::eyre::{Report, Result};
-use std::path::PathBuf;
+use flate2::write::DeflateEncoder;
+use flate2::{Compression, Crc};
+use std::fs::{create_dir_all, read_to_string, write};
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+/// Download the toy1 dataset into a test output directory, returning its path.
+async fn toy1_dataset(dir: &Path) -> Result<PathBuf, Report> {
+    let mut args = cli::dataset::download::Args {
+        name: Name::Toy1,
+        tag: Tag::from_str("custom")?,
+        output_dir: dir.join("dataset"),
+        summary: None,
+        accession: None,
+        max_missing: None,
+        max_private: None,
+        phylogeny: None,
+        conflict_policy: ConflictPolicy::default(),
+        theme: Theme::new(),
+        strict: false,
+    };
+    download::dataset(&mut args).await?;
+    Ok(args.output_dir)
+}
 
 #[tokio::test]
 async fn toy1() -> Result<(), Report> {
     let output_dir = PathBuf::from("output").join("tests").join("toy1");
 
     // Dataset Download
-    let mut args = cli::dataset::download::Args {
-        name: Name::Toy1,
+    let dataset_dir = toy1_dataset(&output_dir).await?;
+
+    // datasets that are not implemented yet are an error, rather than a panic
+    let mut rsv_args = cli::dataset::download::Args {
+        name: Name::RsvA,
         tag: Tag::from_str("custom")?,
-        output_dir: output_dir.join("dataset"),
+        output_dir: output_dir.join("dataset_rsv_a"),
         summary: None,
+        accession: None,
+        max_missing: None,
+        max_private: None,
+        phylogeny: None,
+        conflict_policy: ConflictPolicy::default(),
+        theme: Theme::new(),
+        strict: false,
     };
-    download::dataset(&mut args).await?;
+    assert!(download::dataset(&mut rsv_args).await.is_err());
+
+    // Strict mode, files created by rebar have checksums
+    let summary_path = dataset_dir.join("summary.json");
+    let mut summary = DatasetSummary::read_strict(&summary_path)?;
+    summary.reference.url = "http://example.com/reference.fasta".to_string();
+    assert!(summary.validate_strict().is_err());
+    summary.reference.url = "HTTP://example.com/reference.fasta".to_string();
+    assert!(summary.validate_strict().is_err());
+    summary.reference.url = "ftp://example.com/reference.fasta".to_string();
+    assert!(summary.validate_strict().is_err());
+    summary.reference.url = "HTTPS://example.com/reference.fasta".to_string();
+    assert!(summary.validate_strict().is_ok());
+    summary.reference.url = String::new();
+    summary.populations.checksum = None;
+    assert!(summary.validate_strict().is_err());
+    let mut json: serde_json::Value =
+        serde_json::from_str(&read_to_string(&summary_path)?)?;
+    json["reference"]["mirror"] = "https://example.com".into();
+    let strict_path = output_dir.join("summary_unknown.json");
+    write(&strict_path, json.to_string())?;
+    assert!(DatasetSummary::read(&strict_path).is_ok());
+    let error = DatasetSummary::read_strict(&strict_path).unwrap_err();
+    assert!(format!("{error:?}").contains("reference.mirror"));
+    assert!(!rsv_args.output_dir.exists());
+
+    // Dataset Export Matrix
+    let args = cli::dataset::export_matrix::Args {
+        dataset_dir: dataset_dir.clone(),
+        populations: vec!["A".to_string(), "B".to_string()],
+        output_dir: output_dir.join("matrix"),
+    };
+    export_matrix(&args)?;
+    let matrix = Table::read(&output_dir.join("matrix").join("matrix.tsv"))?;
+    assert_eq!(matrix.headers, vec!["coord", "reference", "alt", "A", "B"]);
+    assert_eq!(matrix.rows[0], vec!["1", "A", "C", "1", "0"]);
+    assert_eq!(matrix.rows[1], vec!["1", "A", "T", "0", "1"]);
+
+    // Dataset Export Phylogeny
+    let args = cli::dataset::export_phylogeny::Args {
+        dataset_dir: dataset_dir.clone(),
+        output: output_dir.join("phylogeny").join("effective.json"),
+        effective: true,
+        layout_hints: false,
+    };
+    export_phylogeny(&args)?;
+    let effective = Phylogeny::read(&args.output)?;
+    assert_eq!(
+        effective.get_names()?,
+        toy1::phylogeny::build()?.get_names()?
+    );
+
+    // Layout hints, recombinants D and E are their own clusters
+    for ext in ["dot", "graphml"] {
+        let args = cli::dataset::export_phylogeny::Args {
+            output: output_dir.join("phylogeny").join(format!("hints.{ext}")),
+            effective: false,
+            layout_hints: true,
+            ..args.clone()
+        };
+        export_phylogeny(&args)?;
+    }
+    let dot = read_to_string(output_dir.join("phylogeny").join("hints.dot"))?;
+    assert!(dot.contains(r#"rank = "2" cluster = "D" group = "D""#));
+    assert!(dot.contains("{ rank = same; 1; 2; 3; }"));
+    let graphml = output_dir.join("phylogeny").join("hints.graphml");
+    assert!(read_to_string(&graphml)?.contains(r#"<data key="cluster">E</data>"#));
+    assert_eq!(
+        Phylogeny::read_graphml(&graphml)?.get_names()?,
+        toy1::phylogeny::build()?.get_names()?
+    );
 
     // Run
     let mut args = cli::run::Args {
         population: Some("*".to_string()),
-        dataset_dir: output_dir.join("dataset"),
+        input: cli::run::Input {
+            populations: Some(vec!["*".to_string()]),
+            ..Default::default()
+        },
+        dataset_dir: Some(dataset_dir.clone()),
         output_dir: output_dir.join("run"),
         mask: vec![0, 0],
         min_length: 3,
@@ -32,14 +175,2279 @@ async fn toy1() -> Result<(), Report> {
     };
     run(&mut args)?;
 
+    // Explain Negatives, the default min_length is longer than the genome
+    let mut negatives_args = cli::run::Args {
+        input: cli::run::Input {
+            populations: Some(vec!["D".to_string()]),
+            ..Default::default()
+        },
+        output_dir: output_dir.join("run_negatives"),
+        min_length: cli::run::Args::default().min_length,
+        explain_negatives: true,
+        ..args.clone()
+    };
+    run(&mut negatives_args)?;
+    let negatives =
+        Table::read(&output_dir.join("run_negatives").join("qc").join("negatives.tsv"))?;
+    let threshold = negatives.header_position("threshold")?;
+    assert_eq!(negatives.rows.len(), 1);
+    assert_eq!(negatives.rows[0][threshold], "min_length=500");
+
+    // Edge Case Stats, both recombinants are detected without edge cases
+    let mut edge_case_args = cli::run::Args {
+        output_dir: output_dir.join("run_edge_cases"),
+        edge_case_stats: true,
+        ..args.clone()
+    };
+    run(&mut edge_case_args)?;
+    let edge_cases = Table::read(
+        &output_dir.join("run_edge_cases").join("qc").join("edge_cases.tsv"),
+    )?;
+    let detection = edge_cases.header_position("detection")?;
+    let edge_case = edge_cases.header_position("edge_case")?;
+    assert_eq!(edge_cases.rows.len(), 2);
+    assert_eq!(edge_cases.rows[0][0], "D");
+    assert_eq!(edge_cases.rows[1][0], "E");
+    assert!(edge_cases.rows.iter().all(|row| row[detection] == "naive"));
+    assert!(edge_cases.rows.iter().all(|row| row[edge_case] == "false"));
+
+    // Auto output directories, runs are never overwritten
+    let runs_dir = output_dir.join("runs");
+    if runs_dir.exists() {
+        std::fs::remove_dir_all(&runs_dir)?;
+    }
+    let mut auto_args = cli::run::Args {
+        output_dir: runs_dir.join("auto"),
+        ..negatives_args.clone()
+    };
+    run(&mut auto_args.clone())?;
+    // the same sequences as the previous run have no changes
+    auto_args.previous_summary = Some(runs_dir.join("latest"));
+    run(&mut auto_args)?;
+    let novelty = Table::read(&auto_args.output_dir.join("qc").join("novelty.tsv"))?;
+    assert_eq!(novelty.headers[0..2], ["recombinant", "status"]);
+    assert!(novelty.rows.is_empty());
+    let run_name = auto_args.output_dir.file_name().unwrap().to_string_lossy();
+    assert!(run_name.starts_with("rebar_toy1_custom_"));
+    assert_eq!(std::fs::read_dir(&runs_dir)?.count(), 3);
+    assert_eq!(
+        std::fs::read_link(runs_dir.join("latest"))?,
+        PathBuf::from(&*run_name)
+    );
+
+    // Extract Case, of a recursive recombinant
+    let mut case_args = cli::extract_case::Args {
+        run_dir: output_dir.join("run"),
+        sequence_id: "population_E".to_string(),
+        output: output_dir.join("case").join("case.tar"),
+        ..Default::default()
+    };
+    extract_case(&case_args)?;
+    let case = std::fs::read(&case_args.output)?;
+    assert_eq!(case.len() % 512, 0);
+    let case = String::from_utf8_lossy(&case);
+    for file in [
+        "case/sequence.fasta",
+        "case/run_args.json",
+        "case/dataset/phylogeny.json",
+    ] {
+        assert!(case.contains(file));
+    }
+    // the parents of E (C and D), and the parents of D (A and B)
+    assert!(case.contains(">A\n") && case.contains(">D\n") && case.contains(">E\n"));
+    case_args.sequence_id = "population_Z".to_string();
+    assert!(extract_case(&case_args).is_err());
+
     // Plot
     let args = cli::plot::Args {
-        annotations: Some(output_dir.join("dataset").join("annotations.tsv")),
+        annotations: Some(dataset_dir.join("annotations.tsv")),
         run_dir: output_dir.join("run"),
         ..Default::default()
     };
     plot(&args)?;
 
+    // Plot as svg
+    let args = cli::plot::Args {
+        format: ImageFormat::Svg,
+        ..args
+    };
+    plot(&args)?;
+    let plots = std::fs::read_dir(output_dir.join("run").join("plots"))?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let svgs = plots.iter().filter(|p| p.extension() == Some("svg".as_ref()));
+    let mut num_svgs = 0;
+    for svg in svgs {
+        let svg = std::fs::read_to_string(svg)?;
+        assert!(svg.starts_with("<svg") && svg.contains("<text"));
+        num_svgs += 1;
+    }
+    assert!(num_svgs > 0);
+
+    // Inspect
+    let args = cli::inspect::Args {
+        output_dir: output_dir.join("run"),
+    };
+    inspect(&args)?;
+
+    // Validate Output
+    let args = cli::validate_output::Args {
+        output_dir: output_dir.join("run"),
+        ..Default::default()
+    };
+    validate_output(&args)?;
+
+    // Dataset Edge Case
+    let mut args = cli::dataset::edge_case::AddArgs {
+        dataset_dir: dataset_dir.clone(),
+        population: "E".to_string(),
+        parents: Some(vec!["A".to_string(), "B".to_string(), "C".to_string()]),
+        max_parents: Some(3),
+        reason: Some("recursive".to_string()),
+        ..Default::default()
+    };
+    edge_case::add(&args)?;
+    // updates keep the other parameters
+    args.parents = None;
+    args.min_subs = Some(0);
+    edge_case::add(&args)?;
+    let dataset = load::dataset(&dataset_dir, &vec![0, 0])?;
+    assert_eq!(dataset.edge_cases.len(), 1);
+    let e = &dataset.edge_cases[0];
+    assert_eq!(e.parents.as_deref().unwrap_or_default(), ["A", "B", "C"]);
+    assert_eq!((e.max_parents, e.min_subs), (3, 0));
+    assert_eq!(e.reason.as_deref(), Some("recursive"));
+    // edge cases are only for recombinants
+    args.population = "A".to_string();
+    assert!(edge_case::add(&args).is_err());
+
+    // population names ignore whitespace and case, unless exact
+    let mut dataset = dataset;
+    assert_eq!(dataset.canonical_name(" e ")?, "E");
+    assert_eq!(
+        dataset.expand_populations(&[" a*".to_string()])?,
+        ["A", "D", "E"]
+    );
+    dataset.name_matching = NameMatching::Trim;
+    assert_eq!(dataset.canonical_name(" e ")?, "e");
+    assert!(dataset.expand_populations(&["e".to_string()]).is_err());
+
+    // knockout removes descendants, even without a wildcard
+    dataset.name_matching = NameMatching::default();
+    let removed = dataset.knockout(&["D"])?;
+    assert_eq!(removed, ["D", "E"]);
+    assert_eq!(
+        dataset.populations.keys().collect::<Vec<_>>(),
+        ["A", "B", "C"]
+    );
+    assert_eq!(dataset.phylogeny.get_names()?, ["root", "A", "B", "C"]);
+    assert!(dataset.phylogeny.recombinants.is_empty());
+    assert!(dataset.mutations.values().flatten().all(|p| !removed.contains(p)));
+
+    // Simulate, random breakpoints are reproducible with a seed
+    let args = cli::simulate::Args {
+        dataset_dir: dataset_dir.clone(),
+        parents: vec!["a".to_string(), "B".to_string()],
+        output_dir: output_dir.join("simulate"),
+        num_sequences: 3,
+        seed: Some(1),
+        ..Default::default()
+    };
+    simulate(&args)?;
+    let fasta =
+        std::fs::read_to_string(output_dir.join("simulate").join("simulated.fasta"))?;
+    let truth = Table::read(&output_dir.join("simulate").join("truth.tsv"))?;
+    assert_eq!(
+        truth.headers,
+        ["strain", "parents", "breakpoints", "regions"]
+    );
+    assert_eq!(truth.rows.len(), 3);
+    assert!(truth.rows.iter().all(|row| row[1] == "A,B" && fasta.contains(&row[0])));
+    simulate(&args)?;
+    let truth_again = Table::read(&output_dir.join("simulate").join("truth.tsv"))?;
+    assert_eq!(truth.rows, truth_again.rows);
+    // manual breakpoints, the regions end at the breakpoints
+    let args = cli::simulate::Args {
+        breakpoints: Some(vec![10]),
+        num_sequences: 1,
+        seed: None,
+        ..args
+    };
+    simulate(&args)?;
+    let truth = Table::read(&output_dir.join("simulate").join("truth.tsv"))?;
+    assert_eq!(truth.rows[0][2..], ["10-11", "1-10|A,11-20|B"]);
+    let args = cli::simulate::Args {
+        breakpoints: Some(vec![20]),
+        ..args
+    };
+    assert!(simulate(&args).is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn output_sinks() -> Result<(), Report> {
+    let output_dir = PathBuf::from("output").join("tests").join("output_sinks");
+
+    let dataset_dir = toy1_dataset(&output_dir).await?;
+
+    // a custom sink, that collects the strains of all results
+    struct Strains(Arc<Mutex<Vec<String>>>);
+    impl OutputSink for Strains {
+        fn on_result(
+            &mut self,
+            best_match: &SearchResult,
+            _recombination: &Recombination,
+            _dataset: &Dataset,
+        ) -> Result<(), Report> {
+            self.0.lock().unwrap().push(best_match.sequence_id.clone());
+            Ok(())
+        }
+        fn on_finish(self: Box<Self>) -> Result<(), Report> {
+            self.0.lock().unwrap().push("finished".to_string());
+            Ok(())
+        }
+    }
+    let strains = Arc::new(Mutex::new(Vec::new()));
+
+    let mut output_format = vec![OutputFormat::Json];
+    if cfg!(feature = "parquet") {
+        output_format.push(OutputFormat::Parquet);
+    }
+
+    let mut args = RunArgs {
+        input: RunInput {
+            populations: Some(vec!["*".to_string()]),
+            ..Default::default()
+        },
+        dataset_dir: Some(dataset_dir.clone()),
+        output_dir: output_dir.join("run"),
+        output_format,
+        mask: vec![0, 0],
+        min_length: 3,
+        ..Default::default()
+    };
+    run_with_sinks(&mut args, vec![Box::new(Strains(strains.clone()))])?;
+
+    // the json linelist has the same rows as the tsv linelist
+    let linelist =
+        Table::read(&output_dir.join("run").join("linelist").join("linelist.tsv"))?;
+    let json = std::fs::read_to_string(
+        output_dir.join("run").join("linelist").join("linelist.json"),
+    )?;
+    let json: Vec<serde_json::Value> = serde_json::from_str(&json)?;
+    assert_eq!(json.len(), linelist.rows.len());
+    assert_eq!(json[0]["strain"], linelist.rows[0][0]);
+
+    #[cfg(feature = "parquet")]
+    {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+        let path = output_dir.join("run").join("linelist").join("linelist.parquet");
+        let reader = SerializedFileReader::new(std::fs::File::open(path)?)?;
+        let num_rows = reader.metadata().file_metadata().num_rows();
+        assert_eq!(num_rows as usize, linelist.rows.len());
+    }
+
+    // the custom sink received every result, then finished
+    let strains = strains.lock().unwrap();
+    assert_eq!(strains.len(), linelist.rows.len() + 1);
+    assert_eq!(strains.last().map(String::as_str), Some("finished"));
+
+    Ok(())
+}
+
+#[test]
+fn novelty() -> Result<(), Report> {
+    let summary = |sequences: usize, recombinants: &[(&str, usize)]| Summary {
+        sequences,
+        recombinant_populations: recombinants
+            .iter()
+            .map(|(r, count)| (r.to_string(), *count))
+            .collect(),
+        ..Default::default()
+    };
+    let previous = summary(100, &[("XBB", 10), ("XE", 10), ("XBL", 5), ("XAY", 2)]);
+    let current = summary(50, &[("XBB", 12), ("XE", 6), ("XBL", 1), ("novel", 1)]);
+
+    // proportions are compared, XE is unchanged (0.10 to 0.12)
+    let novelty = current.novelty(&previous, 2.0);
+    let rows = novelty.rows.iter().map(|row| row[0..2].join(" ")).collect::<Vec<_>>();
+    assert_eq!(
+        rows,
+        [
+            "XAY decreased",
+            "XBB increased",
+            "XBL decreased",
+            "novel new"
+        ]
+    );
+    let xbb = &novelty.rows[1];
+    assert_eq!(xbb[2..], ["10", "12", "0.10", "0.24", "2.40"]);
+    assert_eq!(novelty.rows[0][6], "0.00");
+    assert_eq!(novelty.rows[3][6], "NA");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn max_memory() -> Result<(), Report> {
+    let output_dir = PathBuf::from("output").join("tests").join("max_memory");
+
+    let dataset_dir = toy1_dataset(&output_dir).await?;
+
+    // peak memory is reported in the summary with --profile
+    let mut args = cli::run::Args {
+        input: cli::run::Input {
+            populations: Some(vec!["*".to_string()]),
+            ..Default::default()
+        },
+        dataset_dir: Some(dataset_dir.clone()),
+        output_dir: output_dir.join("run"),
+        mask: vec![0, 0],
+        min_length: 3,
+        profile: true,
+        ..Default::default()
+    };
+    run(&mut args.clone())?;
+    let summary = Summary::read(&output_dir.join("run").join("summary.json"))?;
+    let usage = summary.memory.clone().expect("Memory usage was not sampled.");
+    assert!(usage.peak_rss_mb > 0 && usage.samples > 0 && !usage.low_memory);
+    let exit_line = summary.exit_line(std::time::Duration::from_millis(1500));
+    assert!(exit_line.starts_with("rebar_summary status=success processed=5"));
+    assert!(exit_line.contains(" failed=0 ") && exit_line.contains(" runtime_s=1.50 "));
+
+    // a limit below the current memory usage stops the run
+    args.output_dir = output_dir.join("run_limit");
+    args.max_memory = Some(1);
+    assert!(run(&mut args).is_err());
+    assert!(!output_dir.join("run_limit").join("summary.json").exists());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn self_test() -> Result<(), Report> {
+    let output_dir = PathBuf::from("output").join("tests").join("self_test");
+
+    let dataset_dir = toy1_dataset(&output_dir).await?;
+
+    let mut args = cli::dataset::self_test::Args {
+        dataset_dir: dataset_dir.clone(),
+        output_dir: output_dir.join("all"),
+        mask: vec![0, 0],
+        min_length: 3,
+        ..Default::default()
+    };
+    self_test::self_test(&args)?;
+    let accuracy: Accuracy = serde_json::from_str(&read_to_string(
+        output_dir.join("all").join("accuracy.json"),
+    )?)?;
+    assert_eq!((accuracy.populations, accuracy.pass), (5, 5));
+    assert_eq!(
+        (accuracy.recombinants, accuracy.recombinants_detected),
+        (2, 2)
+    );
+    assert_eq!((accuracy.sensitivity, accuracy.specificity), (1.0, 1.0));
+    assert_eq!(accuracy.detection.get("naive"), Some(&2));
+    let outcomes = Table::read(&output_dir.join("all").join("self_test.tsv"))?;
+    let columns = [
+        "population",
+        "expected_recombinant",
+        "observed_parents",
+        "validate",
+    ]
+    .map(|column| outcomes.header_position(column))
+    .into_iter()
+    .collect::<Result<Vec<_>, Report>>()?;
+    let rows = outcomes
+        .rows
+        .iter()
+        .map(|row| columns.iter().map(|i| row[*i].as_str()).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    assert_eq!(rows[0], ["A", "", "", "pass"]);
+    assert_eq!(rows[3], ["D", "D", "A,B", "pass"]);
+
+    // regions longer than the genome can't be detected, so the recombinants
+    // fail validation
+    args.populations = vec!["D".to_string(), "E".to_string()];
+    args.min_length = 100;
+    args.output_dir = output_dir.join("undetected");
+    self_test::self_test(&args)?;
+    let accuracy: Accuracy = serde_json::from_str(&read_to_string(
+        output_dir.join("undetected").join("accuracy.json"),
+    )?)?;
+    assert_eq!((accuracy.populations, accuracy.pass), (2, 0));
+    assert_eq!(accuracy.recombinants_detected, 0);
+    assert_eq!(accuracy.sensitivity, 0.0);
+    assert_eq!(accuracy.failures.get("no_recombination_detected"), Some(&2));
+    assert_eq!(accuracy.detection.get("fail"), Some(&2));
+
+    // the phylogeny is the expected result
+    args.dataset_dir = output_dir.join("no_dataset");
+    let error = self_test::self_test(&args).unwrap_err();
+    assert!(format!("{error:?}").contains("No phylogeny was found"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn amplicon_dropout() -> Result<(), Report> {
+    let output_dir = PathBuf::from("output").join("tests").join("amplicon_dropout");
+
+    let dataset_dir = toy1_dataset(&output_dir).await?;
+
+    // D (A: 1-11, B: 12-18), with amplicon 3 (13-16) dropped out
+    let alignment = output_dir.join("alignment.fasta");
+    write(&alignment, ">X\nCCCCCCAACCCTNNNNNTAA\n")?;
+    let primer_bed = output_dir.join("primers.bed");
+    let primers = [
+        (0, 2),
+        (6, 8),
+        (5, 7),
+        (10, 12),
+        (10, 12),
+        (16, 18),
+        (16, 18),
+        (18, 20),
+    ]
+    .iter()
+    .enumerate()
+    .map(|(i, (start, end))| {
+        let side = if i % 2 == 0 { "LEFT" } else { "RIGHT" };
+        format!("toy1\t{start}\t{end}\ttoy1_{}_{side}", i / 2 + 1)
+    })
+    .collect::<Vec<_>>();
+    let primers = format!("{}\n", primers.join("\n"));
+    write(&primer_bed, primers)?;
+
+    // B only has 4 sites, without D it's too short to be a parental region
+    let mut args = cli::run::Args {
+        input: cli::run::Input {
+            alignment: Some(alignment),
+            ..Default::default()
+        },
+        dataset_dir: Some(dataset_dir.clone()),
+        output_dir: output_dir.join("run"),
+        mask: vec![0, 0],
+        min_length: 3,
+        min_consecutive: 5,
+        knockout: Some(vec!["D".to_string()]),
+        ..Default::default()
+    };
+    run(&mut args.clone())?;
+    let linelist_path = output_dir.join("run").join("linelist").join("linelist.tsv");
+    let linelist = Table::read(&linelist_path)?;
+    assert_eq!(linelist.get_as::<String>(0, "parents")?, "");
+
+    // unless the missing amplicon counts towards the region
+    args.primer_bed = Some(primer_bed);
+    run(&mut args)?;
+    let linelist = Table::read(&linelist_path)?;
+    assert_eq!(linelist.get_as::<String>(0, "parents")?, "A,B");
+    let warnings = linelist.get_as::<String>(0, "warnings")?;
+    assert!(warnings.starts_with(
+        "region 12-20|B kept by amplicon dropout (toy1_3): min_consecutive=5->4, min_length=3->0;"
+    ));
+
+    // the breakpoint of D (12-12) is on the boundary of amplicons 2 and 3
+    let mut args = cli::run::Args {
+        input: cli::run::Input {
+            populations: Some(vec!["D".to_string()]),
+            ..Default::default()
+        },
+        min_consecutive: 3,
+        knockout: None,
+        ..args
+    };
+    run(&mut args.clone())?;
+    let linelist = Table::read(&linelist_path)?;
+    assert_eq!(
+        linelist.get_as::<String>(0, "warnings")?,
+        "breakpoint 12-12 coincides with amplicon boundary (toy1_2,toy1_3)"
+    );
+
+    // masked primer sites are not evidence for the regions
+    args.mask_primers = true;
+    run(&mut args.clone())?;
+    let linelist = Table::read(&linelist_path)?;
+    assert_eq!(linelist.get_as::<String>(0, "regions")?, "3-10|A,13-16|B");
+    args.primer_bed = None;
+    assert!(run(&mut args).is_err());
+
+    // P1 and P2 have 9 informative sites, less than twice min_consecutive, so
+    // P2 is pre-screened, unless amplicon dropouts relax min_consecutive
+    let dataset_dir = output_dir.join("dataset_pair");
+    create_dir_all(&dataset_dir)?;
+    write(
+        dataset_dir.join("reference.fasta"),
+        ">Reference\nAAAAAAAAAAAAAAAAAAAA\n",
+    )?;
+    write(
+        dataset_dir.join("populations.fasta"),
+        ">P1\nCCCCCAAAAAAAAAAAAAAA\n>P2\nAAAAAAAAAAAAGAGAAAGG\n",
+    )?;
+    let alignment = output_dir.join("alignment_pair.fasta");
+    write(&alignment, ">Y\nCCCCCAAAAAANNNNNNAGG\n")?;
+    let primer_bed = output_dir.join("primers_pair.bed");
+    let primers = [
+        (0, 2),
+        (6, 8),
+        (6, 8),
+        (9, 11),
+        (9, 11),
+        (17, 19),
+        (17, 19),
+        (18, 20),
+    ]
+    .iter()
+    .enumerate()
+    .map(|(i, (start, end))| {
+        let side = if i % 2 == 0 { "LEFT" } else { "RIGHT" };
+        format!("pair\t{start}\t{end}\tpair_{}_{side}", i / 2 + 1)
+    })
+    .collect::<Vec<_>>();
+    let primers = format!("{}\n", primers.join("\n"));
+    write(&primer_bed, primers)?;
+    let args = cli::run::Args {
+        input: cli::run::Input {
+            alignment: Some(alignment),
+            ..Default::default()
+        },
+        dataset_dir: Some(dataset_dir),
+        output_dir: output_dir.join("run_pair"),
+        mask: vec![0, 0],
+        min_length: 3,
+        min_consecutive: 5,
+        primer_bed: Some(primer_bed),
+        ..Default::default()
+    };
+    run(&mut args.clone())?;
+    let linelist_path = args.output_dir.join("linelist").join("linelist.tsv");
+    let linelist = Table::read(&linelist_path)?;
+    assert_eq!(linelist.get_as::<String>(0, "parents")?, "P1,P2");
+    assert_eq!(linelist.get_as::<String>(0, "regions")?, "1-5|P1,19-20|P2");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn approximate_search() -> Result<(), Report> {
+    let output_dir = PathBuf::from("output").join("tests").join("approximate_search");
+
+    let dataset_dir = toy1_dataset(&output_dir).await?;
+
+    // with k of at least the number of populations, every population is scored
+    let mut args = cli::run::Args {
+        input: cli::run::Input {
+            populations: Some(vec!["*".to_string()]),
+            ..Default::default()
+        },
+        dataset_dir: Some(dataset_dir.clone()),
+        output_dir: output_dir.join("exact"),
+        mask: vec![0, 0],
+        min_length: 3,
+        ..Default::default()
+    };
+    run(&mut args.clone())?;
+    args.output_dir = output_dir.join("approximate");
+    args.approximate = Some(5);
+    run(&mut args)?;
+
+    let exact =
+        Table::read(&output_dir.join("exact").join("linelist").join("linelist.tsv"))?;
+    let approximate = Table::read(
+        &output_dir.join("approximate").join("linelist").join("linelist.tsv"),
+    )?;
+    assert_eq!(exact.rows.len(), 5);
+    assert_eq!(approximate.headers, exact.headers);
+    assert_eq!(approximate.rows, exact.rows);
+
+    Ok(())
+}
+
+#[test]
+fn resequencer() -> Result<(), Report> {
+    // workers finish out of order, the writer receives results in input order
+    let capacity = 2;
+    let resequencer = Resequencer::new(capacity, true);
+    let inputs = Mutex::new(0..50);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let written = std::thread::scope(|scope| {
+        for worker in 0..4 {
+            let (resequencer, inputs, sender) = (&resequencer, &inputs, sender.clone());
+            scope.spawn(move || loop {
+                let Some(index) = inputs.lock().unwrap().next() else {
+                    break;
+                };
+                assert!(resequencer.wait(index).unwrap());
+                let delay = ((index * 7 + worker) % 5) as u64 * 100;
+                std::thread::sleep(std::time::Duration::from_micros(delay));
+                sender.send(index).unwrap();
+            });
+        }
+        drop(sender);
+        let mut written = Vec::new();
+        for index in receiver {
+            // a worker never starts an input past the window
+            assert!(index < written.len() + capacity);
+            written.extend(resequencer.push(index, index).unwrap());
+        }
+        written
+    });
+    assert_eq!(written, (0..50).collect::<Vec<_>>());
+
+    // inputs past the window wait, until the writer is closed
+    let resequencer = Arc::new(Resequencer::<usize>::new(1, true));
+    assert!(resequencer.wait(0)?);
+    let waiting = {
+        let resequencer = resequencer.clone();
+        std::thread::spawn(move || resequencer.wait(1).unwrap())
+    };
+    resequencer.close();
+    assert!(!waiting.join().unwrap());
+
+    // unordered results are ready immediately
+    let resequencer = Resequencer::new(1, false);
+    assert_eq!(resequencer.push(3, "c")?, vec!["c"]);
+    assert_eq!(resequencer.push(1, "a")?, vec!["a"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn run_threads() -> Result<(), Report> {
+    let output_dir = PathBuf::from("output").join("tests").join("run_threads");
+
+    let dataset_dir = toy1_dataset(&output_dir).await?;
+
+    // sequences named in reverse, so input order is not sorted order
+    let populations = ["A", "B", "C", "D", "E"];
+    let sequences = [
+        "CCCCCCAACCCCCCCCCCCC",
+        "TTTTTTTTTTTTTTTTTTAA",
+        "AAGGGGGGGGGGGGGGGGGG",
+        "CCCCCCAACCCTTTTTTTAA",
+        "AAGCCCAACCCTTTTTTTAA",
+    ];
+    let strains = (0..40).rev().map(|i| format!("s{i:02}")).collect::<Vec<_>>();
+    let alignment = output_dir.join("alignment.fasta");
+    let records = strains
+        .iter()
+        .enumerate()
+        .map(|(i, strain)| format!(">{strain}\n{}", sequences[i % sequences.len()]))
+        .collect::<Vec<_>>();
+    let records = format!("{}\n", records.join("\n"));
+    write(&alignment, records)?;
+
+    let mut args = cli::run::Args {
+        input: cli::run::Input {
+            alignment: Some(alignment),
+            ..Default::default()
+        },
+        dataset_dir: Some(dataset_dir.clone()),
+        output_dir: output_dir.join("ordered"),
+        mask: vec![0, 0],
+        min_length: 3,
+        threads: 4,
+        queue_size: Some(2),
+        ..Default::default()
+    };
+    run(&mut args.clone())?;
+    let linelist_path =
+        |dir: &str| output_dir.join(dir).join("linelist").join("linelist.tsv");
+    let linelist = Table::read(&linelist_path("ordered"))?;
+    let strain_i = linelist.header_position("strain")?;
+    let population_i = linelist.header_position("population")?;
+    let ordered =
+        linelist.rows.iter().map(|row| row[strain_i].clone()).collect::<Vec<_>>();
+    assert_eq!(ordered, strains);
+    for (i, row) in linelist.rows.iter().enumerate() {
+        assert_eq!(row[population_i], populations[i % populations.len()]);
+    }
+
+    // unordered runs to completion, with the same results in any order
+    args.output_dir = output_dir.join("unordered");
+    args.unordered = true;
+    run(&mut args)?;
+    let mut unordered = Table::read(&linelist_path("unordered"))?.rows;
+    let mut ordered = linelist.rows;
+    unordered.sort();
+    ordered.sort();
+    assert_eq!(unordered, ordered);
+
+    // sequences are read from the stream as workers are ready for them, with
+    // duplicates skipped and records that can't be parsed failed in order
+    let alignment = output_dir.join("stream.fasta");
+    let records = [
+        ">s1",
+        sequences[0],
+        ">s2",
+        sequences[1],
+        ">s1",
+        sequences[2],
+        ">short",
+        "AAAAA",
+        ">s3",
+        sequences[3],
+    ];
+    write(&alignment, records.join("\n"))?;
+    let mut stream = cli::run::Args {
+        output_dir: output_dir.join("stream"),
+        threads: 2,
+        queue_size: Some(1),
+        unordered: false,
+        ..args.clone()
+    };
+    stream.input.alignment = Some(alignment);
+    assert!(run(&mut stream).is_err());
+    let linelist = Table::read(&linelist_path("stream"))?;
+    let strains =
+        linelist.rows.iter().map(|row| row[strain_i].as_str()).collect::<Vec<_>>();
+    assert_eq!(strains, ["s1", "s2", "s3"]);
+    assert_eq!(linelist.rows[0][population_i], "A");
+    let failures =
+        Table::read(&output_dir.join("stream").join("qc").join("failures.tsv"))?;
+    assert_eq!(failures.rows.len(), 1);
+    assert_eq!(failures.rows[0][..2], ["short", "parse"]);
+
+    // an input that can't be read stops the run, before any outputs
+    let alignment = output_dir.join("unreadable.fasta");
+    write(&alignment, "not a fasta\n")?;
+    stream.output_dir = output_dir.join("unreadable");
+    stream.input.alignment = Some(alignment);
+    assert!(run(&mut stream).is_err());
+    assert!(!output_dir.join("unreadable").join("summary.json").exists());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn mixture() -> Result<(), Report> {
+    let output_dir = PathBuf::from("output").join("tests").join("mixture");
+
+    let dataset_dir = toy1_dataset(&output_dir).await?;
+
+    // A, with ambiguous bases (Y) where B has a T, and a duplicate of it
+    let alignment = output_dir.join("alignment.fasta");
+    write(
+        &alignment,
+        ">pure\nCCCCCCAACCCCCCCCCCCC\n>mixed\nCCCCCCAAYYYYYCCCCCCC\n>mixed_dup\nCCCCCCAAYYYYYCCCCCCC\n",
+    )?;
+
+    // the mixture is analyzed by the search workers, before the outputs
+    struct Mixtures(Arc<Mutex<Vec<Option<Mixture>>>>);
+    impl OutputSink for Mixtures {
+        fn on_result(
+            &mut self,
+            _best_match: &SearchResult,
+            recombination: &Recombination,
+            _dataset: &Dataset,
+        ) -> Result<(), Report> {
+            self.0.lock().unwrap().push(recombination.mixture.clone());
+            Ok(())
+        }
+        fn on_finish(self: Box<Self>) -> Result<(), Report> {
+            Ok(())
+        }
+    }
+    let mixtures = Arc::new(Mutex::new(Vec::new()));
+
+    let mut args = cli::run::Args {
+        input: cli::run::Input {
+            alignment: Some(alignment),
+            ..Default::default()
+        },
+        dataset_dir: Some(dataset_dir.clone()),
+        output_dir: output_dir.join("run"),
+        mask: vec![0, 0],
+        min_length: 3,
+        ..Default::default()
+    };
+    run_with_sinks(&mut args, vec![Box::new(Mixtures(Arc::clone(&mixtures)))])?;
+
+    let mixtures = mixtures.lock().unwrap().clone();
+    assert_eq!(mixtures.len(), 3);
+    assert_eq!(mixtures[0], None);
+    assert!(mixtures[1..].iter().all(Option::is_some));
+    for mixture in mixtures[1..].iter().flatten() {
+        assert_eq!(mixture.status, MixtureStatus::LikelyCoinfection);
+        assert_eq!(mixture.parents, vec!["A", "B"]);
+        assert_eq!(mixture.ambiguous, 5);
+    }
+
+    let linelist =
+        Table::read(&output_dir.join("run").join("linelist").join("linelist.tsv"))?;
+    let mixture_i = linelist.header_position("mixture")?;
+    assert!(linelist.rows[0][mixture_i].is_empty());
+    assert!(linelist.rows[1][mixture_i].starts_with("likely_coinfection"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn missing_in_region() -> Result<(), Report> {
+    let output_dir = PathBuf::from("output").join("tests").join("missing_in_region");
+    let dataset_dir = toy1_dataset(&output_dir).await?;
+
+    // D (A: 1-11, B: 12-20), with missing data inside the region of A, that
+    // either leaves both pieces long enough, or discards the second
+    let alignment = output_dir.join("alignment.fasta");
+    write(
+        &alignment,
+        ">kept\nCCCCCCNNCCCTTTTTTTAA\n>discarded\nCCCCCCANNCCTTTTTTTAA\n",
+    )?;
+    let mut args = cli::run::Args {
+        input: cli::run::Input {
+            alignment: Some(alignment),
+            ..Default::default()
+        },
+        dataset_dir: Some(dataset_dir),
+        output_dir: output_dir.join("default"),
+        mask: vec![0, 0],
+        min_length: 3,
+        ..Default::default()
+    };
+    run(&mut args.clone())?;
+    args.output_dir = output_dir.join("split");
+    args.max_missing_in_region = Some(0.1);
+    run(&mut args)?;
+
+    let linelist = |dir: &str| {
+        Table::read(&output_dir.join(dir).join("linelist").join("linelist.tsv"))
+    };
+    let columns = ["regions", "regions_missing", "breakpoints", "warnings"];
+    let default = linelist("default")?.select_columns(&columns)?;
+    assert_eq!(
+        default.rows[0],
+        ["1-11|A,12-20|B", "0.18,0.00", "12-12", ""]
+    );
+
+    // regions of the same parent, split by missing data, are not a breakpoint
+    let split = linelist("split")?.select_columns(&columns)?;
+    assert_eq!(
+        split.rows[0],
+        ["1-6|A,9-11|A,12-20|B", "0.00,0.00,0.00", "12-12", ""]
+    );
+    assert_eq!(
+        split.rows[1],
+        [
+            "1-7|A,12-20|B",
+            "0.00,0.00",
+            "8-11",
+            "region 10-11|A discarded: too much missing data"
+        ]
+    );
+
+    let mut sequence = Sequence::new();
+    sequence.missing = vec![7, 8];
+    assert_eq!(
+        recombination::missing_fraction(&sequence, 1, 11),
+        2.0 / 11.0
+    );
+    assert_eq!(recombination::missing_fraction(&sequence, 7, 8), 1.0);
+    assert_eq!(recombination::missing_fraction(&sequence, 9, 8), 0.0);
+
+    let region = |start: usize, end: usize, origin: &str| Region {
+        start,
+        end,
+        origin: origin.to_string(),
+        substitutions: Vec::new(),
+        reversions: Vec::new(),
+        missing: 0.0,
+    };
+    let regions = [region(1, 6, "A"), region(9, 11, "A"), region(14, 20, "B")]
+        .into_iter()
+        .map(|region| (region.start, region))
+        .collect();
+    let breakpoints = recombination::identify_breakpoints(&regions)?;
+    assert_eq!(
+        breakpoints.iter().map(|b| b.to_string()).collect::<Vec<_>>(),
+        ["12-13"]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn max_reversions() -> Result<(), Report> {
+    let output_dir = PathBuf::from("output").join("tests").join("max_reversions");
+    let dataset_dir = output_dir.join("dataset");
+    create_dir_all(&dataset_dir)?;
+    write(
+        dataset_dir.join("reference.fasta"),
+        ">Reference\nAAAAAAAAAAAAAAAAAAAA\n",
+    )?;
+    write(
+        dataset_dir.join("populations.fasta"),
+        ">P1\nCCCCCCCCCCAAAAAAAAAA\n>P2\nAAAAAAAAAATTTTTTTTTT\n",
+    )?;
+
+    // recombinant of P1 (1-10) and P2 (11-20), with P1 sites 4-5 reverted
+    let alignment = output_dir.join("alignment.fasta");
+    write(&alignment, ">Q\nCCCAACCCCCTTTTTTTTTT\n")?;
+
+    let mut args = cli::run::Args {
+        input: cli::run::Input {
+            alignment: Some(alignment),
+            ..Default::default()
+        },
+        dataset_dir: Some(dataset_dir),
+        output_dir: output_dir.join("run"),
+        mask: vec![0, 0],
+        min_length: 1,
+        min_consecutive: 2,
+        ..Default::default()
+    };
+    let linelist_path = output_dir.join("run").join("linelist").join("linelist.tsv");
+
+    // by default, the reverted sites are a region of P2
+    for max_reversions in [0, 1] {
+        args.max_reversions = max_reversions;
+        run(&mut args.clone())?;
+        let linelist = Table::read(&linelist_path)?;
+        assert_eq!(
+            linelist.get_as::<String>(0, "regions")?,
+            "1-3|P1,4-5|P2,6-10|P1,11-20|P2"
+        );
+        assert_eq!(linelist.get_as::<String>(0, "reversions")?, "");
+    }
+
+    // unless they are tolerated as reversions, back mutations of P1
+    args.max_reversions = 2;
+    run(&mut args)?;
+    let linelist = Table::read(&linelist_path)?;
+    assert_eq!(linelist.get_as::<String>(0, "regions")?, "1-10|P1,11-20|P2");
+    assert_eq!(linelist.get_as::<String>(0, "breakpoints")?, "11-11");
+    assert_eq!(linelist.get_as::<String>(0, "reversions")?, "C4A,C5A|P1");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn strict_tampered() -> Result<(), Report> {
+    let output_dir = PathBuf::from("output").join("tests").join("strict_tampered");
+
+    let mut args = cli::dataset::download::Args {
+        name: Name::Toy1,
+        tag: Tag::from_str("custom")?,
+        output_dir: output_dir.join("dataset"),
+        summary: None,
+        accession: None,
+        max_missing: None,
+        max_private: None,
+        phylogeny: None,
+        conflict_policy: ConflictPolicy::default(),
+        theme: Theme::new(),
+        strict: true,
+    };
+    download::dataset(&mut args).await?;
+
+    let mut args = cli::run::Args {
+        input: cli::run::Input {
+            populations: Some(vec!["D".to_string()]),
+            ..Default::default()
+        },
+        dataset_dir: Some(output_dir.join("dataset")),
+        output_dir: output_dir.join("run"),
+        mask: vec![0, 0],
+        min_length: 3,
+        strict: true,
+        ..Default::default()
+    };
+    run(&mut args.clone())?;
+
+    // populations modified after download fail strict mode
+    let populations = output_dir.join("dataset").join("populations.fasta");
+    let tampered = read_to_string(&populations)?.replace(">B\nT", ">B\nA");
+    write(&populations, tampered)?;
+    let error = run(&mut args.clone()).unwrap_err();
+    assert!(format!("{error:?}").contains("populations checksum does not match"));
+    args.strict = false;
+    run(&mut args)?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn temporal_flag() -> Result<(), Report> {
+    let output_dir = PathBuf::from("output").join("tests").join("temporal_flag");
+
+    let dataset_dir = toy1_dataset(&output_dir).await?;
+
+    // recombinants of A (2020-01-01) and B (2020-02-01), like D
+    let alignment = output_dir.join("alignment.fasta");
+    let sequence = "CCCCCCAACCCTTTTTTTAA";
+    write(
+        &alignment,
+        format!(">early\n{sequence}\n>late\n{sequence}\n>undated\n{sequence}\n"),
+    )?;
+    let metadata = output_dir.join("metadata.tsv");
+    write(
+        &metadata,
+        "strain\tdate\nearly\t2020-01-15\nlate\t2020-06-01\nundated\t2020-06\n",
+    )?;
+
+    let mut args = cli::run::Args {
+        input: cli::run::Input {
+            alignment: Some(alignment),
+            ..Default::default()
+        },
+        dataset_dir: Some(dataset_dir.clone()),
+        output_dir: output_dir.join("run"),
+        mask: vec![0, 0],
+        min_length: 3,
+        knockout: Some(vec!["D".to_string()]),
+        metadata: Some(metadata),
+        ..Default::default()
+    };
+    run(&mut args)?;
+    let linelist =
+        Table::read(&output_dir.join("run").join("linelist").join("linelist.tsv"))?;
+    let flags = linelist.rows.iter().map(|row| {
+        let strain_i = linelist.header_position("strain").unwrap();
+        let flag_i = linelist.header_position("temporal_flag").unwrap();
+        (row[strain_i].as_str(), row[flag_i].as_str())
+    });
+    assert_eq!(
+        flags.collect::<Vec<_>>(),
+        [
+            (
+                "early",
+                "parent B first seen 2020-02-01 after collection 2020-01-15"
+            ),
+            ("late", ""),
+            ("undated", ""),
+        ]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn toy_mlst() -> Result<(), Report> {
+    let output_dir = PathBuf::from("output").join("tests").join("toy_mlst");
+
+    let mut args = cli::dataset::download::Args {
+        name: Name::ToyMlst,
+        tag: Tag::from_str("custom")?,
+        output_dir: output_dir.join("dataset"),
+        summary: None,
+        accession: None,
+        max_missing: None,
+        max_private: None,
+        phylogeny: None,
+        conflict_policy: ConflictPolicy::default(),
+        theme: Theme::new(),
+        strict: false,
+    };
+    download::dataset(&mut args).await?;
+
+    let mut args = cli::run::Args {
+        input: cli::run::Input {
+            populations: Some(vec!["*".to_string()]),
+            ..Default::default()
+        },
+        dataset_dir: Some(output_dir.join("dataset")),
+        output_dir: output_dir.join("run"),
+        ..Default::default()
+    };
+    run(&mut args)?;
+    let linelist =
+        Table::read(&output_dir.join("run").join("linelist").join("linelist.tsv"))?;
+    let linelist = linelist.filter("recombinant", "ST4")?;
+    assert_eq!(linelist.rows.len(), 1);
+    assert_eq!(
+        linelist.rows[0][linelist.header_position("parents")?],
+        "ST1,ST2"
+    );
+
+    // reads are summarized by sample, alignment records are not samples, even
+    // if their name has the read separator
+    let dataset = load::dataset(&output_dir.join("dataset"), &vec![0, 0])?;
+    let population_seq = |population: &str| {
+        let sequence = &dataset.populations[population];
+        (1..=sequence.genome_length)
+            .map(|coord| sequence.base(coord, &dataset.reference))
+            .collect::<String>()
+    };
+    let mut reads = Vec::new();
+    for (sample, population) in [("s1", "ST1"), ("s2", "ST4")] {
+        let seq = population_seq(population);
+        let records = (0..3000)
+            .step_by(500)
+            .enumerate()
+            .map(|(i, start)| format!(">read{i}\n{}", &seq[start..start + 600]))
+            .collect::<Vec<_>>();
+        let path = output_dir.join(format!("{sample}.fasta"));
+        write(&path, format!("{}\n", records.join("\n")))?;
+        reads.push(path);
+    }
+    let alignment = output_dir.join("alignment.fasta");
+    write(&alignment, format!(">lab/ST1\n{}\n", population_seq("ST1")))?;
+    let mut args = cli::run::Args {
+        input: cli::run::Input {
+            alignment: Some(alignment),
+            reads: Some(reads),
+            ..Default::default()
+        },
+        output_dir: output_dir.join("run_reads"),
+        ..args
+    };
+    run(&mut args)?;
+    let summary =
+        Table::read(&output_dir.join("run_reads").join("linelist").join("reads.tsv"))?;
+    assert_eq!(
+        summary.rows.iter().map(|row| &row[..2]).collect::<Vec<_>>(),
+        [["s1", "6"], ["s2", "6"]]
+    );
+    let populations_i = summary.header_position("populations")?;
+    assert_eq!(summary.rows[0][populations_i], "ST1:6");
+
+    // a core-genome scheme of thousands of loci, populations are stored sparsely
+    let scheme = Scheme::new(2000, 100, 1);
+    let dataset_dir = output_dir.join("cgmlst");
+    create_dir_all(&dataset_dir)?;
+    write(
+        dataset_dir.join("reference.fasta"),
+        scheme.reference_fasta(),
+    )?;
+    write(
+        dataset_dir.join("populations.fasta"),
+        scheme.populations_fasta(),
+    )?;
+    let dataset = load::dataset(&dataset_dir, &vec![0, 0])?;
+    assert_eq!(dataset.reference.genome_length, 200_000);
+    let population = &dataset.populations["ST4"];
+    assert!(population.seq.is_empty());
+    let expected = &scheme.populations["ST4"];
+    assert!(expected
+        .chars()
+        .enumerate()
+        .all(|(i, base)| population.base(i + 1, &dataset.reference) == base));
+
+    Ok(())
+}
+
+#[test]
+fn crlf_bom() -> Result<(), Report> {
+    let output_dir = PathBuf::from("output").join("tests").join("crlf_bom");
+    create_dir_all(&output_dir)?;
+
+    // Table with windows line endings and a byte order mark
+    let path = output_dir.join("dates.tsv");
+    write(&path, "\u{feff}population\tdate\r\nA\t2020-01-01\r\n")?;
+    let table = Table::read(&path)?;
+    assert_eq!(table.headers, vec!["population", "date"]);
+    assert_eq!(table.rows, vec![vec!["A", "2020-01-01"]]);
+
+    // Alignment with windows line endings and a byte order mark
+    let path = output_dir.join("alignment.fasta");
+    write(&path, "\u{feff}>A\r\nAAAAAAAAAA\r\n>B\r\nAAAAACCCCC\r\n")?;
+    let dataset = load::alignment(&path, &vec![0, 0])?;
+    assert_eq!(dataset.reference.id, "A");
+    assert_eq!(
+        dataset.populations.keys().collect::<Vec<_>>(),
+        vec!["A", "B"]
+    );
+    assert_eq!(dataset.populations["B"].substitutions.len(), 5);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn fasta_ingest() -> Result<(), Report> {
+    let output_dir = PathBuf::from("output").join("tests").join("fasta_ingest");
+
+    let dataset_dir = toy1_dataset(&output_dir).await?;
+
+    // wrapped lines, lowercase, unknown (?) and uracil (U) bases are normalized,
+    // and records with a different length than the reference fail
+    let path = output_dir.join("alignment.fasta");
+    let records = [
+        ">s1",
+        "ccccccaacc",
+        "ccccccc?cc",
+        ">short",
+        "AAAAA",
+        ">s2",
+        "TTTTTTTTTT",
+        "uuuuuuuuAA",
+    ];
+    write(&path, records.join("\n"))?;
+
+    let mut args = cli::run::Args {
+        input: cli::run::Input {
+            alignment: Some(path),
+            ..Default::default()
+        },
+        dataset_dir: Some(dataset_dir.clone()),
+        output_dir: output_dir.join("run"),
+        mask: vec![0, 0],
+        min_length: 3,
+        ..Default::default()
+    };
+    // the run fails after writing the outputs of the other sequences
+    assert!(run(&mut args.clone()).is_err());
+    let failures = Table::read(&output_dir.join("run").join("qc").join("failures.tsv"))?;
+    assert_eq!(failures.rows.len(), 1);
+    assert_eq!(failures.rows[0][..2], ["short", "parse"]);
+    let summary = Summary::read(&output_dir.join("run").join("summary.json"))?;
+    assert_eq!((summary.sequences, summary.failures), (2, 1));
+
+    let linelist =
+        Table::read(&output_dir.join("run").join("linelist").join("linelist.tsv"))?;
+    let population = linelist.header_position("population")?;
+    let populations = linelist
+        .rows
+        .iter()
+        .map(|row| (row[0].as_str(), row[population].as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(populations, vec![("s1", "A"), ("s2", "B")]);
+
+    // dataset populations and the alignment are chained, tagged by source
+    let mut chained = cli::run::Args {
+        output_dir: output_dir.join("run_chained"),
+        sample_outputs: true,
+        threads: 0,
+        ..args.clone()
+    };
+    chained.input.populations = Some(vec!["C".to_string()]);
+    assert!(run(&mut chained).is_err());
+    let linelist = Table::read(
+        &output_dir.join("run_chained").join("linelist").join("linelist.tsv"),
+    )?;
+    let source = linelist.header_position("source")?;
+    let sources = linelist
+        .rows
+        .iter()
+        .map(|row| (row[0].as_str(), row[source].as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        sources,
+        vec![
+            ("population_C", "population"),
+            ("s1", "alignment"),
+            ("s2", "alignment")
+        ]
+    );
+    // the full result of each sequence is written with --sample-outputs
+    let samples_dir = output_dir.join("run_chained").join("samples");
+    let sample = read_to_string(samples_dir.join("population_C.json"))?;
+    assert!(sample.contains(r#""source": "population""#));
+    assert!(sample.contains(r#""consensus_population": "C""#));
+    assert!(!samples_dir.join("short.json").exists());
+
+    // sequences with the same content have the same hash and results, even if
+    // their search was reused
+    let path = output_dir.join("duplicates.fasta");
+    let records = [">d1", "CCCCCCAACCCTTTTTTTAA", ">d2", "CCCCCCAACCCTTTTTTTAA"];
+    write(&path, records.join("\n"))?;
+    let mut duplicates = cli::run::Args {
+        output_dir: output_dir.join("run_duplicates"),
+        ..args.clone()
+    };
+    duplicates.input.alignment = Some(path);
+    duplicates.input.populations = Some(vec!["D".to_string()]);
+    run(&mut duplicates)?;
+    let linelist = Table::read(
+        &output_dir.join("run_duplicates").join("linelist").join("linelist.tsv"),
+    )?;
+    let columns = ["sequence_hash", "recombinant", "parents", "breakpoints"]
+        .map(|column| linelist.header_position(column))
+        .into_iter()
+        .collect::<Result<Vec<_>, Report>>()?;
+    let results = linelist
+        .rows
+        .iter()
+        .map(|row| columns.iter().map(|i| row[*i].as_str()).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    assert_eq!(linelist.rows.len(), 3);
+    assert_eq!(results[0][1], "D");
+    assert!(results.iter().all(|result| *result == results[0]));
+    assert_eq!(results[0][0].len(), 16);
+
+    // with --fail-fast, the run stops at the failed sequence
+    args.output_dir = output_dir.join("run_fail_fast");
+    args.fail_fast = true;
+    assert!(run(&mut args).is_err());
+    assert!(!output_dir.join("run_fail_fast").join("summary.json").exists());
+
+    Ok(())
+}
+
+#[test]
+fn locale_safe() -> Result<(), Report> {
+    let output_dir = PathBuf::from("output").join("tests").join("locale_safe");
+    create_dir_all(&output_dir)?;
+
+    // values that spreadsheets convert to dates or locale numbers are quoted
+    let values = [
+        ("12-12", "\"12-12\""),
+        ("2023-11-30", "\"2023-11-30\""),
+        ("1.5", "\"1.5\""),
+        ("0.00,0.56", "\"0.00,0.56\""),
+        ("100", "100"),
+        ("C6A,T7G", "C6A,T7G"),
+        ("1-11|A,12-20|B", "1-11|A,12-20|B"),
+        ("", ""),
+    ];
+    for (value, expected) in values {
+        assert_eq!(table::locale_safe(value), expected);
+    }
+
+    // quoted values are unquoted when read
+    let path = output_dir.join("linelist.tsv");
+    let rows = values.iter().map(|(_, quoted)| *quoted).collect::<Vec<_>>();
+    write(&path, format!("value\n{}\n", rows.join("\n")))?;
+    let table = Table::read(&path)?;
+    let read = table.rows.iter().map(|row| row[0].as_str()).collect::<Vec<_>>();
+    assert_eq!(read, values.map(|(value, _)| value));
+
+    Ok(())
+}
+
+#[test]
+fn table_parse() -> Result<(), Report> {
+    let mut table = Table::new();
+    table.headers = vec!["gene".to_string(), "start".to_string(), "end".to_string()];
+    table.rows = vec![
+        vec!["ORF1a".to_string(), "266".to_string(), "13483".to_string()],
+        vec!["S".to_string(), "21563".to_string(), "NA".to_string()],
+    ];
+
+    // values are parsed by column, with the row and column in errors
+    assert_eq!(table.get_column_as::<usize>("start")?, vec![266, 21563]);
+    assert_eq!(table.get_as::<f64>(0, "end")?, 13483.0);
+    let error = table.get_column_as::<usize>("end").unwrap_err().to_string();
+    assert!(error.contains("row 2 of column 'end' as usize: 'NA'"));
+    assert!(table.get_as::<usize>(2, "start").is_err());
+    assert!(table.get_as::<usize>(0, "length").is_err());
+
+    // markdown columns are aligned by characters, with pipes escaped
+    table.rows[1][0] = "1-11|A".to_string();
+    let markdown = table.to_markdown()?;
+    let lines = markdown.lines().collect::<Vec<_>>();
+    assert_eq!(lines[0], "|  gene   | start |  end  |");
+    assert_eq!(lines[3], "| 1-11\\|A | 21563 |  NA   |");
+    table.rows[1].pop();
+    assert!(table.to_markdown().is_err());
+
+    // filtered, sorted, and selected tables are new tables
+    table.rows[1].push("NA".to_string());
+    let subset = table.filter_column("end", |end| end != "NA")?;
+    assert_eq!(subset.rows.len(), 1);
+    assert_eq!(table.filter_rows(|row| row[0] == "ORF1a").rows, subset.rows);
+    let sorted = table.sort_by_column("start", SortType::Numeric)?;
+    assert_eq!(sorted.rows[0][0], "ORF1a");
+    let select = table.select_columns(&["end", "gene"])?;
+    assert_eq!(select.headers, ["end", "gene"]);
+    assert_eq!(select.rows[0], ["13483", "ORF1a"]);
+    assert!(table.select_columns(&["length"]).is_err());
+
+    // rows without a value for the column are an error, rather than a panic
+    table.rows[1].pop();
+    assert!(table.filter_column("end", |end| end != "NA").is_err());
+    assert!(table.sort_by_column("end", SortType::Numeric).is_err());
+    assert!(table.select_columns(&["end"]).is_err());
+    assert!(table.select_columns(&["gene"]).is_ok());
+
+    Ok(())
+}
+
+#[test]
+fn table_read() -> Result<(), Report> {
+    // quoted values can contain the delimiter and quotes
+    let text =
+        "strain,regions,note\r\nX,\"1-11|A,12-20|B\",\"a \"\"b\"\"\"\r\nY,,5'-\"UTR\"\n";
+    let table = Table::from_reader(text.as_bytes(), ',', true)?;
+    assert_eq!(table.headers, ["strain", "regions", "note"]);
+    assert_eq!(table.rows[0], ["X", "1-11|A,12-20|B", "a \"b\""]);
+    assert_eq!(table.rows[1], ["Y", "", "5'-\"UTR\""]);
+
+    // without headers, every line is a row
+    let table = Table::from_reader(text.as_bytes(), ',', false)?;
+    assert!(table.headers.is_empty());
+    assert_eq!(table.rows.len(), 3);
+
+    // the delimiter is from the file extension
+    let output_dir = PathBuf::from("output").join("tests").join("table_read");
+    create_dir_all(&output_dir)?;
+    let path = output_dir.join("regions.csv");
+    write(&path, text)?;
+    assert_eq!(Table::read(&path)?.rows[0][1], "1-11|A,12-20|B");
+    assert_eq!(Table::read_headerless(&path)?.rows.len(), 3);
+    assert!(Table::read(&output_dir.join("regions.bed")).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn float_format() -> Result<(), Report> {
+    // ties are rounded away from zero, and -0 is 0
+    let values = [
+        (0.125, "0.13"),
+        (-0.125, "-0.13"),
+        (1.0, "1.00"),
+        (-0.001, "0.00"),
+        (f64::NAN, "NA"),
+        (f64::INFINITY, "NA"),
+    ];
+    for (value, expected) in values {
+        assert_eq!(fmt::float(value), expected);
+    }
+    assert_eq!(fmt::float_with(0.5, 0), "1");
+    assert_eq!(fmt::round(0.125, fmt::PRECISION), 0.13);
+
+    Ok(())
+}
+
+#[test]
+fn ncbi_genbank() -> Result<(), Report> {
+    let output_dir = PathBuf::from("output").join("tests").join("ncbi_genbank");
+    create_dir_all(&output_dir)?;
+
+    // CDS with joined, complement, and partial ranges, and multi-line qualifiers
+    let path = output_dir.join("reference.gb");
+    let record = [
+        "LOCUS       TOY1                      20 bp    RNA     linear   VRL",
+        "ACCESSION   TOY1",
+        "VERSION     TOY1.1",
+        "FEATURES             Location/Qualifiers",
+        "     source          1..20",
+        "     CDS             join(1..2,",
+        "                     2..3)",
+        "                     /gene=\"Gene1\"",
+        "     CDS             complement(<12..>20)",
+        "                     /product=\"Gene2",
+        "                     protein\"",
+        "ORIGIN",
+        "        1 aaaaaaaaaa aaaaaaaaaa",
+        "//",
+    ];
+    write(&path, record.join("\n"))?;
+    let genbank = GenBank::read(&path)?;
+    assert_eq!(genbank.id, "TOY1.1");
+    assert_eq!(genbank.sequence, "A".repeat(20));
+    assert_eq!(
+        genbank.annotations.rows,
+        vec![
+            vec!["Gene1", "Gene1", "1", "3"],
+            vec!["Gene2 protein", "Gene2 protein", "12", "20"]
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn nextclade_import() -> Result<(), Report> {
+    let output_dir = PathBuf::from("output").join("tests").join("nextclade_import");
+    let bundle_dir = output_dir.join("bundle");
+    create_dir_all(&bundle_dir)?;
+
+    // clades 19A (root), 20A (A5T), and 20B (A5T, A15G), with a tip of 20A
+    // that has a private mutation (A10C)
+    let tree = serde_json::json!({
+        "version": "v2",
+        "tree": {
+            "name": "root_node",
+            "node_attrs": {"clade_membership": {"value": "19A"}},
+            "children": [{
+                "name": "node1",
+                "node_attrs": {"clade_membership": {"value": "20A"}},
+                "branch_attrs": {"mutations": {"nuc": ["A5T"]}},
+                "children": [
+                    {
+                        "name": "tip1",
+                        "node_attrs": {"clade_membership": {"value": "20A"}},
+                        "branch_attrs": {"mutations": {"nuc": ["A10C"]}},
+                    },
+                    {
+                        "name": "tip2",
+                        "node_attrs": {"clade_membership": {"value": "20B"}},
+                        "branch_attrs": {"mutations": {"nuc": ["A15G"]}},
+                    },
+                ],
+            }],
+        },
+    });
+    let gff = [
+        "##gff-version 3",
+        "ref\tsource\tgene\t1\t9\t.\t+\t.\tgene_name=Gene1",
+        "ref\tsource\tCDS\t1\t9\t.\t+\t0\tName=Gene1",
+        "ref\tsource\tgene\t12\t20\t.\t+\t.\tgene_name=Gene2",
+    ];
+    let files = [
+        ("reference.fasta", format!(">ref\n{}\n", "A".repeat(20))),
+        ("tree.json", tree.to_string()),
+        ("genome_annotation.gff3", format!("{}\n", gff.join("\n"))),
+    ];
+    for (name, contents) in &files {
+        write(bundle_dir.join(name), contents)?;
+    }
+
+    let mut args = cli::dataset::import_nextclade::Args {
+        input: bundle_dir,
+        output_dir: output_dir.join("dataset"),
+        ..Default::default()
+    };
+    nextclade::import(&args)?;
+    let dataset = load::dataset(&args.output_dir, &vec![0, 0])?;
+    let substitutions = |population: &str| {
+        let substitutions = &dataset.populations[population].substitutions;
+        substitutions.iter().map(|sub| sub.to_string()).collect::<Vec<_>>().join(",")
+    };
+    assert_eq!(substitutions("19A"), "");
+    assert_eq!(substitutions("20A"), "A5T");
+    assert_eq!(substitutions("20B"), "A5T,A15G");
+    assert_eq!(dataset.phylogeny.get_parents("20B")?, vec!["20A"]);
+    assert_eq!(dataset.phylogeny.get_parents("20A")?, vec!["19A"]);
+    assert_eq!(
+        dataset.annotations.rows,
+        vec![
+            vec!["Gene1", "Gene1", "1", "9"],
+            vec!["Gene2", "Gene2", "12", "20"]
+        ]
+    );
+
+    // a zip bundle, with the files in a directory, is extracted first
+    let zip_path = output_dir.join("bundle.zip");
+    let (mut archive, mut central) = (Vec::new(), Vec::new());
+    for (name, contents) in &files {
+        let name = format!("sars-cov-2/{name}");
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(contents.as_bytes())?;
+        let data = encoder.finish()?;
+        let mut crc = Crc::new();
+        crc.update(contents.as_bytes());
+        // fields shared by the local and central headers, from the version
+        let mut fields = vec![20u16, 0, 8, 0, 0]
+            .into_iter()
+            .flat_map(u16::to_le_bytes)
+            .collect::<Vec<_>>();
+        for value in [crc.sum(), data.len() as u32, contents.len() as u32] {
+            fields.extend(value.to_le_bytes());
+        }
+        fields.extend((name.len() as u16).to_le_bytes());
+        fields.extend(0u16.to_le_bytes());
+
+        central.extend(0x0201_4b50u32.to_le_bytes());
+        central.extend(20u16.to_le_bytes());
+        central.extend(&fields);
+        central.extend([0; 10]);
+        central.extend((archive.len() as u32).to_le_bytes());
+        central.extend(name.as_bytes());
+
+        archive.extend(0x0403_4b50u32.to_le_bytes());
+        archive.extend(&fields);
+        archive.extend(name.as_bytes());
+        archive.extend(data);
+    }
+    let num_files = (files.len() as u16).to_le_bytes();
+    let (central_size, central_offset) = (central.len() as u32, archive.len() as u32);
+    archive.extend(central);
+    archive.extend(0x0605_4b50u32.to_le_bytes());
+    archive.extend([0; 4]);
+    archive.extend(num_files);
+    archive.extend(num_files);
+    archive.extend(central_size.to_le_bytes());
+    archive.extend(central_offset.to_le_bytes());
+    archive.extend([0; 2]);
+    write(&zip_path, archive)?;
+
+    args.input = zip_path;
+    args.output_dir = output_dir.join("dataset_zip");
+    nextclade::import(&args)?;
+    let populations =
+        |dir: &str| read_to_string(output_dir.join(dir).join("populations.fasta"));
+    assert_eq!(populations("dataset_zip")?, populations("dataset")?);
+
+    Ok(())
+}
+
+#[test]
+fn phylogeny_merge() -> Result<(), Report> {
+    let toy1 = toy1::phylogeny::build()?;
+
+    // a backbone where D descends from C, and a new population F from E
+    let mut other = Phylogeny::new();
+    let root = other.add_node("root".to_string());
+    let c = other.add_node("C".to_string());
+    let d = other.add_node("D".to_string());
+    let e = other.add_node("E".to_string());
+    let f = other.add_node("F".to_string());
+    other.graph.add_edge(root, c, 1.0);
+    other.graph.add_edge(c, d, 1.0);
+    other.graph.add_edge(c, e, 1.0);
+    other.graph.add_edge(e, f, 1.0);
+
+    // D and E have different parents
+    assert!(toy1.clone().merge(&other, ConflictPolicy::Error).is_err());
+
+    let mut phylogeny = toy1.clone();
+    let conflicts = phylogeny.merge(&other, ConflictPolicy::Keep)?;
+    assert_eq!(
+        conflicts.iter().map(|c| &c.name).collect::<Vec<_>>(),
+        vec!["D", "E"]
+    );
+    assert_eq!(phylogeny.get_parents("D")?, toy1.get_parents("D")?);
+    assert_eq!(phylogeny.get_parents("F")?, vec!["E"]);
+
+    let mut phylogeny = toy1.clone();
+    phylogeny.merge(&other, ConflictPolicy::Replace)?;
+    assert_eq!(phylogeny.get_parents("D")?, vec!["C"]);
+    assert!(!phylogeny.is_recombinant("D")?);
+
+    let mut phylogeny = toy1.clone();
+    phylogeny.merge(&other, ConflictPolicy::Union)?;
+    assert_eq!(phylogeny.get_parents("D")?.len(), 3);
+
+    // a merge that creates a cycle (A -> D -> E -> A) leaves the phylogeny unchanged
+    let mut cyclic = Phylogeny::new();
+    let e = cyclic.add_node("E".to_string());
+    let a = cyclic.add_node("A".to_string());
+    let g = cyclic.add_node("G".to_string());
+    cyclic.graph.add_edge(e, a, 1.0);
+    cyclic.graph.add_edge(a, g, 1.0);
+    cyclic.dates.insert(
+        "G".to_string(),
+        NaiveDate::from_ymd_opt(2020, 1, 1).unwrap(),
+    );
+    let mut phylogeny = toy1.clone();
+    assert!(phylogeny.merge(&cyclic, ConflictPolicy::Union).is_err());
+    assert_eq!(phylogeny.get_names()?, toy1.get_names()?);
+    assert_eq!(phylogeny.graph.edge_count(), toy1.graph.edge_count());
+    assert_eq!(phylogeny.get_parents("A")?, vec!["root"]);
+    assert_eq!(phylogeny.dates, toy1.dates);
+    assert!(phylogeny.get_node("G").is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn dataset_download_merge() -> Result<(), Report> {
+    let output_dir = PathBuf::from("output").join("tests").join("dataset_download_merge");
+    create_dir_all(&output_dir)?;
+
+    // a curated phylogeny where D descends from C, and a new population F from E
+    let mut other = Phylogeny::new();
+    let root = other.add_node("root".to_string());
+    let c = other.add_node("C".to_string());
+    let d = other.add_node("D".to_string());
+    let e = other.add_node("E".to_string());
+    let f = other.add_node("F".to_string());
+    other.graph.add_edge(root, c, 1.0);
+    other.graph.add_edge(c, d, 1.0);
+    other.graph.add_edge(c, e, 1.0);
+    other.graph.add_edge(e, f, 1.0);
+    let other_path = output_dir.join("curated.json");
+    other.write(&other_path)?;
+
+    let mut args = cli::dataset::download::Args {
+        name: Name::Toy1,
+        tag: Tag::from_str("custom")?,
+        output_dir: output_dir.join("dataset"),
+        summary: None,
+        accession: None,
+        max_missing: None,
+        max_private: None,
+        phylogeny: Some(other_path),
+        conflict_policy: ConflictPolicy::Error,
+        theme: Theme::new(),
+        strict: false,
+    };
+    assert!(download::dataset(&mut args).await.is_err());
+
+    args.conflict_policy = ConflictPolicy::Keep;
+    download::dataset(&mut args).await?;
+    let phylogeny = Phylogeny::read(&args.output_dir.join("phylogeny.json"))?;
+    assert_eq!(phylogeny.get_parents("F")?, vec!["E"]);
+    assert_eq!(
+        phylogeny.get_parents("D")?,
+        toy1::phylogeny::build()?.get_parents("D")?
+    );
+    let conflicts = Table::read(&args.output_dir.join("phylogeny_conflicts.tsv"))?;
+    assert_eq!(
+        conflicts.rows.iter().map(|row| &row[0]).collect::<Vec<_>>(),
+        vec!["D", "E"]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn breakpoint_intervals() -> Result<(), Report> {
+    // a single coordinate is a breakpoint of width 1
+    assert_eq!(Breakpoint::from_str("12")?, Breakpoint::new(12, 12));
+    assert_eq!(Breakpoint::from_str("12-15")?.width(), 4);
+    assert!(Breakpoint::from_str("15-12").is_err());
+    assert!(Breakpoint::from_str("12-").is_err());
+    assert_eq!(
+        Breakpoint::parse_list("12-15,20")?,
+        vec![Breakpoint::new(12, 15), Breakpoint::new(20, 20)]
+    );
+    assert!(Breakpoint::parse_list("12-15,15-12").is_err());
+
+    // overlapping intervals
+    let a = Breakpoint::new(10, 20);
+    let b = Breakpoint::new(15, 25);
+    assert_eq!(a.intersection(&b), Some(Breakpoint::new(15, 20)));
+    assert_eq!(a.distance(&b), 0);
+    assert_eq!(a.shift(&b), 5);
+
+    // disjoint intervals, in either order
+    let c = Breakpoint::new(30, 40);
+    assert_eq!(a.intersection(&c), None);
+    assert_eq!(a.distance(&c), 10);
+    assert_eq!(c.distance(&a), 10);
+    assert_eq!(a.shift(&c), 20);
+
+    // adjacent intervals share no coordinates
+    let d = Breakpoint::new(21, 22);
+    assert_eq!(a.intersection(&d), None);
+    assert_eq!(a.distance(&d), 1);
+
+    Ok(())
+}
+
+#[test]
+fn phylogeny_newick() -> Result<(), Report> {
+    // unrooted tree (trifurcating top node)
+    let newick = "((A:1,B:2)AB:1,C:3,'D':4);";
+    let phylogeny = Phylogeny::from_newick(newick, None, BranchLength::Mutations)?;
+    assert_eq!(phylogeny.get_children("root")?, vec!["AB", "C", "D"]);
+
+    // root on the outgroup branch
+    let phylogeny = Phylogeny::from_newick(newick, Some("D"), BranchLength::Mutations)?;
+    assert_eq!(phylogeny.get_children("root")?.len(), 2);
+    assert_eq!(phylogeny.get_parents("D")?, vec!["root"]);
+    assert_eq!(phylogeny.get_parents("AB")?, phylogeny.get_parents("C")?);
+    assert_eq!(phylogeny.get_descendants("root")?.len(), 7);
+
+    // root on a named node
+    let mut phylogeny = Phylogeny::from_newick(newick, None, BranchLength::Mutations)?;
+    phylogeny.set_root("AB")?;
+    assert_eq!(phylogeny.get_children("root")?, vec!["AB"]);
+    assert_eq!(phylogeny.get_parents("C")?, phylogeny.get_parents("D")?);
+    assert_eq!(phylogeny.get_ancestors("D")?[0][1], "AB");
+
+    // collapse unary nodes, summing branch lengths
+    let newick = "(((A:1)X:2,B:1)Y:1,C:1);";
+    let mut phylogeny = Phylogeny::from_newick(newick, None, BranchLength::Mutations)?;
+    assert_eq!(phylogeny.collapse_unary_nodes()?, vec!["X"]);
+    assert_eq!(phylogeny.get_parents("A")?, vec!["Y"]);
+    let edge =
+        phylogeny.graph.find_edge(phylogeny.get_node("Y")?, phylogeny.get_node("A")?);
+    assert_eq!(edge.map(|edge| phylogeny.graph[edge]), Some(3.0));
+
+    // collapse branches with low support (bootstrap) into polytomies
+    let newick = "((A:1,B:1)95:1,((C:1,D:1)40:2,E:1)80:1);";
+    let mut phylogeny = Phylogeny::from_newick(newick, None, BranchLength::Mutations)?;
+    let num_nodes = phylogeny.get_names()?.len();
+    let cd = phylogeny.get_parents("C")?;
+    assert_eq!(phylogeny.confidence.get(&cd[0]), Some(&40.0));
+    assert_eq!(phylogeny.collapse_branches(|b| b.confidence < 70.0)?, cd);
+    assert_eq!(phylogeny.get_names()?.len(), num_nodes - 1);
+    assert_eq!(phylogeny.get_parents("C")?, phylogeny.get_parents("E")?);
+    let branch = phylogeny.get_branches()?.into_iter().find(|b| b.child == "C");
+    assert_eq!(branch.map(|b| b.length), Some(3.0));
+    let parent = &phylogeny.get_parents("E")?[0];
+    assert_eq!(phylogeny.confidence.get(parent), Some(&80.0));
+
+    // recombinants are written and read as extended newick hybrids
+    let phylogeny = examples::xbl()?;
+    let newick = phylogeny.to_newick()?;
+    assert!(newick.contains("XBB#H1") && newick.contains("XBL#H2"));
+    let other = Phylogeny::from_newick(&newick, None, phylogeny.branch_length)?;
+    assert_eq!(other.recombinants, phylogeny.recombinants);
+    for name in phylogeny.get_names()? {
+        let mut parents = phylogeny.get_parents(&name)?;
+        let mut other_parents = other.get_parents(&name)?;
+        parents.sort();
+        other_parents.sort();
+        assert_eq!(parents, other_parents);
+    }
+
+    // unnamed hybrids (ex. PhyloNet), and other types of hybrid (ex. #R)
+    let newick = "((A,(B)#H1),(#H1,C))root;";
+    let phylogeny = Phylogeny::from_newick(newick, None, BranchLength::None)?;
+    assert_eq!(phylogeny.recombinants, vec!["hybrid1"]);
+    assert_eq!(phylogeny.get_children("hybrid1")?, vec!["B"]);
+    let newick = "((A,X#R1),((Y)X#R1,C))root;";
+    let phylogeny = Phylogeny::from_newick(newick, None, BranchLength::None)?;
+    assert_eq!(phylogeny.recombinants, vec!["X"]);
+    assert_eq!(phylogeny.get_children("X")?, vec!["Y"]);
+    let newick = "((A,X#H1),(Y#H1,C))root;";
+    assert!(Phylogeny::from_newick(newick, None, BranchLength::None).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn phylogeny_examples() -> Result<(), Report> {
+    let phylogeny = examples::xe()?;
+    assert_eq!(phylogeny.recombinants, vec!["XE"]);
+    let mut parents = phylogeny.get_parents("XE")?;
+    parents.sort();
+    assert_eq!(parents, vec!["BA.1", "BA.2"]);
+
+    // XBL is a recursive recombinant, through XBB
+    let phylogeny = examples::xbl()?;
+    assert_eq!(phylogeny.recombinants, vec!["XBB", "XBL"]);
+    assert_eq!(
+        phylogeny.get_recombinant_ancestor("XBB.1.5")?,
+        Some("XBB".to_string())
+    );
+    // paths to the root through both parents of XBB, and BA.2.75
+    assert_eq!(phylogeny.get_ancestors("XBL")?.len(), 3);
+
+    Ok(())
+}
+
+#[test]
+fn phylogeny_graphml() -> Result<(), Report> {
+    let toy1 = toy1::phylogeny::build()?;
+
+    // round trip through graphml
+    let phylogeny = Phylogeny::from_graphml(&toy1.to_graphml()?)?;
+    assert_eq!(phylogeny.get_names()?, toy1.get_names()?);
+    assert_eq!(phylogeny.get_recombinants()?, toy1.get_recombinants()?);
+    assert_eq!(phylogeny.get_parents("D")?, toy1.get_parents("D")?);
+
+    // nodes are typed for visualization
+    let gml = toy1.to_gml()?;
+    assert!(gml.contains("label \"D\"\n    type \"recombinant\""));
+
+    // round trip through json, with a schema version
+    let json = toy1.to_json()?;
+    let phylogeny = Phylogeny::from_json(&json)?;
+    assert_eq!(phylogeny.get_names()?, toy1.get_names()?);
+    assert_eq!(phylogeny.recombinants, vec!["D", "E"]);
+    let mut value: serde_json::Value = serde_json::from_str(&json)?;
+    assert_eq!(value["schema_version"], 1);
+
+    // phylogenies before the schema was versioned, or from newer versions
+    value.as_object_mut().unwrap().remove("schema_version");
+    assert!(Phylogeny::from_json(&value.to_string()).is_ok());
+    value["schema_version"] = 2.into();
+    assert!(Phylogeny::from_json(&value.to_string()).is_err());
+    value["schema_version"] = 1.into();
+    value["graph"]["edges"][0][1] = 10.into();
+    assert!(Phylogeny::from_json(&value.to_string()).is_err());
+
+    // round trip through mermaid, with branch lengths
+    let mut mutations = toy1.clone();
+    mutations.branch_length = BranchLength::Mutations;
+    let mermaid = mutations.to_mermaid()?;
+    assert!(mermaid.contains(r#"n4["D"]:::recombinant"#));
+    let phylogeny = Phylogeny::from_mermaid(&mermaid)?;
+    assert_eq!(phylogeny.get_names()?, toy1.get_names()?);
+    assert_eq!(phylogeny.get_recombinants()?, toy1.get_recombinants()?);
+    assert_eq!(phylogeny.branch_length, BranchLength::Mutations);
+
+    // the diagram of the dataset docs
+    let mermaid = "graph LR;\n    root-->A;\n    root-->B;\n    root-->C;\n    A-->D;\n    B-->D;\n    C-->E;\n    D-->E;\n\nstyle D fill:#00758f\n";
+    let phylogeny = Phylogeny::from_mermaid(mermaid)?;
+    assert_eq!(phylogeny.get_names()?, toy1.get_names()?);
+    let mut parents = phylogeny.get_parents("E")?;
+    parents.sort();
+    assert_eq!(parents, vec!["C", "D"]);
+
+    // the recombinant class must match the graph
+    let mermaid = "graph TD\n    root --> A:::recombinant --> B";
+    assert!(Phylogeny::from_mermaid(mermaid).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn phylogeny_random() -> Result<(), Report> {
+    let phylogeny = Phylogeny::random(20, 5, 42)?;
+    let names = phylogeny.get_names()?;
+    // root, 38 nodes of a bifurcating tree with 20 tips, and 5 recombinants
+    assert_eq!(names.len(), 1 + 38 + 5);
+    assert_eq!(phylogeny.get_recombinants()?.len(), 5);
+    for recombinant in phylogeny.get_recombinants()? {
+        assert_eq!(phylogeny.get_parents(&recombinant)?.len(), 2);
+    }
+
+    // the same seed generates the same phylogeny, exports round trip
+    let other = Phylogeny::random(20, 5, 42)?;
+    assert_eq!(other.to_graphml()?, phylogeny.to_graphml()?);
+    let graphml = Phylogeny::from_graphml(&phylogeny.to_graphml()?)?;
+    assert_eq!(graphml.get_names()?, names);
+    assert_ne!(
+        Phylogeny::random(20, 5, 7)?.to_graphml()?,
+        phylogeny.to_graphml()?
+    );
+
+    assert!(Phylogeny::random(1, 0, 42).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn phylogeny_node_index() -> Result<(), Report> {
+    // node lookups are the same as searching the graph
+    let scan = |phylogeny: &Phylogeny, name: &str| {
+        phylogeny.graph.node_indices().find(|node| phylogeny.graph[*node] == name)
+    };
+    let mut phylogeny = Phylogeny::random(50, 10, 42)?;
+    for name in phylogeny.get_names()? {
+        let node = phylogeny.get_node(&name)?;
+        assert_eq!(Some(node), scan(&phylogeny, &name));
+        assert_eq!(phylogeny.get_name(&node)?, name);
+    }
+
+    // removing nodes moves the last node of the graph
+    let removed = phylogeny.retain(|name| !name.starts_with('X'))?;
+    assert_eq!(removed.len(), 10);
+    for name in phylogeny.get_names()? {
+        assert_eq!(Some(phylogeny.get_node(&name)?), scan(&phylogeny, &name));
+    }
+    assert!(phylogeny.get_node(&removed[0]).is_err());
+
+    // nodes added to the graph directly are still found
+    let node = phylogeny.graph.add_node("Y".to_string());
+    assert_eq!(phylogeny.get_node("Y")?, node);
+    phylogeny.graph[node] = "Z".to_string();
+    assert!(phylogeny.get_node("Y").is_err());
+    phylogeny.index_nodes();
+    assert_eq!(phylogeny.get_node("Z")?, node);
+
+    Ok(())
+}
+
+#[test]
+fn phylogeny_effective() -> Result<(), Report> {
+    // populations without sequence data are removed, grandchildren reconnected
+    let mut phylogeny = examples::from_edges(&[
+        ("root", "A"),
+        ("A", "B"),
+        ("A", "C"),
+        ("B", "X"),
+        ("C", "X"),
+    ])?;
+    let removed = phylogeny.retain(|name| name != "A" && name != "B")?;
+    assert_eq!(removed, vec!["A", "B"]);
+    let mut children = phylogeny.get_children("root")?;
+    children.sort();
+    assert_eq!(children, vec!["C", "X"]);
+    let mut parents = phylogeny.get_parents("X")?;
+    parents.sort();
+    assert_eq!(parents, vec!["C", "root"]);
+    assert_eq!(phylogeny.get_recombinants()?, vec!["X"]);
+
+    Ok(())
+}
+
+#[test]
+fn phylogeny_mrca() -> Result<(), Report> {
+    let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+    let phylogeny = examples::xbl()?;
+
+    // (names, mrca, mrca ignoring recombination)
+    let expected = [
+        (names(&["BA.1", "BA.2"]), "B.1.1.529", "B.1.1.529"),
+        (names(&["XBB.1", "XBB.1.5"]), "XBB.1", "XBB.1"),
+        (names(&["XBB.1.5", "BJ.1"]), "BJ.1", "root"),
+        (names(&["XBL", "XBB.1", "BA.2.75.3"]), "BA.2.75.3", "root"),
+        (names(&["XBB", "BA.1"]), "B.1.1.529", "root"),
+    ];
+    for (names, mrca, mrca_ignored) in expected {
+        assert_eq!(phylogeny.get_mrca(&names, false)?, mrca);
+        assert_eq!(phylogeny.get_mrca(&names, true)?, mrca_ignored);
+    }
+    assert!(phylogeny.get_mrca(&names(&["BA.1", "XYZ"]), false).is_err());
+    assert!(phylogeny.get_mrca(&[], false).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn phylogeny_limits() -> Result<(), Report> {
+    let mut phylogeny = toy1::phylogeny::build()?;
+    assert_eq!(phylogeny.get_ancestors("D")?.len(), 2);
+
+    // recombinant D has two paths to the root
+    phylogeny.config.max_paths = 1;
+    assert!(phylogeny.get_ancestors("D").is_err());
+
+    phylogeny.config = PhylogenyConfig::default();
+    phylogeny.config.max_visited = 2;
+    assert!(phylogeny.get_descendants("root").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn dataset_as_of() -> Result<(), Report> {
+    let output_dir = PathBuf::from("output").join("tests").join("dataset_as_of");
+    create_dir_all(&output_dir)?;
+
+    let path = output_dir.join("alignment.fasta");
+    write(
+        &path,
+        ">Reference\nAAAAAAAA\n>A\nCAAAAAAA\n>B\nCCCAAAAA\n>C\nCCCCCCAA\n>D\nCCAAAAAA\n",
+    )?;
+    let mut dataset = load::alignment(&path, &vec![0, 0])?;
+    let newick = "(((C:3)B:2,D:1)A:1);";
+    dataset.phylogeny = Phylogeny::from_newick(newick, None, BranchLength::Mutations)?;
+    let date = |d: &str| NaiveDate::parse_from_str(d, "%Y-%m-%d");
+    dataset.phylogeny.dates.insert("A".to_string(), date("2020-01-01")?);
+    dataset.phylogeny.dates.insert("B".to_string(), date("2021-01-01")?);
+    // descendants are removed, even if dated earlier
+    dataset.phylogeny.dates.insert("C".to_string(), date("2020-06-01")?);
+
+    // D is undated, and retained with its branch length
+    let removed = dataset.as_of(&date("2020-12-31")?)?;
+    assert_eq!(removed, ["B", "C"]);
+    assert_eq!(
+        dataset.populations.keys().collect::<Vec<_>>(),
+        ["A", "D", "Reference"]
+    );
+    assert_eq!(dataset.phylogeny.get_names()?, ["root", "A", "D"]);
+    assert!(dataset.phylogeny.dates.keys().all(|p| p == "A"));
+    assert!(dataset.mutations.values().flatten().all(|p| !removed.contains(p)));
+    let branch = dataset.phylogeny.get_branches()?.into_iter().find(|b| b.child == "D");
+    assert_eq!(branch.map(|b| b.length), Some(1.0));
+    dataset.phylogeny.validate_branch_lengths()?;
+
+    Ok(())
+}
+
+#[test]
+fn scoring_model() -> Result<(), Report> {
+    let output_dir = PathBuf::from("output").join("tests").join("scoring_model");
+    create_dir_all(&output_dir)?;
+
+    // candidates with the same support, but different conflicts
+    let path = output_dir.join("alignment.fasta");
+    write(
+        &path,
+        ">Reference\nAAAAAAAAAA\n>P1\nCCCCCCCCCC\n>P2\nCCCCCAAAAA\n>Q\nCCCCAAAAAA\n",
+    )?;
+    let mut dataset = load::alignment(&path, &vec![0, 0])?;
+    let query = dataset.populations["Q"].clone();
+    let (p1, p2) = ("P1".to_string(), "P2".to_string());
+    let candidates = vec![&p1, &p2];
+
+    // parsimony prefers the fewest conflicts
+    let result = dataset.search(&query, Some(&candidates), None)?;
+    assert_eq!(result.consensus_population, "P2");
+
+    // a custom model that prefers the most conflicts
+    #[derive(Debug)]
+    struct MostConflicts;
+    impl ScoringModel for MostConflicts {
+        fn score(&self, summary: &parsimony::Summary) -> f64 {
+            summary.conflict_ref.len() as f64
+        }
+    }
+    dataset.scoring = Arc::new(MostConflicts);
+    let result = dataset.search(&query, Some(&candidates), None)?;
+    assert_eq!(result.consensus_population, "P1");
+    // the reported score is still parsimony
+    assert_eq!(result.score["P1"], -2);
+
+    Ok(())
+}
+
+#[test]
+fn polytomy_consensus() -> Result<(), Report> {
+    let output_dir = PathBuf::from("output").join("tests").join("polytomy_consensus");
+    create_dir_all(&output_dir)?;
+
+    // a large polytomy, P (without a sequence) with children P.1 ... P.20
+    let children = (1..=20).map(|i| format!("P.{i}")).collect::<Vec<_>>();
+    let edges = std::iter::once(("root", "P"))
+        .chain(children.iter().map(|c| ("P", c.as_str())))
+        .collect::<Vec<_>>();
+    let phylogeny = examples::from_edges(&edges)?;
+    let named = vec!["P.3".to_string(), "P.7".to_string(), "P.12".to_string()];
+    assert_eq!(phylogeny.get_common_ancestor(&named)?, "P");
+    let named = vec!["P".to_string(), "P.7".to_string()];
+    assert_eq!(phylogeny.get_common_ancestor(&named)?, "P");
+
+    // children share substitutions A1C and A2C, each has one private substitution
+    let mut fasta = format!(">Reference\n{}\n", "A".repeat(30));
+    for (i, child) in children.iter().take(5).enumerate() {
+        let mut seq = ['A'; 30];
+        seq[0] = 'C';
+        seq[1] = 'C';
+        seq[i + 2] = 'G';
+        fasta.push_str(&format!(">{child}\n{}\n", seq.iter().collect::<String>()));
+    }
+    fasta.push_str(&format!(">Q\nCC{}\n", "A".repeat(28)));
+    let path = output_dir.join("alignment.fasta");
+    write(&path, fasta)?;
+    let mut dataset = load::alignment(&path, &vec![0, 0])?;
+    dataset.phylogeny = phylogeny;
+    let query = dataset.populations["Q"].clone();
+    let candidates = children.iter().take(5).collect::<Vec<_>>();
+
+    // the tied children are resolved to the polytomy parent
+    let mut result = dataset.search(&query, Some(&candidates), None)?;
+    assert_eq!(result.consensus_population, "P");
+    assert_eq!(result.mrca_candidates, children[0..5]);
+
+    // truncating the top populations keeps the total
+    assert_eq!(result.top_populations_total, 5);
+    result.truncate_top_populations(3);
+    assert_eq!(result.top_populations, children[0..3]);
+    assert_eq!(result.mrca_candidates, children[0..3]);
+    assert_eq!(result.top_populations_total, 5);
+    assert_eq!(
+        result.score.keys().collect::<Vec<_>>(),
+        ["P", "P.1", "P.2", "P.3"]
+    );
+
+    // a single best match has no candidates
+    let query = dataset.populations["P.1"].clone();
+    let result = dataset.search(&query, Some(&candidates), None)?;
+    assert_eq!(result.consensus_population, "P.1");
+    assert!(result.mrca_candidates.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn barcode_classes() -> Result<(), Report> {
+    let output_dir = PathBuf::from("output").join("tests").join("barcode_classes");
+    create_dir_all(&output_dir)?;
+
+    // A and B have identical barcodes, C differs
+    let fasta = [
+        ">Reference",
+        "AAAAAAAAAA",
+        ">A",
+        "CCAAAAAAAA",
+        ">B",
+        "CCAAAAAAAA",
+        ">C",
+        "CCGAAAAAAA",
+        ">Q",
+        "CCAAAAAAAT",
+    ];
+    let path = output_dir.join("alignment.fasta");
+    write(&path, fasta.join("\n"))?;
+    let mut dataset = load::alignment(&path, &vec![0, 0])?;
+    let query = dataset.populations["Q"].clone();
+    let candidates = ["A", "B", "C"].map(String::from);
+    let candidates = candidates.iter().collect::<Vec<_>>();
+
+    let classes = BarcodeClasses::from_populations(&dataset.populations);
+    assert_eq!(classes.collapsed().count(), 1);
+    assert_eq!(classes.members("A"), ["A", "B"]);
+    assert!(!classes.is_representative("B"));
+    assert_eq!(classes.num_collapsed(), 1);
+
+    // collapsed classes are expanded back to their members
+    let expected = dataset.search(&query, Some(&candidates), None)?;
+    dataset.barcode_classes = Some(classes);
+    let result = dataset.search(&query, Some(&candidates), None)?;
+    assert_eq!(result, expected);
+    assert_eq!(result.top_populations, ["A", "B"]);
+
+    // a member is searched, even if its representative is not
+    let candidates = vec![candidates[1], candidates[2]];
+    let result = dataset.search(&query, Some(&candidates), None)?;
+    assert_eq!(result.top_populations, ["B"]);
+
+    Ok(())
+}
+
+#[test]
+fn population_selection() -> Result<(), Report> {
+    let selection = Selection::from_str("XBB* & !XBB.1.16* | BA.2")?;
+    assert_eq!(selection.to_string(), "((XBB* & !XBB.1.16*) | BA.2)");
+    // '-' is only an operator with spaces
+    let selection = Selection::from_str("A-1 - (B | C)")?;
+    assert_eq!(selection.to_string(), "(A-1 - (B | C))");
+    assert!(Selection::from_str("(A & B").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn mutation_signature() -> Result<(), Report> {
+    let signature = Signature::from_str("has(C22995A) and not has(T22917G) or has(A1C)")?;
+    assert_eq!(
+        signature.to_string(),
+        "((has(C22995A) and not has(T22917G)) or has(A1C))"
+    );
+    assert!(Signature::from_str("has(C22995A) and").is_err());
+    assert!(Signature::from_str("has(C22995A").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn sequence_translate() -> Result<(), Report> {
+    let translate = |seq: &str| translate::translate(&seq.chars().collect::<Vec<_>>());
+    assert_eq!(translate("ATGGCCTAA"), "MA*");
+    // deleted codons are '-', partial deletions and ambiguity are unknown
+    assert_eq!(translate("ATG---A-GNNNATGC"), "M-XXM");
+
+    Ok(())
+}
+
+#[test]
+fn plot_combine_barcodes() -> Result<(), Report> {
+    let table = |rows: &[&str]| {
+        let mut table = Table::new();
+        let mut rows = rows.iter().map(|r| r.split(' ').map(String::from).collect());
+        table.headers = rows.next().unwrap_or_default();
+        table.rows = rows.collect();
+        table
+    };
+    // samples with the same parents, but different breakpoints and private sites
+    let s1 = table(&[
+        "coord origin Reference A B s1",
+        "5 A A C T C",
+        "9 B A C T T",
+    ]);
+    let s2 = table(&[
+        "coord origin Reference A B s2",
+        "5 B A C T T",
+        "7 private A A A G",
+    ]);
+    let parents = vec!["A".to_string(), "B".to_string()];
+    let combined = combine_barcodes(&[s1, s2], &parents)?;
+
+    assert_eq!(
+        combined.headers,
+        vec!["coord", "origin", "Reference", "A", "B", "s1", "s2"]
+    );
+    assert_eq!(
+        combined.rows,
+        vec![
+            vec!["5", "?", "A", "C", "T", "C", "T"],
+            vec!["7", "private", "A", "A", "A", "A", "G"],
+            vec!["9", "B", "A", "C", "T", "T", "N"],
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn search_result_pretty_print() -> Result<(), Report> {
+    // empty results don't panic
+    let sequence = Sequence::new();
+    let mut result = SearchResult::new(&sequence);
+    result.consensus_population = "A".to_string();
+    assert!(result.pretty_print(2).contains("consensus_population: A"));
+    assert!(result.one_line().contains("A (score: 0"));
+
+    // consensus first, then by score and name, capped
+    for (pop, score) in [("A", 1), ("B", 3), ("C", 3), ("D", 2)] {
+        result.score.insert(pop.to_string(), score);
+    }
+    let pretty = result.pretty_print(3);
+    assert!(pretty.contains("score:\n  - A: 1\n  - B: 3\n  - C: 3\n  - ... (+1 more)"));
+    assert!(pretty.contains("support:\n  - A (0): \n"));
+
     Ok(())
 }
 
@@ -54,13 +2462,20 @@ async fn sarscov2_populations() -> Result<(), Report> {
         tag: Tag::from_str("2023-11-17")?,
         output_dir: output_dir.join("dataset"),
         summary: None,
+        accession: None,
+        max_missing: None,
+        max_private: None,
+        phylogeny: None,
+        conflict_policy: ConflictPolicy::default(),
+        theme: Theme::new(),
+        strict: false,
     };
     download::dataset(&mut args).await?;
 
     // Run
     let mut args = cli::run::Args {
         population: Some("AY.4.2*,BA.5.2,XBC.1.6*,XBB.1.5.1,XBL".to_string()),
-        dataset_dir: output_dir.join("dataset"),
+        dataset_dir: Some(output_dir.join("dataset")),
         output_dir: output_dir.join("run"),
         ..Default::default()
     };
@@ -0,0 +1,63 @@
+use rebar::cli;
+use rebar::dataset::attributes::{Name, Tag};
+use rebar::dataset::download;
+use rebar::dataset::load;
+use rebar::phylogeny::ConflictPolicy;
+use rebar::run::run;
+use rebar::utils::theme::Theme;
+
+use color_eyre::eyre::{Report, Result};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+#[tokio::test]
+async fn did_you_mean() -> Result<(), Report> {
+    // suggestions are sections of the color_eyre handler, which must be
+    // installed before any error is created, so this test has its own binary
+    color_eyre::install()?;
+    let output_dir = PathBuf::from("output").join("tests").join("did_you_mean");
+
+    let mut args = cli::dataset::download::Args {
+        name: Name::Toy1,
+        tag: Tag::from_str("custom")?,
+        output_dir: output_dir.join("dataset"),
+        summary: None,
+        accession: None,
+        max_missing: None,
+        max_private: None,
+        phylogeny: None,
+        conflict_policy: ConflictPolicy::default(),
+        theme: Theme::new(),
+        strict: false,
+    };
+    download::dataset(&mut args).await?;
+
+    // misspelled --knockout
+    let args = cli::run::Args {
+        input: cli::run::Input {
+            populations: Some(vec!["D".to_string()]),
+            ..Default::default()
+        },
+        dataset_dir: Some(output_dir.join("dataset")),
+        output_dir: output_dir.join("run"),
+        mask: vec![0, 0],
+        min_length: 3,
+        knockout: Some(vec!["BB".to_string()]),
+        ..Default::default()
+    };
+    let error = run(&mut args.clone()).unwrap_err();
+    assert!(format!("{error:?}").contains("Did you mean: B?"));
+
+    // misspelled --populations, and descendants of a misspelled population
+    let dataset = load::dataset(&output_dir.join("dataset"), &vec![0, 0])?;
+    let error = dataset.expand_populations(&["DD".to_string()]).unwrap_err();
+    assert!(format!("{error:?}").contains("Did you mean: D?"));
+    let error = dataset.expand_populations(&["EE*".to_string()]).unwrap_err();
+    assert!(format!("{error:?}").contains("Did you mean: E?"));
+
+    // no suggestion when nothing is close
+    let error = dataset.expand_populations(&["XBB.1.5".to_string()]).unwrap_err();
+    assert!(!format!("{error:?}").contains("Did you mean"));
+
+    Ok(())
+}